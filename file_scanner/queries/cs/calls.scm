@@ -0,0 +1,6 @@
+;; Call sites, one @callee capture per call holding the called method's name.
+(invocation_expression function: (identifier) @callee)
+(invocation_expression function: (member_access_expression name: (identifier) @callee))
+(invocation_expression function: (generic_name (identifier) @callee))
+(invocation_expression function: (member_access_expression name: (generic_name (identifier) @callee)))
+(object_creation_expression type: (identifier) @callee)
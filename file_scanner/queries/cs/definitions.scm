@@ -0,0 +1,8 @@
+;; Other compactness levels: names with their definition span.
+((method_declaration (identifier) @method_name) @function_definition)
+((constructor_declaration name: (identifier) @method_name) @function_definition)
+((local_function_statement name: (identifier) @method_name) @function_definition)
+
+;; Property, indexer, and event accessors, named get_X/set_X/add_X/remove_X.
+((accessor_declaration) @accessor @function_definition)
+((property_declaration value: (arrow_expression_clause)) @accessor @function_definition)
@@ -0,0 +1,2 @@
+;; Import/use/using statements, one @import capture per statement.
+(using_directive) @import
@@ -0,0 +1,8 @@
+;; Compactness 0: function/method names only.
+((method_declaration (identifier) @method_name))
+((constructor_declaration name: (identifier) @method_name))
+((local_function_statement name: (identifier) @method_name))
+
+;; Property, indexer, and event accessors, named get_X/set_X/add_X/remove_X.
+((accessor_declaration) @accessor @function_definition)
+((property_declaration value: (arrow_expression_clause)) @accessor @function_definition)
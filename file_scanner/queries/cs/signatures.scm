@@ -0,0 +1,11 @@
+;; Compactness 1-3: name, parameters, return type, body, and definition span.
+((method_declaration type: (_) @return_type name: (identifier) @method_name parameters: (parameter_list) @parameters body: (block) @body) @function_definition)
+((constructor_declaration name: (identifier) @method_name parameters: (parameter_list) @parameters [(block) (arrow_expression_clause)] @body) @function_definition)
+((local_function_statement type: (_) @return_type name: (identifier) @method_name parameters: (parameter_list) @parameters [(block) (arrow_expression_clause)] @body) @function_definition)
+
+;; Property, indexer, and event accessors, named get_X/set_X/add_X/remove_X, with the
+;; member's type as return type. Auto-implemented accessors (`get;`) have no body.
+((property_declaration type: (_) @return_type accessors: (accessor_list (accessor_declaration [(block) (arrow_expression_clause)]? @body) @accessor @function_definition)))
+((indexer_declaration type: (_) @return_type parameters: (bracketed_parameter_list) @parameters accessors: (accessor_list (accessor_declaration [(block) (arrow_expression_clause)]? @body) @accessor @function_definition)))
+((event_declaration type: (_) @return_type accessors: (accessor_list (accessor_declaration [(block) (arrow_expression_clause)]? @body) @accessor @function_definition)))
+((property_declaration type: (_) @return_type value: (arrow_expression_clause) @body) @accessor @function_definition)
@@ -0,0 +1,3 @@
+;; Call sites, one @callee capture per call holding the called function's name.
+(call function: (identifier) @callee)
+(call function: (attribute attribute: (identifier) @callee))
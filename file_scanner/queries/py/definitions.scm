@@ -0,0 +1,5 @@
+;; Other compactness levels: names with their definition span.
+((function_definition name: (identifier) @method_name) @function_definition)
+
+;; Lambdas assigned to a name.
+((assignment left: (identifier) @method_name right: (lambda)) @function_definition)
@@ -0,0 +1,4 @@
+;; Import/use/using statements, one @import capture per statement.
+(import_statement) @import
+(import_from_statement) @import
+(future_import_statement) @import
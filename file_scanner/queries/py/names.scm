@@ -0,0 +1,5 @@
+;; Compactness 0: function/method names only.
+((function_definition name: (identifier) @method_name))
+
+;; Lambdas assigned to a name.
+((assignment left: (identifier) @method_name right: (lambda)))
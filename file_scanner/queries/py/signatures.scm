@@ -0,0 +1,5 @@
+;; Compactness 1-3: name, parameters, return type, body, and definition span.
+((function_definition name: (identifier) @method_name parameters: (parameters) @parameters return_type: (_)? @return_type body: (block) @body) @function_definition)
+
+;; Lambdas assigned to a name.
+((assignment left: (identifier) @method_name right: (lambda parameters: (lambda_parameters)? @parameters body: (_) @body)) @function_definition)
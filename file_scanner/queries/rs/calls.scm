@@ -0,0 +1,7 @@
+;; Call sites, one @callee capture per call holding the called function's name.
+(call_expression function: (identifier) @callee)
+(call_expression function: (field_expression field: (field_identifier) @callee))
+(call_expression function: (scoped_identifier name: (identifier) @callee))
+(call_expression function: (generic_function function: (identifier) @callee))
+(call_expression function: (generic_function function: (field_expression field: (field_identifier) @callee)))
+(call_expression function: (generic_function function: (scoped_identifier name: (identifier) @callee)))
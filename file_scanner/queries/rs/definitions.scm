@@ -0,0 +1,8 @@
+;; Other compactness levels: names with their definition span.
+((function_item name: (identifier) @method_name) @function_definition)
+
+;; Trait methods without a default body.
+((function_signature_item name: (identifier) @method_name) @function_definition)
+
+;; Closures bound with `let`.
+((let_declaration pattern: (identifier) @method_name value: (closure_expression)) @function_definition)
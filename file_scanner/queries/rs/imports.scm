@@ -0,0 +1,3 @@
+;; Import/use/using statements, one @import capture per statement.
+(use_declaration) @import
+(extern_crate_declaration) @import
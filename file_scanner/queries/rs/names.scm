@@ -0,0 +1,8 @@
+;; Compactness 0: function/method names only.
+((function_item name: (identifier) @method_name))
+
+;; Trait methods without a default body.
+((function_signature_item name: (identifier) @method_name))
+
+;; Closures bound with `let`.
+((let_declaration pattern: (identifier) @method_name value: (closure_expression)))
@@ -0,0 +1,8 @@
+;; Compactness 1-3: name, parameters, return type, body, and definition span.
+((function_item name: (identifier) @method_name parameters: (parameters) @parameters return_type: (_)? @return_type body: (block) @body) @function_definition)
+
+;; Trait methods without a default body.
+((function_signature_item name: (identifier) @method_name parameters: (parameters) @parameters return_type: (_)? @return_type) @function_definition)
+
+;; Closures bound with `let`.
+((let_declaration pattern: (identifier) @method_name value: (closure_expression parameters: (closure_parameters) @parameters return_type: (_)? @return_type body: (_) @body)) @function_definition)
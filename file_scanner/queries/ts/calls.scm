@@ -0,0 +1,4 @@
+;; Call sites, one @callee capture per call holding the called function's name.
+(call_expression function: (identifier) @callee)
+(call_expression function: (member_expression property: (property_identifier) @callee))
+(new_expression constructor: (identifier) @callee)
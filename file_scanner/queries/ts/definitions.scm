@@ -0,0 +1,6 @@
+;; Other compactness levels: names with their definition span.
+((function_declaration name: (identifier) @method_name) @function_definition)
+((method_definition name: (property_identifier) @method_name) @function_definition)
+
+;; Arrow functions bound to a name (`const f = () => {}`), at any nesting level.
+((lexical_declaration (variable_declarator name: (identifier) @method_name value: (arrow_function))) @function_definition)
@@ -0,0 +1,6 @@
+;; Compactness 0: function/method names only.
+((function_declaration name: (identifier) @method_name))
+((method_definition name: (property_identifier) @method_name))
+
+;; Arrow functions bound to a name (`const f = () => {}`), at any nesting level.
+((variable_declarator name: (identifier) @method_name value: (arrow_function)))
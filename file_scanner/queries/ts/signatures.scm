@@ -0,0 +1,6 @@
+;; Compactness 1-3: name, parameters, return type, body, and definition span.
+((function_declaration name: (identifier) @method_name parameters: (formal_parameters) @parameters return_type: (_)? @return_type body: (statement_block) @body) @function_definition)
+((method_definition name: (property_identifier) @method_name parameters: (formal_parameters) @parameters return_type: (_)? @return_type body: (statement_block) @body) @function_definition)
+
+;; Arrow functions bound to a name (`const f = () => {}`), at any nesting level.
+((lexical_declaration (variable_declarator name: (identifier) @method_name value: (arrow_function parameters: (formal_parameters)? @parameters return_type: (_)? @return_type body: (_) @body))) @function_definition)
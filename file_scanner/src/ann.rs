@@ -0,0 +1,243 @@
+use crate::structs::EmbeddingModelTag;
+use crate::utils;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Below this many functions per model, exact search is cheap and always used.
+pub const MIN_FUNCTIONS: usize = 20_000;
+/// k-means refinement rounds when (re)building the index.
+const KMEANS_ITERATIONS: usize = 8;
+/// Vectors sampled per cluster to train the centroids.
+const TRAINING_SAMPLES_PER_CLUSTER: usize = 64;
+/// One in this many clusters, the ones closest to the query, is scored.
+const PROBE_DIVISOR: usize = 8;
+/// The index is rebuilt once more than this share of the functions is missing from it.
+const MAX_UNINDEXED_SHARE: f64 = 0.2;
+
+/// Inverted-file index over the function embeddings of one model, for approximate
+/// nearest-neighbour search in large projects: the embeddings are clustered with
+/// k-means, and a query only scores the functions of the clusters closest to it.
+#[derive(Serialize, Deserialize)]
+struct IvfIndex {
+    centroids: Vec<Vec<f32>>,
+    /// Cluster of every indexed function, by the key the caller identifies it with.
+    members: HashMap<String, u32>,
+}
+
+/// Key of `tag`'s index in the embedding cache. File entries are keyed by model name
+/// first, so this cannot collide with them.
+fn index_key(tag: EmbeddingModelTag) -> String {
+    format!("ann/{}", tag.as_str())
+}
+
+/// Position of the centroid closest to `vector`.
+fn nearest(centroids: &[Vec<f32>], vector: &[f32]) -> usize {
+    centroids
+        .iter()
+        .map(|centroid| utils::cosine_similarity(centroid, vector))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Clusters `vectors` into about `sqrt(n)` groups.
+fn build(keys: &[String], vectors: &[&[f32]]) -> IvfIndex {
+    let k = ((vectors.len() as f64).sqrt() as usize).max(1);
+    let step = (vectors.len() / (k * TRAINING_SAMPLES_PER_CLUSTER)).max(1);
+    let sample: Vec<&[f32]> = vectors.iter().step_by(step).copied().collect();
+    let mut centroids: Vec<Vec<f32>> = (0..k).map(|i| sample[i * sample.len() / k].to_vec()).collect();
+    for _ in 0..KMEANS_ITERATIONS {
+        let assignments: Vec<usize> = sample.par_iter().map(|vector| nearest(&centroids, vector)).collect();
+        let mut sums = vec![vec![0.0f32; centroids[0].len()]; k];
+        let mut counts = vec![0usize; k];
+        for (vector, &cluster) in sample.iter().zip(&assignments) {
+            for (sum, x) in sums[cluster].iter_mut().zip(vector.iter()) {
+                *sum += x;
+            }
+            counts[cluster] += 1;
+        }
+        // Cosine similarity ignores length, so the sum serves as the mean. An empty
+        // cluster keeps its previous centroid.
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                *centroid = sum;
+            }
+        }
+    }
+    let members = keys
+        .par_iter()
+        .zip(vectors.par_iter())
+        .map(|(key, vector)| (key.clone(), nearest(&centroids, vector) as u32))
+        .collect();
+    IvfIndex { centroids, members }
+}
+
+/// Returns the positions in `vectors` worth scoring exactly against `query`: the
+/// functions in the clusters closest to it, plus any function the index does not know
+/// yet (new since it was built), so fresh code is never missed. `keys` name the
+/// functions in the same order and must stay stable across searches.
+///
+/// The index for `tag` is loaded from `db`, and rebuilt and stored again when missing,
+/// built for vectors of another length, or too stale.
+pub fn candidates(
+    db: &sled::Db,
+    tag: EmbeddingModelTag,
+    keys: &[String],
+    vectors: &[&[f32]],
+    query: &[f32],
+    debug_log: &mut Option<Vec<String>>,
+) -> Vec<usize> {
+    let stored = db
+        .get(index_key(tag))
+        .ok()
+        .flatten()
+        .and_then(|bytes| bincode::deserialize::<IvfIndex>(&bytes).ok())
+        .filter(|index| {
+            let unindexed = keys.par_iter().filter(|key| !index.members.contains_key(*key)).count();
+            index.centroids.first().is_some_and(|centroid| centroid.len() == query.len())
+                && (unindexed as f64) <= keys.len() as f64 * MAX_UNINDEXED_SHARE
+        });
+    let index = match stored {
+        Some(index) => index,
+        None => {
+            let index = build(keys, vectors);
+            if let Some(log_ref) = debug_log {
+                log_ref.push(format!(
+                    "[Ann] Built {} clusters over {} {} embeddings.",
+                    index.centroids.len(),
+                    keys.len(),
+                    tag.as_str()
+                ));
+            }
+            match bincode::serialize(&index) {
+                Ok(bytes) => {
+                    if let Err(e) = db.insert(index_key(tag), bytes) {
+                        if let Some(log_ref) = debug_log {
+                            log_ref.push(format!("[Ann] Error storing index: {}", e));
+                        }
+                    }
+                }
+                Err(e) => {
+                    if let Some(log_ref) = debug_log {
+                        log_ref.push(format!("[Ann] Error serializing index: {}", e));
+                    }
+                }
+            }
+            index
+        }
+    };
+
+    let mut ranked: Vec<(usize, f32)> = index
+        .centroids
+        .iter()
+        .map(|centroid| utils::cosine_similarity(centroid, query))
+        .enumerate()
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let probes = index.centroids.len().div_ceil(PROBE_DIVISOR);
+    let probed: HashSet<u32> = ranked.iter().take(probes).map(|(i, _)| *i as u32).collect();
+    let candidates: Vec<usize> = keys
+        .par_iter()
+        .enumerate()
+        .filter(|(_, key)| index.members.get(*key).is_none_or(|cluster| probed.contains(cluster)))
+        .map(|(i, _)| i)
+        .collect();
+    if let Some(log_ref) = debug_log {
+        log_ref.push(format!(
+            "[Ann] Probed {} of {} clusters: {} of {} functions to score.",
+            probes,
+            index.centroids.len(),
+            candidates.len(),
+            keys.len()
+        ));
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUPS: usize = 8;
+
+    /// `GROUPS` groups of `GROUPS` vectors each near one axis, in group order, so the
+    /// index has one cluster per group and probes one of them.
+    fn grouped_vectors() -> (Vec<String>, Vec<Vec<f32>>) {
+        let mut keys = Vec::new();
+        let mut vectors = Vec::new();
+        for group in 0..GROUPS {
+            for member in 0..GROUPS {
+                let mut vector = vec![0.0; GROUPS];
+                vector[group] = 1.0;
+                vector[(group + 1) % GROUPS] = member as f32 * 0.01;
+                keys.push(format!("g{}/{}", group, member));
+                vectors.push(vector);
+            }
+        }
+        (keys, vectors)
+    }
+
+    fn axis(i: usize, dimension: usize) -> Vec<f32> {
+        let mut vector = vec![0.0; dimension];
+        vector[i] = 1.0;
+        vector
+    }
+
+    fn search(db: &sled::Db, keys: &[String], vectors: &[Vec<f32>], query: &[f32]) -> (Vec<usize>, Vec<String>) {
+        let vectors: Vec<&[f32]> = vectors.iter().map(Vec::as_slice).collect();
+        let mut debug_log = Some(Vec::new());
+        let mut found = candidates(db, EmbeddingModelTag::BgeSmall, keys, &vectors, query, &mut debug_log);
+        found.sort_unstable();
+        (found, debug_log.unwrap_or_default())
+    }
+
+    fn built(log: &[String]) -> bool {
+        log.iter().any(|line| line.starts_with("[Ann] Built"))
+    }
+
+    #[test]
+    fn probes_the_cluster_closest_to_the_query() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let (keys, vectors) = grouped_vectors();
+        let (found, log) = search(&db, &keys, &vectors, &axis(3, GROUPS));
+        assert!(built(&log));
+        assert_eq!(found, (3 * GROUPS..4 * GROUPS).collect::<Vec<_>>());
+
+        // The stored index is reused.
+        let (found, log) = search(&db, &keys, &vectors, &axis(6, GROUPS));
+        assert!(!built(&log));
+        assert_eq!(found, (6 * GROUPS..7 * GROUPS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unindexed_functions_are_always_candidates() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let (mut keys, mut vectors) = grouped_vectors();
+        search(&db, &keys, &vectors, &axis(0, GROUPS));
+
+        keys.push("new".to_string());
+        vectors.push(axis(5, GROUPS));
+        let (found, log) = search(&db, &keys, &vectors, &axis(1, GROUPS));
+        assert!(!built(&log));
+        let mut expected: Vec<usize> = (GROUPS..2 * GROUPS).collect();
+        expected.push(GROUPS * GROUPS);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn index_is_rebuilt_when_stale_or_of_another_length() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let (keys, vectors) = grouped_vectors();
+        search(&db, &keys, &vectors, &axis(0, GROUPS));
+
+        let renamed: Vec<String> = keys.iter().map(|key| format!("renamed/{}", key)).collect();
+        let (found, log) = search(&db, &renamed, &vectors, &axis(2, GROUPS));
+        assert!(built(&log));
+        assert_eq!(found, (2 * GROUPS..3 * GROUPS).collect::<Vec<_>>());
+
+        let shorter: Vec<Vec<f32>> = (0..keys.len()).map(|i| axis(i % 4, 4)).collect();
+        let (_, log) = search(&db, &renamed, &shorter, &axis(0, 4));
+        assert!(built(&log));
+    }
+}
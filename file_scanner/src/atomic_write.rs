@@ -0,0 +1,226 @@
+use crate::utils::UTF8_BOM;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes temp files of concurrent writes from the same process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Temp file next to `path`, so the final rename stays on one filesystem.
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        ".{}.{}.{}.rapid-tmp",
+        file_name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// The file the symbolic link `path` points to. A dangling link resolves to the path it
+/// names, so the write creates the file the link expects.
+fn link_target(path: &Path) -> io::Result<PathBuf> {
+    match fs::canonicalize(path) {
+        Ok(target) => Ok(target),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let target = fs::read_link(path)?;
+            Ok(path.parent().map_or_else(|| target.clone(), |directory| directory.join(&target)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces `path` with `contents` so readers see either the old or the new file,
+/// never a partial write.
+///
+/// The data goes to a temp file in the same directory, is flushed to disk, takes over
+/// the permissions of the file it replaces, and is renamed over `path`. On Unix the
+/// directory is synced too, so the rename survives a crash.
+///
+/// A symbolic link at `path` is kept: the file it points to is replaced instead.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let target;
+    let path = if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        target = link_target(path)?;
+        target.as_path()
+    } else {
+        path
+    };
+    let temp = temp_path(path);
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+
+    #[cfg(unix)]
+    if let Some(directory) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        // Best effort: some filesystems cannot sync directories.
+        let _ = fs::File::open(directory).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
+/// Writes `text` to `path` atomically (see `write_atomic`), keeping the conventions of
+/// the file it replaces: CRLF line endings and a UTF-8 byte order mark are preserved.
+/// New files get `text` as is. Returns the number of bytes written.
+pub fn write_text_preserving(path: &Path, text: &str) -> io::Result<usize> {
+    let existing = match fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let mut contents = Vec::with_capacity(text.len() + UTF8_BOM.len());
+    match existing {
+        Some(bytes) => {
+            // The existing file decides whether there is a byte order mark.
+            let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+            if bytes.starts_with(UTF8_BOM) {
+                contents.extend_from_slice(UTF8_BOM);
+            }
+            // The first line ending decides; mixed files are rare enough not to matter.
+            let uses_crlf = bytes
+                .iter()
+                .position(|&b| b == b'\n')
+                .is_some_and(|i| i > 0 && bytes[i - 1] == b'\r');
+            let normalized = text.replace("\r\n", "\n");
+            if uses_crlf {
+                contents.extend_from_slice(normalized.replace('\n', "\r\n").as_bytes());
+            } else {
+                contents.extend_from_slice(normalized.as_bytes());
+            }
+        }
+        None => contents.extend_from_slice(text.as_bytes()),
+    }
+
+    write_atomic(path, &contents)?;
+    Ok(contents.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own under the temp directory, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let name = format!("rapid-atomic-write-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+            let path = std::env::temp_dir().join(name);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn has_temp_files(&self) -> bool {
+            fs::read_dir(&self.0)
+                .unwrap()
+                .any(|entry| entry.unwrap().file_name().to_string_lossy().ends_with(".rapid-tmp"))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn write_atomic_replaces_content_without_leaving_temp_files() {
+        let dir = TempDir::new();
+        let path = dir.0.join("file.txt");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!dir.has_temp_files());
+    }
+
+    #[test]
+    fn failed_write_leaves_no_temp_file() {
+        let dir = TempDir::new();
+        // A file cannot be renamed over a non-empty directory.
+        let path = dir.0.join("directory");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("inner.txt"), "keep").unwrap();
+        assert!(write_atomic(&path, b"contents").is_err());
+        assert!(!dir.has_temp_files());
+        assert_eq!(fs::read_to_string(path.join("inner.txt")).unwrap(), "keep");
+
+        assert!(write_atomic(&dir.0.join("missing").join("file.txt"), b"contents").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let path = dir.0.join("script.sh");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_replaces_the_target_of_a_symlink() {
+        let dir = TempDir::new();
+        let target = dir.0.join("target.txt");
+        let link = dir.0.join("link.txt");
+        fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink("target.txt", &link).unwrap();
+        write_atomic(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+
+        let dangling = dir.0.join("dangling.txt");
+        std::os::unix::fs::symlink("created.txt", &dangling).unwrap();
+        write_atomic(&dangling, b"created").unwrap();
+        assert!(fs::symlink_metadata(&dangling).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(dir.0.join("created.txt")).unwrap(), "created");
+        assert!(!dir.has_temp_files());
+    }
+
+    #[test]
+    fn write_text_preserving_keeps_line_endings_and_bom() {
+        let dir = TempDir::new();
+        let path = dir.0.join("windows.txt");
+        fs::write(&path, b"\xEF\xBB\xBFone\r\ntwo\r\n").unwrap();
+        let written = write_text_preserving(&path, "one\nthree\r\n").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\xEF\xBB\xBFone\r\nthree\r\n");
+        assert_eq!(written, 15);
+
+        // The existing file has no byte order mark, so the one in `text` is dropped.
+        let path = dir.0.join("unix.txt");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        write_text_preserving(&path, "\u{feff}one\r\nthree\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\nthree\n");
+    }
+
+    #[test]
+    fn write_text_preserving_writes_new_files_unchanged() {
+        let dir = TempDir::new();
+        let path = dir.0.join("new.txt");
+        let text = "\u{feff}one\r\ntwo\n";
+        assert_eq!(write_text_preserving(&path, text).unwrap(), text.len());
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    }
+}
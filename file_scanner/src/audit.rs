@@ -0,0 +1,89 @@
+use crate::config;
+use crate::structs::AuditRecord;
+use crate::utils;
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Environment variable that enables the audit log when set to `1` or `true`.
+pub const AUDIT_ENV_VAR: &str = "RAPID_AUDIT_LOG";
+
+/// Whether operations should be recorded in the per-root audit log.
+pub fn enabled() -> bool {
+    std::env::var(AUDIT_ENV_VAR)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true"))
+        .unwrap_or(false)
+}
+
+/// Location of the audit log for a scanned root, next to the embedding cache.
+pub fn log_path(root: &Path) -> PathBuf {
+    root.join(config::CACHE_DIR).join("file_scanner_audit.jsonl")
+}
+
+/// Hashes the inputs of an operation (query, extensions, options JSON, ...) so
+/// records can be grouped by identical requests without storing the inputs themselves.
+pub fn hash_inputs(inputs: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for input in inputs {
+        hasher.update(input.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Appends a record for `operation` to the root's audit log if auditing is enabled.
+///
+/// Auditing is best-effort: failures to write the log never affect the operation.
+pub fn record(
+    root_path_str: &str,
+    operation: &str,
+    inputs: &[&str],
+    start_time: Instant,
+    result_count: usize,
+    error: Option<String>,
+) {
+    if !enabled() || root_path_str.is_empty() {
+        return;
+    }
+    let entry = AuditRecord {
+        timestamp_ms: utils::now_ms(),
+        operation: operation.to_string(),
+        inputs_hash: hash_inputs(inputs),
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        result_count,
+        error,
+    };
+    let path = log_path(Path::new(root_path_str));
+    let Ok(mut line) = serde_json::to_string(&entry) else {
+        return;
+    };
+    line.push('\n');
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+/// Reads the most recent `limit` records (all of them if `limit` is 0), oldest first.
+/// Lines that cannot be parsed are skipped.
+pub fn read(root: &Path, limit: usize) -> Result<Vec<AuditRecord>, std::io::Error> {
+    let path = log_path(root);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let records: Vec<AuditRecord> = BufReader::new(fs::File::open(path)?)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    let skip = if limit == 0 {
+        0
+    } else {
+        records.len().saturating_sub(limit)
+    };
+    Ok(records.into_iter().skip(skip).collect())
+}
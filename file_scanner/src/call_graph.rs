@@ -0,0 +1,48 @@
+use crate::structs::FileContext;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Identifier of a function in the call graph: `<path relative to root>:<qualified name>`.
+pub fn function_id(root: &Path, file: &str, qualified_name: &str) -> String {
+    let path = Path::new(file);
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    format!("{}:{}", relative.to_string_lossy(), qualified_name)
+}
+
+/// Builds a caller → callees adjacency list from functions scanned with
+/// `ScanOptions::calls` set.
+///
+/// Call sites only record the called name, so a call is linked to every scanned
+/// function with that name in the same language. Calls that match no scanned function (standard library,
+/// dependencies) are left out.
+pub fn build(root: &Path, file_contexts: &[FileContext]) -> BTreeMap<String, Vec<String>> {
+    let mut ids_by_name: HashMap<(&str, &str), Vec<String>> = HashMap::new();
+    for context in file_contexts {
+        for function in &context.functions {
+            ids_by_name
+                .entry((context.language.as_str(), function.name.as_str()))
+                .or_default()
+                .push(function_id(root, &context.path, &function.qualified_name));
+        }
+    }
+
+    let mut adjacency = BTreeMap::new();
+    for context in file_contexts {
+        for function in &context.functions {
+            let mut callees: Vec<String> = function
+                .calls
+                .iter()
+                .filter_map(|name| ids_by_name.get(&(context.language.as_str(), name.as_str())))
+                .flatten()
+                .cloned()
+                .collect();
+            callees.sort();
+            callees.dedup();
+            adjacency.insert(
+                function_id(root, &context.path, &function.qualified_name),
+                callees,
+            );
+        }
+    }
+    adjacency
+}
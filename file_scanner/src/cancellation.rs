@@ -0,0 +1,100 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Flags of the tokens handed out by `create_token` and not yet released.
+static TOKENS: Lazy<Mutex<HashMap<u64, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// `0` is never handed out, so hosts can use it for "no token".
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// Creates a token that a host can pass as `cancellation_token` in the options of a
+/// scan or search, and later `cancel` from another thread.
+pub fn create_token() -> u64 {
+    let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    TOKENS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(token, Arc::new(AtomicBool::new(false)));
+    token
+}
+
+/// Cancels every operation running with `token`, and any started with it later.
+/// Returns `false` for unknown or released tokens.
+pub fn cancel(token: u64) -> bool {
+    match TOKENS.lock().unwrap_or_else(|e| e.into_inner()).get(&token) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Forgets `token`, so `cancel` can no longer reach operations started with it.
+pub fn release_token(token: u64) -> bool {
+    TOKENS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&token)
+        .is_some()
+}
+
+/// The cancellation state one operation checks while it runs: its token, and
+/// optionally the deadline of its timeout.
+#[derive(Clone, Default, Debug)]
+pub struct CancelFlag {
+    flag: Option<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+}
+
+impl CancelFlag {
+    /// Looks up `token`; `None`, unknown, and released tokens never cancel.
+    pub fn for_token(token: Option<u64>) -> CancelFlag {
+        CancelFlag {
+            flag: token.and_then(|token| {
+                TOKENS
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get(&token)
+                    .cloned()
+            }),
+            deadline: None,
+        }
+    }
+
+    /// Also stops the operation once `timeout_ms` have passed since `start`. `0` sets
+    /// no deadline, like the scan timeouts.
+    pub fn with_timeout(mut self, start: Instant, timeout_ms: u32) -> CancelFlag {
+        self.deadline = (timeout_ms > 0).then(|| start + Duration::from_millis(timeout_ms as u64));
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Milliseconds left before the deadline, for handing the rest of the timeout on to
+    /// a nested operation. `0` when there is no deadline; at least `1` otherwise, so a
+    /// deadline that just passed is not mistaken for none.
+    pub fn remaining_ms(&self) -> u32 {
+        self.deadline.map_or(0, |deadline| {
+            let remaining = deadline.saturating_duration_since(Instant::now()).as_millis();
+            remaining.clamp(1, u32::MAX as u128) as u32
+        })
+    }
+
+    /// True when the operation should stop, through its token or its deadline.
+    pub fn should_stop(&self) -> bool {
+        self.is_cancelled() || self.is_timed_out()
+    }
+}
@@ -1,16 +1,141 @@
-use tree_sitter::Parser;
+use crate::options::ScanOptions;
+use crate::runtime;
+use ignore::overrides::{Override, OverrideBuilder};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tree_sitter::{Language, Parser, Query};
+
+/// Built-in queries for one language, embedded from `queries/<dir>/<name>.scm`.
+pub struct BuiltinQueries {
+    /// Compactness 0: function/method names.
+    pub names: &'static str,
+    /// Compactness 1-3: signatures, parameters, return types, comments and bodies.
+    pub signatures: &'static str,
+    /// Other compactness levels: names with their definition span.
+    pub definitions: &'static str,
+    /// Import/use/using statements, captured as `@import`.
+    pub imports: &'static str,
+    /// Call sites, captured as `@callee`.
+    pub calls: &'static str,
+}
+
+impl BuiltinQueries {
+    fn get(&self, name: &str) -> Option<&'static str> {
+        match name {
+            "names" => Some(self.names),
+            "signatures" => Some(self.signatures),
+            "definitions" => Some(self.definitions),
+            "imports" => Some(self.imports),
+            "calls" => Some(self.calls),
+            _ => None,
+        }
+    }
+}
+
+/// Embeds every built-in query file of `queries/<dir>/`.
+#[allow(unused_macros)] // Unused when every `lang-*` feature is disabled.
+macro_rules! builtin_queries {
+    ($dir:literal) => {
+        BuiltinQueries {
+            names: include_str!(concat!("../queries/", $dir, "/names.scm")),
+            signatures: include_str!(concat!("../queries/", $dir, "/signatures.scm")),
+            definitions: include_str!(concat!("../queries/", $dir, "/definitions.scm")),
+            imports: include_str!(concat!("../queries/", $dir, "/imports.scm")),
+            calls: include_str!(concat!("../queries/", $dir, "/calls.scm")),
+        }
+    };
+}
+
+/// Everything the scanner needs to know about one supported language.
+pub struct LanguageSpec {
+    /// Name reported in `FileContext::language`.
+    pub name: &'static str,
+    /// File extensions (without the leading dot) parsed with this grammar.
+    pub extensions: &'static [&'static str],
+    pub grammar: fn() -> Language,
+    /// Separator between enclosing scopes in qualified names.
+    pub scope_separator: &'static str,
+    /// Interpreters and editor modes, besides `name`, that identify an extensionless
+    /// script as this language in a shebang or modeline (version suffixes are ignored).
+    pub interpreters: &'static [&'static str],
+    pub queries: BuiltinQueries,
+}
+
+/// Languages compiled into this build, each behind its own `lang-*` cargo feature.
+///
+/// Adding a language takes a grammar dependency and feature in `Cargo.toml`, its
+/// `queries/<ext>/*.scm` files, and an entry here.
+const LANGUAGES: &[LanguageSpec] = &[
+    #[cfg(feature = "lang-csharp")]
+    LanguageSpec {
+        name: "csharp",
+        extensions: &["cs"],
+        grammar: tree_sitter_c_sharp::language,
+        scope_separator: ".",
+        interpreters: &["dotnet-script", "cs"],
+        queries: builtin_queries!("cs"),
+    },
+    #[cfg(feature = "lang-python")]
+    LanguageSpec {
+        name: "python",
+        extensions: &["py"],
+        grammar: tree_sitter_python::language,
+        scope_separator: ".",
+        interpreters: &["pypy"],
+        queries: builtin_queries!("py"),
+    },
+    #[cfg(feature = "lang-rust")]
+    LanguageSpec {
+        name: "rust",
+        extensions: &["rs"],
+        grammar: tree_sitter_rust::language,
+        scope_separator: "::",
+        interpreters: &["rust-script"],
+        queries: builtin_queries!("rs"),
+    },
+    #[cfg(feature = "lang-typescript")]
+    LanguageSpec {
+        name: "typescript",
+        extensions: &["ts"],
+        grammar: tree_sitter_typescript::language_typescript,
+        scope_separator: ".",
+        interpreters: &["deno", "ts-node", "tsx", "bun"],
+        queries: builtin_queries!("ts"),
+    },
+];
+
+/// Extensions (without the leading dot) of every language compiled into this build.
+pub fn supported_extensions() -> Vec<String> {
+    LANGUAGES
+        .iter()
+        .flat_map(|language| language.extensions.iter().map(|extension| extension.to_string()))
+        .collect()
+}
+
+/// Looks up the registered language for a file extension (with or without the dot).
+pub fn language_for(extension: &str) -> Option<&'static LanguageSpec> {
+    let extension = extension.trim_start_matches('.');
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension))
+}
+
+/// Looks up the registered language for a shebang interpreter or modeline mode
+/// (`python3.12`, `deno`, `rust`), ignoring version suffixes.
+pub fn language_for_interpreter(name: &str) -> Option<&'static LanguageSpec> {
+    let name = name
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.name == name || language.interpreters.contains(&name.as_str()))
+}
 
 /// Retrieves a tree-sitter parser for a given file extension.
 pub fn get_parser(extension: &str) -> Option<Parser> {
     let mut parser = Parser::new();
-    let language = match extension {
-        "cs" => tree_sitter_c_sharp::language(),
-        "py" => tree_sitter_python::language(),
-        "rs" => tree_sitter_rust::language(),
-        "ts" => tree_sitter_typescript::language_typescript(),
-        // TODO: Add support for more languages
-        _ => return None,
-    };
+    let language = (language_for(extension)?.grammar)();
     if parser.set_language(language).is_err() {
         // Log an error or handle it appropriately.
         // For now, returning None is consistent with other failure paths.
@@ -19,49 +144,375 @@ pub fn get_parser(extension: &str) -> Option<Parser> {
     Some(parser)
 }
 
-/// Retrieves a tree-sitter query string for a given file extension and compactness level.
+/// Resolves the query for an extension, applying any replacement or additional
+/// patterns supplied in `options`.
+pub fn resolve_query(extension: &str, compactness: u8, options: &ScanOptions) -> Option<String> {
+    let base = match options.query_overrides.get(extension) {
+        Some(query) => query.clone(),
+        None => get_query(extension, compactness)?,
+    };
+    match options.extra_queries.get(extension) {
+        Some(extra) => Some(format!("{}\n{}", base, extra)),
+        None => Some(base),
+    }
+}
+
+/// Checks that `query` compiles against the grammar for `extension`.
+pub fn validate_query(extension: &str, query: &str) -> Result<(), String> {
+    let parser = get_parser(extension)
+        .ok_or_else(|| format!("No parser available for extension '{}'", extension))?;
+    let language = parser
+        .language()
+        .ok_or_else(|| format!("No language set for extension '{}'", extension))?;
+    Query::new(language, query)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid query for extension '{}': {}", extension, e))
+}
+
+/// Human-readable language name for a supported file extension.
+pub fn language_name(extension: &str) -> Option<&'static str> {
+    language_for(extension).map(|language| language.name)
+}
+
+/// Separator between scopes in qualified names (`::` for Rust, `.` elsewhere).
+pub fn scope_separator(extension: &str) -> &'static str {
+    language_for(extension).map_or(".", |language| language.scope_separator)
+}
+
+/// Directory, relative to the scanned root, holding the embedding cache, model files,
+/// and audit log.
+pub const CACHE_DIR: &str = ".cache";
+
+/// Environment variable naming the directory embedding models are downloaded to and
+/// loaded from, shared by every scanned root.
+pub const MODEL_CACHE_ENV_VAR: &str = "RAPID_MODEL_CACHE";
+
+/// Directory holding downloaded embedding models. Models are large and the same for
+/// every project, so one copy serves all roots: `model_cache_dir` from `rapid_init` or
+/// `RAPID_MODEL_CACHE` if set, otherwise `rapid/models` in the user's cache directory
+/// (`$XDG_CACHE_HOME`, `~/.cache`, or `%LOCALAPPDATA%`), and
+/// `<root>/.cache/file_scanner_model_cache` only when there is no user cache directory.
+pub fn model_cache_dir(root: &Path) -> PathBuf {
+    if let Some(dir) = runtime::config().and_then(|config| config.model_cache_dir.as_deref()).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = std::env::var_os(MODEL_CACHE_ENV_VAR).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").filter(|dir| !dir.is_empty()).map(|home| Path::new(&home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").filter(|dir| !dir.is_empty()).map(PathBuf::from))
+        .map(|dir| dir.join("rapid").join("models"))
+        .unwrap_or_else(|| root.join(CACHE_DIR).join("file_scanner_model_cache"))
+}
+
+/// Every entry this crate creates in `CACHE_DIR` starts with this prefix.
+const CACHE_ENTRY_PREFIX: &str = "file_scanner_";
+
+/// Whether `path` is one of this crate's own cache directories or logs
+/// (`<root>/.cache/file_scanner_*`). Walks always skip them, whatever the ignore
+/// settings, so sled files and model weights are never size-checked or sniffed.
+pub fn is_own_cache_path(path: &Path) -> bool {
+    let is_cache_entry = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(CACHE_ENTRY_PREFIX));
+    is_cache_entry
+        && path.parent().and_then(|parent| parent.file_name()) == Some(CACHE_DIR.as_ref())
+}
+
+/// Builds the matcher for the `include`/`exclude` glob options, relative to `root`
+/// (e.g. `src/**`, `**/vendor/**`, `**/*_test.go`).
 ///
-/// Compactness levels determine the detail captured:
-/// - `0`: Function/method names only.
-/// - `1`: Signatures (name + parameters, up to body start).
-/// - `2`: Signatures + preceding comments.
-/// - `3`: Full definition (body + comments).
-/// - Other values default to names only.
-pub fn get_query(extension: &str, compactness: u8) -> Option<String> {
-    let query_str = match extension {
-        "cs" => match compactness {
-            0 => r#"((method_declaration (identifier) @method_name))"#.to_string(),
-            1 | 2 | 3 => r#"((method_declaration (identifier) @method_name body: (block) @body) @function_definition)"#.to_string(),
-            _ => r#"((method_declaration (identifier) @method_name) @function_definition)"#.to_string(),
-        },
-        "py" => match compactness {
-            0 => r#"((function_definition name: (identifier) @method_name))"#.to_string(),
-            1 | 2 | 3 => r#"((function_definition name: (identifier) @method_name body: (block) @body) @function_definition)"#.to_string(),
-            _ => r#"((function_definition name: (identifier) @method_name) @function_definition)"#.to_string(),
-        },
-        "rs" => match compactness {
-            0 => r#"((function_item name: (identifier) @method_name))"#.to_string(),
-            1 | 2 | 3 => r#"((function_item name: (identifier) @method_name body: (block) @body) @function_definition)"#.to_string(),
-            _ => r#"((function_item name: (identifier) @method_name) @function_definition)"#.to_string(),
-        },
-        "ts" => {
-            let base_queries = [
-                ("function_declaration", "identifier", "statement_block"),
-                ("method_definition", "property_identifier", "statement_block"),
-            ];
-            match compactness {
-                0 => base_queries.iter().map(|(node_type, name_field, _body_field)| {
-                    format!(r#"(({} name: ({}) @method_name))"#, node_type, name_field)
-                }).collect::<Vec<_>>().join("\n"),
-                1 | 2 | 3 => base_queries.iter().map(|(node_type, name_field, body_field)| {
-                    format!(r#"(({} name: ({}) @method_name body: ({}) @body) @function_definition)"#, node_type, name_field, body_field)
-                }).collect::<Vec<_>>().join("\n"),
-                _ => base_queries.iter().map(|(node_type, name_field, _body_field)| {
-                    format!(r#"(({} name: ({}) @method_name) @function_definition)"#, node_type, name_field)
-                }).collect::<Vec<_>>().join("\n"),
+/// Used as a filter on top of the extension and ignore-file rules rather than as walker
+/// overrides, so an `include` glob never pulls in gitignored or hidden files.
+pub fn path_filter(
+    root: &Path,
+    include: &[String],
+    exclude: &[String],
+) -> Result<Override, String> {
+    let mut builder = OverrideBuilder::new(root);
+    for glob in include {
+        builder
+            .add(glob)
+            .map_err(|e| format!("Invalid include glob '{}': {}", glob, e))?;
+    }
+    for glob in exclude {
+        builder
+            .add(&format!("!{}", glob))
+            .map_err(|e| format!("Invalid exclude glob '{}': {}", glob, e))?;
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Invalid glob patterns: {}", e))
+}
+
+/// Whether `path_filter` rejects `path`. Directories are only rejected by `exclude`
+/// globs, so files under them can still match an `include` glob.
+pub fn is_filtered_out(filter: &Override, path: &Path, is_dir: bool) -> bool {
+    filter.matched(path, is_dir).is_ignore()
+}
+
+/// Whether an `extensions` entry is a file-name glob rather than an extension.
+pub fn is_extension_glob(entry: &str) -> bool {
+    entry.contains(['*', '?', '['])
+}
+
+/// The files an `extensions` argument selects. Each entry is an extension, with or
+/// without its dot (`rs`, `.py`), compared with the whole extension a file is parsed as
+/// (see `parsing::file_extension`), or a glob matched against the file name
+/// (`*.test.ts`, `*_pb2.py`) for selections an extension cannot express.
+pub struct ExtensionFilter {
+    extensions: HashSet<String>,
+    globs: Vec<glob::Pattern>,
+}
+
+impl ExtensionFilter {
+    pub fn new<S: AsRef<str>>(entries: &[S]) -> Result<ExtensionFilter, String> {
+        let mut filter = ExtensionFilter {
+            extensions: HashSet::new(),
+            globs: Vec::new(),
+        };
+        for entry in entries {
+            let entry = entry.as_ref().trim();
+            if is_extension_glob(entry) {
+                let pattern = glob::Pattern::new(entry)
+                    .map_err(|e| format!("Invalid extension glob '{}': {}", entry, e))?;
+                filter.globs.push(pattern);
+            } else if !entry.is_empty() {
+                filter.extensions.insert(entry.trim_start_matches('.').to_string());
             }
         }
-        _ => return None,
-    };
-    Some(query_str)
+        Ok(filter)
+    }
+
+    /// Whether the file at `path`, parsed as `extension`, is selected.
+    pub fn matches(&self, path: &Path, extension: &str) -> bool {
+        self.extensions.contains(extension)
+            || path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| self.globs.iter().any(|glob| glob.matches(name)))
+    }
+}
+
+/// Ignore file, in `.gitignore` syntax, for excluding paths from scans and searches
+/// without changing what git ignores. Honored in the root and any subdirectory, and
+/// outside git repositories too.
+pub const IGNORE_FILE: &str = ".rapidignore";
+
+/// Whether `path` is git's own data directory. Skipped even when hidden files are
+/// included, since it never holds project sources.
+pub fn is_git_dir(path: &Path) -> bool {
+    path.file_name() == Some(".git".as_ref())
+}
+
+/// Dependency, virtualenv, and build output directories that walks skip unless
+/// `skip_vendor_dirs` is turned off (`.git` is always skipped, see `is_git_dir`).
+/// They are often not gitignored, and indexing them can mean millions of files.
+pub const VENDOR_DIRS: &[&str] = &["node_modules", "target", ".venv", "dist", "build"];
+
+/// `exclude` globs for test code, added by `ConceptSearchOptions::exclude_tests`:
+/// test directories and the usual test file names of the supported languages.
+pub const TEST_PATH_GLOBS: &[&str] = &[
+    "**/test/**",
+    "**/tests/**",
+    "**/__tests__/**",
+    "**/spec/**",
+    "**/test_*.py",
+    "**/*_test.py",
+    "**/conftest.py",
+    "**/*.test.ts",
+    "**/*.spec.ts",
+    "**/*.test.tsx",
+    "**/*.spec.tsx",
+    "**/*Tests.cs",
+    "**/*Test.cs",
+];
+
+/// Attributes and decorators that mark a test function (`#[test]`,
+/// `#[tokio::test]`, `[Fact]`, `[TestMethod]`, `@pytest.mark.parametrize`), matched
+/// against the annotation's name without its arguments.
+pub fn is_test_annotation(annotation: &str) -> bool {
+    let name = annotation
+        .trim_start_matches(['#', '[', '@'])
+        .split(['(', ']'])
+        .next()
+        .unwrap_or("")
+        .trim();
+    let last = name.rsplit([':', '.']).next().unwrap_or(name);
+    matches!(last, "test" | "Test" | "TestMethod" | "TestCase" | "Fact" | "Theory") || name.starts_with("pytest")
+}
+
+/// Whether `entry` is a directory named in `VENDOR_DIRS` below the walk root. The
+/// root itself is walked even if it is, say, a `build` directory.
+pub fn is_vendor_dir(entry: &ignore::DirEntry) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_some_and(|t| t.is_dir())
+        && entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| VENDOR_DIRS.contains(&name))
+}
+
+/// Resolves a `files` option (paths relative to `root`) for a scan or search that visits
+/// the listed files instead of walking. Walk filters only apply below a walk root, so
+/// the same rules are checked here: a file is left out unless it exists inside `root`
+/// once `..` and symbolic links are resolved, and it is also left out if it is in the
+/// crate's own cache, under `.git`, under a vendored directory (with `skip_vendor_dirs`),
+/// or rejected by `path_filter`.
+///
+/// Returns the files kept, joined onto `root` and without duplicates, and why each other
+/// entry was left out.
+pub fn listed_files(
+    root: &Path,
+    files: &[String],
+    path_filter: &Override,
+    skip_vendor_dirs: bool,
+) -> (Vec<PathBuf>, Vec<String>) {
+    let canonical_root = fs::canonicalize(root);
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    let mut rejected = Vec::new();
+    for file in files {
+        let relative = match (&canonical_root, fs::canonicalize(root.join(file))) {
+            (Ok(canonical_root), Ok(path)) => match path.strip_prefix(canonical_root) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => {
+                    rejected.push(format!("{}: outside the root", file));
+                    continue;
+                }
+            },
+            _ => {
+                rejected.push(format!("{}: not found", file));
+                continue;
+            }
+        };
+        let path = root.join(&relative);
+        let directories: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| *ancestor != root)
+            .collect();
+        let reason = if path.ancestors().any(is_own_cache_path) {
+            Some("in the cache directory")
+        } else if path.ancestors().any(is_git_dir) {
+            Some("in .git")
+        } else if skip_vendor_dirs
+            && directories.iter().any(|directory| {
+                directory.file_name().and_then(|name| name.to_str()).is_some_and(|name| VENDOR_DIRS.contains(&name))
+            })
+        {
+            Some("in a vendored directory")
+        } else if is_filtered_out(path_filter, &path, false)
+            || directories.iter().any(|directory| is_filtered_out(path_filter, directory, true))
+        {
+            Some("excluded by include/exclude globs")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => rejected.push(format!("{}: {}", file, reason)),
+            None if seen.insert(relative) => kept.push(path),
+            None => {}
+        }
+    }
+    (kept, rejected)
+}
+
+/// Directory, relative to the scanned root, whose `<ext>/<level>.scm` files take
+/// precedence over the built-in queries (e.g. `.rapid/queries/rs/signatures.scm`).
+pub const PROJECT_QUERY_DIR: &str = ".rapid/queries";
+
+fn builtin_query(extension: &str, name: &str) -> Option<&'static str> {
+    language_for(extension)?.queries.get(name)
+}
+
+/// Name of the query file used for a compactness level.
+///
+/// Compactness levels determine the detail captured:
+/// - `0` (`names`): Function/method names only.
+/// - `1` (`signatures`): Signatures (name + parameters, up to body start), plus structured parameters and return type.
+/// - `2` (`signatures`): Signatures + preceding comments.
+/// - `3` (`signatures`): Full definition (body + comments).
+/// - Other values (`definitions`): Names with their definition span.
+pub fn query_name(compactness: u8) -> &'static str {
+    match compactness {
+        0 => "names",
+        1..=3 => "signatures",
+        _ => "definitions",
+    }
+}
+
+/// Retrieves a tree-sitter query capturing each import/use/using statement as `@import`.
+pub fn get_imports_query(extension: &str) -> Option<&'static str> {
+    builtin_query(extension, "imports")
+}
+
+/// Retrieves a tree-sitter query capturing the name of each called function as `@callee`.
+pub fn get_calls_query(extension: &str) -> Option<&'static str> {
+    builtin_query(extension, "calls")
+}
+
+/// Retrieves the built-in tree-sitter query string for a given file extension and
+/// compactness level (see `query_name`).
+pub fn get_query(extension: &str, compactness: u8) -> Option<String> {
+    builtin_query(extension, query_name(compactness)).map(str::to_string)
+}
+
+/// Reads the project's query overrides for `extensions` at `compactness` from
+/// `<root>/PROJECT_QUERY_DIR`. Returns `(extension, query)` pairs for the files that exist.
+pub fn load_project_queries(
+    root: &Path,
+    extensions: &[String],
+    compactness: u8,
+) -> Vec<(String, String)> {
+    let query_dir = root.join(PROJECT_QUERY_DIR);
+    extensions
+        .iter()
+        .map(|ext| ext.trim_start_matches('.').to_string())
+        .filter_map(|ext| {
+            let path = query_dir
+                .join(&ext)
+                .join(format!("{}.scm", query_name(compactness)));
+            fs::read_to_string(path).ok().map(|query| (ext, query))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selects(entries: &[&str], file: &str) -> bool {
+        let path = Path::new(file);
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        ExtensionFilter::new(entries).unwrap().matches(path, extension)
+    }
+
+    #[test]
+    fn extensions_compare_the_whole_extension() {
+        assert!(selects(&["rs"], "src/main.rs"));
+        assert!(selects(&[".rs"], "src/main.rs"));
+        assert!(!selects(&["rs"], "this_is_not_rust.mars"));
+        assert!(selects(&["mars"], "this_is_not_rust.mars"));
+        assert!(!selects(&["s"], "src/main.rs"));
+        assert!(!selects(&["rs"], "Makefile"));
+    }
+
+    #[test]
+    fn globs_match_the_file_name() {
+        assert!(selects(&["*.test.ts"], "src/a.test.ts"));
+        assert!(!selects(&["*.test.ts"], "src/a.ts"));
+        assert!(!selects(&["*.test.ts"], "src/a.test.tsx"));
+        assert!(selects(&["py", "*.test.ts"], "src/a.py"));
+        assert!(!selects(&["*_pb2.py"], "src/a.py"));
+    }
+
+    #[test]
+    fn invalid_globs_are_reported() {
+        assert!(ExtensionFilter::new(&["[*.rs"]).is_err());
+    }
 }
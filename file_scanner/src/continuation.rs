@@ -0,0 +1,79 @@
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// How many timed-out scans can be resumed at once; older ones are forgotten first.
+const MAX_CONTINUATIONS: usize = 64;
+
+/// What a timed-out scan had already covered, so the next call can skip it.
+#[derive(Clone, Debug, Default)]
+pub struct ScanContinuation {
+    /// Files the walk visited, whether or not they produced functions.
+    pub files: HashSet<PathBuf>,
+    /// Roots of a multi-root scan that were walked completely.
+    pub completed_roots: HashSet<PathBuf>,
+}
+
+/// Saved continuations by token. A `BTreeMap`, so the oldest token is the first key.
+static CONTINUATIONS: Lazy<Mutex<BTreeMap<u64, ScanContinuation>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// Keeps `continuation` in the loaded library and returns the token that resumes it.
+/// The walk order of parallel scans is not stable, so a token records the visited files
+/// rather than a position in the tree.
+pub fn save(continuation: ScanContinuation) -> u64 {
+    let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    let mut continuations = CONTINUATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    continuations.insert(token, continuation);
+    while continuations.len() > MAX_CONTINUATIONS {
+        continuations.pop_first();
+    }
+    token
+}
+
+/// The continuation saved under `token`. It stays available, so a resumed scan that
+/// fails can be retried with the same token.
+pub fn load(token: u64) -> Result<ScanContinuation, String> {
+    CONTINUATIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&token)
+        .cloned()
+        .ok_or_else(|| format!("Unknown or expired continuation token: {}", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_continuations_load_until_expired() {
+        let continuation = ScanContinuation {
+            files: HashSet::from([PathBuf::from("src/lib.rs")]),
+            completed_roots: HashSet::from([PathBuf::from("vendor")]),
+        };
+        let token = save(continuation);
+        // Loading leaves the continuation in place.
+        for _ in 0..2 {
+            let loaded = load(token).unwrap();
+            assert!(loaded.files.contains(&PathBuf::from("src/lib.rs")));
+            assert!(loaded.completed_roots.contains(&PathBuf::from("vendor")));
+        }
+
+        let newer: Vec<u64> = (0..MAX_CONTINUATIONS).map(|_| save(ScanContinuation::default())).collect();
+        assert!(newer.iter().all(|&newer_token| newer_token > token));
+        let error = load(token).unwrap_err();
+        assert!(error.starts_with("Unknown or expired"), "{}", error);
+        assert!(load(*newer.last().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn unknown_token_is_an_error() {
+        // Tokens start at 1.
+        assert_eq!(load(0).unwrap_err(), "Unknown or expired continuation token: 0");
+    }
+}
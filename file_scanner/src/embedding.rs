@@ -1,12 +1,126 @@
+use crate::options::ModelRouting;
+use crate::structs::{CrossEncoderTag, EmbeddingModelTag, Quantization, StoredEmbedding};
 use anyhow::Context as AnyhowContext; // Alias to avoid conflict with struct Context if any
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use fastembed::{
+    EmbeddingModel, InitOptions, InitOptionsUserDefined, RerankInitOptions, RerankerModel, TextEmbedding, TextRerank, TokenizerFiles,
+    UserDefinedEmbeddingModel,
+};
+use half::f16;
 use once_cell::sync::OnceCell;
 use std::fs;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tracing_subscriber::{fmt, EnvFilter};
 
-pub static MODEL: OnceCell<TextEmbedding> = OnceCell::new();
+static BGE_SMALL: OnceCell<TextEmbedding> = OnceCell::new();
+static BGE_BASE: OnceCell<TextEmbedding> = OnceCell::new();
+static BGE_LARGE: OnceCell<TextEmbedding> = OnceCell::new();
+static JINA_CODE: OnceCell<TextEmbedding> = OnceCell::new();
+
+/// Returns the process-wide instance of `tag`, loading it on first use from where
+/// `routing` says (see `ModelRouting::local_models` and `ModelRouting::offline`).
+///
+/// Each model is initialized at most once; a failed initialization is retried on the
+/// next call.
+pub fn get_model(
+    tag: EmbeddingModelTag,
+    cache_dir: &Path,
+    routing: &ModelRouting,
+) -> Result<&'static TextEmbedding, anyhow::Error> {
+    let cell = match tag {
+        EmbeddingModelTag::BgeSmall => &BGE_SMALL,
+        EmbeddingModelTag::BgeBase => &BGE_BASE,
+        EmbeddingModelTag::BgeLarge => &BGE_LARGE,
+        EmbeddingModelTag::JinaCode => &JINA_CODE,
+    };
+    cell.get_or_try_init(|| match routing.local_models.get(&tag) {
+        Some(dir) => load_local_model(tag, Path::new(dir)),
+        None if routing.offline && !is_cached(tag, cache_dir) => Err(anyhow::anyhow!(
+            "Model '{}' is not in the model cache at {:?}, and offline mode forbids downloading it. \
+             Add it to local_models or warm the cache once with network access.",
+            tag.as_str(),
+            cache_dir
+        )),
+        None => initialize_model(tag, cache_dir),
+    })
+}
+
+/// Files the tokenizer is built from, besides the ONNX model itself.
+const TOKENIZER_FILES: [&str; 4] = ["tokenizer.json", "config.json", "special_tokens_map.json", "tokenizer_config.json"];
+
+/// Whether every file of `tag` is already in `cache_dir`, where the hub client keeps
+/// downloads as `models--<org>--<name>/snapshots/<commit>/<file>`. Like fastembed, a
+/// host's `HF_HOME` overrides `cache_dir`.
+fn is_cached(tag: EmbeddingModelTag, cache_dir: &Path) -> bool {
+    TextEmbedding::get_model_info(&fastembed_model(tag))
+        .is_ok_and(|info| is_repo_cached(&info.model_code, &info.model_file, cache_dir))
+}
+
+/// Whether the hub repository `model_code` has `model_file` and the tokenizer files in
+/// `cache_dir` (see `is_cached`).
+fn is_repo_cached(model_code: &str, model_file: &str, cache_dir: &Path) -> bool {
+    let hub_dir = std::env::var_os("HF_HOME").map(PathBuf::from).unwrap_or_else(|| cache_dir.to_path_buf());
+    let repo = hub_dir.join(format!("models--{}", model_code.replace('/', "--")));
+    let Ok(commit) = fs::read_to_string(repo.join("refs").join("main")) else {
+        return false;
+    };
+    let snapshot = repo.join("snapshots").join(commit.trim());
+    snapshot.join(model_file).is_file() && TOKENIZER_FILES.iter().all(|file| snapshot.join(file).is_file())
+}
+
+/// Loads `tag` from `dir`, a pre-downloaded copy of its hub repository, without any
+/// network access. The ONNX file may sit where the repository keeps it (e.g.
+/// `onnx/model.onnx`) or directly in `dir` as `model.onnx`.
+fn load_local_model(tag: EmbeddingModelTag, dir: &Path) -> Result<TextEmbedding, anyhow::Error> {
+    let model = fastembed_model(tag);
+    let info = TextEmbedding::get_model_info(&model)?;
+    let onnx_path = [dir.join(&info.model_file), dir.join("model.onnx")]
+        .into_iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No ONNX file for local model '{}' in {:?}: expected {} or model.onnx",
+                tag.as_str(),
+                dir,
+                info.model_file
+            )
+        })?;
+    let read = |path: &Path| {
+        fs::read(path).with_context(|| format!("Failed to read {:?} for local model '{}'", path, tag.as_str()))
+    };
+    let [tokenizer_file, config_file, special_tokens_map_file, tokenizer_config_file] =
+        TOKENIZER_FILES.map(|file| read(&dir.join(file)));
+    let mut user_model = UserDefinedEmbeddingModel::new(
+        read(&onnx_path)?,
+        TokenizerFiles {
+            tokenizer_file: tokenizer_file?,
+            config_file: config_file?,
+            special_tokens_map_file: special_tokens_map_file?,
+            tokenizer_config_file: tokenizer_config_file?,
+        },
+    );
+    // The hub models declare their pooling in fastembed's registry, not in their files.
+    if let Some(pooling) = TextEmbedding::get_default_pooling_method(&model) {
+        user_model = user_model.with_pooling(pooling);
+    }
+    TextEmbedding::try_new_from_user_defined(user_model, InitOptionsUserDefined::new())
+        .with_context(|| format!("Failed to load local model '{}' from {:?}", tag.as_str(), dir))
+}
+
+/// Length of the vectors `tag` produces, according to the model registry.
+pub fn dimension(tag: EmbeddingModelTag) -> Option<usize> {
+    TextEmbedding::get_model_info(&fastembed_model(tag)).ok().map(|info| info.dim)
+}
+
+fn fastembed_model(tag: EmbeddingModelTag) -> EmbeddingModel {
+    match tag {
+        EmbeddingModelTag::BgeSmall => EmbeddingModel::BGESmallENV15,
+        EmbeddingModelTag::BgeBase => EmbeddingModel::BGEBaseENV15,
+        EmbeddingModelTag::BgeLarge => EmbeddingModel::BGELargeENV15,
+        EmbeddingModelTag::JinaCode => EmbeddingModel::JinaEmbeddingsV2BaseCode,
+    }
+}
 
 // LogWriter captures tracing logs during model initialization.
 struct LogWriter {
@@ -29,9 +143,9 @@ impl std::io::Write for LogWriter {
     }
 }
 
-/// Initializes the TextEmbedding model, sets up tracing for initialization logs,
-/// and configures the cache directory for Hugging Face models.
-pub fn initialize_model(cache_dir: &Path) -> Result<TextEmbedding, anyhow::Error> {
+/// Initializes the TextEmbedding model, downloading it into `cache_dir` if needed, and
+/// sets up tracing for initialization logs.
+fn initialize_model(tag: EmbeddingModelTag, cache_dir: &Path) -> Result<TextEmbedding, anyhow::Error> {
     let log_buffer = Arc::new(Mutex::new(Vec::new()));
     let log_buffer_for_writer = Arc::clone(&log_buffer);
 
@@ -61,17 +175,13 @@ pub fn initialize_model(cache_dir: &Path) -> Result<TextEmbedding, anyhow::Error
     fs::create_dir_all(cache_dir)
         .with_context(|| format!("Failed to create cache directory at {:?}", cache_dir))?;
 
-    // Set HF_HOME environment variable to the specified cache directory.
-    // Note: Modifying environment variables can have global effects and concurrency implications.
-    // This is done to direct Hugging Face Hub where to store/load models.
-    // Ideally, this would be configurable directly via `fastembed` or `hf-hub` APIs if available.
-    let hf_home_path = cache_dir.to_str().ok_or_else(|| {
-        anyhow::anyhow!("Cache directory path is not valid UTF-8: {:?}", cache_dir)
-    })?;
-    std::env::set_var("HF_HOME", hf_home_path);
-
+    // The model location is passed to fastembed rather than through `HF_HOME`, which
+    // would change it for the whole host process and race with other threads reading
+    // the environment. A host that sets `HF_HOME` itself still takes precedence.
     TextEmbedding::try_new(
-        InitOptions::new(EmbeddingModel::BGEBaseENV15).with_show_download_progress(true),
+        InitOptions::new(fastembed_model(tag))
+            .with_cache_dir(cache_dir.to_path_buf())
+            .with_show_download_progress(true),
     )
     .with_context(|| {
         // Attempt to get logs. Lock poisoning is a remote possibility.
@@ -80,8 +190,215 @@ pub fn initialize_model(cache_dir: &Path) -> Result<TextEmbedding, anyhow::Error
             |guard| String::from_utf8_lossy(&guard).to_string(),
         );
         format!(
-            "Failed to initialize TextEmbedding model. Captured logs during init:\n{}",
+            "Failed to initialize TextEmbedding model '{}'. Captured logs during init:\n{}",
+            tag.as_str(),
             logs
         )
     })
 }
+
+static BGE_RERANKER_BASE: OnceCell<TextRerank> = OnceCell::new();
+static JINA_RERANKER_TURBO: OnceCell<TextRerank> = OnceCell::new();
+
+/// Returns the process-wide instance of the cross-encoder `tag`, downloading it into
+/// `cache_dir` on first use unless `routing` is offline. Like `get_model`, a failed
+/// initialization is retried on the next call.
+pub fn get_cross_encoder(
+    tag: CrossEncoderTag,
+    cache_dir: &Path,
+    routing: &ModelRouting,
+) -> Result<&'static TextRerank, anyhow::Error> {
+    let (cell, model) = match tag {
+        CrossEncoderTag::BgeRerankerBase => (&BGE_RERANKER_BASE, RerankerModel::BGERerankerBase),
+        CrossEncoderTag::JinaRerankerTurbo => (&JINA_RERANKER_TURBO, RerankerModel::JINARerankerV1TurboEn),
+    };
+    cell.get_or_try_init(|| {
+        let info = TextRerank::get_model_info(&model);
+        if routing.offline && !is_repo_cached(&info.model_code, &info.model_file, cache_dir) {
+            return Err(anyhow::anyhow!(
+                "Cross-encoder '{}' is not in the model cache at {:?}, and offline mode forbids downloading it.",
+                tag.as_str(),
+                cache_dir
+            ));
+        }
+        fs::create_dir_all(cache_dir)
+            .with_context(|| format!("Failed to create cache directory at {:?}", cache_dir))?;
+        TextRerank::try_new(
+            RerankInitOptions::new(model)
+                .with_cache_dir(cache_dir.to_path_buf())
+                .with_show_download_progress(true),
+        )
+        .with_context(|| format!("Failed to initialize cross-encoder '{}'", tag.as_str()))
+    })
+}
+
+/// Maximum number of tokens the model reads per input, if the tokenizer truncates.
+pub fn max_input_tokens(model: &TextEmbedding) -> Option<usize> {
+    model.tokenizer.get_truncation().map(|params| params.max_length)
+}
+
+/// Tokens shared by consecutive chunks of a long text, so code around a chunk boundary
+/// is seen whole by at least one chunk.
+const CHUNK_OVERLAP_TOKENS: usize = 64;
+
+/// Byte ranges of `text` that each fit the model's input window, overlapping by
+/// `CHUNK_OVERLAP_TOKENS`. Text that fits is a single range covering all of it.
+pub fn chunk_ranges(model: &TextEmbedding, text: &str) -> Vec<Range<usize>> {
+    let whole = std::iter::once(0..text.len()).collect();
+    let (Some(max_tokens), Ok(encoding)) = (max_input_tokens(model), model.tokenizer.encode(text, true)) else {
+        return whole;
+    };
+    if encoding.get_overflowing().is_empty() {
+        return whole;
+    }
+    // The tokenizer truncates to the window and returns the rest as overflowing
+    // encodings; collect the offsets of all their tokens, without the special tokens
+    // (empty offsets) it adds to each.
+    let mut offsets: Vec<(usize, usize)> = std::iter::once(&encoding)
+        .chain(encoding.get_overflowing())
+        .flat_map(|part| part.get_offsets().iter().copied())
+        .filter(|(start, end)| end > start)
+        .collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    let special_tokens = encoding.len() - encoding.get_offsets().iter().filter(|(start, end)| end > start).count();
+    let window = max_tokens.saturating_sub(special_tokens).max(CHUNK_OVERLAP_TOKENS + 1);
+    token_windows(&offsets, window)
+}
+
+/// Byte ranges spanning `window` consecutive tokens of the sorted token `offsets`,
+/// each starting `CHUNK_OVERLAP_TOKENS` tokens before the previous one ends.
+fn token_windows(offsets: &[(usize, usize)], window: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut first = 0;
+    loop {
+        let last = (first + window).min(offsets.len()) - 1;
+        ranges.push(offsets[first].0..offsets[last].1);
+        if last + 1 == offsets.len() {
+            return ranges;
+        }
+        first += window - CHUNK_OVERLAP_TOKENS;
+    }
+}
+
+/// Element-wise mean of the embeddings of a function's chunks.
+pub fn mean_embedding(embeddings: impl Iterator<Item = Vec<f32>>) -> Vec<f32> {
+    let mut count = 0;
+    let mut sum: Vec<f32> = Vec::new();
+    for embedding in embeddings {
+        if sum.is_empty() {
+            sum = vec![0.0; embedding.len()];
+        }
+        for (total, x) in sum.iter_mut().zip(embedding) {
+            *total += x;
+        }
+        count += 1;
+    }
+    sum.iter_mut().for_each(|total| *total /= count.max(1) as f32);
+    sum
+}
+
+/// Stores `embedding` at the precision `quantization` says.
+pub fn quantize(embedding: &[f32], quantization: Quantization) -> StoredEmbedding {
+    match quantization {
+        Quantization::F32 => StoredEmbedding::F32(embedding.to_vec()),
+        Quantization::F16 => StoredEmbedding::F16(embedding.iter().map(|&x| f16::from_f32(x).to_bits()).collect()),
+        Quantization::Int8 => {
+            let scale = embedding.iter().fold(0.0f32, |max, x| max.max(x.abs())) / i8::MAX as f32;
+            let values = embedding
+                .iter()
+                .map(|&x| if scale > 0.0 { (x / scale).round() as i8 } else { 0 })
+                .collect();
+            StoredEmbedding::Int8 { scale, values }
+        }
+    }
+}
+
+/// The `f32` vector a cached embedding approximates.
+pub fn dequantize(stored: &StoredEmbedding) -> Vec<f32> {
+    match stored {
+        StoredEmbedding::F32(values) => values.clone(),
+        StoredEmbedding::F16(bits) => bits.iter().map(|&bits| f16::from_bits(bits).to_f32()).collect(),
+        StoredEmbedding::Int8 { scale, values } => values.iter().map(|&x| x as f32 * scale).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit-length vector with components of mixed sign and magnitude, like the
+    /// normalized embeddings the models return.
+    fn sample_embedding() -> Vec<f32> {
+        let raw: Vec<f32> = (0..384).map(|i| ((i as f32) * 0.37).sin() * (1.0 + (i % 7) as f32)).collect();
+        let norm = raw.iter().map(|x| x * x).sum::<f32>().sqrt();
+        raw.iter().map(|x| x / norm).collect()
+    }
+
+    #[test]
+    fn f32_round_trip_is_exact() {
+        let embedding = sample_embedding();
+        assert_eq!(dequantize(&quantize(&embedding, Quantization::F32)), embedding);
+    }
+
+    #[test]
+    fn f16_round_trip_keeps_half_precision() {
+        let embedding = sample_embedding();
+        let restored = dequantize(&quantize(&embedding, Quantization::F16));
+        assert_eq!(restored.len(), embedding.len());
+        for (x, y) in embedding.iter().zip(&restored) {
+            // Half the spacing of f16 values near `x`, plus the subnormal spacing.
+            assert!((x - y).abs() <= x.abs() / 2048.0 + 1e-7, "{} restored as {}", x, y);
+        }
+    }
+
+    #[test]
+    fn int8_round_trip_is_within_half_a_step() {
+        let embedding = sample_embedding();
+        let stored = quantize(&embedding, Quantization::Int8);
+        let StoredEmbedding::Int8 { scale, .. } = stored else { panic!("expected int8, got {:?}", stored) };
+        let largest = embedding.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+        assert_eq!(scale, largest / i8::MAX as f32);
+        let restored = dequantize(&stored);
+        assert_eq!(restored.len(), embedding.len());
+        for (x, y) in embedding.iter().zip(&restored) {
+            assert!((x - y).abs() <= scale / 2.0 + 1e-6, "{} restored as {}", x, y);
+        }
+    }
+
+    /// Offsets of `count` tokens of 3 bytes, separated by single spaces.
+    fn token_offsets(count: usize) -> Vec<(usize, usize)> {
+        (0..count).map(|i| (i * 4, i * 4 + 3)).collect()
+    }
+
+    #[test]
+    fn token_windows_overlap_and_cover_every_token() {
+        let window = CHUNK_OVERLAP_TOKENS + 100;
+        let offsets = token_offsets(3 * window);
+        let ranges = token_windows(&offsets, window);
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, offsets.last().unwrap().1);
+        for pair in ranges.windows(2) {
+            // The next chunk starts CHUNK_OVERLAP_TOKENS tokens before this one ends.
+            assert_eq!(pair[1].start, pair[0].end + 1 - CHUNK_OVERLAP_TOKENS * 4);
+        }
+        for range in &ranges {
+            let tokens = offsets.iter().filter(|(start, end)| range.start <= *start && *end <= range.end).count();
+            assert!(tokens <= window, "{:?} holds {} tokens", range, tokens);
+        }
+    }
+
+    #[test]
+    fn token_windows_of_a_short_text_are_one_range() {
+        let offsets = token_offsets(10);
+        assert_eq!(token_windows(&offsets, CHUNK_OVERLAP_TOKENS + 1), vec![0..39]);
+    }
+
+    #[test]
+    fn zero_vectors_round_trip_to_zero() {
+        let zeros = vec![0.0f32; 16];
+        for quantization in [Quantization::F32, Quantization::F16, Quantization::Int8] {
+            assert_eq!(dequantize(&quantize(&zeros, quantization)), zeros, "{:?}", quantization);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config;
+use serde_json::Value;
+
+/// Extensions of container formats whose source is extracted before it is searched or
+/// parsed, rather than read as stored.
+pub const EXTRACTED_EXTENSIONS: &[&str] = &["ipynb"];
+
+/// Source text extracted from a container file.
+pub struct Extracted {
+    pub text: String,
+    /// Extension of the language the text is written in, if it is one the parser
+    /// supports.
+    pub extension: Option<&'static str>,
+}
+
+/// Whether files with `extension` are extracted (see `extract`).
+pub fn is_extracted(extension: &str) -> bool {
+    EXTRACTED_EXTENSIONS.contains(&extension.trim_start_matches('.'))
+}
+
+/// Extracts the source of `text`, the content of a file with `extension`. Returns `None`
+/// for extensions without an extractor or content the extractor cannot read.
+pub fn extract(extension: &str, text: &str) -> Option<Extracted> {
+    match extension.trim_start_matches('.') {
+        "ipynb" => notebook_source(text),
+        _ => None,
+    }
+}
+
+/// The cells of a Jupyter notebook in the "percent" script format: each cell starts
+/// with a `# %%` line, code cells keep their source, and markdown cells become line
+/// comments. Outputs are dropped. Line numbers refer to this text, not the JSON.
+fn notebook_source(text: &str) -> Option<Extracted> {
+    let notebook: Value = serde_json::from_str(text).ok()?;
+    let metadata = &notebook["metadata"];
+    let language = metadata["kernelspec"]["language"]
+        .as_str()
+        .or_else(|| metadata["language_info"]["name"].as_str())
+        .unwrap_or("python");
+    let extension = config::language_for_interpreter(language).and_then(|spec| spec.extensions.first().copied());
+    // Kernels for Python, R, Julia and most others comment with `#`.
+    let comment = match extension {
+        Some("rs" | "ts" | "cs") => "//",
+        _ => "#",
+    };
+
+    let mut source = String::new();
+    for cell in notebook["cells"].as_array()? {
+        // nbformat 4 stores a cell's source as a list of lines, but a string is valid too.
+        let cell_source: String = match &cell["source"] {
+            Value::String(s) => s.clone(),
+            Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+            _ => continue,
+        };
+        match cell["cell_type"].as_str() {
+            Some("code") => {
+                source.push_str(&format!("{} %%\n", comment));
+                source.push_str(&cell_source);
+            }
+            Some("markdown") => {
+                source.push_str(&format!("{} %% [markdown]\n", comment));
+                for line in cell_source.lines() {
+                    source.push_str(comment);
+                    if !line.is_empty() {
+                        source.push(' ');
+                        source.push_str(line);
+                    }
+                    source.push('\n');
+                }
+            }
+            _ => continue,
+        }
+        if !source.ends_with('\n') {
+            source.push('\n');
+        }
+        source.push('\n');
+    }
+    Some(Extracted { text: source, extension })
+}
@@ -1,355 +1,1444 @@
+use crate::ann;
+use crate::atomic_write;
+use crate::audit;
+use crate::call_graph;
+use crate::cancellation::{self, CancelFlag};
+use crate::config;
 use crate::embedding;
+use crate::extract;
+use crate::fuzzy;
+use crate::git;
+use crate::lexical;
+use crate::options::{Budget, BudgetDefaults, ConceptSearchOptions, FuzzyFindOptions, ModelRouting, RuntimeConfig, ScanOptions, SearchOptions};
+use crate::parse_cache;
+use crate::parsing;
+use crate::pattern::{LinePattern, MultiMatcher, PatternMatch, SearchQuery};
+use crate::pool;
+use crate::progress::{ProgressReporter, ScanProgressFn};
+use crate::ranking;
+use crate::runtime;
 use crate::scanner;
 use crate::structs::{
-    CachedFileEmbeddings, ConceptSearchResultItem, ConceptSearchServiceResult,
-    ConceptSearchStats, FileSearchResult, ScanResult, SearchMatch,
-    SearchServiceResult, SearchStats,
+    AuditLogResult, CacheClearResult, CacheCompactionResult, CachedFileEmbeddings, EMBEDDING_CACHE_SCHEMA_VERSION, CallGraphResult, ConceptCacheModelStats, ConceptCacheStats, ConceptIndexResult, ConceptSearchResultItem, ConceptSearchServiceResult,
+    ConceptSearchProgress, ConceptSearchStats, CrossEncoderTag, DefinitionResult, EmbeddingModelTag, FileContext, FileSearchResult, FunctionInfo, FuzzyFindResult, Granularity, LineOffset, MatchContext, ProgressPhase, Quantization, ReferenceResult, RuntimeInitResult, RuntimeShutdownResult, ScanProgress, ScanResult, ScanStats, ScoringBackend,
+    SearchMatch, SearchServiceResult, SearchStats, SymbolSyncResult, TermCorrection, TodoScanResult, WarmCacheResult, WatchStartResult, WriteFileResult,
 };
+use crate::summaries;
+use crate::symbols;
 use crate::utils;
+use crate::vector_file::{self, VectorFile, VectorFileWriter};
+use crate::watcher::{self, WatchKind};
 
 use anyhow::Context as AnyhowContext;
+use fastembed::TextEmbedding;
+use once_cell::sync::Lazy;
 use ignore::WalkBuilder;
 use rayon::prelude::*;
+use serde::de::DeserializeOwned;
 use sha2::{Digest, Sha256};
-use sled;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::{CStr, CString};
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::os::raw::c_char;
-use std::path::{Path};
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-// Helper function for concept_search, kept close to its FFI counterpart
-fn concept_search_inner(
-    root_path_str: &str,
-    query_str: &str,
-    extensions: Vec<String>,
+/// A function tracked through the cache, embedding, and ranking phases of concept search.
+#[derive(Clone, Debug)]
+struct FunctionIdentifier {
+    file: String,
+    function: FunctionInfo,
+}
+
+/// A function paired with its embedding vector.
+type FunctionEmbedding = (FunctionIdentifier, Vec<f32>);
+
+/// Identifies a function in the approximate index across searches.
+fn ann_key(ident: &FunctionIdentifier) -> String {
+    format!("{}#{}", ident.file, ident.function.qualified_name)
+}
+
+/// Builds the text that represents a function in the embedding space, or a class or
+/// file summary at coarser `granularity`.
+///
+/// Documentation is included when present since it usually states the function's intent
+/// more directly than the body does.
+fn embedding_text(file_path: &str, func_info: &FunctionInfo, granularity: Granularity) -> String {
+    let label = match granularity {
+        Granularity::Function => "Function",
+        Granularity::Class => "Class",
+        Granularity::File => "File summary",
+    };
+    match func_info.comment.as_deref().filter(|c| !c.trim().is_empty()) {
+        Some(comment) => format!(
+            "{}: {}\nFile: {}\nDocumentation:\n{}\nBody:\n{}",
+            label,
+            func_info.qualified_name,
+            file_path,
+            comment,
+            func_info.body.as_deref().unwrap_or("")
+        ),
+        None => format!(
+            "{}: {}\nFile: {}\nBody:\n{}",
+            label,
+            func_info.qualified_name,
+            file_path,
+            func_info.body.as_deref().unwrap_or("")
+        ),
+    }
+}
+
+/// Whether a function passes the result filters in `options`.
+fn passes_filters(ident: &FunctionIdentifier, options: &ConceptSearchOptions) -> bool {
+    if options.exclude_tests && ident.function.annotations.iter().any(|a| config::is_test_annotation(a)) {
+        return false;
+    }
+    match &options.parameter_type {
+        Some(wanted) => ident.function.parameters.iter().any(|p| {
+            p.type_name.as_deref().is_some_and(|t| t.contains(wanted.as_str()))
+        }),
+        None => true,
+    }
+}
+
+/// Builds the text that represents a function in the BM25 index.
+///
+/// Names are repeated so that exact identifier hits outweigh incidental mentions in bodies.
+fn lexical_text(func_info: &FunctionInfo) -> String {
+    format!(
+        "{} {} {}\n{}\n{}",
+        func_info.name,
+        func_info.name,
+        func_info.qualified_name,
+        func_info.comment.as_deref().unwrap_or(""),
+        func_info.body.as_deref().unwrap_or("")
+    )
+}
+
+/// Flattens scanned files into one identifier per function.
+fn identifiers_from_contexts(file_contexts: &[FileContext]) -> Vec<FunctionIdentifier> {
+    file_contexts
+        .iter()
+        .flat_map(|file_context| {
+            file_context.functions.iter().map(|function| FunctionIdentifier {
+                file: file_context.path.clone(),
+                function: function.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Ranks functions with BM25 over their names, documentation, and bodies.
+///
+/// Scores are divided by the best score so they share the 0–1 range of embedding scores.
+/// Also returns any misspelled query terms that were corrected against the index.
+fn rank_lexically(
+    query: &str,
+    identifiers: &[FunctionIdentifier],
+    options: &ConceptSearchOptions,
+) -> (Vec<(usize, f32)>, Vec<TermCorrection>) {
+    let documents: Vec<String> = identifiers
+        .iter()
+        .map(|ident| lexical_text(&ident.function))
+        .collect();
+    let index = lexical::Bm25Index::new(&documents);
+    let (terms, corrections) = index.resolve_query(query, options.spelling_tolerance);
+    let scores = index.score_terms(&terms);
+    let max_score = scores.iter().cloned().fold(0.0_f32, f32::max);
+
+    let mut ranked: Vec<(usize, f32)> = scores
+        .into_iter()
+        .enumerate()
+        .filter(|(i, score)| *score > 0.0 && passes_filters(&identifiers[*i], options))
+        .map(|(i, score)| (i, score / max_score))
+        .collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    (ranked, corrections)
+}
+
+/// Rank offset of reciprocal rank fusion; larger values flatten the gap between the
+/// top ranks. 60 is the value from the original RRF paper.
+const RRF_K: f32 = 60.0;
+
+/// Combines the embedding ranking `similarities` with a BM25 ranking of the same
+/// `identifiers` by reciprocal rank fusion: each function scores `1 / (RRF_K + rank)`
+/// per ranking it appears in. Scores are divided by that of a function ranked first by
+/// both, so they stay within 0–1. Also returns the corrected query terms.
+fn fuse_with_keywords(
+    query: &str,
+    similarities: &[(usize, f32)],
+    identifiers: &[FunctionIdentifier],
+    options: &ConceptSearchOptions,
+) -> (Vec<(usize, f32)>, Vec<TermCorrection>) {
+    let (lexical, corrections) = rank_lexically(query, identifiers, options);
+    let mut fused: HashMap<usize, f32> = HashMap::new();
+    for ranking in [similarities, lexical.as_slice()] {
+        for (rank, (idx, _)) in ranking.iter().enumerate() {
+            *fused.entry(*idx).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let best = 2.0 / (RRF_K + 1.0);
+    let mut ranked: Vec<(usize, f32)> = fused.into_iter().map(|(idx, score)| (idx, score / best)).collect();
+    // Ties are broken by position, so equal scores come out in a stable order.
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    (ranked, corrections)
+}
+
+/// Runs a complete BM25 concept search over already-scanned files. With `ranking`,
+/// the ranking is handed over there instead of being turned into results.
+fn lexical_search_result(
+    query: &str,
+    file_contexts: &[FileContext],
     top_n: usize,
-    timeout_ms: u32,
-    debug: bool,
-) -> Result<ConceptSearchServiceResult, anyhow::Error> {
-    let start_time = Instant::now();
-    let root_path_obj = Path::new(root_path_str);
+    options: &ConceptSearchOptions,
+    start_time: Instant,
+    ranking: Option<&mut RootRanking>,
+) -> ConceptSearchServiceResult {
+    let identifiers = identifiers_from_contexts(file_contexts);
+    let functions_analyzed = identifiers.len();
+    let (mut ranked, corrected_terms) = rank_lexically(query, &identifiers, options);
+    ranked.truncate(above_min_similarity(&ranked, options).len());
+    let results = match ranking {
+        Some(ranking) => {
+            *ranking = RootRanking { identifiers, similarities: ranked };
+            Vec::new()
+        }
+        None => build_result_items(&ranked, &identifiers, top_n, options),
+    };
+    ConceptSearchServiceResult {
+        results,
+        stats: ConceptSearchStats {
+            functions_analyzed,
+            search_duration_seconds: start_time.elapsed().as_secs_f32(),
+            scoring_backend: ScoringBackend::Bm25,
+            corrected_terms,
+            ..Default::default()
+        },
+        error: None,
+        debug_log: None,
+    }
+}
+
+/// Candidates considered per result slot when diversifying results.
+const MMR_POOL_FACTOR: usize = 5;
+
+/// How much two results cover the same area of the codebase: fully for the same file,
+/// half for files in the same directory.
+fn location_overlap(a: &str, b: &str) -> f32 {
+    if a == b {
+        1.0
+    } else if Path::new(a).parent() == Path::new(b).parent() {
+        0.5
+    } else {
+        0.0
+    }
+}
+
+/// Picks `top_n` of the best-ranked entries by maximal marginal relevance: each pick
+/// is the candidate whose score, minus `diversity` times its largest overlap with an
+/// earlier pick (see `location_overlap`), is highest. Ties go to the better-ranked one.
+fn diversify(
+    ranked: &[(usize, f32)],
+    identifiers: &[FunctionIdentifier],
+    top_n: usize,
+    diversity: f32,
+) -> Vec<(usize, f32)> {
+    let mut pool: Vec<(usize, f32)> = ranked.iter().take(top_n.saturating_mul(MMR_POOL_FACTOR)).copied().collect();
+    let mut picked: Vec<(usize, f32)> = Vec::new();
+    while picked.len() < top_n && !pool.is_empty() {
+        let marginal = |(idx, score): (usize, f32)| {
+            let overlap = picked
+                .iter()
+                .map(|(other, _)| location_overlap(&identifiers[idx].file, &identifiers[*other].file))
+                .fold(0.0, f32::max);
+            score - diversity * overlap
+        };
+        let best = (0..pool.len())
+            .rev()
+            .max_by(|&a, &b| marginal(pool[a]).total_cmp(&marginal(pool[b])))
+            .unwrap_or(0);
+        picked.push(pool.remove(best));
+    }
+    picked
+}
+
+/// Marker ending bodies cut to `max_body_chars`.
+const TRUNCATION_MARKER: &str = "…";
 
-    // Configure paths for model cache and embedding database
-    let model_init_cache_dir = root_path_obj.join(".cache").join("file_scanner_model_cache");
+/// `body` cut to `max_chars` characters followed by `TRUNCATION_MARKER`, and whether it
+/// was cut. `0` keeps it whole.
+fn truncate_body(body: Option<String>, max_chars: usize) -> (Option<String>, bool) {
+    match body {
+        Some(body) if max_chars > 0 => match body.char_indices().nth(max_chars) {
+            Some((cut, _)) => (Some(format!("{}{}", &body[..cut], TRUNCATION_MARKER)), true),
+            None => (Some(body), false),
+        },
+        body => (body, false),
+    }
+}
+
+/// The start of `ranked` (sorted, best first) scored at least `options.min_similarity`.
+///
+/// The threshold is on first-stage scores (cosine, BM25, or fused), so rankings are cut
+/// before `rerank` re-scores them on the re-ranker's own scale, and not after.
+fn above_min_similarity<'a>(ranked: &'a [(usize, f32)], options: &ConceptSearchOptions) -> &'a [(usize, f32)] {
+    &ranked[..ranked.partition_point(|(_, sim)| *sim >= options.min_similarity)]
+}
+
+/// Converts ranked `(index into identifiers, score)` pairs, already cut to
+/// `above_min_similarity`, into the top `top_n` result items, with `options.diversity`
+/// spreading them over the codebase (see `diversify`).
+fn build_result_items(
+    ranked: &[(usize, f32)],
+    identifiers: &[FunctionIdentifier],
+    top_n: usize,
+    options: &ConceptSearchOptions,
+) -> Vec<ConceptSearchResultItem> {
+    let relevant: Vec<(usize, f32)> = ranked
+        .iter()
+        .take(top_n.saturating_mul(MMR_POOL_FACTOR))
+        .copied()
+        .collect();
+    let ranked = if options.diversity > 0.0 {
+        diversify(&relevant, identifiers, top_n, options.diversity)
+    } else {
+        relevant
+    };
+    ranked
+        .iter()
+        .take(top_n)
+        .filter_map(|(idx, sim)| {
+            identifiers.get(*idx).map(|ident| {
+                let (body, truncated) = truncate_body(ident.function.body.clone(), options.max_body_chars);
+                let (context_before, context_after) = utils::read_surrounding_lines(
+                    Path::new(&ident.file),
+                    ident.function.start_line,
+                    ident.function.end_line,
+                    options.context_lines,
+                );
+                ConceptSearchResultItem {
+                    file: ident.file.clone(),
+                    function: ident.function.name.clone(),
+                    qualified_name: ident.function.qualified_name.clone(),
+                    similarity: *sim,
+                    start_line: ident.function.start_line,
+                    end_line: ident.function.end_line,
+                    signature: (options.granularity != Granularity::File)
+                        .then(|| summaries::signature(&ident.function).to_string()),
+                    body,
+                    truncated,
+                    context_before,
+                    context_after,
+                    uri: options.uri_format.map(|format| {
+                        utils::location_uri(
+                            format,
+                            &ident.file,
+                            ident.function.start_line,
+                            ident.function.end_line,
+                        )
+                    }),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Returns the options JSON text for the audit log, or `""` if absent or not UTF-8.
+unsafe fn options_text<'a>(options_c: *const c_char) -> &'a str {
+    if options_c.is_null() {
+        return "";
+    }
+    CStr::from_ptr(options_c).to_str().unwrap_or_default()
+}
+
+/// Reads the root argument of `scan_and_parse`, `project_wide_search`, and
+/// `concept_search`: a single path or, for workspaces whose code lives in several
+/// top-level folders, a JSON array of paths. Repeated roots and roots nested inside
+/// another root are dropped, so no file is visited twice.
+fn parse_roots(root_arg: &str) -> Result<Vec<String>, String> {
+    if !root_arg.trim_start().starts_with('[') {
+        return Ok(vec![root_arg.to_string()]);
+    }
+    let roots: Vec<String> = serde_json::from_str::<Vec<String>>(root_arg)
+        .map_err(|e| format!("Failed to parse root paths JSON: {}. Input was: '{}'", e, root_arg))?
+        .into_iter()
+        .filter(|root| !root.is_empty())
+        .collect();
+    let resolved: Vec<PathBuf> = roots.iter().map(|root| utils::canonical_or_original(Path::new(root))).collect();
+    let kept: Vec<String> = roots
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| {
+            !resolved.iter().enumerate().any(|(j, other)| {
+                // Of two identical roots, the first one is kept.
+                j != i && resolved[i].starts_with(other) && (resolved[i] != *other || j < i)
+            })
+        })
+        .map(|(_, root)| root.clone())
+        .collect();
+    if kept.is_empty() {
+        return Err("Root paths JSON array is empty.".to_string());
+    }
+    Ok(kept)
+}
+
+/// Parses an optional JSON options object passed from C.
+///
+/// A null pointer or an empty string yields the default options. A `"budget"` key fills
+/// in the settings of its bundle (see `BudgetDefaults`) that the object does not set.
+unsafe fn parse_options_json<T: DeserializeOwned + Default + BudgetDefaults>(
+    options_c: *const c_char,
+) -> Result<T, String> {
+    if options_c.is_null() {
+        return Ok(T::default());
+    }
+    let options_str = CStr::from_ptr(options_c)
+        .to_str()
+        .map_err(|e| format!("Options string is not valid UTF-8: {}", e))?;
+    if options_str.trim().is_empty() {
+        return Ok(T::default());
+    }
+    let mut value: serde_json::Value = serde_json::from_str(options_str)
+        .map_err(|e| format!("Failed to parse options JSON: {}. Input was: '{}'", e, options_str))?;
+    if let Some(object) = value.as_object_mut() {
+        if let Some(budget) = object.remove("budget") {
+            let budget: Budget = serde_json::from_value(budget)
+                .map_err(|e| format!("Invalid budget: {}. Expected \"fast\", \"balanced\" or \"thorough\".", e))?;
+            if let serde_json::Value::Object(defaults) = T::budget_defaults(budget) {
+                for (key, setting) in defaults {
+                    object.entry(key).or_insert(setting);
+                }
+            }
+        }
+    }
+    serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse options JSON: {}. Input was: '{}'", e, options_str))
+}
+
+/// Embedding caches opened so far, per root. sled locks its directory, so a watch
+/// refreshing a root and a concept search over it must share one handle.
+static EMBEDDING_DATABASES: Lazy<Mutex<HashMap<PathBuf, sled::Db>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Opens the shared model directory (see `config::model_cache_dir`) and the project's
+/// embedding cache under `<root>/.cache`.
+fn open_project_caches(root_path_obj: &Path) -> Result<(PathBuf, sled::Db), anyhow::Error> {
+    let model_init_cache_dir = config::model_cache_dir(root_path_obj);
     fs::create_dir_all(&model_init_cache_dir)
         .with_context(|| format!("Failed to create model cache directory at {:?}", model_init_cache_dir))?;
 
-    let embedding_db_dir = root_path_obj.join(".cache").join("file_scanner_embedding_cache");
+    let embedding_db_dir = root_path_obj.join(config::CACHE_DIR).join("file_scanner_embedding_cache");
     fs::create_dir_all(&embedding_db_dir)
         .with_context(|| format!("Failed to create embedding DB directory at {:?}", embedding_db_dir))?;
-    
+
     let db_path = embedding_db_dir.join("embeddings.sled");
-    let db = sled::open(&db_path)
-        .with_context(|| format!("Failed to open embedding cache DB at {:?}", db_path))?;
+    let mut databases = EMBEDDING_DATABASES.lock().unwrap_or_else(|e| e.into_inner());
+    let db = match databases.get(&db_path) {
+        Some(db) => db.clone(),
+        None => {
+            let db = sled::open(&db_path)
+                .with_context(|| format!("Failed to open embedding cache DB at {:?}", db_path))?;
+            databases.insert(db_path, db.clone());
+            db
+        }
+    };
+    Ok((model_init_cache_dir, db))
+}
 
-    let mut debug_log_accumulator: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+/// Number of functions embedded per model call; `on_chunk` observers see progress at this granularity.
+const EMBED_CHUNK_SIZE: usize = 256;
 
-    if let Some(log_acc) = &mut debug_log_accumulator {
-        log_acc.push(format!(
-            "[ConceptSearchInner] START. Debug: {}, Extensions: {:?}, Query: '{}', Path: '{}', DB: '{}'",
-            debug, extensions, query_str, root_path_str, db_path.display()
-        ));
+/// Key of a file's cache entry for `tag`, so each model keeps its own vectors. Class
+/// and file summaries are cached apart from the functions of the same file.
+fn embedding_cache_key(tag: EmbeddingModelTag, granularity: Granularity, relative_path: &str) -> String {
+    match granularity {
+        Granularity::Function => format!("{}/{}", tag.as_str(), relative_path),
+        _ => format!("{}#{}/{}", tag.as_str(), granularity.as_str(), relative_path),
     }
+}
 
-    // 1. Scan files to get function contexts
-    let scan_result = scanner::perform_scan(root_path_str, extensions.clone(), 3, timeout_ms, debug);
-    if debug {
-        if let Some(scan_log) = scan_result.debug_log { // scan_result is moved if not careful
-            debug_log_accumulator.get_or_insert_with(Vec::new).extend(scan_log);
-        }
-    }
-    
-    if scan_result.file_contexts.is_empty() {
-         if let Some(log_ref) = &mut debug_log_accumulator {
-            log_ref.push("[ConceptSearchInner] No file contexts found from scan.".to_string());
-        }
-        return Ok(ConceptSearchServiceResult {
-            results: vec![],
-            stats: ConceptSearchStats { functions_analyzed: 0, search_duration_seconds: start_time.elapsed().as_secs_f32() },
-            error: Some("Initial file scan found no processable files or functions.".to_string()),
-            debug_log: debug_log_accumulator,
-        });
-    }
+/// Key of a function's embedding within its file's cache entry: a hash of the text it
+/// is embedded from, so a function keeps its embedding while other parts of the file
+/// change, and is re-embedded as soon as anything it is embedded from changes.
+fn embedding_text_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// A function still to be embedded: its identifier, embedding text, and text hash.
+type PendingEmbedding = (FunctionIdentifier, String, String);
+
+/// A cached function embedding and the hash of the text it was embedded from.
+type CachedEmbedding = (FunctionEmbedding, String);
+
+/// A file cache entry to rewrite: the file's relative path and its embeddings by text hash.
+type CacheUpdate = (String, HashMap<String, Vec<f32>>);
 
-    // 2. Process file contexts: check cache, collect texts for embedding
-    //    (file_path_abs, func_name, func_body_for_result_struct), embedding_vector
-    let mut all_function_embeddings: Vec<((String, String, Option<String>), Vec<f32>)> = Vec::new();
-    //    (file_path_abs, func_name, func_body_for_result_struct), text_to_embed
-    let mut texts_to_embed_collector: Vec<((String, String, Option<String>), String)> = Vec::new();
+/// What the cache lookup found for one file: its cached embeddings, the functions still
+/// to embed, the entry to rewrite if any, and whether the vector file served it.
+type FileEmbeddingLookup = (Vec<CachedEmbedding>, Vec<PendingEmbedding>, Option<CacheUpdate>, bool);
 
-    let processing_results: Vec<(
-        Vec<((String, String, Option<String>), Vec<f32>)>, // cached_embeddings for this file
-        Vec<((String, String, Option<String>), String)>,   // texts_to_embed for this file
-        Option<(String, String, HashMap<String, Vec<f32>>)> // Option<(rel_path, hash, func_embeddings_map)> for cache update
-    )> = scan_result
-        .file_contexts
+/// Returns an embedding for every function in `file_contexts`, reusing cached vectors
+/// for functions whose embedding text is unchanged and embedding everything else with
+/// `model` (identified by `tag`). Newly embedded functions are written back to the
+/// cache, stored with `quantization`; their count is returned alongside. Warm files
+/// are read from the project's vector file (see `VectorFile`), which is rewritten
+/// whenever anything had to come from elsewhere.
+///
+/// `on_chunk` is called with every cached embedding up front and then with each chunk
+/// of new embeddings as it is produced, in the same order as the returned vector.
+/// Once `cancel` is set or its deadline passes, no further chunks are embedded.
+#[allow(clippy::too_many_arguments)]
+fn embed_functions(
+    root_path_obj: &Path,
+    db: &sled::Db,
+    tag: EmbeddingModelTag,
+    granularity: Granularity,
+    quantization: Quantization,
+    model: &TextEmbedding,
+    file_contexts: &[FileContext],
+    debug_log: &mut Option<Vec<String>>,
+    cancel: &CancelFlag,
+    on_chunk: &mut dyn FnMut(&[FunctionEmbedding]),
+) -> Result<(Vec<FunctionEmbedding>, usize), anyhow::Error> {
+    // Entries of a model whose vectors changed length (e.g. a different local model
+    // under the same name) are re-embedded rather than compared with the query.
+    let expected_dimension = embedding::dimension(tag);
+
+    // Files whose functions are exactly the ones the vector file holds for them are
+    // served from it. The others check their sled entry: functions whose text hash is
+    // in it reuse its embedding, the rest are collected for embedding. Files with new,
+    // changed, or removed functions get their entry rewritten below, starting from the
+    // reused embeddings (text hash -> vector).
+    let vector_file_path = vector_file::path(root_path_obj, tag, granularity);
+    let vectors = VectorFile::open(&vector_file_path, expected_dimension);
+    let processing_results: Vec<FileEmbeddingLookup> = file_contexts
         .par_iter()
         .map(|file_context| {
-            let mut file_cached_embeddings = Vec::new();
-            let mut file_texts_to_embed = Vec::new();
-            let mut functions_for_this_file_cache_update: HashMap<String, Vec<f32>> = HashMap::new();
-
             let file_path_abs = Path::new(&file_context.path);
             let relative_file_path = file_path_abs.strip_prefix(root_path_obj).unwrap_or(file_path_abs);
             let cache_key = relative_file_path.to_string_lossy().into_owned();
 
-            let file_content = match fs::read_to_string(file_path_abs) {
-                Ok(content) => content,
-                Err(_) => return (file_cached_embeddings, file_texts_to_embed, None), // Skip if file unreadable
-            };
+            let functions: Vec<PendingEmbedding> = file_context
+                .functions
+                .iter()
+                .map(|func_info| {
+                    let identifier = FunctionIdentifier { file: file_context.path.clone(), function: func_info.clone() };
+                    let text_to_embed = embedding_text(&file_context.path, func_info, granularity);
+                    let text_hash = embedding_text_hash(&text_to_embed);
+                    (identifier, text_to_embed, text_hash)
+                })
+                .collect();
 
-            let mut hasher = Sha256::new();
-            hasher.update(file_content.as_bytes());
-            let current_file_hash = format!("{:x}", hasher.finalize());
-            
-            let mut needs_re_embedding_for_cache_update = false;
-
-            match db.get(&cache_key) {
-                Ok(Some(ivec)) => {
-                    match bincode::deserialize::<CachedFileEmbeddings>(&ivec) {
-                        Ok(cached_data) if cached_data.file_content_hash == current_file_hash => {
-                            for func_info in &file_context.functions {
-                                let identifier = (file_context.path.clone(), func_info.name.clone(), func_info.body.clone());
-                                if let Some(embedding) = cached_data.function_embeddings.get(&func_info.name) {
-                                    file_cached_embeddings.push((identifier, embedding.clone()));
-                                    functions_for_this_file_cache_update.insert(func_info.name.clone(), embedding.clone());
-                                } else { // New function in an otherwise unchanged file
-                                    let text_to_embed = format!("Function: {}\nFile: {}\nBody:\n{}", func_info.name, file_context.path, func_info.body.as_deref().unwrap_or(""));
-                                    file_texts_to_embed.push((identifier, text_to_embed));
-                                    needs_re_embedding_for_cache_update = true;
-                                }
-                            }
-                        }
-                        _ => { // Hash mismatch or deserialization error
-                            needs_re_embedding_for_cache_update = true;
-                            for func_info in &file_context.functions {
-                                let identifier = (file_context.path.clone(), func_info.name.clone(), func_info.body.clone());
-                                let text_to_embed = format!("Function: {}\nFile: {}\nBody:\n{}", func_info.name, file_context.path, func_info.body.as_deref().unwrap_or(""));
-                                file_texts_to_embed.push((identifier, text_to_embed));
-                            }
-                        }
-                    }
+            if let Some(vectors) = &vectors {
+                let text_hashes: Vec<&str> = functions.iter().map(|(_, _, text_hash)| text_hash.as_str()).collect();
+                if let Some(rows) = vectors.rows_for(&cache_key, &text_hashes) {
+                    let file_cached_embeddings = functions
+                        .into_iter()
+                        .zip(rows)
+                        .map(|((identifier, _, text_hash), row)| ((identifier, vectors.row(row)), text_hash))
+                        .collect();
+                    return (file_cached_embeddings, Vec::new(), None, true);
                 }
-                _ => { // Not in cache or DB error
-                    needs_re_embedding_for_cache_update = true;
-                    for func_info in &file_context.functions {
-                        let identifier = (file_context.path.clone(), func_info.name.clone(), func_info.body.clone());
-                        let text_to_embed = format!("Function: {}\nFile: {}\nBody:\n{}", func_info.name, file_context.path, func_info.body.as_deref().unwrap_or(""));
-                        file_texts_to_embed.push((identifier, text_to_embed));
+            }
+
+            let cached_data = db
+                .get(embedding_cache_key(tag, granularity, &cache_key))
+                .ok()
+                .flatten()
+                .and_then(|ivec| bincode::deserialize::<CachedFileEmbeddings>(&ivec).ok())
+                .filter(|cached_data| {
+                    cached_data.schema_version == EMBEDDING_CACHE_SCHEMA_VERSION
+                        && cached_data.model == tag
+                        && expected_dimension.is_none_or(|dimension| cached_data.dimension == dimension)
+                });
+
+            let mut file_cached_embeddings = Vec::new();
+            let mut file_texts_to_embed = Vec::new();
+            let mut reused: HashMap<String, Vec<f32>> = HashMap::new();
+            for (identifier, text_to_embed, text_hash) in functions {
+                match cached_data.as_ref().and_then(|cached_data| cached_data.function_embeddings.get(&text_hash)) {
+                    Some(stored) => {
+                        let embedding = embedding::dequantize(stored);
+                        reused.insert(text_hash.clone(), embedding.clone());
+                        file_cached_embeddings.push(((identifier, embedding), text_hash));
                     }
+                    None => file_texts_to_embed.push((identifier, text_to_embed, text_hash)),
                 }
             }
-            
-            let cache_update_info = if needs_re_embedding_for_cache_update {
-                // Placeholder, actual embeddings will be filled after batch embedding
-                Some((cache_key.clone(), current_file_hash.clone(), HashMap::new()))
-            } else if !functions_for_this_file_cache_update.is_empty() {
-                 // File was fully cached and valid, ensure its data is available for potential re-write if other parts of cache are sparse
-                Some((cache_key.clone(), current_file_hash.clone(), functions_for_this_file_cache_update))
-            } else {
-                None
-            };
 
-            (file_cached_embeddings, file_texts_to_embed, cache_update_info)
+            // Unchanged when every function was found and the entry holds nothing else.
+            let unchanged = file_texts_to_embed.is_empty()
+                && cached_data.map_or(reused.is_empty(), |cached_data| cached_data.function_embeddings.len() == reused.len());
+            let cache_update_info = (!unchanged).then_some((cache_key, reused));
+            (file_cached_embeddings, file_texts_to_embed, cache_update_info, false)
         })
         .collect();
 
-    let mut files_requiring_cache_update: HashMap<String, (String, HashMap<String, Vec<f32>>)> = HashMap::new();
-    for (cached_for_file, to_embed_for_file, cache_update_opt) in processing_results {
-        all_function_embeddings.extend(cached_for_file);
+    //    function_identifier, embedding_vector
+    let mut all_function_embeddings: Vec<FunctionEmbedding> = Vec::new();
+    // Text hash of every entry of `all_function_embeddings`, for the vector file.
+    let mut text_hashes: Vec<String> = Vec::new();
+    let mut texts_to_embed_collector: Vec<PendingEmbedding> = Vec::new();
+    let mut files_requiring_cache_update: HashMap<String, HashMap<String, Vec<f32>>> = HashMap::new();
+    let mut all_from_vector_file = vectors.is_some();
+    for (cached_for_file, to_embed_for_file, cache_update_opt, from_vector_file) in processing_results {
+        for (function_embedding, text_hash) in cached_for_file {
+            all_function_embeddings.push(function_embedding);
+            text_hashes.push(text_hash);
+        }
         texts_to_embed_collector.extend(to_embed_for_file);
-        if let Some((rel_path, hash, func_map)) = cache_update_opt {
-            files_requiring_cache_update.entry(rel_path).or_insert_with(|| (hash, func_map));
+        if let Some((rel_path, reused)) = cache_update_opt {
+            files_requiring_cache_update.insert(rel_path, reused);
         }
+        all_from_vector_file &= from_vector_file;
     }
-    
-    if let Some(log_ref) = &mut debug_log_accumulator {
-        log_ref.push(format!("[ConceptSearchInner] {} functions loaded from cache, {} functions to embed.", all_function_embeddings.len(), texts_to_embed_collector.len()));
+
+    if let Some(log_ref) = debug_log {
+        log_ref.push(format!("[EmbedFunctions] {} functions loaded from cache, {} functions to embed.", all_function_embeddings.len(), texts_to_embed_collector.len()));
     }
 
-    // 3. Embed texts for functions not found in cache (if any)
-    let model = embedding::MODEL.get_or_try_init(|| embedding::initialize_model(&model_init_cache_dir))?;
-    if let Some(log_ref) = &mut debug_log_accumulator {
-        log_ref.push("[ConceptSearchInner] Embedding model initialized/retrieved.".to_string());
+    if !all_function_embeddings.is_empty() {
+        on_chunk(&all_function_embeddings);
     }
 
-    if !texts_to_embed_collector.is_empty() {
-        let actual_texts_to_embed: Vec<String> = texts_to_embed_collector.iter().map(|(_, text)| text.clone()).collect();
-        let new_embeddings_vec = model.embed(actual_texts_to_embed, None)
+    // Embed texts for functions not found in cache (if any)
+    let cached_count = all_function_embeddings.len();
+    for chunk in texts_to_embed_collector.chunks(EMBED_CHUNK_SIZE) {
+        // Chunks embedded so far are still cached below.
+        if cancel.should_stop() {
+            if let Some(log_ref) = debug_log {
+                log_ref.push("[EmbedFunctions] Cancelled or timed out; skipping remaining chunks.".to_string());
+            }
+            break;
+        }
+        // Texts longer than the model's input window are embedded in overlapping pieces
+        // whose embeddings are averaged, so no part of a long function is ignored.
+        let pieces: Vec<Vec<String>> = chunk
+            .par_iter()
+            .map(|(_, text, _)| {
+                embedding::chunk_ranges(model, text)
+                    .into_iter()
+                    .map(|range| text[range].to_string())
+                    .collect()
+            })
+            .collect();
+        let piece_embeddings = model.embed(pieces.iter().flatten().cloned().collect::<Vec<_>>(), None)
             .with_context(|| "Failed to embed documents")?;
+        let mut piece_embeddings = piece_embeddings.into_iter();
+        let new_embeddings_vec: Vec<Vec<f32>> = pieces
+            .iter()
+            .map(|function_pieces| embedding::mean_embedding(piece_embeddings.by_ref().take(function_pieces.len())))
+            .collect();
 
-        if let Some(log_ref) = &mut debug_log_accumulator {
-            log_ref.push(format!("[ConceptSearchInner] {} new embeddings generated.", new_embeddings_vec.len()));
+        if let Some(log_ref) = debug_log {
+            log_ref.push(format!("[EmbedFunctions] {} new embeddings generated.", new_embeddings_vec.len()));
         }
 
-        for (i, ((file_path_abs, func_name, func_body_for_result), _)) in texts_to_embed_collector.into_iter().enumerate() {
-            if let Some(embedding_vec) = new_embeddings_vec.get(i) {
-                all_function_embeddings.push(((file_path_abs.clone(), func_name.clone(), func_body_for_result), embedding_vec.clone()));
-                
-                // Update data for cache
-                let relative_file_path_for_cache = Path::new(&file_path_abs).strip_prefix(root_path_obj).unwrap_or(Path::new(&file_path_abs));
-                let cache_key_for_update = relative_file_path_for_cache.to_string_lossy().into_owned();
-
-                if let Some((_hash, func_map)) = files_requiring_cache_update.get_mut(&cache_key_for_update) {
-                    func_map.insert(func_name.clone(), embedding_vec.clone());
-                }
+        let chunk_start = all_function_embeddings.len();
+        for ((identifier, _, text_hash), embedding_vec) in chunk.iter().zip(new_embeddings_vec) {
+            // Update data for cache
+            let relative_file_path_for_cache = Path::new(&identifier.file).strip_prefix(root_path_obj).unwrap_or(Path::new(&identifier.file));
+            let cache_key_for_update = relative_file_path_for_cache.to_string_lossy().into_owned();
+            if let Some(func_map) = files_requiring_cache_update.get_mut(&cache_key_for_update) {
+                func_map.insert(text_hash.clone(), embedding_vec.clone());
             }
+            all_function_embeddings.push((identifier.clone(), embedding_vec));
+            text_hashes.push(text_hash.clone());
         }
+        on_chunk(&all_function_embeddings[chunk_start..]);
     }
+    let newly_embedded = all_function_embeddings.len() - cached_count;
 
-    // 4. Update sled cache with new/changed embeddings
-    for (rel_path, (hash, func_embeddings_map)) in files_requiring_cache_update {
-        if func_embeddings_map.is_empty() && all_function_embeddings.iter().any(|((fp,_,_),_)| Path::new(fp).strip_prefix(root_path_obj).map_or(false, |p| p.to_string_lossy() == rel_path)) {
-            // This means a file marked for cache update had no functions successfully embedded or retrieved.
-            // We should ensure its functions are populated in func_embeddings_map from all_function_embeddings.
-            let mut temp_map = func_embeddings_map.clone(); // Avoid mutable borrow issue
-            for ((fp, fn_name, _), emb_vec) in &all_function_embeddings {
-                if Path::new(fp).strip_prefix(root_path_obj).map_or(false, |p| p.to_string_lossy() == rel_path) {
-                    temp_map.insert(fn_name.clone(), emb_vec.clone());
+    // Update sled cache with new/changed embeddings. Functions that were skipped after
+    // a cancellation are missing from their file's entry and embedded next time.
+    for (rel_path, func_embeddings_map) in files_requiring_cache_update {
+        if func_embeddings_map.is_empty() {
+            // Every function of the file is gone.
+            if let Err(e) = db.remove(embedding_cache_key(tag, granularity, &rel_path)) {
+                if let Some(log_ref) = debug_log {
+                    log_ref.push(format!("[EmbedFunctions] Error removing cache entry for {}: {}", rel_path, e));
                 }
             }
-             if !temp_map.is_empty() { // Only update if we actually have embeddings for this file
-                let cache_entry = CachedFileEmbeddings {
-                    file_content_hash: hash,
-                    function_embeddings: temp_map,
-                };
-                match bincode::serialize(&cache_entry) {
-                    Ok(serialized_data) => {
-                        if let Err(e) = db.insert(rel_path.as_bytes(), serialized_data) {
-                            if let Some(log_ref) = &mut debug_log_accumulator {
-                                log_ref.push(format!("[ConceptSearchInner] Error inserting into cache for {}: {}", rel_path, e));
-                            }
-                        }
-                    }
-                    Err(e) => {
-                         if let Some(log_ref) = &mut debug_log_accumulator {
-                            log_ref.push(format!("[ConceptSearchInner] Error serializing cache entry for {}: {}", rel_path, e));
-                        }
+            continue;
+        }
+        let cache_entry = CachedFileEmbeddings {
+            schema_version: EMBEDDING_CACHE_SCHEMA_VERSION,
+            model: tag,
+            dimension: func_embeddings_map.values().next().map_or(0, Vec::len),
+            updated_ms: utils::now_ms(),
+            function_embeddings: func_embeddings_map.into_iter().map(|(text_hash, v)| (text_hash, embedding::quantize(&v, quantization))).collect(),
+        };
+        match bincode::serialize(&cache_entry) {
+            Ok(serialized_data) => {
+                if let Err(e) = db.insert(embedding_cache_key(tag, granularity, &rel_path), serialized_data) {
+                    if let Some(log_ref) = debug_log {
+                        log_ref.push(format!("[EmbedFunctions] Error inserting into cache for {}: {}", rel_path, e));
                     }
                 }
             }
-        } else if !func_embeddings_map.is_empty() { // Original logic if map was populated during new embedding phase
-             let cache_entry = CachedFileEmbeddings {
-                file_content_hash: hash,
-                function_embeddings: func_embeddings_map,
-            };
-            match bincode::serialize(&cache_entry) {
-                Ok(serialized_data) => {
-                    if let Err(e) = db.insert(rel_path.as_bytes(), serialized_data) {
-                        if let Some(log_ref) = &mut debug_log_accumulator {
-                            log_ref.push(format!("[ConceptSearchInner] Error inserting into cache for {}: {}", rel_path, e));
-                        }
-                    }
-                }
-                Err(e) => {
-                     if let Some(log_ref) = &mut debug_log_accumulator {
-                        log_ref.push(format!("[ConceptSearchInner] Error serializing cache entry for {}: {}", rel_path, e));
-                    }
+            Err(e) => {
+                if let Some(log_ref) = debug_log {
+                    log_ref.push(format!("[EmbedFunctions] Error serializing cache entry for {}: {}", rel_path, e));
                 }
             }
         }
     }
     if let Err(e) = db.flush() {
-        if let Some(log_ref) = &mut debug_log_accumulator {
-            log_ref.push(format!("[ConceptSearchInner] Error flushing cache DB: {}", e));
+        if let Some(log_ref) = debug_log {
+            log_ref.push(format!("[EmbedFunctions] Error flushing cache DB: {}", e));
+        }
+    }
+
+    // Rewrite the vector file unless it already served every file. Scanned files get
+    // their current rows; files outside this scan keep theirs while they still exist.
+    let dimension = all_function_embeddings.first().map(|(_, embedding)| embedding.len()).or(expected_dimension);
+    if let Some(dimension) = dimension.filter(|_| !all_from_vector_file) {
+        let mut scanned: HashMap<String, HashMap<&str, &[f32]>> = file_contexts
+            .iter()
+            .map(|file_context| {
+                let file_path_abs = Path::new(&file_context.path);
+                (file_path_abs.strip_prefix(root_path_obj).unwrap_or(file_path_abs).to_string_lossy().into_owned(), HashMap::new())
+            })
+            .collect();
+        for ((identifier, embedding), text_hash) in all_function_embeddings.iter().zip(&text_hashes) {
+            let file_path_abs = Path::new(&identifier.file);
+            let relative_path = file_path_abs.strip_prefix(root_path_obj).unwrap_or(file_path_abs).to_string_lossy();
+            if let Some(functions) = scanned.get_mut(relative_path.as_ref()) {
+                functions.insert(text_hash.as_str(), embedding.as_slice());
+            }
+        }
+        let mut writer = VectorFileWriter::new(dimension);
+        if let Some(vectors) = &vectors {
+            for relative_path in vectors.files() {
+                if !scanned.contains_key(relative_path) && root_path_obj.join(relative_path).is_file() {
+                    writer.copy_file(vectors, relative_path);
+                }
+            }
+        }
+        for (relative_path, functions) in scanned {
+            writer.push_file(relative_path, functions);
+        }
+        // Unix keeps the old content mapped until the map is dropped; Windows cannot
+        // replace a mapped file at all.
+        drop(vectors);
+        match writer.finish(&vector_file_path) {
+            Ok(rows) => {
+                if let Some(log_ref) = debug_log {
+                    log_ref.push(format!("[EmbedFunctions] Wrote {} vectors to {:?}.", rows, vector_file_path));
+                }
+            }
+            Err(e) => {
+                if let Some(log_ref) = debug_log {
+                    log_ref.push(format!("[EmbedFunctions] Error writing vector file {:?}: {}", vector_file_path, e));
+                }
+            }
         }
     }
 
+    Ok((all_function_embeddings, newly_embedded))
+}
 
-    if all_function_embeddings.is_empty() {
-        if let Some(log_ref) = &mut debug_log_accumulator {
-            log_ref.push("[ConceptSearchInner] No documents available after cache processing and embedding.".to_string());
+/// Similarity of a function embedding to the query embedding, reported as
+/// `ConceptSearchResultItem::similarity`: cosine similarity mapped from -1..1 to 0..1.
+fn similarity_score(query_embedding: &[f32], embedding: &[f32]) -> f32 {
+    (utils::cosine_similarity(query_embedding, embedding) + 1.0) / 2.0
+}
+
+/// Embeds a search query with `model`.
+fn embed_query(model: &TextEmbedding, query: &str) -> Result<Vec<f32>, anyhow::Error> {
+    model
+        .embed(vec![query.to_string()], None)
+        .with_context(|| "Failed to embed query string")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Failed to embed query string, got empty result."))
+}
+
+/// Embeds a search query with `model`, minus the embedding of `options.negative_query`
+/// scaled by `options.negative_weight`, so functions close to the negative concept
+/// score lower. Both embeddings are unit length, so neither dominates by magnitude.
+fn embed_search_query(model: &TextEmbedding, query: &str, options: &ConceptSearchOptions) -> Result<Vec<f32>, anyhow::Error> {
+    let mut query_embedding = embed_query(model, query)?;
+    let negative_query = options.negative_query.as_deref().map(str::trim).filter(|negative| !negative.is_empty());
+    if let Some(negative_query) = negative_query.filter(|_| options.negative_weight > 0.0) {
+        let negative_embedding = embed_query(model, negative_query)?;
+        for (x, negative) in query_embedding.iter_mut().zip(&negative_embedding) {
+            *x -= options.negative_weight * negative;
         }
-        return Ok(ConceptSearchServiceResult {
-            results: vec![],
-            stats: ConceptSearchStats { functions_analyzed: 0, search_duration_seconds: start_time.elapsed().as_secs_f32() },
-            error: Some("No functions available for similarity search after caching and embedding steps.".to_string()),
-            debug_log: debug_log_accumulator,
-        });
     }
-    
-    // 5. Embed query
-    let mut query_embeddings = model.embed(vec![query_str.to_string()], None)
-        .with_context(|| "Failed to embed query string")?;
-    if query_embeddings.is_empty() {
-        return Err(anyhow::anyhow!("Failed to embed query string, got empty result."));
+    Ok(query_embedding)
+}
+
+/// Scan settings for concept search: detail level 3 files filtered like `options` says.
+fn concept_scan_options(options: &ConceptSearchOptions) -> ScanOptions {
+    let mut exclude = options.exclude.clone();
+    if options.exclude_tests {
+        exclude.extend(config::TEST_PATH_GLOBS.iter().map(|glob| glob.to_string()));
     }
-    let query_embedding = query_embeddings.remove(0);
-    if let Some(log_ref) = &mut debug_log_accumulator {
-        log_ref.push(format!("[ConceptSearchInner] Query embedded. Dim: {}. First 5: {:?}", query_embedding.len(), query_embedding.iter().take(5).collect::<Vec<_>>()));
+    ScanOptions {
+        include_nested: options.include_nested,
+        max_file_size_bytes: options.max_file_size_bytes,
+        include: options.include.clone(),
+        exclude,
+        respect_gitignore: options.respect_gitignore,
+        include_hidden: options.include_hidden,
+        max_depth: options.max_depth,
+        follow_links: options.follow_links,
+        skip_vendor_dirs: options.skip_vendor_dirs,
+        num_threads: options.num_threads,
+        cancellation_token: options.cancellation_token,
+        // Class summaries are built from the outline, and file summaries from the
+        // leading comment parsed with it. It also keeps files that declare types but no
+        // functions in the scan.
+        outline: options.granularity != Granularity::Function,
+        ..Default::default()
     }
+}
 
-    // 6. Prepare final doc_identifiers and doc_embeddings for similarity search
-    let final_doc_identifiers: Vec<(String, String, Option<String>)> = all_function_embeddings.iter().map(|(ident, _)| ident.clone()).collect();
-    let final_doc_embeddings: Vec<Vec<f32>> = all_function_embeddings.iter().map(|(_, emb)| emb.clone()).collect();
+/// Re-scores the best `options.rerank_candidates` of `similarities` (sorted, best first,
+/// and cut to `above_min_similarity`) with `options.rerank_model`, then with
+/// `options.cross_encoder`, re-sorting them by the new score after each. The functions
+/// that were not re-scored are dropped, so the ranking is by the last re-ranker's score
+/// alone rather than a mix of scores on different scales. Returns the models used;
+/// `None` for a model that is not set, or when there is nothing to re-score.
+fn rerank(
+    similarities: &mut Vec<(usize, f32)>,
+    identifiers: &[FunctionIdentifier],
+    query_str: &str,
+    options: &ConceptSearchOptions,
+    model_init_cache_dir: &Path,
+    debug_log: &mut Option<Vec<String>>,
+) -> Result<(Option<EmbeddingModelTag>, Option<CrossEncoderTag>), anyhow::Error> {
+    let candidates = options.rerank_candidates.min(similarities.len());
+    if candidates == 0 || (options.rerank_model.is_none() && options.cross_encoder.is_none()) {
+        return Ok((None, None));
+    }
+    similarities.truncate(candidates);
+    let texts: Vec<String> = similarities[..candidates]
+        .iter()
+        .map(|(i, _)| embedding_text(&identifiers[*i].file, &identifiers[*i].function, options.granularity))
+        .collect();
 
-    if let Some(log_ref) = &mut debug_log_accumulator {
-         log_ref.push(format!("[ConceptSearchInner] Total functions for similarity search: {}. First identifier: {:?}", 
-            final_doc_identifiers.len(), 
-            final_doc_identifiers.first()));
+    if let Some(rerank_tag) = options.rerank_model {
+        let rerank_model = embedding::get_model(rerank_tag, model_init_cache_dir, &options.routing)?;
+        let query_embedding = embed_search_query(rerank_model, query_str, options)?;
+        // Long candidates are embedded whole, in averaged chunks, as when indexing.
+        let pieces: Vec<Vec<String>> = texts
+            .iter()
+            .map(|text| embedding::chunk_ranges(rerank_model, text).into_iter().map(|range| text[range].to_string()).collect())
+            .collect();
+        let piece_embeddings = rerank_model.embed(pieces.iter().flatten().cloned().collect::<Vec<_>>(), None)
+            .with_context(|| "Failed to embed re-ranking candidates")?;
+        let mut piece_embeddings = piece_embeddings.into_iter();
+        for ((_, score), function_pieces) in similarities[..candidates].iter_mut().zip(&pieces) {
+            let embedding = embedding::mean_embedding(piece_embeddings.by_ref().take(function_pieces.len()));
+            *score = similarity_score(&query_embedding, &embedding);
+        }
+        if let Some(log_ref) = debug_log {
+            log_ref.push(format!("[ConceptSearch] Re-ranked top {} candidates with {}.", candidates, rerank_tag.as_str()));
+        }
     }
 
-    // 7. Cosine similarity
-    let mut similarities: Vec<(usize, f32)> = final_doc_embeddings
-        .par_iter()
-        .enumerate()
-        .map(|(i, doc_emb)| {
-            let sim = utils::cosine_similarity(&query_embedding, doc_emb);
-            (i, sim)
-        })
-        .collect();
+    if let Some(cross_encoder_tag) = options.cross_encoder {
+        let cross_encoder = embedding::get_cross_encoder(cross_encoder_tag, model_init_cache_dir, &options.routing)?;
+        let reranked = cross_encoder.rerank(query_str, texts.iter().map(String::as_str).collect(), false, None)
+            .with_context(|| "Failed to score re-ranking candidates with the cross-encoder")?;
+        // Cross-encoders return a relevance logit; the logistic function maps it to 0..1
+        // like the other similarities.
+        for result in reranked {
+            similarities[result.index].1 = 1.0 / (1.0 + (-result.score).exp());
+        }
+        if let Some(log_ref) = debug_log {
+            log_ref.push(format!("[ConceptSearch] Re-scored top {} candidates with {}.", candidates, cross_encoder_tag.as_str()));
+        }
+    }
 
-    similarities.par_sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    Ok((options.rerank_model, options.cross_encoder))
+}
 
-    // 8. Get top N results
-    let results: Vec<ConceptSearchResultItem> = similarities
-        .iter()
-        .take(top_n)
-        .filter_map(|(idx, sim)| {
-            final_doc_identifiers.get(*idx).map(|ident| ConceptSearchResultItem {
-                file: ident.0.clone(),
-                function: ident.1.clone(),
-                similarity: *sim,
-                body: ident.2.clone(),
-            })
-        })
-        .collect();
+/// Observer for partial concept search rankings, called after each scored chunk.
+type ProgressFn<'a> = &'a mut (dyn FnMut(&ConceptSearchProgress) + Send);
 
-    if let Some(log_ref) = &mut debug_log_accumulator {
-        log_ref.push(format!("[ConceptSearchInner] Top {} results collected. Similarity calculation done.", results.len()));
-    }
-    
-    Ok(ConceptSearchServiceResult {
-        results,
-        stats: ConceptSearchStats {
-            functions_analyzed: final_doc_identifiers.len(),
-            search_duration_seconds: start_time.elapsed().as_secs_f32(),
-        },
-        error: None,
-        debug_log: debug_log_accumulator,
-    })
+/// Every function one root of a multi-root concept search scored, best first, as
+/// `(index into identifiers, score)` pairs. The rankings of all roots are merged before
+/// re-ranking and building results, so the roots behave like one project.
+#[derive(Default)]
+struct RootRanking {
+    identifiers: Vec<FunctionIdentifier>,
+    similarities: Vec<(usize, f32)>,
 }
 
-/// # Safety
-///
-/// This function is unsafe because it dereferences raw pointers passed from C.
-/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
-/// null-terminated UTF-8 encoded strings. The memory pointed to by these pointers
-/// must remain valid for the duration of this call.
-/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
-#[no_mangle]
-pub unsafe extern "C" fn scan_and_parse(
-    root_path_c: *const c_char,
+// Helper function for concept_search, kept close to its FFI counterpart. With
+// `ranking`, the search stops short of re-ranking and building results, and hands its
+// ranking over there instead (see `concept_search_roots`).
+#[allow(clippy::too_many_arguments)]
+fn concept_search_inner(
+    root_path_str: &str,
+    query_str: &str,
+    extensions: Vec<String>,
+    top_n: usize,
+    timeout_ms: u32,
+    options: &ConceptSearchOptions,
+    mut progress: Option<ProgressFn>,
+    mut ranking: Option<&mut RootRanking>,
+    debug: bool,
+) -> Result<ConceptSearchServiceResult, anyhow::Error> {
+    let start_time = Instant::now();
+    let root_path_obj = Path::new(root_path_str);
+
+    // Reject invalid include/exclude globs before opening caches or loading models.
+    config::path_filter(root_path_obj, &options.include, &options.exclude).map_err(anyhow::Error::msg)?;
+    let (model_init_cache_dir, db) = open_project_caches(root_path_obj)?;
+
+    let mut debug_log_accumulator: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+
+    if let Some(log_acc) = &mut debug_log_accumulator {
+        log_acc.push(format!(
+            "[ConceptSearchInner] START. Debug: {}, Extensions: {:?}, Query: '{}', Path: '{}'",
+            debug, extensions, query_str, root_path_str
+        ));
+    }
+
+    // 1. Scan files to get function contexts, reporting the scan counts as progress
+    let reports_progress = progress.is_some();
+    let mut report_scan = |scan: &ScanProgress| {
+        if let Some(progress) = progress.as_mut() {
+            progress(&ConceptSearchProgress {
+                scan: *scan,
+                results: vec![],
+                functions_scored: 0,
+                functions_total: 0,
+            });
+        }
+    };
+    let scan_progress = ProgressReporter::new(reports_progress.then_some(&mut report_scan as ScanProgressFn));
+    let scan_start = Instant::now();
+    let scan_result = scanner::perform_scan_with_progress(
+        root_path_str,
+        extensions.clone(),
+        3,
+        timeout_ms,
+        &concept_scan_options(options),
+        &scan_progress,
+        debug,
+    );
+    let scan_ms = scan_start.elapsed().as_millis() as u64;
+    let scan_counts = scan_progress.snapshot();
+    if debug {
+        if let Some(scan_log) = scan_result.debug_log { // scan_result is moved if not careful
+            debug_log_accumulator.get_or_insert_with(Vec::new).extend(scan_log);
+        }
+    }
+    
+    if scan_result.cancelled {
+        return Ok(ConceptSearchServiceResult {
+            results: vec![],
+            stats: ConceptSearchStats {
+                search_duration_seconds: start_time.elapsed().as_secs_f32(),
+                cancelled: true,
+                ..Default::default()
+            },
+            error: Some("Concept search was cancelled.".to_string()),
+            debug_log: debug_log_accumulator,
+        });
+    }
+
+    let file_contexts = summaries::summary_contexts(scan_result.file_contexts, options.granularity);
+    if file_contexts.is_empty() {
+         if let Some(log_ref) = &mut debug_log_accumulator {
+            log_ref.push("[ConceptSearchInner] No file contexts found from scan.".to_string());
+        }
+        return Ok(ConceptSearchServiceResult {
+            results: vec![],
+            stats: ConceptSearchStats {
+                search_duration_seconds: start_time.elapsed().as_secs_f32(),
+                ..Default::default()
+            },
+            error: Some("Initial file scan found no processable files or functions.".to_string()),
+            debug_log: debug_log_accumulator,
+        });
+    }
+
+    if options.backend == ScoringBackend::Bm25 {
+        if let Some(log_ref) = &mut debug_log_accumulator {
+            log_ref.push("[ConceptSearchInner] Using BM25 backend; skipping embedding.".to_string());
+        }
+        let mut result = lexical_search_result(query_str, &file_contexts, top_n, options, start_time, ranking.as_deref_mut());
+        result.stats.timed_out = scan_result.timed_out_internally;
+        result.stats.scan_ms = scan_ms;
+        result.debug_log = debug_log_accumulator;
+        return Ok(result);
+    }
+
+    // 2. Load the embedding model for every routed extension (or fall back to keyword scoring)
+    let model_for_context = |fc: &FileContext| {
+        let extension = parsing::file_extension(Path::new(&fc.path)).unwrap_or("");
+        options.routing.model_for(extension)
+    };
+    let model_init_start = Instant::now();
+    let mut models: BTreeMap<EmbeddingModelTag, &TextEmbedding> = BTreeMap::new();
+    for tag in file_contexts.iter().map(model_for_context).collect::<BTreeSet<_>>() {
+        match embedding::get_model(tag, &model_init_cache_dir, &options.routing) {
+            Ok(model) => {
+                models.insert(tag, model);
+            }
+            Err(e) if options.keyword_fallback => {
+                if let Some(log_ref) = &mut debug_log_accumulator {
+                    log_ref.push(format!("[ConceptSearchInner] Model initialization failed, falling back to BM25: {:#}", e));
+                }
+                let mut result = lexical_search_result(query_str, &file_contexts, top_n, options, start_time, ranking.as_deref_mut());
+                result.stats.model_fallback = true;
+                result.stats.scan_ms = scan_ms;
+                result.stats.model_init_ms = model_init_start.elapsed().as_millis() as u64;
+                result.stats.model_error = Some(format!("{:#}", e));
+                result.debug_log = debug_log_accumulator;
+                return Ok(result);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let model_init_ms = model_init_start.elapsed().as_millis() as u64;
+    if let Some(log_ref) = &mut debug_log_accumulator {
+        log_ref.push(format!("[ConceptSearchInner] Embedding models initialized/retrieved: {:?}", models.keys().collect::<Vec<_>>()));
+    }
+
+    let functions_total = file_contexts.iter().map(|fc| fc.functions.len()).sum();
+    let mut routed_contexts: BTreeMap<EmbeddingModelTag, Vec<FileContext>> = BTreeMap::new();
+    for fc in file_contexts {
+        routed_contexts.entry(model_for_context(&fc)).or_default().push(fc);
+    }
+
+    // Functions longer than the model's input window are embedded in several chunks;
+    // report how many are affected.
+    let max_input_tokens = models.values().filter_map(|model| embedding::max_input_tokens(model)).min();
+    let (functions_chunked, function_chunks) = routed_contexts
+        .iter()
+        .map(|(tag, contexts)| {
+            let model = models[tag];
+            contexts
+                .par_iter()
+                .flat_map_iter(|fc| fc.functions.iter().map(move |func| (fc, func)))
+                .map(|(fc, func)| embedding::chunk_ranges(model, &embedding_text(&fc.path, func, options.granularity)).len())
+                .filter(|&chunks| chunks > 1)
+                .fold(|| (0, 0), |(functions, chunks), n| (functions + 1, chunks + n))
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
+        })
+        .fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+    if functions_chunked > 0 {
+        if let Some(log_ref) = &mut debug_log_accumulator {
+            log_ref.push(format!(
+                "[ConceptSearchInner] {} of {} function texts exceed the model's input length ({:?} tokens) and were split into {} chunks.",
+                functions_chunked, functions_total, max_input_tokens, function_chunks
+            ));
+        }
+    }
+
+    // The timeout covers the whole search, not only the scan: functions embedded by
+    // then are ranked, and the rest are left for the next search to embed.
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token).with_timeout(start_time, timeout_ms);
+    let mut final_doc_identifiers: Vec<FunctionIdentifier> = Vec::new();
+    let mut similarities: Vec<(usize, f32)> = Vec::new();
+    let mut approximate_search = false;
+    // Scoring happens between embedding chunks; it is timed separately and taken out
+    // of the embedding time.
+    let (mut embed_time, mut similarity_time) = (Duration::ZERO, Duration::ZERO);
+    let (mut cache_hit_count, mut cache_miss_count) = (0, 0);
+    for (tag, contexts) in &routed_contexts {
+        if cancel_flag.should_stop() {
+            break;
+        }
+        let model = models[tag];
+        let embed_start = Instant::now();
+        let similarity_time_before = similarity_time;
+
+        // 3. Embed the query with the same model as the functions it is compared to
+        let query_embedding = embed_search_query(model, query_str, options)?;
+        if let Some(log_ref) = &mut debug_log_accumulator {
+            log_ref.push(format!("[ConceptSearchInner] Query embedded with {}. Dim: {}. First 5: {:?}", tag.as_str(), query_embedding.len(), query_embedding.iter().take(5).collect::<Vec<_>>()));
+        }
+
+        // 4. Reuse cached embeddings and embed functions that are new or changed, scoring
+        //    each chunk as soon as it is available. Cosine similarity is restricted to
+        //    functions accepting `parameter_type`, if set. Large projects are scored
+        //    once all embeddings are in, through the approximate index.
+        let functions_routed: usize = contexts.iter().map(|fc| fc.functions.len()).sum();
+        let approximate = !options.exact_search && functions_routed >= ann::MIN_FUNCTIONS;
+        let tag_offset = final_doc_identifiers.len();
+        let (embeddings, newly_embedded) = embed_functions(
+            root_path_obj,
+            &db,
+            *tag,
+            options.granularity,
+            options.routing.quantization,
+            model,
+            contexts,
+            &mut debug_log_accumulator,
+            &cancel_flag,
+            &mut |chunk| {
+                let offset = final_doc_identifiers.len();
+                final_doc_identifiers.extend(chunk.iter().map(|(ident, _)| ident.clone()));
+                if approximate {
+                    return;
+                }
+                let scoring_start = Instant::now();
+                similarities.par_extend(
+                    chunk
+                        .par_iter()
+                        .enumerate()
+                        .filter(|(_, (ident, _))| passes_filters(ident, options))
+                        .map(|(i, (_, doc_emb))| (offset + i, similarity_score(&query_embedding, doc_emb))),
+                );
+                similarities.par_sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+                similarity_time += scoring_start.elapsed();
+                if let Some(progress) = progress.as_mut() {
+                    progress(&ConceptSearchProgress {
+                        scan: ScanProgress { phase: ProgressPhase::Embedding, ..scan_counts },
+                        results: build_result_items(
+                            above_min_similarity(&similarities, options),
+                            &final_doc_identifiers,
+                            top_n,
+                            options,
+                        ),
+                        functions_scored: final_doc_identifiers.len(),
+                        functions_total,
+                    });
+                }
+            },
+        )?;
+        embed_time += embed_start.elapsed().saturating_sub(similarity_time - similarity_time_before);
+        let cached = embeddings.len() - newly_embedded;
+        cache_hit_count += cached;
+        cache_miss_count += functions_routed - cached;
+        if approximate {
+            let scoring_start = Instant::now();
+            let keys: Vec<String> = embeddings.iter().map(|(ident, _)| ann_key(ident)).collect();
+            let vectors: Vec<&[f32]> = embeddings.iter().map(|(_, doc_emb)| doc_emb.as_slice()).collect();
+            let candidates = ann::candidates(&db, *tag, &keys, &vectors, &query_embedding, &mut debug_log_accumulator);
+            similarities.par_extend(
+                candidates
+                    .par_iter()
+                    .filter(|&&i| passes_filters(&embeddings[i].0, options))
+                    .map(|&i| (tag_offset + i, similarity_score(&query_embedding, &embeddings[i].1))),
+            );
+            similarities.par_sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            approximate_search = true;
+            similarity_time += scoring_start.elapsed();
+        }
+    }
+
+    if final_doc_identifiers.is_empty() {
+        if let Some(log_ref) = &mut debug_log_accumulator {
+            log_ref.push("[ConceptSearchInner] No documents available after cache processing and embedding.".to_string());
+        }
+        return Ok(ConceptSearchServiceResult {
+            results: vec![],
+            stats: ConceptSearchStats {
+                search_duration_seconds: start_time.elapsed().as_secs_f32(),
+                timed_out: scan_result.timed_out_internally || cancel_flag.is_timed_out(),
+                cancelled: cancel_flag.is_cancelled(),
+                ..Default::default()
+            },
+            error: Some("No functions available for similarity search after caching and embedding steps.".to_string()),
+            debug_log: debug_log_accumulator,
+        });
+    }
+
+    if let Some(log_ref) = &mut debug_log_accumulator {
+         log_ref.push(format!("[ConceptSearchInner] Total functions for similarity search: {}. First identifier: {:?}", 
+            final_doc_identifiers.len(), 
+            final_doc_identifiers.first()));
+    }
+
+    // The hybrid backend also ranks by keywords, so exact identifier matches surface
+    // even when their embeddings are not the closest.
+    let ranking_start = Instant::now();
+    let mut corrected_terms = Vec::new();
+    if options.backend == ScoringBackend::Hybrid {
+        (similarities, corrected_terms) =
+            fuse_with_keywords(query_str, &similarities, &final_doc_identifiers, options);
+    }
+    similarities.truncate(above_min_similarity(&similarities, options).len());
+
+    // Optionally re-score the best candidates with a second (usually stronger) model.
+    // A cancelled or timed-out search returns what was ranked so far without re-scoring.
+    let cancelled = cancel_flag.is_cancelled();
+    let timed_out = scan_result.timed_out_internally || cancel_flag.is_timed_out();
+    let (reranked_with, cross_encoded_with) = if cancelled || timed_out || ranking.is_some() {
+        (None, None)
+    } else {
+        let reranks = (options.rerank_model.is_some() || options.cross_encoder.is_some())
+            && options.rerank_candidates.min(similarities.len()) > 0;
+        if let (true, Some(progress)) = (reranks, progress.as_mut()) {
+            progress(&ConceptSearchProgress {
+                scan: ScanProgress { phase: ProgressPhase::Reranking, ..scan_counts },
+                results: build_result_items(&similarities, &final_doc_identifiers, top_n, options),
+                functions_scored: final_doc_identifiers.len(),
+                functions_total: final_doc_identifiers.len(),
+            });
+        }
+        rerank(
+            &mut similarities,
+            &final_doc_identifiers,
+            query_str,
+            options,
+            &model_init_cache_dir,
+            &mut debug_log_accumulator,
+        )?
+    };
+
+    similarity_time += ranking_start.elapsed();
+
+    // 5. Get top N results
+    let functions_analyzed = final_doc_identifiers.len();
+    let results = match ranking {
+        Some(ranking) => {
+            *ranking = RootRanking { identifiers: final_doc_identifiers, similarities };
+            Vec::new()
+        }
+        None => build_result_items(&similarities, &final_doc_identifiers, top_n, options),
+    };
+
+    if let Some(log_ref) = &mut debug_log_accumulator {
+        log_ref.push(format!("[ConceptSearchInner] Top {} results collected. Similarity calculation done.", results.len()));
+    }
+    
+    Ok(ConceptSearchServiceResult {
+        results,
+        stats: ConceptSearchStats {
+            functions_analyzed,
+            search_duration_seconds: start_time.elapsed().as_secs_f32(),
+            scoring_backend: options.backend,
+            corrected_terms,
+            functions_chunked,
+            function_chunks,
+            max_input_tokens,
+            models_used: routed_contexts.keys().copied().collect(),
+            reranked_with,
+            cross_encoded_with,
+            approximate: approximate_search,
+            timed_out,
+            cancelled,
+            scan_ms,
+            model_init_ms,
+            cache_hit_count,
+            cache_miss_count,
+            embed_ms: embed_time.as_millis() as u64,
+            similarity_ms: similarity_time.as_millis() as u64,
+            ..Default::default()
+        },
+        error: None,
+        debug_log: debug_log_accumulator,
+    })
+}
+
+/// Runs `concept_search_inner` for each root and ranks their functions together: the
+/// rankings are merged, then re-ranked, filtered, diversified, and cut to `top_n` as one.
+/// Every root keeps its own embedding cache, and the timeout covers all roots together.
+/// Progress reports during a multi-root search rank one root at a time.
+#[allow(clippy::too_many_arguments)]
+fn concept_search_roots(
+    roots: &[String],
+    query_str: &str,
+    extensions: Vec<String>,
+    top_n: usize,
+    timeout_ms: u32,
+    options: &ConceptSearchOptions,
+    mut progress: Option<ProgressFn>,
+    debug: bool,
+) -> Result<ConceptSearchServiceResult, anyhow::Error> {
+    if let [root] = roots {
+        return concept_search_inner(root, query_str, extensions, top_n, timeout_ms, options, progress, None, debug);
+    }
+
+    let start_time = Instant::now();
+    let mut merged = ConceptSearchServiceResult {
+        results: vec![],
+        stats: ConceptSearchStats::default(),
+        error: None,
+        debug_log: if debug { Some(Vec::new()) } else { None },
+    };
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token).with_timeout(start_time, timeout_ms);
+    let mut identifiers: Vec<FunctionIdentifier> = Vec::new();
+    let mut similarities: Vec<(usize, f32)> = Vec::new();
+    for root in roots {
+        if cancel_flag.is_cancelled() {
+            merged.stats.cancelled = true;
+            break;
+        }
+        if cancel_flag.is_timed_out() {
+            if let Some(log) = &mut merged.debug_log {
+                log.push(format!("[ConceptSearch] Timeout reached before searching root '{}'.", root));
+            }
+            merged.stats.timed_out = true;
+            break;
+        }
+        let mut ranking = RootRanking::default();
+        let result = match concept_search_inner(
+            root,
+            query_str,
+            extensions.clone(),
+            top_n,
+            cancel_flag.remaining_ms(),
+            options,
+            progress.as_mut().map(|p| &mut **p as ProgressFn),
+            Some(&mut ranking),
+            debug,
+        ) {
+            Ok(result) => result,
+            Err(e) => {
+                // One unusable root (e.g. an unreadable cache) leaves the others searchable.
+                if let Some(log) = &mut merged.debug_log {
+                    log.push(format!("[ConceptSearch] Error searching root '{}': {}", root, e));
+                }
+                merged.stats.root_errors.push(format!("{}: {}", root, e));
+                continue;
+            }
+        };
+        let offset = identifiers.len();
+        identifiers.extend(ranking.identifiers);
+        similarities.extend(ranking.similarities.into_iter().map(|(i, score)| (offset + i, score)));
+        if let Some(error) = result.error {
+            if let Some(log) = &mut merged.debug_log {
+                log.push(format!("[ConceptSearch] Root '{}' returned no results: {}", root, error));
+            }
+            merged.stats.root_errors.push(format!("{}: {}", root, error));
+        }
+        if let (Some(log), Some(root_log)) = (&mut merged.debug_log, result.debug_log) {
+            log.extend(root_log);
+        }
+        let stats = &mut merged.stats;
+        stats.functions_analyzed += result.stats.functions_analyzed;
+        stats.scoring_backend = result.stats.scoring_backend;
+        stats.model_fallback |= result.stats.model_fallback;
+        stats.model_error = stats.model_error.take().or(result.stats.model_error);
+        stats.corrected_terms.extend(result.stats.corrected_terms);
+        stats.functions_chunked += result.stats.functions_chunked;
+        stats.function_chunks += result.stats.function_chunks;
+        stats.max_input_tokens = stats.max_input_tokens.or(result.stats.max_input_tokens);
+        for model in result.stats.models_used {
+            if !stats.models_used.contains(&model) {
+                stats.models_used.push(model);
+            }
+        }
+        stats.approximate |= result.stats.approximate;
+        stats.timed_out |= result.stats.timed_out;
+        stats.cancelled |= result.stats.cancelled;
+        stats.scan_ms += result.stats.scan_ms;
+        stats.model_init_ms += result.stats.model_init_ms;
+        stats.cache_hit_count += result.stats.cache_hit_count;
+        stats.cache_miss_count += result.stats.cache_miss_count;
+        stats.embed_ms += result.stats.embed_ms;
+        stats.similarity_ms += result.stats.similarity_ms;
+    }
+    if merged.stats.root_errors.len() == roots.len() {
+        merged.error = Some(merged.stats.root_errors.join("; "));
+        merged.stats.search_duration_seconds = start_time.elapsed().as_secs_f32();
+        return Ok(merged);
+    }
+    let ranking_start = Instant::now();
+    similarities.par_sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    // Re-score the best candidates of all roots together. Roots share the model cache,
+    // unless the fallback under a root's own `.cache` is in use.
+    if !merged.stats.cancelled && !merged.stats.timed_out {
+        if let (Some(progress), true) = (progress.as_mut(), options.rerank_model.is_some() || options.cross_encoder.is_some()) {
+            progress(&ConceptSearchProgress {
+                scan: ScanProgress { phase: ProgressPhase::Reranking, ..Default::default() },
+                results: build_result_items(&similarities, &identifiers, top_n, options),
+                functions_scored: identifiers.len(),
+                functions_total: identifiers.len(),
+            });
+        }
+        let model_init_cache_dir = config::model_cache_dir(Path::new(&roots[0]));
+        (merged.stats.reranked_with, merged.stats.cross_encoded_with) =
+            rerank(&mut similarities, &identifiers, query_str, options, &model_init_cache_dir, &mut merged.debug_log)?;
+    }
+    merged.stats.similarity_ms += ranking_start.elapsed().as_millis() as u64;
+    merged.results = build_result_items(&similarities, &identifiers, top_n, options);
+    merged.stats.search_duration_seconds = start_time.elapsed().as_secs_f32();
+    Ok(merged)
+}
+
+/// `root_path_c` is a project root or a JSON array of roots (see `parse_roots`).
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it must be
+/// a null-terminated UTF-8 JSON object matching `ScanOptions`. The memory pointed to by
+/// these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn scan_and_parse(
+    root_path_c: *const c_char,
     extensions_c: *const c_char,
     compactness_level: u8,
     timeout_milliseconds: u32,
+    options_c: *const c_char,
     debug_c: bool,
 ) -> *mut c_char {
+    run_scan_and_parse(root_path_c, extensions_c, compactness_level, timeout_milliseconds, options_c, None, debug_c)
+}
+
+/// Receives a JSON-encoded `ScanProgress` during `scan_and_parse_streaming`.
+/// The string is owned by Rust and only valid for the duration of the call.
+pub type ScanProgressCallback = unsafe extern "C" fn(progress_json: *const c_char, user_data: *mut c_void);
+
+/// Same as `scan_and_parse`, but reports the files discovered and processed so far
+/// through `callback` while the scan runs (at most every 100ms, plus once at the end),
+/// so hosts can show a progress bar for large repositories.
+///
+/// # Safety
+///
+/// Same requirements as `scan_and_parse`. `callback` may be null, in which case no
+/// progress is reported. It is invoked from walker threads, one call at a time.
+/// `user_data` is passed through to `callback` unchanged.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn scan_and_parse_streaming(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    compactness_level: u8,
+    timeout_milliseconds: u32,
+    options_c: *const c_char,
+    callback: Option<ScanProgressCallback>,
+    user_data: *mut c_void,
+    debug_c: bool,
+) -> *mut c_char {
+    let user_data = CallbackUserData(user_data);
+    let mut emit = move |progress: &ScanProgress| {
+        let Some(callback) = callback else { return };
+        if let Ok(Ok(json_c)) = serde_json::to_string(progress).map(CString::new) {
+            callback(json_c.as_ptr(), user_data.get());
+        }
+    };
+    run_scan_and_parse(
+        root_path_c,
+        extensions_c,
+        compactness_level,
+        timeout_milliseconds,
+        options_c,
+        Some(&mut emit),
+        debug_c,
+    )
+}
+
+/// Shared body of `scan_and_parse` and `scan_and_parse_streaming`.
+unsafe fn run_scan_and_parse(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    compactness_level: u8,
+    timeout_milliseconds: u32,
+    options_c: *const c_char,
+    progress: Option<ScanProgressFn>,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
     if timeout_milliseconds == 0 {
         let err_result = ScanResult {
             file_contexts: Vec::new(),
@@ -360,6 +1449,11 @@ pub unsafe extern "C" fn scan_and_parse(
             },
             timed_out_internally: true,
             files_processed_before_timeout: 0,
+            cancelled: false,
+            stats: ScanStats::default(),
+            continuation_token: None,
+            skipped: Vec::new(),
+            errors: Vec::new(),
         };
         return CString::new(serde_json::to_string(&err_result).unwrap_or_default())
             .map_or(std::ptr::null_mut(), |s| s.into_raw());
@@ -379,6 +1473,11 @@ pub unsafe extern "C" fn scan_and_parse(
                 },
                 timed_out_internally: false,
                 files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors: Vec::new(),
             };
             return CString::new(serde_json::to_string(&err_result).unwrap_or_default())
                 .map_or(std::ptr::null_mut(), |s| s.into_raw());
@@ -405,19 +1504,75 @@ pub unsafe extern "C" fn scan_and_parse(
             },
             timed_out_internally: false,
             files_processed_before_timeout: 0,
+            cancelled: false,
+            stats: ScanStats::default(),
+            continuation_token: None,
+            skipped: Vec::new(),
+            errors: Vec::new(),
         };
         return CString::new(serde_json::to_string(&err_result).unwrap_or_default())
             .map_or(std::ptr::null_mut(), |s| s.into_raw());
     }
 
-    let scan_result = scanner::perform_scan(
-        root_path_str,
+    let options: ScanOptions = match parse_options_json(options_c)
+        .and_then(|opts: ScanOptions| opts.validate().map(|_| opts))
+    {
+        Ok(opts) => opts,
+        Err(e) => {
+            let err_result = ScanResult {
+                file_contexts: Vec::new(),
+                debug_log: if debug_c { Some(vec![format!("Error: {}", e)]) } else { None },
+                timed_out_internally: false,
+                files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors: vec![e],
+            };
+            return CString::new(serde_json::to_string(&err_result).unwrap_or_default())
+                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+
+    let roots = match parse_roots(root_path_str) {
+        Ok(roots) => roots,
+        Err(e) => {
+            let err_result = ScanResult {
+                file_contexts: Vec::new(),
+                debug_log: if debug_c { Some(vec![format!("Error: {}", e)]) } else { None },
+                timed_out_internally: false,
+                files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors: vec![e],
+            };
+            return CString::new(serde_json::to_string(&err_result).unwrap_or_default())
+                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+
+    let scan_result = scanner::perform_scan_roots(
+        &roots,
         extensions,
         compactness_level,
         timeout_milliseconds,
+        &options,
+        &ProgressReporter::new(progress),
         debug_c,
     );
 
+    audit::record(
+        &roots[0],
+        "scan_and_parse",
+        &[extensions_str, &compactness_level.to_string(), options_text(options_c)],
+        start_time,
+        scan_result.file_contexts.len(),
+        (!scan_result.errors.is_empty()).then(|| scan_result.errors.join("; ")),
+    );
+
     let json_output = serde_json::to_string(&scan_result).unwrap_or_else(|e| {
         let mut current_debug_log = scan_result.debug_log; // This is already an Option
         if debug_c {
@@ -429,6 +1584,11 @@ pub unsafe extern "C" fn scan_and_parse(
             debug_log: current_debug_log,
             timed_out_internally: scan_result.timed_out_internally,
             files_processed_before_timeout: scan_result.files_processed_before_timeout,
+            cancelled: scan_result.cancelled,
+            stats: ScanStats::default(),
+            continuation_token: None,
+            skipped: scan_result.skipped,
+            errors: scan_result.errors,
         };
         serde_json::to_string(&error_fallback).unwrap_or_else(|_| {
             if debug_c {
@@ -442,11 +1602,105 @@ pub unsafe extern "C" fn scan_and_parse(
     CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
 }
 
+/// Same as `scan_and_parse`, but only parses the files that changed since `git_ref_c`
+/// (a commit, branch, or tag): files modified in the working tree, committed since the
+/// ref, or untracked and not ignored (see `git::changed_since`). Requires `git` on the
+/// `PATH`; git failures, such as an unknown ref, are reported in `ScanResult::errors`.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c`, `git_ref_c`, and `extensions_c` are valid,
+/// non-null, null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it
+/// must be a null-terminated UTF-8 JSON object matching `ScanOptions`. The memory pointed
+/// to by these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn scan_changed_since(
+    root_path_c: *const c_char,
+    git_ref_c: *const c_char,
+    extensions_c: *const c_char,
+    compactness_level: u8,
+    timeout_milliseconds: u32,
+    options_c: *const c_char,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let git_ref = CStr::from_ptr(git_ref_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let error_result = |error: String| ScanResult {
+        file_contexts: Vec::new(),
+        debug_log: if debug_c { Some(vec![format!("Error: {}", error)]) } else { None },
+        timed_out_internally: false,
+        files_processed_before_timeout: 0,
+        cancelled: false,
+        stats: ScanStats::default(),
+        continuation_token: None,
+        skipped: Vec::new(),
+        errors: vec![error],
+    };
+    let scan_result = if root_path_str.is_empty() {
+        error_result("root_path is null, empty, or invalid UTF-8.".to_string())
+    } else if extensions.is_empty() {
+        error_result("extensions is empty or resulted in no valid extensions.".to_string())
+    } else {
+        match parse_options_json(options_c)
+            .and_then(|opts: ScanOptions| opts.validate().map(|_| opts))
+            .and_then(|opts| git::changed_since(Path::new(root_path_str), git_ref).map(|files| (opts, files)))
+        {
+            Err(e) => error_result(e),
+            // An empty `files` list would scan the whole root instead.
+            Ok((_, files)) if files.is_empty() => ScanResult {
+                file_contexts: Vec::new(),
+                debug_log: if debug_c { Some(vec![format!("[Scanner] No files changed since '{}'.", git_ref)]) } else { None },
+                timed_out_internally: false,
+                files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors: Vec::new(),
+            },
+            Ok((options, files)) => scanner::perform_scan(
+                root_path_str,
+                extensions,
+                compactness_level,
+                timeout_milliseconds,
+                &ScanOptions { files, ..options },
+                debug_c,
+            ),
+        }
+    };
+
+    audit::record(
+        root_path_str,
+        "scan_changed_since",
+        &[git_ref, extensions_str, &compactness_level.to_string(), options_text(options_c)],
+        start_time,
+        scan_result.file_contexts.len(),
+        (!scan_result.errors.is_empty()).then(|| scan_result.errors.join("; ")),
+    );
+
+    let json_output = serde_json::to_string(&scan_result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// `root_path_c` is a project root or a JSON array of roots (see `parse_roots`).
+///
 /// # Safety
 ///
 /// This function is unsafe because it dereferences raw pointers passed from C.
 /// The caller must ensure that `root_path_c`, `query_c`, and `extensions_c`
 /// are valid, non-null, null-terminated UTF-8 encoded strings.
+/// `options_c` may be null; otherwise it must be a null-terminated UTF-8 JSON object
+/// matching `ConceptSearchOptions`.
 /// The memory pointed to by these pointers must remain valid for the duration of this call.
 /// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
 #[no_mangle]
@@ -456,16 +1710,93 @@ pub unsafe extern "C" fn concept_search(
     extensions_c: *const c_char,
     top_n_c: usize,
     timeout_ms_c: u32,
+    options_c: *const c_char,
     debug_c: bool,
 ) -> *mut c_char {
-    // Create a temporary debug log for FFI entry diagnostics
-    let mut ffi_entry_debug_log: Option<Vec<String>> = if debug_c { Some(Vec::new()) } else { None };
-    if let Some(log) = &mut ffi_entry_debug_log {
-        log.push(format!("[FFI concept_search] Entry. Received debug_c: {}, root_path_c: {:?}, query_c: {:?}, extensions_c: {:?}, top_n_c: {}, timeout_ms_c: {}", 
-            debug_c, root_path_c, query_c, extensions_c, top_n_c, timeout_ms_c));
-    }
+    run_concept_search(root_path_c, query_c, extensions_c, top_n_c, timeout_ms_c, options_c, None, debug_c)
+}
 
-    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+/// Receives a JSON-encoded `ConceptSearchProgress` during `concept_search_streaming`.
+/// The string is owned by Rust and only valid for the duration of the call.
+pub type ConceptSearchProgressCallback =
+    unsafe extern "C" fn(progress_json: *const c_char, user_data: *mut c_void);
+
+/// Caller-owned context pointer handed back to `ConceptSearchProgressCallback`.
+struct CallbackUserData(*mut c_void);
+
+// The pointer is never dereferenced on the Rust side; the caller's callback is
+// responsible for any synchronization it needs.
+unsafe impl Send for CallbackUserData {}
+
+impl CallbackUserData {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Same as `concept_search`, but also reports the running top-N through `callback` as
+/// soon as cached embeddings are scored and again after each newly embedded chunk, so
+/// interactive callers can show early candidates during a cold first query. The final
+/// ranking is still returned as the function result.
+///
+/// # Safety
+///
+/// Same requirements as `concept_search`. `callback` may be null, in which case no
+/// progress is reported. It may be invoked from a worker thread. `user_data` is passed
+/// through to `callback` unchanged.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn concept_search_streaming(
+    root_path_c: *const c_char,
+    query_c: *const c_char,
+    extensions_c: *const c_char,
+    top_n_c: usize,
+    timeout_ms_c: u32,
+    options_c: *const c_char,
+    callback: Option<ConceptSearchProgressCallback>,
+    user_data: *mut c_void,
+    debug_c: bool,
+) -> *mut c_char {
+    let user_data = CallbackUserData(user_data);
+    let mut emit = move |progress: &ConceptSearchProgress| {
+        let Some(callback) = callback else { return };
+        if let Ok(Ok(json_c)) = serde_json::to_string(progress).map(CString::new) {
+            callback(json_c.as_ptr(), user_data.get());
+        }
+    };
+    run_concept_search(
+        root_path_c,
+        query_c,
+        extensions_c,
+        top_n_c,
+        timeout_ms_c,
+        options_c,
+        Some(&mut emit),
+        debug_c,
+    )
+}
+
+/// Shared body of `concept_search` and `concept_search_streaming`.
+#[allow(clippy::too_many_arguments)]
+unsafe fn run_concept_search(
+    root_path_c: *const c_char,
+    query_c: *const c_char,
+    extensions_c: *const c_char,
+    top_n_c: usize,
+    timeout_ms_c: u32,
+    options_c: *const c_char,
+    progress: Option<ProgressFn>,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    // Create a temporary debug log for FFI entry diagnostics
+    let mut ffi_entry_debug_log: Option<Vec<String>> = if debug_c { Some(Vec::new()) } else { None };
+    if let Some(log) = &mut ffi_entry_debug_log {
+        log.push(format!("[FFI concept_search] Entry. Received debug_c: {}, root_path_c: {:?}, query_c: {:?}, extensions_c: {:?}, top_n_c: {}, timeout_ms_c: {}", 
+            debug_c, root_path_c, query_c, extensions_c, top_n_c, timeout_ms_c));
+    }
+
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
     let query_str = CStr::from_ptr(query_c).to_str().unwrap_or_default();
     let extensions_json_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
 
@@ -522,17 +1853,60 @@ pub unsafe extern "C" fn concept_search(
         }
     };
 
+    let options: ConceptSearchOptions = match parse_options_json(options_c) {
+        Ok(opts) => opts,
+        Err(e) => {
+            let mut current_debug_log = ffi_entry_debug_log;
+            if debug_c {
+                current_debug_log.get_or_insert_with(Vec::new).push(e.clone());
+            }
+            let error_result = ConceptSearchServiceResult {
+                results: vec![],
+                stats: ConceptSearchStats::default(),
+                error: Some(e),
+                debug_log: current_debug_log,
+            };
+            let json_output = serde_json::to_string(&error_result).unwrap_or_default();
+            return CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+
     // If we pass the initial checks, call concept_search_inner
     // concept_search_inner will create its own debug_log_accumulator based on debug_c
     // We need to merge ffi_entry_debug_log with the one from concept_search_inner
-    let inner_result = match concept_search_inner(
-        root_path_str,
-        query_str,
-        extensions,
-        top_n_c,
-        timeout_ms_c,
-        debug_c, // Pass the received debug_c
-    ) {
+    // Run inside the shared pool so rayon phases don't oversubscribe cores next to the walker.
+    let roots = match parse_roots(root_path_str) {
+        Ok(roots) => roots,
+        Err(e) => {
+            let mut current_debug_log = ffi_entry_debug_log;
+            if debug_c {
+                current_debug_log.get_or_insert_with(Vec::new).push(e.clone());
+            }
+            let error_result = ConceptSearchServiceResult {
+                results: vec![],
+                stats: ConceptSearchStats::default(),
+                error: Some(e),
+                debug_log: current_debug_log,
+            };
+            let json_output = serde_json::to_string(&error_result).unwrap_or_default();
+            return CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+
+    let inner_result = match pool::with_threads(options.num_threads).map_err(anyhow::Error::msg).and_then(|pool| {
+        pool.install(|| {
+            concept_search_roots(
+                &roots,
+                query_str,
+                extensions,
+                top_n_c,
+                timeout_ms_c,
+                &options,
+                progress,
+                debug_c, // Pass the received debug_c
+            )
+        })
+    }) {
         Ok(mut res) => {
             // Prepend ffi_entry_debug_log to the logs from concept_search_inner
             if let Some(mut entry_logs) = ffi_entry_debug_log {
@@ -560,6 +1934,15 @@ pub unsafe extern "C" fn concept_search(
         }
     };
 
+    audit::record(
+        &roots[0],
+        "concept_search",
+        &[query_str, extensions_json_str, &top_n_c.to_string(), options_text(options_c)],
+        start_time,
+        inner_result.results.len(),
+        inner_result.error.clone(),
+    );
+
     let json_output = serde_json::to_string(&inner_result).unwrap_or_else(|e| {
         // Attempt to use the debug log from inner_result if serialization fails
         let mut current_debug_log = inner_result.debug_log;
@@ -583,126 +1966,1330 @@ pub unsafe extern "C" fn concept_search(
     CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
 }
 
+/// Embedded functions of a root, kept in memory by `build_concept_index` so that
+/// `query_concept_index` only has to embed the query.
+struct ConceptIndex {
+    /// Options the index was built with; they also filter and format query results.
+    options: ConceptSearchOptions,
+    /// The indexed root, whose embedding cache holds the approximate indexes.
+    root: PathBuf,
+    model_init_cache_dir: PathBuf,
+    /// Every indexed function, and its embedding from the model its file is routed to.
+    /// Empty with the BM25 backend, which ranks `file_contexts` instead.
+    functions: BTreeMap<EmbeddingModelTag, Vec<FunctionEmbedding>>,
+    /// Approximate index keys (see `ann_key`) of the functions of each model that has
+    /// enough of them to be searched approximately.
+    ann_keys: BTreeMap<EmbeddingModelTag, Vec<String>>,
+    /// The scanned files, kept for the BM25 backend only.
+    file_contexts: Vec<FileContext>,
+}
+
+/// Concept indexes built so far, per canonical root. An index lives as long as the
+/// loaded library, or until `build_concept_index` replaces it.
+static CONCEPT_INDEXES: Lazy<Mutex<HashMap<PathBuf, Arc<ConceptIndex>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Scans `root_path_str`, embeds every function (reusing the embedding cache), and
+/// stores the result as the root's concept index.
+fn build_concept_index_inner(
+    root_path_str: &str,
+    extensions: Vec<String>,
+    timeout_ms: u32,
+    options: &ConceptSearchOptions,
+    debug: bool,
+) -> Result<ConceptIndexResult, anyhow::Error> {
+    let start_time = Instant::now();
+    let root_path_obj = Path::new(root_path_str);
+    config::path_filter(root_path_obj, &options.include, &options.exclude).map_err(anyhow::Error::msg)?;
+    let (model_init_cache_dir, db) = open_project_caches(root_path_obj)?;
+    let mut debug_log_accumulator: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+
+    let scan_result = scanner::perform_scan(root_path_str, extensions, 3, timeout_ms, &concept_scan_options(options), debug);
+    if let (Some(log_ref), Some(scan_log)) = (&mut debug_log_accumulator, scan_result.debug_log) {
+        log_ref.extend(scan_log);
+    }
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token).with_timeout(start_time, timeout_ms);
+    if scan_result.cancelled {
+        return Ok(ConceptIndexResult {
+            duration_seconds: start_time.elapsed().as_secs_f32(),
+            cancelled: true,
+            error: Some("Building the concept index was cancelled.".to_string()),
+            debug_log: debug_log_accumulator,
+            ..Default::default()
+        });
+    }
+
+    let file_contexts = summaries::summary_contexts(scan_result.file_contexts, options.granularity);
+    let files_indexed = file_contexts.len();
+    if options.backend == ScoringBackend::Bm25 {
+        // Keyword scoring needs no embeddings; queries rank the parsed functions.
+        let functions_indexed = file_contexts.iter().map(|fc| fc.functions.len()).sum();
+        if let Some(log_ref) = &mut debug_log_accumulator {
+            log_ref.push(format!(
+                "[BuildConceptIndex] Indexed {} functions from {} files for BM25; skipping embedding.",
+                functions_indexed, files_indexed
+            ));
+        }
+        let index = ConceptIndex {
+            options: options.clone(),
+            root: root_path_obj.to_path_buf(),
+            model_init_cache_dir,
+            functions: BTreeMap::new(),
+            ann_keys: BTreeMap::new(),
+            file_contexts,
+        };
+        CONCEPT_INDEXES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(utils::canonical_or_original(root_path_obj), Arc::new(index));
+        return Ok(ConceptIndexResult {
+            files_indexed,
+            functions_indexed,
+            duration_seconds: start_time.elapsed().as_secs_f32(),
+            timed_out_internally: scan_result.timed_out_internally,
+            debug_log: debug_log_accumulator,
+            ..Default::default()
+        });
+    }
+
+    let mut routed_contexts: BTreeMap<EmbeddingModelTag, Vec<FileContext>> = BTreeMap::new();
+    for fc in file_contexts {
+        let extension = parsing::file_extension(Path::new(&fc.path)).unwrap_or("");
+        routed_contexts.entry(options.routing.model_for(extension)).or_default().push(fc);
+    }
+    let mut functions = BTreeMap::new();
+    let mut functions_embedded = 0;
+    for (tag, contexts) in &routed_contexts {
+        let model = embedding::get_model(*tag, &model_init_cache_dir, &options.routing)?;
+        let (embeddings, newly_embedded) = embed_functions(
+            root_path_obj,
+            &db,
+            *tag,
+            options.granularity,
+            options.routing.quantization,
+            model,
+            contexts,
+            &mut debug_log_accumulator,
+            &cancel_flag,
+            &mut |_| {},
+        )?;
+        functions_embedded += newly_embedded;
+        functions.insert(*tag, embeddings);
+    }
+    if cancel_flag.is_cancelled() {
+        return Ok(ConceptIndexResult {
+            functions_embedded,
+            duration_seconds: start_time.elapsed().as_secs_f32(),
+            cancelled: true,
+            error: Some("Building the concept index was cancelled.".to_string()),
+            debug_log: debug_log_accumulator,
+            ..Default::default()
+        });
+    }
+
+    let functions_indexed = functions.values().map(Vec::len).sum();
+    if let Some(log_ref) = &mut debug_log_accumulator {
+        log_ref.push(format!(
+            "[BuildConceptIndex] Indexed {} functions from {} files ({} newly embedded).",
+            functions_indexed, files_indexed, functions_embedded
+        ));
+    }
+    // Keys are computed once here, so approximate queries only score the probed clusters.
+    let ann_keys = functions
+        .iter()
+        .filter(|(_, embeddings)| !options.exact_search && embeddings.len() >= ann::MIN_FUNCTIONS)
+        .map(|(tag, embeddings)| (*tag, embeddings.iter().map(|(ident, _)| ann_key(ident)).collect()))
+        .collect();
+    let index = ConceptIndex {
+        options: options.clone(),
+        root: root_path_obj.to_path_buf(),
+        model_init_cache_dir,
+        functions,
+        ann_keys,
+        file_contexts: Vec::new(),
+    };
+    CONCEPT_INDEXES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(utils::canonical_or_original(root_path_obj), Arc::new(index));
+
+    Ok(ConceptIndexResult {
+        files_indexed,
+        functions_indexed,
+        functions_embedded,
+        models_used: routed_contexts.keys().copied().collect(),
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        timed_out_internally: scan_result.timed_out_internally || cancel_flag.is_timed_out(),
+        cancelled: false,
+        error: None,
+        debug_log: debug_log_accumulator,
+    })
+}
+
+/// Ranks the functions of `index` against `query_str` without scanning or embedding
+/// anything but the query. Scores the same way `concept_search_inner` does: BM25 alone
+/// for the BM25 backend, and through the approximate index for large projects unless
+/// `exact_search` is set.
+fn query_concept_index_inner(
+    index: &ConceptIndex,
+    query_str: &str,
+    top_n: usize,
+) -> Result<ConceptSearchServiceResult, anyhow::Error> {
+    let start_time = Instant::now();
+    let options = &index.options;
+    if options.backend == ScoringBackend::Bm25 {
+        return Ok(lexical_search_result(query_str, &index.file_contexts, top_n, options, start_time, None));
+    }
+    let mut identifiers: Vec<FunctionIdentifier> = Vec::new();
+    let mut similarities: Vec<(usize, f32)> = Vec::new();
+    let mut approximate_search = false;
+    for (tag, embeddings) in &index.functions {
+        let model = embedding::get_model(*tag, &index.model_init_cache_dir, &options.routing)?;
+        let query_embedding = embed_search_query(model, query_str, options)?;
+        let offset = identifiers.len();
+        identifiers.extend(embeddings.iter().map(|(ident, _)| ident.clone()));
+        if let Some(keys) = index.ann_keys.get(tag) {
+            let (_, db) = open_project_caches(&index.root)?;
+            let vectors: Vec<&[f32]> = embeddings.iter().map(|(_, doc_emb)| doc_emb.as_slice()).collect();
+            let candidates = ann::candidates(&db, *tag, keys, &vectors, &query_embedding, &mut None);
+            similarities.par_extend(
+                candidates
+                    .par_iter()
+                    .filter(|&&i| passes_filters(&embeddings[i].0, options))
+                    .map(|&i| (offset + i, similarity_score(&query_embedding, &embeddings[i].1))),
+            );
+            approximate_search = true;
+            continue;
+        }
+        similarities.par_extend(
+            embeddings
+                .par_iter()
+                .enumerate()
+                .filter(|(_, (ident, _))| passes_filters(ident, options))
+                .map(|(i, (_, doc_emb))| (offset + i, similarity_score(&query_embedding, doc_emb))),
+        );
+    }
+    similarities.par_sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    let (mut scoring_backend, mut corrected_terms) = (ScoringBackend::Embedding, Vec::new());
+    if options.backend == ScoringBackend::Hybrid {
+        (similarities, corrected_terms) = fuse_with_keywords(query_str, &similarities, &identifiers, options);
+        scoring_backend = ScoringBackend::Hybrid;
+    }
+    similarities.truncate(above_min_similarity(&similarities, options).len());
+    let (reranked_with, cross_encoded_with) =
+        rerank(&mut similarities, &identifiers, query_str, options, &index.model_init_cache_dir, &mut None)?;
+
+    Ok(ConceptSearchServiceResult {
+        results: build_result_items(&similarities, &identifiers, top_n, options),
+        stats: ConceptSearchStats {
+            functions_analyzed: identifiers.len(),
+            search_duration_seconds: start_time.elapsed().as_secs_f32(),
+            scoring_backend,
+            corrected_terms,
+            models_used: index.functions.keys().copied().collect(),
+            reranked_with,
+            cross_encoded_with,
+            approximate: approximate_search,
+            ..Default::default()
+        },
+        error: None,
+        debug_log: None,
+    })
+}
+
+/// Scans and embeds a project ahead of time, so later `query_concept_index` calls only
+/// embed the query. Meant to run in the background, e.g. when a project is opened.
+///
+/// The index is a snapshot: it is kept in memory until the library is unloaded or this
+/// is called again for the same root, and does not see later file changes. Rebuilding
+/// is cheap for unchanged files, whose embeddings come from the embedding cache.
+/// `extensions_c` and `options_c` are the same as for `concept_search`; the options
+/// also apply to every query against the index.
+///
 /// # Safety
 ///
 /// This function is unsafe because it dereferences raw pointers passed from C.
-/// The caller must ensure that `root_path_c`, `search_string_c`, and `extensions_c`
-/// are valid, non-null, null-terminated UTF-8 encoded strings.
-/// The memory pointed to by these pointers must remain valid for the duration of this call.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it must be
+/// a null-terminated UTF-8 JSON object matching `ConceptSearchOptions`. The memory
+/// pointed to by these pointers must remain valid for the duration of this call.
 /// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
 #[no_mangle]
-pub unsafe extern "C" fn project_wide_search(
+pub unsafe extern "C" fn build_concept_index(
     root_path_c: *const c_char,
-    search_string_c: *const c_char,
     extensions_c: *const c_char,
-    context_lines_c: u8,
     timeout_ms_c: u32,
+    options_c: *const c_char,
     debug_c: bool,
 ) -> *mut c_char {
     let start_time = Instant::now();
-    let mut debug_log: Option<Vec<String>> = if debug_c { Some(Vec::new()) } else { None };
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let extensions_json_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
 
-    let root_path_str = match CStr::from_ptr(root_path_c).to_str() {
-        Ok(s) if !s.is_empty() => s,
-        _ => {
-            let result = SearchServiceResult {
-                results: vec![],
-                stats: Default::default(),
-                debug_log: if debug_c {
-                    Some(vec![
-                        "Error: Root path is null, empty, or invalid UTF-8.".to_string()
-                    ])
-                } else {
-                    None
-                },
-            };
-            return CString::new(serde_json::to_string(&result).unwrap_or_default())
-                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+    let result = if root_path_str.is_empty() {
+        ConceptIndexResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
         }
-    };
-    let search_string = match CStr::from_ptr(search_string_c).to_str() {
-        Ok(s) if !s.is_empty() => s,
-        _ => {
-            let result = SearchServiceResult {
-                results: vec![],
-                stats: Default::default(),
-                debug_log: if debug_c {
-                    Some(vec![
-                        "Error: Search string is null, empty, or invalid UTF-8.".to_string(),
-                    ])
-                } else {
-                    None
-                },
-            };
-            return CString::new(serde_json::to_string(&result).unwrap_or_default())
-                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+    } else {
+        match serde_json::from_str::<Vec<String>>(extensions_json_str)
+            .map_err(|e| format!("Failed to parse extensions JSON: {}. Input was: '{}'", e, extensions_json_str))
+            .and_then(|extensions| {
+                parse_options_json::<ConceptSearchOptions>(options_c).map(|options| (extensions, options))
+            }) {
+            Ok((extensions, options)) => pool::with_threads(options.num_threads)
+                .map_err(anyhow::Error::msg)
+                .and_then(|pool| {
+                    pool.install(|| build_concept_index_inner(root_path_str, extensions, timeout_ms_c, &options, debug_c))
+                })
+                .unwrap_or_else(|e| ConceptIndexResult {
+                    error: Some(format!("Concept index internal error: {:?}", e)),
+                    ..Default::default()
+                }),
+            Err(e) => ConceptIndexResult {
+                error: Some(e),
+                ..Default::default()
+            },
         }
     };
-    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or("");
-    let extensions: Vec<&str> = extensions_str
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect();
 
-    if extensions.is_empty() {
-        let result = SearchServiceResult {
-            results: vec![],
-            stats: Default::default(),
-            debug_log: if debug_c {
-                Some(vec![
-                    "Error: Extensions string is empty or resulted in no valid extensions."
-                        .to_string(),
-                ])
-            } else {
-                None
-            },
-        };
-        return CString::new(serde_json::to_string(&result).unwrap_or_default())
-            .map_or(std::ptr::null_mut(), |s| s.into_raw());
-    }
+    audit::record(
+        root_path_str,
+        "build_concept_index",
+        &[extensions_json_str, options_text(options_c)],
+        start_time,
+        result.functions_indexed,
+        result.error.clone(),
+    );
 
-    if let Some(log) = &mut debug_log {
-        log.push(format!(
-            "[ProjectSearch] Root: {}, Query: '{}', Exts: {:?}, Timeout: {}ms",
-            root_path_str, search_string, extensions, timeout_ms_c
-        ));
-    }
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize concept index result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
 
-    let root_path = Path::new(root_path_str);
-    let walker = WalkBuilder::new(root_path)
-        .git_ignore(true) // Standard gitignore behavior
-        .git_global(true) // Include global gitignore
-        .build_parallel();
+/// Ranks the functions indexed by `build_concept_index` for `root_path_c` against
+/// `query_c` and returns the best `top_n`, in the same shape as `concept_search`.
+/// Returns an error if the root has no index yet.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `query_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. The memory pointed to by these pointers must
+/// remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn query_concept_index(
+    root_path_c: *const c_char,
+    query_c: *const c_char,
+    top_n_c: usize,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let query_str = CStr::from_ptr(query_c).to_str().unwrap_or_default();
 
-    let results_arc = Arc::new(Mutex::new(Vec::<FileSearchResult>::new()));
-    let stats_arc = Arc::new(Mutex::new(SearchStats::default()));
-    let timed_out_arc = Arc::new(AtomicBool::new(false));
-    let debug_log_arc = Arc::new(Mutex::new(debug_log));
+    let error_result = |error: String| ConceptSearchServiceResult {
+        results: vec![],
+        stats: ConceptSearchStats::default(),
+        error: Some(error),
+        debug_log: None,
+    };
+    let index = CONCEPT_INDEXES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&utils::canonical_or_original(Path::new(root_path_str)))
+        .cloned();
+    let result = if root_path_str.is_empty() || query_str.is_empty() {
+        error_result("Error: root_path or query is null, empty or invalid UTF-8.".to_string())
+    } else if let Some(index) = index {
+        pool::with_threads(index.options.num_threads)
+            .map_err(anyhow::Error::msg)
+            .and_then(|pool| pool.install(|| query_concept_index_inner(&index, query_str, top_n_c)))
+            .unwrap_or_else(|e| error_result(format!("Concept index query internal error: {:?}", e)))
+    } else {
+        error_result("No concept index for this root; call build_concept_index first.".to_string())
+    };
 
-    walker.run(|| {
-        let results_arc_box = Arc::clone(&results_arc); 
-        let stats_arc_box = Arc::clone(&stats_arc); 
-        let timed_out_clone_box = Arc::clone(&timed_out_arc); 
-        let local_extensions_clone_box: Vec<String> =
-            extensions.iter().map(|&s| s.to_string()).collect();
-        let search_string_clone_box = search_string.to_string(); 
-        let debug_log_arc_clone_box = Arc::clone(&debug_log_arc); 
+    audit::record(
+        root_path_str,
+        "query_concept_index",
+        &[query_str, &top_n_c.to_string()],
+        start_time,
+        result.results.len(),
+        result.error.clone(),
+    );
 
-        Box::new(move |entry_result| {
-            if debug_c {
-                if timeout_ms_c > 0 && start_time.elapsed().as_millis() as u32 > timeout_ms_c {
-                    if !timed_out_clone_box.swap(true, Ordering::Relaxed) {
-                        if let Ok(mut guard) = debug_log_arc_clone_box.lock() {
-                            if let Some(log_vec) = guard.as_mut() {
-                                log_vec.push(
-                                    "[ProjectSearch] Timeout reached during walk.".to_string(),
-                                );
-                            }
-                        }
-                    }
-                    return ignore::WalkState::Quit;
-                }
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize concept search result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Ranks the functions of the project by how similar their embedding is to that of
+/// `function_name` (plain or qualified name) in `file_str`, leaving the function
+/// itself out. Only files with the same extension are compared, embedded with the
+/// model `options` routes it to, so the embedding cache is shared with concept search.
+///
+/// A non-zero `line` picks the function whose definition spans it, for files with
+/// several functions of that name; without one, more than one match is an error.
+#[allow(clippy::too_many_arguments)]
+fn find_similar_functions_inner(
+    root_path_str: &str,
+    file_str: &str,
+    function_name: &str,
+    line: usize,
+    top_n: usize,
+    timeout_ms: u32,
+    options: &ConceptSearchOptions,
+    debug: bool,
+) -> Result<ConceptSearchServiceResult, anyhow::Error> {
+    let start_time = Instant::now();
+    let root_path_obj = Path::new(root_path_str);
+    let file_path = root_path_obj.join(file_str);
+    let extension = parsing::file_extension(&file_path).unwrap_or("").to_string();
+    let tag = options.routing.model_for(&extension);
+    let (model_init_cache_dir, db) = open_project_caches(root_path_obj)?;
+    let mut debug_log_accumulator: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+
+    let error_result = |error: String, debug_log: Option<Vec<String>>, cancel_flag: &CancelFlag| ConceptSearchServiceResult {
+        results: vec![],
+        stats: ConceptSearchStats {
+            search_duration_seconds: start_time.elapsed().as_secs_f32(),
+            timed_out: cancel_flag.is_timed_out(),
+            cancelled: cancel_flag.is_cancelled(),
+            ..Default::default()
+        },
+        error: Some(error),
+        debug_log,
+    };
+
+    // The timeout covers the scan and embedding the functions missing from the cache.
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token).with_timeout(start_time, timeout_ms);
+    let scan_result =
+        scanner::perform_scan(root_path_str, vec![extension], 3, timeout_ms, &concept_scan_options(options), debug);
+    if let (Some(log_ref), Some(scan_log)) = (&mut debug_log_accumulator, scan_result.debug_log) {
+        log_ref.extend(scan_log);
+    }
+    if scan_result.cancelled {
+        return Ok(error_result("Find similar functions was cancelled.".to_string(), debug_log_accumulator, &cancel_flag));
+    }
+    let file_contexts = summaries::summary_contexts(scan_result.file_contexts, options.granularity);
+    let model = embedding::get_model(tag, &model_init_cache_dir, &options.routing)?;
+    let (embeddings, _) = embed_functions(
+        root_path_obj,
+        &db,
+        tag,
+        options.granularity,
+        options.routing.quantization,
+        model,
+        &file_contexts,
+        &mut debug_log_accumulator,
+        &cancel_flag,
+        &mut |_| {},
+    )?;
+    if cancel_flag.is_cancelled() {
+        return Ok(error_result("Find similar functions was cancelled.".to_string(), debug_log_accumulator, &cancel_flag));
+    }
+
+    let target_path = utils::canonical_or_original(&file_path);
+    let matches: Vec<usize> = embeddings
+        .iter()
+        .enumerate()
+        .filter(|(_, (ident, _))| {
+            (ident.function.name == function_name || ident.function.qualified_name == function_name)
+                && (line == 0 || (ident.function.start_line..=ident.function.end_line).contains(&line))
+                && utils::canonical_or_original(Path::new(&ident.file)) == target_path
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let target = match matches[..] {
+        [target] => target,
+        [] => {
+            let error = if cancel_flag.is_timed_out() || scan_result.timed_out_internally {
+                format!("Timed out before function '{}' in {} was embedded.", function_name, file_str)
+            } else if line > 0 {
+                format!("Function '{}' not found at line {} of {}.", function_name, line, file_str)
+            } else {
+                format!("Function '{}' not found in {}.", function_name, file_str)
+            };
+            return Ok(error_result(error, debug_log_accumulator, &cancel_flag));
+        }
+        _ => {
+            let lines: Vec<String> = matches.iter().map(|&i| embeddings[i].0.function.start_line.to_string()).collect();
+            let error = format!(
+                "Function '{}' is ambiguous in {}: defined at lines {}. Pass a line to pick one.",
+                function_name,
+                file_str,
+                lines.join(", ")
+            );
+            return Ok(error_result(error, debug_log_accumulator, &cancel_flag));
+        }
+    };
+
+    let target_embedding = &embeddings[target].1;
+    let mut similarities: Vec<(usize, f32)> = embeddings
+        .par_iter()
+        .enumerate()
+        .filter(|(i, (ident, _))| *i != target && passes_filters(ident, options))
+        .map(|(i, (_, doc_emb))| (i, similarity_score(target_embedding, doc_emb)))
+        .collect();
+    similarities.par_sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    let identifiers: Vec<FunctionIdentifier> = embeddings.into_iter().map(|(ident, _)| ident).collect();
+    if let Some(log_ref) = &mut debug_log_accumulator {
+        log_ref.push(format!(
+            "[FindSimilarFunctions] Compared '{}' with {} functions.",
+            identifiers[target].function.qualified_name,
+            similarities.len()
+        ));
+    }
+
+    Ok(ConceptSearchServiceResult {
+        results: build_result_items(above_min_similarity(&similarities, options), &identifiers, top_n, options),
+        stats: ConceptSearchStats {
+            functions_analyzed: identifiers.len(),
+            search_duration_seconds: start_time.elapsed().as_secs_f32(),
+            models_used: vec![tag],
+            timed_out: scan_result.timed_out_internally || cancel_flag.is_timed_out(),
+            ..Default::default()
+        },
+        error: None,
+        debug_log: debug_log_accumulator,
+    })
+}
+
+/// Returns the `top_n` functions of the project most similar to `function_name_c` in
+/// `file_c` (absolute, or relative to `root_path_c`), in the same shape as
+/// `concept_search`, with `similarity` measured against that function's embedding
+/// instead of a query. Useful for clone detection and for checking whether a helper
+/// already exists before writing one. Embeddings come from the project's embedding
+/// cache, so this is fast once concept search or `warm_cache` has run.
+///
+/// When the file defines several functions of that name, `line_c` (1-based, any line of
+/// the definition) picks one; `0` accepts only a unique name. `timeout_milliseconds`
+/// bounds the scan and the embedding of uncached functions (`0` for no limit); functions
+/// embedded by then are compared.
+///
+/// `options_c` takes the concept search options; model routing, `granularity`, the
+/// `cancellation_token`, and the result filters (`exclude_tests`, `min_similarity`,
+/// `diversity`, ...) apply.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c`, `file_c`, and `function_name_c` are
+/// valid, non-null, null-terminated UTF-8 encoded strings. `options_c` may be null;
+/// otherwise it must be a null-terminated UTF-8 JSON object matching
+/// `ConceptSearchOptions`. The memory pointed to by these pointers must remain valid
+/// for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn find_similar_functions(
+    root_path_c: *const c_char,
+    file_c: *const c_char,
+    function_name_c: *const c_char,
+    line_c: usize,
+    top_n_c: usize,
+    timeout_milliseconds: u32,
+    options_c: *const c_char,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let file_str = CStr::from_ptr(file_c).to_str().unwrap_or_default();
+    let function_name = CStr::from_ptr(function_name_c).to_str().unwrap_or_default();
+
+    let error_result = |error: String| ConceptSearchServiceResult {
+        results: vec![],
+        stats: ConceptSearchStats::default(),
+        error: Some(error),
+        debug_log: None,
+    };
+    let result = if root_path_str.is_empty() || file_str.is_empty() || function_name.is_empty() {
+        error_result("Error: root_path, file or function_name is null, empty or invalid UTF-8.".to_string())
+    } else {
+        match parse_options_json::<ConceptSearchOptions>(options_c) {
+            Ok(options) => pool::with_threads(options.num_threads)
+                .map_err(anyhow::Error::msg)
+                .and_then(|pool| {
+                    pool.install(|| {
+                        find_similar_functions_inner(
+                            root_path_str,
+                            file_str,
+                            function_name,
+                            line_c,
+                            top_n_c,
+                            timeout_milliseconds,
+                            &options,
+                            debug_c,
+                        )
+                    })
+                })
+                .unwrap_or_else(|e| error_result(format!("Find similar functions internal error: {:?}", e))),
+            Err(e) => error_result(e),
+        }
+    };
+
+    audit::record(
+        root_path_str,
+        "find_similar_functions",
+        &[
+            file_str,
+            function_name,
+            &line_c.to_string(),
+            &top_n_c.to_string(),
+            &timeout_milliseconds.to_string(),
+            options_text(options_c),
+        ],
+        start_time,
+        result.results.len(),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize concept search result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Makes sure every function in `file_contexts` has a cached embedding from the model
+/// `routing` assigns it. Returns how many were newly embedded, and how many there are.
+fn embed_routed(
+    root_path_obj: &Path,
+    file_contexts: &[FileContext],
+    routing: &ModelRouting,
+    debug_log: &mut Option<Vec<String>>,
+) -> Result<(usize, usize), anyhow::Error> {
+    let (model_init_cache_dir, db) = open_project_caches(root_path_obj)?;
+    let mut routed_contexts: BTreeMap<EmbeddingModelTag, Vec<FileContext>> = BTreeMap::new();
+    for fc in file_contexts {
+        let extension = parsing::file_extension(Path::new(&fc.path)).unwrap_or("");
+        routed_contexts.entry(routing.model_for(extension)).or_default().push(fc.clone());
+    }
+    let (mut functions_embedded, mut functions_total) = (0, 0);
+    for (tag, contexts) in &routed_contexts {
+        let model = embedding::get_model(*tag, &model_init_cache_dir, routing)?;
+        let (embeddings, newly_embedded) = embed_functions(
+            root_path_obj,
+            &db,
+            *tag,
+            Granularity::Function,
+            routing.quantization,
+            model,
+            contexts,
+            debug_log,
+            &CancelFlag::default(),
+            &mut |_| {},
+        )?;
+        functions_embedded += newly_embedded;
+        functions_total += embeddings.len();
+    }
+    Ok((functions_embedded, functions_total))
+}
+
+/// Parses exactly `paths` (absolute, or relative to the root) and makes sure every
+/// function in them has a cached embedding from the model `routing` assigns it.
+fn warm_cache_inner(
+    root_path_str: &str,
+    paths: Vec<String>,
+    routing: &ModelRouting,
+    debug: bool,
+) -> Result<WarmCacheResult, anyhow::Error> {
+    let start_time = Instant::now();
+    let root_path_obj = Path::new(root_path_str);
+    let mut debug_log_accumulator: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+
+    // Same detail level as the concept search scan, so embeddings are interchangeable.
+    let parsed: Vec<(String, Option<FileContext>)> = paths
+        .into_par_iter()
+        .map(|path| {
+            let context = parsing::parse_file(&root_path_obj.join(&path), 3, &ScanOptions::default()).ok();
+            (path, context)
+        })
+        .collect();
+    let mut skipped = Vec::new();
+    let mut file_contexts = Vec::new();
+    for (path, context) in parsed {
+        match context {
+            Some(context) => file_contexts.push(context),
+            None => skipped.push(path),
+        }
+    }
+    if let Some(log_ref) = &mut debug_log_accumulator {
+        log_ref.push(format!(
+            "[WarmCacheInner] {} files parsed, {} skipped.",
+            file_contexts.len(),
+            skipped.len()
+        ));
+    }
+
+    let (functions_embedded, functions_total) =
+        embed_routed(root_path_obj, &file_contexts, routing, &mut debug_log_accumulator)?;
+
+    Ok(WarmCacheResult {
+        files_warmed: file_contexts.len(),
+        functions_cached: functions_total - functions_embedded,
+        functions_embedded,
+        skipped,
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        error: None,
+        debug_log: debug_log_accumulator,
+    })
+}
+
+/// Embeds exactly the given files (e.g. those open in the editor or touched by a PR)
+/// into the project's embedding cache, so later concept searches over them are warm
+/// without indexing the whole repository first.
+///
+/// `paths_c` is a JSON array of file paths, absolute or relative to `root_path_c`.
+/// `options_c` selects the embedding models (see `ModelRouting`), typically a small,
+/// fast model for this kind of background indexing.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `paths_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it must be
+/// a null-terminated UTF-8 JSON object matching `ModelRouting`. The memory pointed to by
+/// these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn warm_cache(
+    root_path_c: *const c_char,
+    paths_c: *const c_char,
+    options_c: *const c_char,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let paths_json_str = CStr::from_ptr(paths_c).to_str().unwrap_or_default();
+
+    let result = if root_path_str.is_empty() {
+        WarmCacheResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        match serde_json::from_str::<Vec<String>>(paths_json_str)
+            .map_err(|e| format!("Failed to parse paths JSON: {}. Input was: '{}'", e, paths_json_str))
+            .and_then(|paths| parse_options_json::<ModelRouting>(options_c).map(|routing| (paths, routing)))
+        {
+            Ok((paths, routing)) => pool::shared()
+                .install(|| warm_cache_inner(root_path_str, paths, &routing, debug_c))
+                .unwrap_or_else(|e| WarmCacheResult {
+                    error: Some(format!("Warm cache internal error: {:?}", e)),
+                    ..Default::default()
+                }),
+            Err(e) => WarmCacheResult {
+                error: Some(e),
+                ..Default::default()
+            },
+        }
+    };
+
+    audit::record(
+        root_path_str,
+        "warm_cache",
+        &[paths_json_str, options_text(options_c)],
+        start_time,
+        result.files_warmed,
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize warm cache result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Removes the embedding cache entries of files that no longer exist under the root,
+/// for every model and granularity, and the vector files derived from them.
+/// Approximate-search indexes are kept; they already ignore functions that are gone.
+fn compact_embedding_cache_inner(root_path_str: &str, debug: bool) -> Result<CacheCompactionResult, anyhow::Error> {
+    let start_time = Instant::now();
+    let root_path_obj = Path::new(root_path_str);
+    let (_, db) = open_project_caches(root_path_obj)?;
+    let mut debug_log_accumulator: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+
+    let mut stale_keys = Vec::new();
+    let mut entries_kept = 0;
+    for entry in db.iter() {
+        let (key, _) = entry.with_context(|| "Failed to read embedding cache DB")?;
+        // Keys are `<model>/<path>` or `<model>#<granularity>/<path>` (see
+        // `embedding_cache_key`), besides the `ann/<model>` indexes.
+        let relative_path = std::str::from_utf8(&key)
+            .ok()
+            .and_then(|key| key.split_once('/'))
+            .filter(|(prefix, _)| *prefix != "ann")
+            .map(|(_, relative_path)| relative_path);
+        match relative_path {
+            Some(relative_path) if !root_path_obj.join(relative_path).is_file() => stale_keys.push(key),
+            _ => entries_kept += 1,
+        }
+    }
+    for key in &stale_keys {
+        db.remove(key).with_context(|| "Failed to remove stale embedding cache entry")?;
+    }
+    db.flush().with_context(|| "Failed to flush embedding cache DB")?;
+    // Vector files are rebuilt from the compacted entries by the next search.
+    let vector_files_removed = vector_file::remove_all(root_path_obj).with_context(|| "Failed to remove vector files")?;
+    if let Some(log_ref) = &mut debug_log_accumulator {
+        log_ref.push(format!(
+            "[CompactEmbeddingCache] Removed {} entries of missing files, kept {}; removed {} vector files.",
+            stale_keys.len(),
+            entries_kept,
+            vector_files_removed
+        ));
+    }
+
+    Ok(CacheCompactionResult {
+        entries_kept,
+        entries_removed: stale_keys.len(),
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        error: None,
+        debug_log: debug_log_accumulator,
+    })
+}
+
+/// Removes the embedding cache entries of deleted and renamed files, which concept
+/// search never reads again but which otherwise stay in the cache forever. Safe to
+/// run at any time, e.g. periodically or after large refactors.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` is a valid, non-null, null-terminated
+/// UTF-8 encoded string. The memory pointed to by this pointer must remain valid for
+/// the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn compact_embedding_cache(root_path_c: *const c_char, debug_c: bool) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+
+    let result = if root_path_str.is_empty() {
+        CacheCompactionResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        compact_embedding_cache_inner(root_path_str, debug_c).unwrap_or_else(|e| CacheCompactionResult {
+            error: Some(format!("Compact embedding cache internal error: {:?}", e)),
+            ..Default::default()
+        })
+    };
+
+    audit::record(
+        root_path_str,
+        "compact_embedding_cache",
+        &[],
+        start_time,
+        result.entries_removed,
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize cache compaction result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Granularity encoded in an embedding cache key prefix (see `embedding_cache_key`).
+fn cache_key_granularity(prefix: &str) -> Granularity {
+    match prefix.split_once('#').map(|(_, granularity)| granularity) {
+        Some("class") => Granularity::Class,
+        Some("file") => Granularity::File,
+        _ => Granularity::Function,
+    }
+}
+
+/// Summarizes the embedding cache of `root_path_str` per model and granularity.
+fn concept_cache_stats_inner(root_path_str: &str) -> Result<ConceptCacheStats, anyhow::Error> {
+    let (_, db) = open_project_caches(Path::new(root_path_str))?;
+    let mut stats = ConceptCacheStats {
+        size_on_disk_bytes: db.size_on_disk().unwrap_or(0) + vector_file::size_on_disk(Path::new(root_path_str)),
+        ..Default::default()
+    };
+    let mut models: BTreeMap<(EmbeddingModelTag, &str), ConceptCacheModelStats> = BTreeMap::new();
+    for entry in db.iter() {
+        let (key, value) = entry.with_context(|| "Failed to read embedding cache DB")?;
+        stats.entries += 1;
+        let prefix = std::str::from_utf8(&key).ok().and_then(|key| key.split_once('/'));
+        if let Some(("ann", model)) = prefix {
+            stats.ann_indexes.push(model.to_string());
+            continue;
+        }
+        let Ok(cached) = bincode::deserialize::<CachedFileEmbeddings>(&value) else {
+            stats.stale_entries += 1;
+            continue;
+        };
+        if cached.schema_version != EMBEDDING_CACHE_SCHEMA_VERSION {
+            stats.stale_entries += 1;
+            continue;
+        }
+        let granularity = cache_key_granularity(prefix.map_or("", |(prefix, _)| prefix));
+        let model_stats = models.entry((cached.model, granularity.as_str())).or_insert(ConceptCacheModelStats {
+            model: cached.model,
+            granularity,
+            files: 0,
+            functions: 0,
+            dimension: cached.dimension,
+            last_updated_ms: 0,
+        });
+        model_stats.files += 1;
+        model_stats.functions += cached.function_embeddings.len();
+        model_stats.last_updated_ms = model_stats.last_updated_ms.max(cached.updated_ms);
+    }
+    stats.models = models.into_values().collect();
+    Ok(stats)
+}
+
+/// Reports what the embedding cache of `root_path_c` holds: entry counts, size on disk,
+/// and per model and granularity the cached files and functions, vector length, and
+/// when it was last written. Hosts can show this next to a "rebuild index" action.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` is a valid, non-null, null-terminated
+/// UTF-8 encoded string. The memory pointed to by this pointer must remain valid for
+/// the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn concept_cache_stats(root_path_c: *const c_char) -> *mut c_char {
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+
+    let result = if root_path_str.is_empty() {
+        ConceptCacheStats {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        concept_cache_stats_inner(root_path_str).unwrap_or_else(|e| ConceptCacheStats {
+            error: Some(format!("Concept cache stats internal error: {:?}", e)),
+            ..Default::default()
+        })
+    };
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize concept cache stats: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Removes every entry of the embedding cache of `root_path_str`.
+fn clear_concept_cache_inner(root_path_str: &str) -> Result<CacheClearResult, anyhow::Error> {
+    let (_, db) = open_project_caches(Path::new(root_path_str))?;
+    let entries_removed = db.len();
+    db.clear().with_context(|| "Failed to clear embedding cache DB")?;
+    db.flush().with_context(|| "Failed to flush embedding cache DB")?;
+    vector_file::remove_all(Path::new(root_path_str)).with_context(|| "Failed to remove vector files")?;
+    Ok(CacheClearResult {
+        entries_removed,
+        error: None,
+    })
+}
+
+/// Empties the embedding cache of `root_path_c`, for every model, so the next concept
+/// search, `warm_cache`, or `build_concept_index` re-embeds the project from scratch.
+/// Concept indexes already built keep their snapshot until rebuilt.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` is a valid, non-null, null-terminated
+/// UTF-8 encoded string. The memory pointed to by this pointer must remain valid for
+/// the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn clear_concept_cache(root_path_c: *const c_char) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+
+    let result = if root_path_str.is_empty() {
+        CacheClearResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        clear_concept_cache_inner(root_path_str).unwrap_or_else(|e| CacheClearResult {
+            error: Some(format!("Clear concept cache internal error: {:?}", e)),
+            ..Default::default()
+        })
+    };
+
+    audit::record(
+        root_path_str,
+        "clear_concept_cache",
+        &[],
+        start_time,
+        result.entries_removed,
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize clear concept cache result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Brings the parse cache and embedding cache of `root_path_str` up to date for `paths`
+/// (relative to the root), or for the whole root when `paths` is empty. Parses through
+/// `perform_scan` at the detail level concept search uses, so its next scan hits the cache.
+fn refresh_watched_files(root_path_str: &str, extensions: &[String], paths: Vec<String>, routing: &ModelRouting) {
+    pool::shared().install(|| {
+        let scan_result = scanner::perform_scan(
+            root_path_str,
+            extensions.to_vec(),
+            3,
+            0,
+            &ScanOptions {
+                files: paths,
+                ..Default::default()
+            },
+            false,
+        );
+        // Nobody waits on a refresh to report to; a failed one is redone by the next
+        // change or by concept search itself.
+        let _ = embed_routed(Path::new(root_path_str), &scan_result.file_contexts, routing, &mut None);
+    });
+}
+
+/// Watches `root_path_c` for changes to files with the comma-separated `extensions_c`
+/// and keeps its parse cache and embedding cache up to date in the background, so
+/// concept searches over it stay warm. The whole root is indexed first; after that, each
+/// burst of changes is re-parsed and re-embedded once it settles. Stop with `watch_stop`.
+///
+/// `options_c` selects the embedding models (see `ModelRouting`) and should match the
+/// routing of later concept searches, since each model caches its own vectors.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it must be
+/// a null-terminated UTF-8 JSON object matching `ModelRouting`. The memory pointed to by
+/// these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn watch_start(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    options_c: *const c_char,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let root = Path::new(root_path_str).canonicalize().unwrap_or_else(|_| PathBuf::from(root_path_str));
+
+    let started = if root_path_str.is_empty() {
+        Err("root_path is null, empty, or invalid UTF-8.".to_string())
+    } else if extensions.is_empty() {
+        Err("extensions is empty or resulted in no valid extensions.".to_string())
+    } else {
+        parse_options_json::<ModelRouting>(options_c).and_then(|routing| {
+            let (refresh_root, refresh_extensions) = (root.to_string_lossy().into_owned(), extensions.clone());
+            watcher::start(
+                &root,
+                WatchKind::Caches,
+                extensions.clone(),
+                Box::new(move |paths| refresh_watched_files(&refresh_root, &refresh_extensions, paths, &routing)),
+            )
+        })
+    };
+    let result = WatchStartResult {
+        root: root.to_string_lossy().into_owned(),
+        extensions,
+        error: started.err(),
+    };
+
+    audit::record(
+        root_path_str,
+        "watch_start",
+        &[extensions_str, options_text(options_c)],
+        start_time,
+        usize::from(result.error.is_none()),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize watch result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Stops the watch `watch_start` began on `root_path_c`, waiting for a refresh in
+/// progress to finish. Returns `false` if the root was not being watched.
+///
+/// # Safety
+///
+/// The caller must ensure that `root_path_c` is a valid, non-null, null-terminated UTF-8
+/// encoded string that remains valid for the duration of this call.
+#[no_mangle]
+pub unsafe extern "C" fn watch_stop(root_path_c: *const c_char) -> bool {
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    !root_path_str.is_empty() && watcher::stop(Path::new(root_path_str), WatchKind::Caches)
+}
+
+/// Matches of `pattern` in a file's `lines`, each with the 0-based first and last line
+/// it touches. Line by line, a line matches at most once; in `multiline` mode the lines
+/// are searched joined by `\n`, so matches can span them. Match offsets are relative to
+/// their line: `start` to the first line, `end` to the last, clamped to its length.
+fn matched_line_ranges(lines: &[&str], pattern: &LinePattern, multiline: bool) -> Vec<(usize, usize, PatternMatch)> {
+    if !multiline {
+        return lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| pattern.find(line).map(|found| (i, i, found)))
+            .collect();
+    }
+    if lines.is_empty() {
+        return Vec::new();
+    }
+    let content = lines.join("\n");
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in lines {
+        line_starts.push(offset);
+        offset += line.len() + 1;
+    }
+    // Index of the line holding byte `offset`; a newline belongs to the line it ends.
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset).saturating_sub(1);
+    pattern
+        .find_all(&content)
+        .into_iter()
+        .map(|mut found| {
+            // `end` is exclusive, so the last matched byte decides the last line.
+            let (first, last) = (line_of(found.start), line_of(found.end.saturating_sub(1).max(found.start)));
+            found.start -= line_starts.get(first).copied().unwrap_or(0);
+            found.end = (found.end - line_starts.get(last).copied().unwrap_or(0))
+                .min(lines.get(last).map_or(0, |line| line.len()));
+            (first, last, found)
+        })
+        .collect()
+}
+
+/// `root_path_c` is a project root or a JSON array of roots (see `parse_roots`).
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c`, `search_string_c`, and `extensions_c`
+/// are valid, non-null, null-terminated UTF-8 encoded strings.
+/// `options_c` may be null; otherwise it must be a null-terminated UTF-8 JSON object
+/// matching `SearchOptions`.
+/// The memory pointed to by these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn project_wide_search(
+    root_path_c: *const c_char,
+    search_string_c: *const c_char,
+    extensions_c: *const c_char,
+    context_lines_c: u8,
+    timeout_ms_c: u32,
+    options_c: *const c_char,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let mut debug_log: Option<Vec<String>> = if debug_c { Some(Vec::new()) } else { None };
+
+    let root_path_str = match CStr::from_ptr(root_path_c).to_str() {
+        Ok(s) if !s.is_empty() => s,
+        _ => {
+            let result = SearchServiceResult {
+                output_version: 1,
+                results: vec![],
+                stats: Default::default(),
+                error: None,
+                debug_log: if debug_c {
+                    Some(vec![
+                        "Error: Root path is null, empty, or invalid UTF-8.".to_string()
+                    ])
+                } else {
+                    None
+                },
+            };
+            return CString::new(serde_json::to_string(&result).unwrap_or_default())
+                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+    let search_string = match CStr::from_ptr(search_string_c).to_str() {
+        Ok(s) if !s.is_empty() => s,
+        _ => {
+            let result = SearchServiceResult {
+                output_version: 1,
+                results: vec![],
+                stats: Default::default(),
+                error: None,
+                debug_log: if debug_c {
+                    Some(vec![
+                        "Error: Search string is null, empty, or invalid UTF-8.".to_string(),
+                    ])
+                } else {
+                    None
+                },
+            };
+            return CString::new(serde_json::to_string(&result).unwrap_or_default())
+                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or("");
+    let extensions: Vec<&str> = extensions_str
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if extensions.is_empty() {
+        let result = SearchServiceResult {
+            output_version: 1,
+            results: vec![],
+            stats: Default::default(),
+            error: None,
+            debug_log: if debug_c {
+                Some(vec![
+                    "Error: Extensions string is empty or resulted in no valid extensions."
+                        .to_string(),
+                ])
+            } else {
+                None
+            },
+        };
+        return CString::new(serde_json::to_string(&result).unwrap_or_default())
+            .map_or(std::ptr::null_mut(), |s| s.into_raw());
+    }
+
+    let (roots, options, path_filters, extension_filter, pattern) = match parse_roots(root_path_str).and_then(|roots| {
+        let opts: SearchOptions = parse_options_json(options_c)?;
+        opts.validate()?;
+        // Globs are relative to the root each file is found under.
+        let filters = roots
+            .iter()
+            .map(|root| config::path_filter(Path::new(root), &opts.include, &opts.exclude))
+            .collect::<Result<Vec<_>, _>>()?;
+        let extension_filter = Arc::new(config::ExtensionFilter::new(&extensions)?);
+        let pattern = SearchQuery::new(search_string, &opts)?;
+        Ok((roots, opts, filters, extension_filter, pattern))
+    }) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let result = SearchServiceResult {
+                output_version: 1,
+                results: vec![],
+                stats: Default::default(),
+                error: Some(e.clone()),
+                debug_log: if debug_c { Some(vec![format!("Error: {}", e)]) } else { None },
+            };
+            return CString::new(serde_json::to_string(&result).unwrap_or_default())
+                .map_or(std::ptr::null_mut(), |s| s.into_raw());
+        }
+    };
+
+    if let Some(log) = &mut debug_log {
+        log.push(format!(
+            "[ProjectSearch] Roots: {:?}, Query: '{}', Exts: {:?}, Timeout: {}ms",
+            roots, search_string, extensions, timeout_ms_c
+        ));
+    }
+
+    let mut walker_builder = WalkBuilder::new(&roots[0]);
+    for root in &roots[1..] {
+        walker_builder.add(root);
+    }
+    // Listed files are relative to the first root.
+    let listed_files_filter = path_filters[0].clone();
+    let skip_vendor_dirs = options.skip_vendor_dirs;
+    walker_builder
+        .hidden(!options.include_hidden)
+        .git_ignore(options.respect_gitignore) // Standard gitignore behavior
+        .git_global(options.respect_gitignore) // Include global gitignore
+        .git_exclude(options.respect_gitignore)
+        .max_depth(options.max_depth)
+        .follow_links(options.follow_links)
+        .add_custom_ignore_filename(config::IGNORE_FILE) // Project-specific excludes
+        .filter_entry(move |entry| {
+            !config::is_own_cache_path(entry.path())
+                && !config::is_git_dir(entry.path())
+                && (!skip_vendor_dirs || !config::is_vendor_dir(entry))
+                && !path_filters.iter().any(|filter| {
+                    entry.path().starts_with(filter.path())
+                        && config::is_filtered_out(filter, entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+                })
+        })
+        .threads(pool::threads_for(options.num_threads));
+
+    // An explicit file list (`SearchOptions::files`) replaces the walk, with the same
+    // checks as for scans (see `config::listed_files`).
+    if !options.files.is_empty() {
+        let (files, rejected) =
+            config::listed_files(Path::new(&roots[0]), &options.files, &listed_files_filter, options.skip_vendor_dirs);
+        if let Some(log) = &mut debug_log {
+            for rejected in &rejected {
+                log.push(format!("[ProjectSearch] Skipping listed file {}", rejected));
+            }
+            log.push(format!("[ProjectSearch] Searching {} listed file(s) without walking.", files.len()));
+        }
+        let mut files = files.into_iter();
+        if let Some(first) = files.next() {
+            walker_builder = WalkBuilder::new(first);
+            for file in files {
+                walker_builder.add(file);
+            }
+        }
+        walker_builder
+            .standard_filters(false)
+            .max_depth(Some(0))
+            .threads(pool::threads_for(options.num_threads));
+    }
+    let walker = walker_builder.build_parallel();
+
+    let results_arc = Arc::new(Mutex::new(Vec::<FileSearchResult>::new()));
+    let stats_arc = Arc::new(Mutex::new(SearchStats::default()));
+    let timed_out_arc = Arc::new(AtomicBool::new(false));
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token);
+    let debug_log_arc = Arc::new(Mutex::new(debug_log));
+
+    let rank_terms = ranking::search_terms(search_string, &options.patterns, options.boolean_query);
+    walker.run(|| {
+        let results_arc_box = Arc::clone(&results_arc); 
+        let stats_arc_box = Arc::clone(&stats_arc); 
+        let timed_out_clone_box = Arc::clone(&timed_out_arc); 
+        let extension_filter_box = Arc::clone(&extension_filter);
+        let pattern = &pattern;
+        let multiline = options.multiline;
+        let output_version = options.output_version;
+        let (symbol_context, function_bodies_only, skip_comments_and_strings) =
+            (options.symbol_context, options.function_bodies_only, options.skip_comments_and_strings);
+        let restricted = function_bodies_only || skip_comments_and_strings;
+        let needs_syntax = symbol_context || restricted;
+        let debug_log_arc_clone_box = Arc::clone(&debug_log_arc); 
+        let uri_format = options.uri_format;
+        let cancel_flag_box = cancel_flag.clone();
+        let (ranked, roots, rank_terms) = (options.ranked, &roots, &rank_terms);
+
+        Box::new(move |entry_result| {
+            if cancel_flag_box.is_cancelled() {
+                return ignore::WalkState::Quit;
+            }
+            if debug_c {
+                if timeout_ms_c > 0 && start_time.elapsed().as_millis() as u32 > timeout_ms_c {
+                    if !timed_out_clone_box.swap(true, Ordering::Relaxed) {
+                        if let Ok(mut guard) = debug_log_arc_clone_box.lock() {
+                            if let Some(log_vec) = guard.as_mut() {
+                                log_vec.push(
+                                    "[ProjectSearch] Timeout reached during walk.".to_string(),
+                                );
+                            }
+                        }
+                    }
+                    return ignore::WalkState::Quit;
+                }
                 if timed_out_clone_box.load(Ordering::Relaxed) {
                     return ignore::WalkState::Quit;
                 }
@@ -714,127 +3301,909 @@ pub unsafe extern "C" fn project_wide_search(
                 if timed_out_clone_box.load(Ordering::Relaxed) {
                     return ignore::WalkState::Quit;
                 }
-            }
+            }
+
+            if let Ok(entry) = entry_result {
+                if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                    let path = entry.path();
+                    if !extension_filter_box.matches(path, parsing::file_extension(path).unwrap_or("")) {
+                        return ignore::WalkState::Continue;
+                    }
+
+                    if entry.metadata().map_or(true, |m| m.len() > options.max_file_size_bytes) {
+                        stats_arc_box.lock().unwrap().files_skipped_large += 1;
+                        if debug_c {
+                            if let Ok(mut guard) = debug_log_arc_clone_box.lock() {
+                                if let Some(log_vec) = guard.as_mut() {
+                                    log_vec.push(format!(
+                                        "[ProjectSearch] Skipping large file (>{} bytes): {:?}",
+                                        options.max_file_size_bytes, path
+                                    ));
+                                }
+                            }
+                        }
+                        return ignore::WalkState::Continue;
+                    }
+                    // Files that are not UTF-8 are decoded rather than skipped, so legacy
+                    // Windows-encoded sources are still searched.
+                    let bytes = utils::read_file_bytes(path);
+                    let decoded = bytes.as_ref().map(|bytes| {
+                        utils::decode_text(bytes).map(|(text, encoding)| {
+                            // Notebooks are searched by their cells, not their JSON.
+                            let extension = parsing::file_extension(path).unwrap_or_default();
+                            match extract::extract(extension, &text) {
+                                Some(extracted) => (Cow::Owned(extracted.text), encoding, extracted.extension),
+                                None => (text, encoding, Some(extension)),
+                            }
+                        })
+                    });
+                    if let Ok(bytes) = &bytes {
+                        stats_arc_box.lock().unwrap().bytes_scanned += bytes.len() as u64;
+                    }
+                    if let Ok(None) = decoded {
+                        stats_arc_box.lock().unwrap().files_skipped_binary += 1;
+                        if debug_c {
+                            if let Ok(mut guard) = debug_log_arc_clone_box.lock() {
+                                if let Some(log_vec) = guard.as_mut() {
+                                    log_vec.push(format!(
+                                        "[ProjectSearch] Skipping binary file: {:?}",
+                                        path
+                                    ));
+                                }
+                            }
+                        }
+                        return ignore::WalkState::Continue;
+                    }
+
+                    if let Ok(Some((text, encoding, language_extension))) = decoded {
+                        // Lines borrow from the file's (possibly memory-mapped) bytes.
+                        let lines = if pattern.may_match(&text) { utils::split_lines(&text) } else { Vec::new() };
+                        let mut file_matches = Vec::new();
+                        let mut found_ranges = match pattern {
+                            SearchQuery::Pattern(pattern) => matched_line_ranges(&lines, pattern, multiline),
+                            SearchQuery::Boolean(expr, scope) => expr
+                                .matched_lines(&lines, *scope)
+                                .into_iter()
+                                .map(|(i, found)| (i, i, found))
+                                .collect(),
+                            SearchQuery::Multi(multi) => match &multi.matcher {
+                                MultiMatcher::Each(patterns) if multiline => {
+                                    let mut ranges: Vec<_> = patterns
+                                        .iter()
+                                        .enumerate()
+                                        .flat_map(|(index, pattern)| {
+                                            matched_line_ranges(&lines, pattern, true).into_iter().map(
+                                                move |(first, last, mut found)| {
+                                                    found.pattern = index;
+                                                    (first, last, found)
+                                                },
+                                            )
+                                        })
+                                        .collect();
+                                    ranges.sort_by_key(|(first, _, found)| (*first, found.pattern));
+                                    ranges
+                                }
+                                _ => multi
+                                    .matched_lines(&lines)
+                                    .into_iter()
+                                    .map(|(i, found)| (i, i, found))
+                                    .collect(),
+                            },
+                        };
+                        // Only files with matches are parsed, once each.
+                        let syntax = match language_extension {
+                            Some(extension) if needs_syntax && !found_ranges.is_empty() => {
+                                parsing::SyntaxIndex::parse(extension, lines.join("\n"))
+                            }
+                            _ => None,
+                        };
+                        let mut symbols = Vec::with_capacity(found_ranges.len());
+                        if needs_syntax {
+                            found_ranges.retain(|(first, _, found)| {
+                                let Some(syntax) = &syntax else { return !restricted };
+                                let location = syntax.locate(*first, found.start);
+                                let keep = (!function_bodies_only || location.in_function_body)
+                                    && (!skip_comments_and_strings || !location.in_comment_or_string);
+                                if keep {
+                                    symbols.push(location.symbol.filter(|_| symbol_context));
+                                }
+                                keep
+                            });
+                        }
+                        let mut symbols = symbols.into_iter();
+
+                        for (first, last, found) in found_ranges {
+                            let start_context = first.saturating_sub(context_lines_c as usize);
+                            let end_context =
+                                (last + context_lines_c as usize + 1).min(lines.len());
+
+                            let context = if output_version >= 2 {
+                                MatchContext::Lines {
+                                    context_before: lines[start_context..first].iter().map(|l| l.to_string()).collect(),
+                                    line: lines[first..=last].join("\n"),
+                                    context_after: lines[last + 1..end_context].iter().map(|l| l.to_string()).collect(),
+                                }
+                            } else {
+                                let mut context_buffer = Vec::new();
+                                for (j, context_line) in
+                                    lines[start_context..end_context].iter().enumerate()
+                                {
+                                    if (first..=last).contains(&(start_context + j)) {
+                                        context_buffer.push(format!(">> {}", context_line));
+                                    } else {
+                                        context_buffer.push(format!("   {}", context_line));
+                                    }
+                                }
+                                MatchContext::Joined { context: context_buffer.join("\n") }
+                            };
+                            let match_start = LineOffset::in_line(lines[first], found.start);
+                            file_matches.push(SearchMatch {
+                                line_number: first + 1, 
+                                end_line_number: last + 1,
+                                column: match_start.char + 1,
+                                match_start,
+                                match_end: LineOffset::in_line(lines[last], found.end),
+                                context,
+                                captures: found.captures,
+                                symbol: symbols.next().flatten(),
+                                pattern: match pattern {
+                                    SearchQuery::Multi(multi) => Some(multi.patterns[found.pattern].clone()),
+                                    _ => None,
+                                },
+                                uri: uri_format.map(|format| {
+                                    utils::location_uri(
+                                        format,
+                                        path.to_str().unwrap_or_default(),
+                                        first + 1,
+                                        last + 1,
+                                    )
+                                }),
+                            });
+                        }
+
+                        if !file_matches.is_empty() {
+                            let mut stats_guard = stats_arc_box.lock().unwrap(); 
+                            stats_guard.total_matches += file_matches.len();
+                            for file_match in &file_matches {
+                                if let Some(matched_pattern) = &file_match.pattern {
+                                    *stats_guard.matches_per_pattern.entry(matched_pattern.clone()).or_default() += 1;
+                                }
+                            }
+                            let score = ranked.then(|| {
+                                let relative = roots
+                                    .iter()
+                                    .find_map(|root| path.strip_prefix(root).ok())
+                                    .unwrap_or(path);
+                                ranking::relevance(relative, rank_terms, file_matches.len(), lines.len())
+                            });
+                            results_arc_box.lock().unwrap().push(FileSearchResult { 
+                                path: path.to_str().unwrap_or_default().to_string(),
+                                encoding: (!matches!(encoding, "utf-8" | "utf-8-bom")).then(|| encoding.to_string()),
+                                matches: file_matches,
+                                score,
+                            });
+                        }
+                    }
+                    stats_arc_box.lock().unwrap().files_scanned += 1; 
+                }
+            }
+            ignore::WalkState::Continue
+        })
+    });
+
+    let mut final_stats = stats_arc.lock().unwrap().clone(); 
+    final_stats.timed_out = timed_out_arc.load(Ordering::Relaxed); 
+    final_stats.cancelled = cancel_flag.is_cancelled();
+    final_stats.elapsed_ms = start_time.elapsed().as_millis() as u64;
+    if let SearchQuery::Multi(multi) = &pattern {
+        // Listing unmatched patterns at 0 tells the host which ones are absent.
+        for unmatched in &multi.patterns {
+            final_stats.matches_per_pattern.entry(unmatched.clone()).or_default();
+        }
+    }
+
+    let mut final_results = results_arc.lock().unwrap().clone(); 
+    if options.ranked {
+        // Best first; the path breaks ties so equal scores keep a stable order.
+        final_results.sort_by(|a, b| {
+            b.score
+                .unwrap_or_default()
+                .total_cmp(&a.score.unwrap_or_default())
+                .then_with(|| a.path.cmp(&b.path))
+        });
+    }
+    let final_debug_log_val = if debug_c {
+        debug_log_arc.lock().unwrap().clone()
+    } else {
+        None
+    };
+
+    let result = SearchServiceResult {
+        output_version: options.output_version,
+        results: final_results,
+        stats: final_stats,
+        error: None,
+        debug_log: final_debug_log_val,
+    };
+
+    audit::record(
+        &roots[0],
+        "project_wide_search",
+        &[search_string, extensions_str, &context_lines_c.to_string(), options_text(options_c)],
+        start_time,
+        result.stats.total_matches,
+        None,
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        let mut current_debug_log = result.debug_log; 
+         if debug_c {
+            current_debug_log.get_or_insert_with(Vec::new).push(format!("Failed to serialize project_wide_search result: {}", e));
+        }
+        if debug_c {
+            format!(
+                "{{\"error\":\"Failed to serialize project_wide_search result: {}\", \"debug_log\":[\"Serialization error\"]}}",
+                e
+            )
+        } else {
+             format!(
+                "{{\"error\":\"Failed to serialize project_wide_search result: {}\"}}",
+                e
+            )
+        }
+    });
+
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Scans the functions under a root and returns their call graph as a JSON
+/// adjacency list (see `CallGraphResult`).
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. The memory pointed to by these pointers must
+/// remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn build_call_graph(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    timeout_milliseconds: u32,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let result = if root_path_str.is_empty() {
+        CallGraphResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if extensions.is_empty() {
+        CallGraphResult {
+            error: Some("Error: extensions is empty or resulted in no valid extensions.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        let scan_result = scanner::perform_scan(
+            root_path_str,
+            extensions,
+            1,
+            timeout_milliseconds,
+            &ScanOptions {
+                calls: true,
+                ..Default::default()
+            },
+            debug_c,
+        );
+        CallGraphResult {
+            adjacency: call_graph::build(Path::new(root_path_str), &scan_result.file_contexts),
+            functions_analyzed: scan_result.file_contexts.iter().map(|fc| fc.functions.len()).sum(),
+            duration_seconds: start_time.elapsed().as_secs_f32(),
+            timed_out_internally: scan_result.timed_out_internally,
+            error: None,
+            debug_log: scan_result.debug_log,
+        }
+    };
+
+    audit::record(
+        root_path_str,
+        "build_call_graph",
+        &[extensions_str],
+        start_time,
+        result.adjacency.len(),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Lists `TODO`/`FIXME`/`HACK`/`XXX` comments under a root as JSON, each with its
+/// file, line, author hint, and enclosing function. The walk takes the same
+/// `ScanOptions` as `scan_and_parse`.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it must be
+/// a null-terminated UTF-8 JSON object matching `ScanOptions`. The memory pointed to by
+/// these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn scan_todos(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    timeout_milliseconds: u32,
+    options_c: *const c_char,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let options = parse_options_json(options_c).and_then(|opts: ScanOptions| opts.validate().map(|_| opts));
+
+    let result = if root_path_str.is_empty() {
+        TodoScanResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if extensions.is_empty() {
+        TodoScanResult {
+            error: Some("Error: extensions is empty or resulted in no valid extensions.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        match options {
+            Ok(options) => scanner::scan_todos(root_path_str, &extensions, timeout_milliseconds, &options, debug_c),
+            Err(e) => TodoScanResult {
+                error: Some(e),
+                ..Default::default()
+            },
+        }
+    };
+
+    audit::record(
+        root_path_str,
+        "scan_todos",
+        &[extensions_str, options_text(options_c)],
+        start_time,
+        result.todos.len(),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Re-parses changed files and returns the function symbols added and removed since the
+/// previous call for the same root (see `symbols::sync`), so editor integrations can keep
+/// a workspace symbol index current without re-pulling full outlines after every save.
+///
+/// `paths_c` is a JSON array of changed file paths, absolute or relative to `root_path_c`;
+/// paths that resolve to outside the root are ignored.
+/// If it is null or an empty array, the whole root is rescanned.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. `paths_c` may be null; otherwise it must be a
+/// null-terminated UTF-8 JSON array of strings. The memory pointed to by these pointers
+/// must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn sync_workspace_symbols(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    paths_c: *const c_char,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let paths_json_str = if paths_c.is_null() {
+        ""
+    } else {
+        CStr::from_ptr(paths_c).to_str().unwrap_or_default()
+    };
+
+    let result = if root_path_str.is_empty() {
+        SymbolSyncResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if extensions.is_empty() {
+        SymbolSyncResult {
+            error: Some("Error: extensions is empty or resulted in no valid extensions.".to_string()),
+            ..Default::default()
+        }
+    } else if paths_json_str.trim().is_empty() {
+        symbols::sync(Path::new(root_path_str), &extensions, &[], debug_c)
+    } else {
+        match serde_json::from_str::<Vec<String>>(paths_json_str) {
+            Ok(paths) => symbols::sync(Path::new(root_path_str), &extensions, &paths, debug_c),
+            Err(e) => SymbolSyncResult {
+                error: Some(format!("Failed to parse paths JSON: {}. Input was: '{}'", e, paths_json_str)),
+                ..Default::default()
+            },
+        }
+    };
+
+    audit::record(
+        root_path_str,
+        "sync_workspace_symbols",
+        &[extensions_str, paths_json_str],
+        start_time,
+        result.added.len() + result.removed.len(),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Receives a JSON-encoded `SymbolSyncResult` from `subscribe_workspace_symbols`.
+/// The string is owned by Rust and only valid for the duration of the call.
+pub type SymbolSyncCallback = unsafe extern "C" fn(delta_json: *const c_char, user_data: *mut c_void);
+
+/// Subscribes to the function symbols of `root_path_c`: it is watched for changes to
+/// files with the comma-separated `extensions_c`, and the symbols added and removed by
+/// each settled burst of changes are pushed to `callback` (see `symbols::subscribe`).
+/// The first call reports every symbol as added. Stop with `unsubscribe_workspace_symbols`.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `extensions_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings that remain valid for the duration of this call.
+/// `callback` is invoked from a background thread, one call at a time, until the
+/// subscription is stopped; `user_data` is passed through to it unchanged and must stay
+/// valid until then.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn subscribe_workspace_symbols(
+    root_path_c: *const c_char,
+    extensions_c: *const c_char,
+    callback: Option<SymbolSyncCallback>,
+    user_data: *mut c_void,
+    debug_c: bool,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let root = Path::new(root_path_str).canonicalize().unwrap_or_else(|_| PathBuf::from(root_path_str));
+
+    let started = if root_path_str.is_empty() {
+        Err("root_path is null, empty, or invalid UTF-8.".to_string())
+    } else if extensions.is_empty() {
+        Err("extensions is empty or resulted in no valid extensions.".to_string())
+    } else if let Some(callback) = callback {
+        let user_data = CallbackUserData(user_data);
+        symbols::subscribe(
+            &root,
+            extensions.clone(),
+            debug_c,
+            Box::new(move |delta| {
+                if let Ok(Ok(json_c)) = serde_json::to_string(&delta).map(CString::new) {
+                    callback(json_c.as_ptr(), user_data.get());
+                }
+            }),
+        )
+    } else {
+        Err("callback is null.".to_string())
+    };
+    let result = WatchStartResult {
+        root: root.to_string_lossy().into_owned(),
+        extensions,
+        error: started.err(),
+    };
 
-            if let Ok(entry) = entry_result {
-                if entry.file_type().is_some_and(|ft| ft.is_file()) {
-                    let path = entry.path();
-                    if !local_extensions_clone_box.iter().any(|ext| {
-                        path.to_str()
-                            .unwrap_or("")
-                            .ends_with(ext.trim_start_matches('.'))
-                    }) {
-                        return ignore::WalkState::Continue;
-                    }
+    audit::record(
+        root_path_str,
+        "subscribe_workspace_symbols",
+        &[extensions_str],
+        start_time,
+        usize::from(result.error.is_none()),
+        result.error.clone(),
+    );
 
-                    if entry.metadata().map_or(true, |m| m.len() > 5_000_000) {
-                        if debug_c {
-                            if let Ok(mut guard) = debug_log_arc_clone_box.lock() {
-                                if let Some(log_vec) = guard.as_mut() {
-                                    log_vec.push(format!(
-                                        "[ProjectSearch] Skipping large file (5MB+): {:?}",
-                                        path
-                                    ));
-                                }
-                            }
-                        }
-                        return ignore::WalkState::Continue;
-                    }
-                    if utils::is_binary(path) {
-                        if debug_c {
-                            if let Ok(mut guard) = debug_log_arc_clone_box.lock() {
-                                if let Some(log_vec) = guard.as_mut() {
-                                    log_vec.push(format!(
-                                        "[ProjectSearch] Skipping binary file: {:?}",
-                                        path
-                                    ));
-                                }
-                            }
-                        }
-                        return ignore::WalkState::Continue;
-                    }
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize subscription result: {}\"}}", e)
+    });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
 
-                    if let Ok(file) = fs::File::open(path) {
-                        let reader = BufReader::new(file);
-                        let lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
-                        let mut file_matches = Vec::new();
+/// Stops the subscription `subscribe_workspace_symbols` began on `root_path_c`, waiting
+/// for a delivery in progress to finish; `callback` is not called after this returns.
+/// Returns `false` if the root had no subscription.
+///
+/// # Safety
+///
+/// The caller must ensure that `root_path_c` is a valid, non-null, null-terminated UTF-8
+/// encoded string that remains valid for the duration of this call.
+#[no_mangle]
+pub unsafe extern "C" fn unsubscribe_workspace_symbols(root_path_c: *const c_char) -> bool {
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    !root_path_str.is_empty() && watcher::stop(Path::new(root_path_str), WatchKind::Symbols)
+}
 
-                        for (i, line) in lines.iter().enumerate() {
-                            if line.contains(&search_string_clone_box) {
-                                let start_context = i.saturating_sub(context_lines_c as usize);
-                                let end_context =
-                                    (i + context_lines_c as usize + 1).min(lines.len());
+/// Fuzzy-matches `query_c` against the paths of files with the comma-separated
+/// `extensions_c` under `root_path_c` and the names of the functions in them, ranked
+/// best first (see `fuzzy::score`), to drive editor "Go to file" and "Go to symbol"
+/// pickers. Function names come from the parse cache, so repeated calls are cheap.
+///
+/// `options_c` selects the candidates and caps the results (see `FuzzyFindOptions`).
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c`, `query_c` and `extensions_c` are valid,
+/// non-null, null-terminated UTF-8 encoded strings. `options_c` may be null; otherwise it
+/// must be a null-terminated UTF-8 JSON object matching `FuzzyFindOptions`. The memory
+/// pointed to by these pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn fuzzy_find(
+    root_path_c: *const c_char,
+    query_c: *const c_char,
+    extensions_c: *const c_char,
+    options_c: *const c_char,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let query_str = CStr::from_ptr(query_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
 
-                                let mut context_buffer = Vec::new();
-                                for (j, context_line) in
-                                    lines[start_context..end_context].iter().enumerate()
-                                {
-                                    if start_context + j == i {
-                                        context_buffer.push(format!(">> {}", context_line));
-                                    } else {
-                                        context_buffer.push(format!("   {}", context_line));
-                                    }
-                                }
-                                file_matches.push(SearchMatch {
-                                    line_number: i + 1, 
-                                    context: context_buffer.join("\n"),
-                                });
-                            }
-                        }
+    let result = if root_path_str.is_empty() {
+        FuzzyFindResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if query_str.trim().is_empty() {
+        FuzzyFindResult {
+            error: Some("Error: query is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if extensions.is_empty() {
+        FuzzyFindResult {
+            error: Some("Error: extensions is empty or resulted in no valid extensions.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        match parse_options_json::<FuzzyFindOptions>(options_c) {
+            Ok(options) => fuzzy::find(Path::new(root_path_str), query_str, &extensions, &options),
+            Err(e) => FuzzyFindResult {
+                error: Some(e),
+                ..Default::default()
+            },
+        }
+    };
 
-                        if !file_matches.is_empty() {
-                            let mut stats_guard = stats_arc_box.lock().unwrap(); 
-                            stats_guard.total_matches += file_matches.len();
-                            results_arc_box.lock().unwrap().push(FileSearchResult { 
-                                path: path.to_str().unwrap_or_default().to_string(),
-                                matches: file_matches,
-                            });
-                        }
-                    }
-                    stats_arc_box.lock().unwrap().files_scanned += 1; 
-                }
-            }
-            ignore::WalkState::Continue
-        })
-    });
+    audit::record(
+        root_path_str,
+        "fuzzy_find",
+        &[query_str, extensions_str, options_text(options_c)],
+        start_time,
+        result.matches.len(),
+        result.error.clone(),
+    );
 
-    let mut final_stats = stats_arc.lock().unwrap().clone(); 
-    final_stats.timed_out = timed_out_arc.load(Ordering::Relaxed); 
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
 
-    let final_results = results_arc.lock().unwrap().clone(); 
-    let final_debug_log_val = if debug_c {
-        debug_log_arc.lock().unwrap().clone()
+/// Finds where `symbol_c` is defined among the files with the comma-separated
+/// `extensions_c` under `root_path_c`: the functions, methods, classes and other
+/// declarations named by it, plainly (`save`) or qualified (`UserRepository.save`,
+/// `FileCache::save`). Gives hosts a go-to-definition without a language server;
+/// symbols come from the parse cache, so repeated lookups are cheap.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c`, `symbol_c` and `extensions_c` are valid,
+/// non-null, null-terminated UTF-8 encoded strings. The memory pointed to by these
+/// pointers must remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn find_definition(
+    root_path_c: *const c_char,
+    symbol_c: *const c_char,
+    extensions_c: *const c_char,
+) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let symbol_str = CStr::from_ptr(symbol_c).to_str().unwrap_or_default();
+    let extensions_str = CStr::from_ptr(extensions_c).to_str().unwrap_or_default();
+    let extensions: Vec<String> = extensions_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let result = if root_path_str.is_empty() {
+        DefinitionResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if symbol_str.trim().is_empty() {
+        DefinitionResult {
+            error: Some("Error: symbol is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if extensions.is_empty() {
+        DefinitionResult {
+            error: Some("Error: extensions is empty or resulted in no valid extensions.".to_string()),
+            ..Default::default()
+        }
     } else {
-        None
+        symbols::find_definitions(Path::new(root_path_str), symbol_str, &extensions)
     };
 
-    let result = SearchServiceResult {
-        results: final_results,
-        stats: final_stats,
-        debug_log: final_debug_log_val,
+    audit::record(
+        root_path_str,
+        "find_definition",
+        &[symbol_str, extensions_str],
+        start_time,
+        result.definitions.len(),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Lists every occurrence of `symbol_c` under `root_path_c`, in all supported languages,
+/// each classified as a definition, call, other reference, or comment/string text (see
+/// `symbols::find_references`), together with the symbol's definitions.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// The caller must ensure that `root_path_c` and `symbol_c` are valid, non-null,
+/// null-terminated UTF-8 encoded strings. The memory pointed to by these pointers must
+/// remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn find_references(root_path_c: *const c_char, symbol_c: *const c_char) -> *mut c_char {
+    let start_time = Instant::now();
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let symbol_str = CStr::from_ptr(symbol_c).to_str().unwrap_or_default();
+
+    let result = if root_path_str.is_empty() {
+        ReferenceResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else if symbol_str.trim().is_empty() {
+        ReferenceResult {
+            error: Some("Error: symbol is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        }
+    } else {
+        symbols::find_references(Path::new(root_path_str), symbol_str)
     };
 
-    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
-        let mut current_debug_log = result.debug_log; 
-         if debug_c {
-            current_debug_log.get_or_insert_with(Vec::new).push(format!("Failed to serialize project_wide_search result: {}", e));
+    audit::record(
+        root_path_str,
+        "find_references",
+        &[symbol_str],
+        start_time,
+        result.references.len(),
+        result.error.clone(),
+    );
+
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Returns the most recent `limit` audit records for a root (all records if `limit` is 0)
+/// as JSON. Records are only written while `RAPID_AUDIT_LOG` is enabled.
+///
+/// # Safety
+///
+/// `root_path_c` must be a valid, non-null, null-terminated UTF-8 string that remains
+/// valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn read_audit_log(root_path_c: *const c_char, limit: usize) -> *mut c_char {
+    let root_path_str = CStr::from_ptr(root_path_c).to_str().unwrap_or_default();
+    let result = if root_path_str.is_empty() {
+        AuditLogResult {
+            error: Some("Error: root_path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
         }
-        if debug_c {
-            format!(
-                "{{\"error\":\"Failed to serialize project_wide_search result: {}\", \"debug_log\":[\"Serialization error\"]}}",
-                e
-            )
-        } else {
-             format!(
-                "{{\"error\":\"Failed to serialize project_wide_search result: {}\"}}",
-                e
-            )
+    } else {
+        match audit::read(Path::new(root_path_str), limit) {
+            Ok(records) => AuditLogResult { records, error: None },
+            Err(e) => AuditLogResult {
+                error: Some(format!("Failed to read audit log: {}", e)),
+                ..Default::default()
+            },
+        }
+    };
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Writes UTF-8 text to a file atomically, keeping the CRLF line endings and byte order
+/// mark of the file it replaces (see `atomic_write::write_text_preserving`). Host-side
+/// features that write files go through this so they share one safe implementation.
+///
+/// # Safety
+///
+/// `path_c` and `text_c` must be valid, non-null, null-terminated UTF-8 strings that
+/// remain valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn write_text_file(path_c: *const c_char, text_c: *const c_char) -> *mut c_char {
+    let path_str = CStr::from_ptr(path_c).to_str().unwrap_or_default();
+    let result = match CStr::from_ptr(text_c).to_str() {
+        _ if path_str.is_empty() => WriteFileResult {
+            error: Some("Error: path is null, empty or invalid UTF-8.".to_string()),
+            ..Default::default()
+        },
+        Ok(text) => match atomic_write::write_text_preserving(Path::new(path_str), text) {
+            Ok(bytes_written) => WriteFileResult { bytes_written, error: None },
+            Err(e) => WriteFileResult {
+                error: Some(format!("Failed to write {:?}: {}", path_str, e)),
+                ..Default::default()
+            },
+        },
+        Err(e) => WriteFileResult {
+            error: Some(format!("Text is not valid UTF-8: {}", e)),
+            ..Default::default()
+        },
+    };
+    let json_output = serde_json::to_string(&result).unwrap_or_default();
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
+
+/// Fixes the process-wide settings from `config` (see `RuntimeConfig`), builds the
+/// shared thread pool, and loads `preload_models`. Only the first call takes effect,
+/// so it is safe to call from several threads or integrations.
+fn rapid_init_inner(config: RuntimeConfig) -> RuntimeInitResult {
+    let already_initialized = !runtime::init(config);
+    let config = runtime::config().cloned().unwrap_or_default();
+    let num_threads = pool::shared().current_num_threads();
+    let model_cache_dir = config::model_cache_dir(&std::env::current_dir().unwrap_or_default());
+    let mut result = RuntimeInitResult {
+        already_initialized,
+        num_threads,
+        model_cache_dir: model_cache_dir.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+    if let Err(e) = fs::create_dir_all(&model_cache_dir) {
+        result.error = Some(format!("Failed to create model cache directory at {:?}: {}", model_cache_dir, e));
+        return result;
+    }
+    for tag in config.preload_models {
+        match embedding::get_model(tag, &model_cache_dir, &config.routing) {
+            Ok(_) => result.models_loaded.push(tag),
+            Err(e) => {
+                result.error.get_or_insert_with(|| format!("{:#}", e));
+            }
         }
+    }
+    result
+}
+
+/// Sets up the library once for the whole host process: thread pool size, model cache
+/// location, and models to load up front, instead of every call initializing global
+/// state on first use and reading environment variables. Optional; calls made without
+/// it use the environment and defaults. Later calls change nothing and report
+/// `already_initialized`, so this is idempotent and safe to call concurrently.
+///
+/// # Safety
+///
+/// This function is unsafe because it dereferences raw pointers passed from C.
+/// `config_c` may be null; otherwise it must be a null-terminated UTF-8 JSON object
+/// matching `RuntimeConfig`, valid for the duration of this call.
+/// The returned `*mut c_char` must be deallocated by the C caller using `free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn rapid_init(config_c: *const c_char) -> *mut c_char {
+    let result = match parse_options_json::<RuntimeConfig>(config_c) {
+        Ok(config) => rapid_init_inner(config),
+        Err(e) => RuntimeInitResult {
+            error: Some(e),
+            ..Default::default()
+        },
+    };
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize init result: {}\"}}", e)
     });
+    CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
 
+/// Releases what the library holds between calls: stops every watch, drops the concept
+/// indexes, and flushes and closes the embedding and parse caches, so their directories
+/// can be removed or opened by another process. Call it once no other call is running,
+/// e.g. when the host unloads the library. Loaded models and thread pools live on for
+/// the rest of the process. Safe to call more than once; later calls reopen what they
+/// need.
+#[no_mangle]
+pub extern "C" fn rapid_shutdown() -> *mut c_char {
+    let watches_stopped = watcher::stop_all();
+    let concept_indexes_released = {
+        let mut indexes = CONCEPT_INDEXES.lock().unwrap_or_else(|e| e.into_inner());
+        let released = indexes.len();
+        indexes.clear();
+        released
+    };
+    let embedding_databases: Vec<(PathBuf, sled::Db)> =
+        EMBEDDING_DATABASES.lock().unwrap_or_else(|e| e.into_inner()).drain().collect();
+    let mut error = None;
+    for (path, db) in &embedding_databases {
+        if let Err(e) = db.flush() {
+            error.get_or_insert_with(|| format!("Failed to flush embedding cache at {:?}: {}", path, e));
+        }
+    }
+    let (parse_caches_closed, parse_cache_error) = parse_cache::close_all();
+    let result = RuntimeShutdownResult {
+        watches_stopped,
+        concept_indexes_released,
+        databases_closed: embedding_databases.len() + parse_caches_closed,
+        error: error.or(parse_cache_error),
+    };
+    let json_output = serde_json::to_string(&result).unwrap_or_else(|e| {
+        format!("{{\"error\":\"Failed to serialize shutdown result: {}\"}}", e)
+    });
     CString::new(json_output).map_or(std::ptr::null_mut(), |s| s.into_raw())
 }
 
+/// Creates a cancellation token. Pass it as `cancellation_token` in the options of
+/// `scan_and_parse`, `concept_search`, or `project_wide_search`, then call `cancel`
+/// from any thread to stop those calls early; they return what they found so far.
+/// Release the token with `release_cancellation_token` when it is no longer needed.
+#[no_mangle]
+pub extern "C" fn create_cancellation_token() -> u64 {
+    cancellation::create_token()
+}
+
+/// Cancels every call running with `token`. Returns `false` if the token is unknown or
+/// was released.
+#[no_mangle]
+pub extern "C" fn cancel(token: u64) -> bool {
+    cancellation::cancel(token)
+}
+
+/// Releases a token from `create_cancellation_token`. Returns `false` if the token is
+/// unknown or was already released.
+#[no_mangle]
+pub extern "C" fn release_cancellation_token(token: u64) -> bool {
+    cancellation::release_token(token)
+}
+
 /// # Safety
 ///
 /// This function is unsafe because it dereferences a raw pointer `s` passed from C.
@@ -849,3 +4218,48 @@ pub unsafe extern "C" fn free_string(s: *mut c_char) {
         let _ = CString::from_raw(s);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(lines: &[&str], search: &str, options: SearchOptions) -> Vec<(usize, usize, usize, usize)> {
+        let pattern = LinePattern::new(search, &options).expect("valid pattern");
+        matched_line_ranges(lines, &pattern, options.multiline)
+            .into_iter()
+            .map(|(first, last, found)| (first, last, found.start, found.end))
+            .collect()
+    }
+
+    fn multiline() -> SearchOptions {
+        SearchOptions { multiline: true, ..Default::default() }
+    }
+
+    #[test]
+    fn empty_file_has_no_matches() {
+        assert!(ranges(&[], "foo", SearchOptions::default()).is_empty());
+        assert!(ranges(&[], "foo", multiline()).is_empty());
+    }
+
+    #[test]
+    fn single_line_search_reports_the_first_match_of_each_line() {
+        let lines = ["foo bar bar", "", "bar"];
+        assert_eq!(ranges(&lines, "bar", SearchOptions::default()), vec![(0, 0, 4, 7), (2, 2, 0, 3)]);
+    }
+
+    #[test]
+    fn multiline_match_spans_lines_with_line_relative_offsets() {
+        let lines = ["let x = [", "    1,", "];"];
+        let options = SearchOptions { use_regex: true, ..multiline() };
+        assert_eq!(ranges(&lines, r"\[\s+1,\s+\]", options), vec![(0, 2, 8, 1)]);
+    }
+
+    #[test]
+    fn match_ending_at_a_line_boundary_stays_on_that_line() {
+        let lines = ["foo", "bar"];
+        // The trailing newline belongs to line 0; `end` is clamped to its length.
+        assert_eq!(ranges(&lines, "foo\n", multiline()), vec![(0, 0, 0, 3)]);
+        assert_eq!(ranges(&lines, "bar", multiline()), vec![(1, 1, 0, 3)]);
+        assert_eq!(ranges(&lines, "o\nb", multiline()), vec![(0, 1, 2, 1)]);
+    }
+}
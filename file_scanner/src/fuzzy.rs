@@ -0,0 +1,216 @@
+use crate::config;
+use crate::options::{FuzzyFindOptions, ScanOptions};
+use crate::parsing;
+use crate::scanner;
+use crate::structs::{FuzzyFindResult, FuzzyMatch, FuzzyMatchKind};
+use ignore::WalkBuilder;
+use rayon::prelude::*;
+use std::path::Path;
+use std::time::Instant;
+
+/// Score of every matched query character.
+const SCORE_MATCH: i64 = 16;
+/// Bonus for a match at the start of a word: after a separator or at the very start.
+const BONUS_BOUNDARY: i64 = 8;
+/// Bonus for a match on a camelCase hump or the first digit of a number.
+const BONUS_CAMEL: i64 = 7;
+/// Bonus for a match directly after the previous one.
+const BONUS_CONSECUTIVE: i64 = 4;
+/// Penalty for the first skipped character between two matches.
+const PENALTY_GAP_START: i64 = -3;
+/// Penalty for every further skipped character.
+const PENALTY_GAP_EXTENSION: i64 = -1;
+
+/// How well `chars[i]` starts a word, with a path separator ranked highest so file-name
+/// matches beat matches inside directory names.
+fn boundary_bonus(chars: &[char], i: usize) -> i64 {
+    let Some(&prev) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return BONUS_BOUNDARY;
+    };
+    let current = chars[i];
+    if prev == '/' || prev == '\\' {
+        BONUS_BOUNDARY + 1
+    } else if !prev.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if (prev.is_lowercase() && current.is_uppercase())
+        || (!prev.is_ascii_digit() && current.is_ascii_digit())
+    {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// Scores `candidate` against `query` the way fzf's v1 algorithm does: finds the first
+/// occurrence of the query as a subsequence, shrinks it to the shortest window ending
+/// there, and rewards word starts and consecutive runs while penalizing gaps. Smart
+/// case: the match is case-sensitive only if the query has an uppercase letter.
+///
+/// Returns `None` if `candidate` does not contain the query, otherwise the score and
+/// the character indices of the matched query characters. Whitespace in the query is
+/// ignored, and an empty query matches everything with score 0.
+pub fn score(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let case_sensitive = query.iter().any(|c| c.is_uppercase());
+    let same = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+    let chars: Vec<char> = candidate.chars().collect();
+
+    let mut matched = 0;
+    let end = chars.iter().position(|&c| {
+        if same(c, query[matched]) {
+            matched += 1;
+        }
+        matched == query.len()
+    })?;
+    // Walking back from `end` finds the latest start, i.e. the shortest window.
+    let mut remaining = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if same(chars[i], query[remaining - 1]) {
+            remaining -= 1;
+            if remaining == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    for (i, &c) in chars.iter().enumerate().take(end + 1).skip(start) {
+        if positions.len() < query.len() && same(c, query[positions.len()]) {
+            positions.push(i);
+        }
+    }
+    let mut total = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        let bonus = boundary_bonus(&chars, i);
+        // The first query character counts double, as in fzf.
+        total += SCORE_MATCH + if n == 0 { bonus * 2 } else { bonus };
+        if n > 0 {
+            let gap = (i - positions[n - 1] - 1) as i64;
+            total += match gap {
+                0 => BONUS_CONSECUTIVE,
+                _ => PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap - 1),
+            };
+        }
+    }
+    Some((total, positions))
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Files below `root` with one of `extensions`, relative to it. Ignore rules apply as
+/// in a default scan.
+fn candidate_files(root: &Path, extensions: &config::ExtensionFilter) -> Vec<String> {
+    let mut files = Vec::new();
+    let walker = WalkBuilder::new(root)
+        .add_custom_ignore_filename(config::IGNORE_FILE)
+        .filter_entry(|entry| {
+            !config::is_own_cache_path(entry.path())
+                && !config::is_git_dir(entry.path())
+                && !config::is_vendor_dir(entry)
+        })
+        .build();
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let extension = parsing::file_extension(entry.path()).unwrap_or("");
+        if extensions.matches(entry.path(), extension) {
+            files.push(relative_path(root, entry.path()));
+        }
+    }
+    files
+}
+
+/// Ranks the files with `extensions` under `root` and the functions in them against
+/// `query` (see `score`), for "go to file" and "go to symbol" pickers. Functions come
+/// from a names-only scan, which the parse cache serves for unchanged files.
+pub fn find(root: &Path, query: &str, extensions: &[String], options: &FuzzyFindOptions) -> FuzzyFindResult {
+    let start_time = Instant::now();
+    let extension_filter = match config::ExtensionFilter::new(extensions) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return FuzzyFindResult {
+                error: Some(e),
+                ..Default::default()
+            };
+        }
+    };
+    // Candidates as (kind, path, qualified name, line).
+    let mut candidates: Vec<(FuzzyMatchKind, String, Option<String>, Option<usize>)> = Vec::new();
+    if options.files {
+        candidates.extend(
+            candidate_files(root, &extension_filter)
+                .into_iter()
+                .map(|path| (FuzzyMatchKind::File, path, None, None)),
+        );
+    }
+    if options.symbols {
+        let scan_result = scanner::perform_scan(
+            &root.to_string_lossy(),
+            extensions.to_vec(),
+            0,
+            0,
+            &ScanOptions::default(),
+            false,
+        );
+        for file_context in &scan_result.file_contexts {
+            let path = relative_path(root, Path::new(&file_context.path));
+            for function in &file_context.functions {
+                candidates.push((
+                    FuzzyMatchKind::Symbol,
+                    path.clone(),
+                    Some(function.qualified_name.clone()),
+                    Some(function.start_line),
+                ));
+            }
+        }
+    }
+
+    let mut matches: Vec<FuzzyMatch> = candidates
+        .par_iter()
+        .filter_map(|(kind, path, name, line)| {
+            let (score, positions) = score(query, name.as_deref().unwrap_or(path))?;
+            Some(FuzzyMatch {
+                kind: *kind,
+                path: path.clone(),
+                name: name.clone(),
+                line: *line,
+                score,
+                positions,
+            })
+        })
+        .collect();
+    // Best score first; among equals, the shorter (more specific) text wins.
+    matches.sort_by(|a, b| {
+        let text = |m: &FuzzyMatch| m.name.clone().unwrap_or_else(|| m.path.clone());
+        b.score
+            .cmp(&a.score)
+            .then_with(|| text(a).len().cmp(&text(b).len()))
+            .then_with(|| text(a).cmp(&text(b)))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    matches.truncate(options.max_results);
+
+    FuzzyFindResult {
+        matches,
+        candidates: candidates.len(),
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        error: None,
+    }
+}
@@ -0,0 +1,60 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Runs `git` in `root` with NUL-separated output (`-z`, so unusual file names are not
+/// quoted) and returns the listed paths, or git's error message.
+fn git_paths(root: &Path, args: &[&str]) -> Result<Vec<String>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            // The first line holds the reason; usage text may follow.
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .next()
+                .unwrap_or_default()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Files under `root` that differ from `git_ref` (a commit, branch, or tag) in the
+/// working tree, including uncommitted edits and untracked files that are not ignored.
+/// Deleted files are left out. Paths are relative to `root`.
+pub fn changed_since(root: &Path, git_ref: &str) -> Result<Vec<String>, String> {
+    if git_ref.is_empty() || git_ref.starts_with('-') {
+        return Err(format!("Invalid git ref: '{}'", git_ref));
+    }
+    // `--relative` limits the diff to `root` and reports paths relative to it, like
+    // `ls-files` does by default.
+    let mut files = git_paths(
+        root,
+        &[
+            "diff",
+            "-z",
+            "--name-only",
+            "--relative",
+            "--diff-filter=d",
+            "--no-renames",
+            git_ref,
+            "--",
+        ],
+    )?;
+    files.extend(git_paths(
+        root,
+        &["ls-files", "-z", "--others", "--exclude-standard"],
+    )?);
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
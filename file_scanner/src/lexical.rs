@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use crate::structs::TermCorrection;
+
+/// BM25 term-frequency saturation parameter.
+const K1: f32 = 1.2;
+/// BM25 document-length normalization parameter.
+const B: f32 = 0.75;
+
+/// Splits text into lowercase search terms.
+///
+/// Identifiers are broken up on non-alphanumeric characters and on camelCase
+/// boundaries, so `parseJsonFile` and `parse_json_file` both yield
+/// `parse`, `json`, `file`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let mut current = String::new();
+        let mut prev_lower = false;
+        for c in word.chars() {
+            if c.is_uppercase() && prev_lower && !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            current.extend(c.to_lowercase());
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+    }
+    tokens
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut current = vec![0; b_chars.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b_chars.len()]
+}
+
+/// An in-memory BM25 index over a fixed set of documents.
+pub struct Bm25Index {
+    term_frequencies: Vec<HashMap<String, u32>>,
+    doc_lengths: Vec<usize>,
+    avg_doc_length: f32,
+    doc_frequencies: HashMap<String, usize>,
+}
+
+impl Bm25Index {
+    /// Builds an index where document `i` is `documents[i]`.
+    pub fn new(documents: &[String]) -> Self {
+        let mut term_frequencies = Vec::with_capacity(documents.len());
+        let mut doc_lengths = Vec::with_capacity(documents.len());
+        let mut doc_frequencies: HashMap<String, usize> = HashMap::new();
+
+        for document in documents {
+            let tokens = tokenize(document);
+            let mut frequencies: HashMap<String, u32> = HashMap::new();
+            for token in &tokens {
+                *frequencies.entry(token.clone()).or_insert(0) += 1;
+            }
+            for term in frequencies.keys() {
+                *doc_frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            doc_lengths.push(tokens.len());
+            term_frequencies.push(frequencies);
+        }
+
+        let avg_doc_length = if doc_lengths.is_empty() {
+            0.0
+        } else {
+            doc_lengths.iter().sum::<usize>() as f32 / doc_lengths.len() as f32
+        };
+
+        Bm25Index {
+            term_frequencies,
+            doc_lengths,
+            avg_doc_length,
+            doc_frequencies,
+        }
+    }
+
+    /// Tokenizes `query`, replacing terms that appear in no document with the closest
+    /// indexed term within `max_edits` edits.
+    ///
+    /// The allowed distance also shrinks with term length (one edit per four characters)
+    /// so short words are not rewritten into unrelated ones. Ties go to the term found
+    /// in more documents.
+    pub fn resolve_query(
+        &self,
+        query: &str,
+        max_edits: usize,
+    ) -> (Vec<String>, Vec<TermCorrection>) {
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut corrections = Vec::new();
+        for term in terms.iter_mut() {
+            let allowed = max_edits.min(term.chars().count() / 4);
+            if allowed == 0 || self.doc_frequencies.contains_key(term.as_str()) {
+                continue;
+            }
+            let best = self
+                .doc_frequencies
+                .iter()
+                .map(|(candidate, &df)| (edit_distance(term, candidate), df, candidate))
+                .filter(|(distance, _, _)| *distance <= allowed)
+                .min_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(b.2)));
+            if let Some((_, _, candidate)) = best {
+                corrections.push(TermCorrection {
+                    original: std::mem::replace(term, candidate.clone()),
+                    corrected: candidate.clone(),
+                });
+            }
+        }
+        terms.sort();
+        terms.dedup();
+        (terms, corrections)
+    }
+
+    /// Scores every document against resolved `query_terms`; the result is indexed like
+    /// the input documents.
+    pub fn score_terms(&self, query_terms: &[String]) -> Vec<f32> {
+        let doc_count = self.term_frequencies.len() as f32;
+
+        self.term_frequencies
+            .iter()
+            .zip(&self.doc_lengths)
+            .map(|(frequencies, &doc_length)| {
+                query_terms
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = *frequencies.get(term)? as f32;
+                        let df = *self.doc_frequencies.get(term)? as f32;
+                        let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        let length_norm = if self.avg_doc_length > 0.0 {
+                            doc_length as f32 / self.avg_doc_length
+                        } else {
+                            1.0
+                        };
+                        Some(idf * (tf * (K1 + 1.0)) / (tf + K1 * (1.0 - B + B * length_norm)))
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
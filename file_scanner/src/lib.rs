@@ -1,8 +1,29 @@
+mod ann;
+mod atomic_write;
+mod audit;
+mod call_graph;
+mod cancellation;
 mod config;
+mod continuation;
 mod embedding;
+mod extract;
 mod ffi;
+mod fuzzy;
+mod git;
+mod lexical;
+mod options;
+mod parse_cache;
 mod parsing;
+mod pattern;
+mod pool;
+mod progress;
+mod ranking;
+mod runtime;
 mod scanner;
 mod structs;
+mod summaries;
+mod symbols;
 mod utils;
+mod vector_file;
+mod watcher;
 pub use ffi::*;
@@ -0,0 +1,558 @@
+use crate::config;
+use crate::structs::{CrossEncoderTag, EmbeddingModelTag, Granularity, Quantization, ScoringBackend};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Editor-friendly URI schemes that results can be annotated with.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UriFormat {
+    /// `file:///abs/path#L10-L20`
+    File,
+    /// `vscode://file/abs/path:10`
+    Vscode,
+}
+
+/// What a boolean search query (see `SearchOptions::boolean_query`) is evaluated against.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BooleanScope {
+    /// Each line on its own: a line matches if the query holds for it.
+    #[default]
+    Line,
+    /// The whole file: if the query holds for it, every line with a non-negated term
+    /// matches.
+    File,
+}
+
+/// Latency budget hint for a single call. Each options type maps it to a bundle of
+/// settings (see `BudgetDefaults`), so callers can trade quality for speed without
+/// learning every option. Settings passed explicitly alongside `budget` win.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Budget {
+    Fast,
+    Balanced,
+    Thorough,
+}
+
+/// Options types that accept a `"budget"` key.
+pub trait BudgetDefaults {
+    /// Settings applied for `budget` unless the caller sets them, as fields of the
+    /// options JSON object.
+    fn budget_defaults(budget: Budget) -> serde_json::Value;
+}
+
+/// Optional settings for `scan_and_parse`, passed by the host as a JSON object.
+///
+/// Every field has a default, so callers only send the settings they want to change.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// Annotate every function with a location URI in this format.
+    pub uri_format: Option<UriFormat>,
+    /// Include nested/local functions and named closures (`depth > 0`).
+    pub include_nested: bool,
+    /// Only keep functions with a decorator/attribute containing this string
+    /// (e.g. `"test"`, `"route"`, `"HttpGet"`).
+    pub annotation_filter: Option<String>,
+    /// Also return each file's hierarchical symbol tree in `FileContext::outline`.
+    pub outline: bool,
+    /// Tree-sitter queries that replace the built-in query for an extension (keyed
+    /// without the leading dot, e.g. `"rs"`), regardless of compactness level.
+    pub query_overrides: HashMap<String, String>,
+    /// Tree-sitter patterns appended to the query for an extension, e.g. to extract
+    /// project-specific macros. Use the same captures as the built-in queries
+    /// (`@method_name`, `@function_definition`, `@body`, ...).
+    pub extra_queries: HashMap<String, String>,
+    /// Report conditions that are normally skipped silently (unsupported extensions,
+    /// no matching files, a query that extracts nothing) in `ScanResult::errors`.
+    /// Intended for CI, where an empty result usually means a misconfiguration.
+    pub strict: bool,
+    /// Also record the names each function calls in `FunctionInfo::calls`.
+    pub calls: bool,
+    /// Files larger than this are skipped.
+    pub max_file_size_bytes: u64,
+    /// Only scan files matching one of these globs, relative to the root (e.g. `src/**`).
+    /// Empty means every file with a requested extension.
+    pub include: Vec<String>,
+    /// Skip files and directories matching any of these globs (e.g. `**/vendor/**`,
+    /// `**/*_test.go`).
+    pub exclude: Vec<String>,
+    /// Honor `.gitignore`, `.git/info/exclude`, and the global gitignore. Turn off to
+    /// index ignored build output. `.rapidignore` applies either way.
+    pub respect_gitignore: bool,
+    /// Also walk hidden files and dot directories (`.github/`, `.config/`). `.git/` is
+    /// always skipped.
+    pub include_hidden: bool,
+    /// Maximum directory depth below the root; `None` walks the whole tree. Bounds deep
+    /// trees such as vendored `node_modules`.
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links, e.g. to index symlinked source trees. Link cycles are
+    /// detected and skipped.
+    pub follow_links: bool,
+    /// Skip `node_modules`, `target`, `.venv`, `dist`, and `build` directories (see
+    /// `config::VENDOR_DIRS`), whether or not they are gitignored.
+    pub skip_vendor_dirs: bool,
+    /// Parse exactly these files (absolute, or relative to the root) instead of walking
+    /// the root, e.g. the output of `git diff --name-only` for diff-scoped tooling. The
+    /// extension, size, glob, and vendor checks still apply; ignore rules do not. Files
+    /// that resolve to outside the root are left out (see `config::listed_files`).
+    pub files: Vec<String>,
+    /// Reuse parse results of unchanged files from earlier scans, cached under
+    /// `<root>/.cache` (see `parse_cache`).
+    pub use_cache: bool,
+    /// Sort `file_contexts` by path and each file's functions by start line, so repeated
+    /// scans return identical output (e.g. for snapshot tests). Turn off to skip the sort
+    /// when order does not matter.
+    pub sort_results: bool,
+    /// `ScanResult::continuation_token` of a timed-out scan with the same root; skips the
+    /// files that scan (and the ones it resumed) already visited.
+    pub continuation_token: Option<u64>,
+    /// Worker threads for this scan, e.g. to keep an editor hosting the library
+    /// responsive. `None` uses the shared budget (`RAPID_NUM_THREADS` or one per core),
+    /// which also caps larger values.
+    pub num_threads: Option<usize>,
+    /// Token from `create_cancellation_token`; cancelling it stops the scan early, with
+    /// `ScanResult::cancelled` set.
+    pub cancellation_token: Option<u64>,
+    /// Approximate cap on the text held in `file_contexts` (bodies, comments, names,
+    /// imports). Once full bodies no longer fit, further files keep only each function's
+    /// first line; once those no longer fit either, files are dropped. Counted in
+    /// `ScanResult::stats`. `None` keeps everything.
+    pub max_result_bytes: Option<u64>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            uri_format: None,
+            include_nested: true,
+            annotation_filter: None,
+            outline: false,
+            query_overrides: HashMap::new(),
+            extra_queries: HashMap::new(),
+            strict: false,
+            calls: false,
+            max_file_size_bytes: 1_000_000,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            respect_gitignore: true,
+            include_hidden: false,
+            max_depth: None,
+            follow_links: false,
+            skip_vendor_dirs: true,
+            files: Vec::new(),
+            use_cache: true,
+            sort_results: true,
+            continuation_token: None,
+            num_threads: None,
+            cancellation_token: None,
+            max_result_bytes: None,
+        }
+    }
+}
+
+impl BudgetDefaults for ScanOptions {
+    fn budget_defaults(budget: Budget) -> serde_json::Value {
+        match budget {
+            Budget::Fast => serde_json::json!({ "max_file_size_bytes": 250_000 }),
+            Budget::Balanced => serde_json::json!({}),
+            Budget::Thorough => serde_json::json!({ "max_file_size_bytes": 4_000_000 }),
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Checks that every user-supplied query compiles for its extension and every
+    /// include/exclude glob parses.
+    pub fn validate(&self) -> Result<(), String> {
+        for (extension, query) in self.query_overrides.iter().chain(&self.extra_queries) {
+            config::validate_query(extension, query)?;
+        }
+        config::path_filter(Path::new(""), &self.include, &self.exclude)?;
+        Ok(())
+    }
+}
+
+/// Optional settings for `project_wide_search`, passed by the host as a JSON object.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Annotate every match with a location URI in this format.
+    pub uri_format: Option<UriFormat>,
+    /// Files larger than this are not searched.
+    pub max_file_size_bytes: u64,
+    /// Only search files matching one of these globs (see `ScanOptions::include`).
+    pub include: Vec<String>,
+    /// Skip files and directories matching any of these globs.
+    pub exclude: Vec<String>,
+    /// Honor gitignore files (see `ScanOptions::respect_gitignore`).
+    pub respect_gitignore: bool,
+    /// Also search hidden files and dot directories, except `.git/`.
+    pub include_hidden: bool,
+    /// Maximum directory depth below the root; `None` walks the whole tree.
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links.
+    pub follow_links: bool,
+    /// Skip dependency and build output directories (see `ScanOptions::skip_vendor_dirs`).
+    pub skip_vendor_dirs: bool,
+    /// Worker threads for this call (see `ScanOptions::num_threads`).
+    pub num_threads: Option<usize>,
+    /// Token from `create_cancellation_token` (see `ScanOptions::cancellation_token`).
+    pub cancellation_token: Option<u64>,
+    /// Treat the search string as a regular expression (regex crate syntax) instead of
+    /// a literal substring. Capture groups are returned with each match.
+    pub use_regex: bool,
+    /// Match regardless of letter case, in literal and regex mode.
+    pub case_insensitive: bool,
+    /// Only match where the search string (or regex match) starts and ends at a word
+    /// boundary, so `get` does not match `get_value`.
+    pub whole_word: bool,
+    /// Search each file as a whole instead of line by line, so a match can span lines
+    /// (e.g. `fn parse\(\s*path` across a wrapped signature). Every line a match touches
+    /// is marked in its context.
+    pub multiline: bool,
+    /// Shape of each match's context: `1` joins the lines into one string with the
+    /// matched lines marked `>> `; `2` returns `context_before`, `line`, and
+    /// `context_after` separately, so they can be rendered without parsing the markers.
+    pub output_version: u32,
+    /// Name every match's innermost enclosing function or class (e.g.
+    /// `UserRepository.save`), parsing only the files that have matches.
+    pub symbol_context: bool,
+    /// Only keep matches inside function bodies, dropping those in signatures and at
+    /// module or class level.
+    pub function_bodies_only: bool,
+    /// Drop matches that start inside a comment or string literal. With this or
+    /// `function_bodies_only` set, files in languages that cannot be parsed have no matches.
+    pub skip_comments_and_strings: bool,
+    /// Search exactly these files (absolute, or relative to the first root) instead of
+    /// walking the roots, e.g. the paths of a previous result to narrow it down. The
+    /// extension, size, glob, and vendor checks still apply; ignore rules do not. Files
+    /// that resolve to outside the root are left out (see `config::listed_files`).
+    pub files: Vec<String>,
+    /// Read the search string as a boolean query of terms combined with `AND`, `OR`,
+    /// `NOT`, and parentheses, e.g. `foo AND bar NOT baz`. Adjacent terms are ANDed, and
+    /// quoted terms (`"a AND b"`) are taken literally. Each term is matched as the other
+    /// options say, e.g. as a regex with `use_regex`.
+    pub boolean_query: bool,
+    /// Whether a boolean query is evaluated per line or per file.
+    pub boolean_scope: BooleanScope,
+    /// Further strings to search for in the same walk as the search string, e.g. a list
+    /// of deprecated APIs. Each match then names the pattern it matched, and the stats
+    /// count matches per pattern.
+    pub patterns: Vec<String>,
+    /// Order files by relevance (see `ranking::relevance`) instead of walk order, and
+    /// report each file's `score`.
+    pub ranked: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            uri_format: None,
+            max_file_size_bytes: 5_000_000,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            respect_gitignore: true,
+            include_hidden: false,
+            max_depth: None,
+            follow_links: false,
+            skip_vendor_dirs: true,
+            num_threads: None,
+            cancellation_token: None,
+            use_regex: false,
+            case_insensitive: false,
+            whole_word: false,
+            multiline: false,
+            output_version: 1,
+            symbol_context: false,
+            function_bodies_only: false,
+            skip_comments_and_strings: false,
+            files: Vec::new(),
+            boolean_query: false,
+            boolean_scope: BooleanScope::Line,
+            patterns: Vec::new(),
+            ranked: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Checks that `output_version` is one this build can produce and that the search
+    /// modes can be combined.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=2).contains(&self.output_version) {
+            return Err(format!("Unsupported output_version {}; expected 1 or 2.", self.output_version));
+        }
+        if self.boolean_query && self.multiline {
+            return Err("boolean_query cannot be combined with multiline.".to_string());
+        }
+        if self.boolean_query && !self.patterns.is_empty() {
+            return Err("boolean_query cannot be combined with patterns.".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl BudgetDefaults for SearchOptions {
+    fn budget_defaults(budget: Budget) -> serde_json::Value {
+        match budget {
+            Budget::Fast => serde_json::json!({ "max_file_size_bytes": 1_000_000 }),
+            Budget::Balanced => serde_json::json!({}),
+            Budget::Thorough => serde_json::json!({ "max_file_size_bytes": 20_000_000 }),
+        }
+    }
+}
+
+/// Optional settings for `fuzzy_find`, passed by the host as a JSON object.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FuzzyFindOptions {
+    /// Maximum number of matches returned, best first.
+    pub max_results: usize,
+    /// Match file paths, relative to the root.
+    pub files: bool,
+    /// Match qualified function names.
+    pub symbols: bool,
+}
+
+impl Default for FuzzyFindOptions {
+    fn default() -> Self {
+        FuzzyFindOptions {
+            max_results: 50,
+            files: true,
+            symbols: true,
+        }
+    }
+}
+
+impl BudgetDefaults for FuzzyFindOptions {
+    fn budget_defaults(_budget: Budget) -> serde_json::Value {
+        serde_json::json!({})
+    }
+}
+
+/// Which embedding model embeds which files, for `concept_search` and `warm_cache`,
+/// and where the models are loaded from.
+///
+/// Cached embeddings are tagged with their model, so switching models re-embeds the
+/// affected files once and keeps the other model's entries.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ModelRouting {
+    /// Model used for every file without a `language_models` rule.
+    pub model: EmbeddingModelTag,
+    /// Per-extension model overrides, keyed without the leading dot
+    /// (e.g. `{"rs": "jina-code"}`).
+    pub language_models: HashMap<String, EmbeddingModelTag>,
+    /// Pre-downloaded models, loaded from these directories instead of the Hugging Face
+    /// hub (e.g. `{"bge-small": "/models/bge-small-en-v1.5"}`). Each directory holds the
+    /// ONNX model and its `tokenizer.json`, `config.json`, `special_tokens_map.json` and
+    /// `tokenizer_config.json`, as in a copy of the model's hub repository.
+    pub local_models: HashMap<EmbeddingModelTag, String>,
+    /// Never download: models not in `local_models` must already be in the project's
+    /// model cache, and fail to load otherwise. For air-gapped environments.
+    pub offline: bool,
+    /// Precision embeddings are cached with. `f16` and `int8` make the cache about
+    /// two and four times smaller; entries are converted when their file is next
+    /// embedded or its entry rewritten.
+    pub quantization: Quantization,
+}
+
+impl BudgetDefaults for ModelRouting {
+    fn budget_defaults(budget: Budget) -> serde_json::Value {
+        match budget {
+            Budget::Fast => serde_json::json!({ "model": "bge-small" }),
+            Budget::Balanced | Budget::Thorough => serde_json::json!({}),
+        }
+    }
+}
+
+impl ModelRouting {
+    /// Model that embeds files with `extension`.
+    pub fn model_for(&self, extension: &str) -> EmbeddingModelTag {
+        self.language_models
+            .get(extension.trim_start_matches('.'))
+            .copied()
+            .unwrap_or(self.model)
+    }
+}
+
+/// Process-wide settings a host passes once to `rapid_init`, before or instead of
+/// relying on environment variables.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Workers of the shared thread pool; overrides `RAPID_NUM_THREADS`.
+    pub num_threads: Option<usize>,
+    /// Directory models are downloaded to and loaded from; overrides `RAPID_MODEL_CACHE`
+    /// (see `config::model_cache_dir`).
+    pub model_cache_dir: Option<String>,
+    /// Models to load during `rapid_init`, so the first search does not pay for it.
+    pub preload_models: Vec<EmbeddingModelTag>,
+    /// Where preloaded models are loaded from (`local_models`, `offline`).
+    #[serde(flatten)]
+    pub routing: ModelRouting,
+}
+
+impl BudgetDefaults for RuntimeConfig {
+    fn budget_defaults(_budget: Budget) -> serde_json::Value {
+        serde_json::json!({})
+    }
+}
+
+/// Optional settings for `concept_search`, passed by the host as a JSON object.
+///
+/// Every field has a default, so callers only send the settings they want to change.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ConceptSearchOptions {
+    /// Number of source lines to include before and after each matched function
+    /// (imports, class headers, etc.). `0` disables context.
+    pub context_lines: usize,
+    /// Cut result bodies longer than this many characters, ending them with `…` and
+    /// setting `ConceptSearchResultItem::truncated`. `0` keeps bodies whole.
+    pub max_body_chars: usize,
+    /// Only rank functions with at least one parameter whose declared type contains
+    /// this string (e.g. `"UserRepository"` matches `&UserRepository` and `Vec<UserRepository>`).
+    pub parameter_type: Option<String>,
+    /// Annotate every result with a location URI in this format.
+    pub uri_format: Option<UriFormat>,
+    /// If the embedding model cannot be loaded (no network, unsupported CPU), rank
+    /// functions with BM25 keyword scoring instead of failing. The fallback is
+    /// reported in `ConceptSearchStats::model_fallback`.
+    pub keyword_fallback: bool,
+    /// Retrieval backend: `"embedding"` (default) or `"bm25"`. BM25 skips the model
+    /// entirely and is better suited to exact identifier queries.
+    pub backend: ScoringBackend,
+    /// Rank functions (default), classes, or whole files. Classes and files are
+    /// embedded as summaries built from their signatures (see `Granularity`), so
+    /// queries like "where is the billing module" find the module itself.
+    pub granularity: Granularity,
+    /// Include nested/local functions and named closures in the ranking.
+    pub include_nested: bool,
+    /// Maximum edit distance used to correct misspelled query terms during keyword
+    /// scoring (`"authetication"` → `"authentication"`). `0` disables correction.
+    pub spelling_tolerance: usize,
+    /// Models used to embed functions and the query (`model`, `language_models`).
+    #[serde(flatten)]
+    pub routing: ModelRouting,
+    /// Re-score the best `rerank_candidates` functions with this model, e.g. a
+    /// higher-quality model on top of a fast indexing model. Re-ranking embeddings are
+    /// computed per search and not cached.
+    pub rerank_model: Option<EmbeddingModelTag>,
+    /// Re-score the best `rerank_candidates` functions with this cross-encoder, after
+    /// `rerank_model` if both are set. Its scores replace `similarity` for those results.
+    pub cross_encoder: Option<CrossEncoderTag>,
+    /// Number of top functions re-scored by `rerank_model` and `cross_encoder`. Only
+    /// these are returned when either is set.
+    pub rerank_candidates: usize,
+    /// Leave out results whose `similarity` is below this (see
+    /// `ConceptSearchResultItem::similarity`). `0` keeps every result. With re-ranking,
+    /// it applies to the scores before re-ranking.
+    pub min_similarity: f32,
+    /// Trade relevance for spreading results over distinct files and directories, by
+    /// maximal marginal relevance: a candidate's similarity is lowered by this much
+    /// when an earlier result is in the same file, and by half of it when one is in
+    /// the same directory. `0` ranks by similarity alone.
+    pub diversity: f32,
+    /// Steer the ranking away from a concept, e.g. `"unit tests"` for the query
+    /// `"authentication"`: its embedding, scaled by `negative_weight`, is subtracted
+    /// from the query's before functions are scored. BM25 scoring ignores it.
+    pub negative_query: Option<String>,
+    /// How strongly `negative_query` pushes results away, from `0` (ignored) to `1`
+    /// (as strongly as the query pulls).
+    pub negative_weight: f32,
+    /// Compare the query with every function. By default, models with many functions
+    /// (20,000 or more) only score the functions in the clusters of an approximate
+    /// nearest-neighbour index that are closest to the query, which is much faster but
+    /// can miss a few matches. The index is kept in the embedding cache.
+    pub exact_search: bool,
+    /// Files larger than this are not scanned.
+    pub max_file_size_bytes: u64,
+    /// Only rank functions from files matching one of these globs (see
+    /// `ScanOptions::include`).
+    pub include: Vec<String>,
+    /// Skip files and directories matching any of these globs.
+    pub exclude: Vec<String>,
+    /// Leave test code out of the ranking, so it does not take the top slots when the
+    /// question is about production logic: files matching `config::TEST_PATH_GLOBS`
+    /// and functions marked as tests (`#[test]`, `[Fact]`, `@pytest...`).
+    pub exclude_tests: bool,
+    /// Honor gitignore files (see `ScanOptions::respect_gitignore`).
+    pub respect_gitignore: bool,
+    /// Also scan hidden files and dot directories, except `.git/`.
+    pub include_hidden: bool,
+    /// Maximum directory depth below the root; `None` walks the whole tree.
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links.
+    pub follow_links: bool,
+    /// Skip dependency and build output directories (see `ScanOptions::skip_vendor_dirs`).
+    pub skip_vendor_dirs: bool,
+    /// Worker threads for this call (see `ScanOptions::num_threads`).
+    pub num_threads: Option<usize>,
+    /// Token from `create_cancellation_token` (see `ScanOptions::cancellation_token`).
+    pub cancellation_token: Option<u64>,
+}
+
+impl Default for ConceptSearchOptions {
+    fn default() -> Self {
+        ConceptSearchOptions {
+            context_lines: 0,
+            max_body_chars: 0,
+            parameter_type: None,
+            uri_format: None,
+            keyword_fallback: false,
+            backend: ScoringBackend::default(),
+            granularity: Granularity::default(),
+            include_nested: true,
+            spelling_tolerance: 1,
+            routing: ModelRouting::default(),
+            rerank_model: None,
+            cross_encoder: None,
+            rerank_candidates: 50,
+            min_similarity: 0.0,
+            diversity: 0.0,
+            negative_query: None,
+            negative_weight: 0.5,
+            exact_search: false,
+            max_file_size_bytes: 1_000_000,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            exclude_tests: false,
+            respect_gitignore: true,
+            include_hidden: false,
+            max_depth: None,
+            follow_links: false,
+            skip_vendor_dirs: true,
+            num_threads: None,
+            cancellation_token: None,
+        }
+    }
+}
+
+impl BudgetDefaults for ConceptSearchOptions {
+    /// `fast` embeds with the small model, without spelling correction or re-ranking;
+    /// `thorough` re-ranks a wider candidate set with the large model. The fast model
+    /// matches `ModelRouting`'s, so a `fast` `warm_cache` fills the cache `fast`
+    /// searches read.
+    fn budget_defaults(budget: Budget) -> serde_json::Value {
+        match budget {
+            Budget::Fast => serde_json::json!({
+                "model": "bge-small",
+                "spelling_tolerance": 0,
+                "max_file_size_bytes": 250_000,
+            }),
+            Budget::Balanced => serde_json::json!({}),
+            Budget::Thorough => serde_json::json!({
+                "rerank_model": "bge-large",
+                "rerank_candidates": 100,
+                "exact_search": true,
+                "spelling_tolerance": 2,
+                "max_file_size_bytes": 4_000_000,
+            }),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::config;
+use crate::options::ScanOptions;
+use crate::parsing::{self, ParseSkip};
+use crate::structs::{CachedFileContext, FileContext};
+use crate::utils;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Databases opened so far, per root. sled locks its directory, so concurrent scans of
+/// one root share a handle instead of opening the database twice.
+static DATABASES: Lazy<Mutex<HashMap<PathBuf, sled::Db>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Version of what a cache entry holds. Bump it whenever `FileContext`, `parse_file`'s
+/// output for the same file, or the built-in queries change, so entries written before
+/// are re-parsed instead of served, even between releases of the same crate version.
+const CACHE_SCHEMA_VERSION: u32 = 1;
+
+/// Parsed files of one root, reused while a file and the parse settings are unchanged.
+#[derive(Clone)]
+pub struct ParseCache {
+    db: sled::Db,
+    /// Hash of everything besides the file that shapes `parse_file`'s result.
+    settings_key: String,
+}
+
+fn modified_nanos(metadata: &fs::Metadata) -> Option<u128> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+}
+
+/// Flushes and closes every open parse cache; the next scan of a root reopens its own.
+/// Returns the databases closed, and the error of the first flush that failed, if any.
+pub fn close_all() -> (usize, Option<String>) {
+    let databases: Vec<(PathBuf, sled::Db)> = DATABASES.lock().unwrap_or_else(|e| e.into_inner()).drain().collect();
+    let mut error = None;
+    for (path, db) in &databases {
+        if let Err(e) = db.flush() {
+            error.get_or_insert_with(|| format!("Failed to flush parse cache at {:?}: {}", path, e));
+        }
+    }
+    (databases.len(), error)
+}
+
+impl ParseCache {
+    /// Opens the cache under `<root>/.cache` for scans at `compactness` with `options`.
+    pub fn open(root: &Path, compactness: u8, options: &ScanOptions) -> Result<ParseCache, String> {
+        let db_path = root
+            .join(config::CACHE_DIR)
+            .join("file_scanner_parse_cache")
+            .join("contexts.sled");
+        let mut databases = DATABASES.lock().unwrap_or_else(|e| e.into_inner());
+        let db = match databases.get(&db_path) {
+            Some(db) => db.clone(),
+            None => {
+                let db = sled::open(&db_path)
+                    .map_err(|e| format!("Failed to open parse cache at {:?}: {}", db_path, e))?;
+                databases.insert(db_path, db.clone());
+                db
+            }
+        };
+
+        // Sorted, so equal settings always produce the same key. The schema and crate
+        // versions cover changes to the built-in queries and to `FileContext`.
+        let settings = serde_json::json!({
+            "schema": CACHE_SCHEMA_VERSION,
+            "version": env!("CARGO_PKG_VERSION"),
+            "compactness": compactness,
+            "query_overrides": options.query_overrides.iter().collect::<BTreeMap<_, _>>(),
+            "extra_queries": options.extra_queries.iter().collect::<BTreeMap<_, _>>(),
+            "calls": options.calls,
+            "outline": options.outline,
+        });
+        Ok(ParseCache {
+            db,
+            settings_key: utils::content_hash(settings.to_string().as_bytes()),
+        })
+    }
+
+    fn key(&self, path: &Path) -> String {
+        format!("{}/{}", self.settings_key, path.to_string_lossy())
+    }
+
+    /// Same as `parsing::parse_file`, but returns the cached result when `path` has not
+    /// changed since it was last parsed with these settings. The size and modification
+    /// time are checked first; if they differ, the content hash decides.
+    pub fn parse_file(
+        &self,
+        path: &Path,
+        compactness: u8,
+        options: &ScanOptions,
+    ) -> Result<FileContext, ParseSkip> {
+        let key = self.key(path);
+        let metadata = fs::metadata(path).map_err(|_| ParseSkip::Unreadable)?;
+        let (size_bytes, modified_nanos) = (metadata.len(), modified_nanos(&metadata));
+        let cached = self
+            .db
+            .get(&key)
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice::<CachedFileContext>(&bytes).ok());
+        match cached {
+            Some(cached)
+                if modified_nanos.is_some()
+                    && cached.size_bytes == size_bytes
+                    && cached.modified_nanos == modified_nanos =>
+            {
+                // Entries written before `FileContext` carried its hash have it only here.
+                return Ok(FileContext {
+                    content_hash: cached.file_content_hash,
+                    ..cached.context
+                });
+            }
+            _ => {}
+        }
+
+        // Read once, for both the hash and a parse.
+        let bytes = fs::read(path).map_err(|_| ParseSkip::Unreadable)?;
+        let file_content_hash = utils::content_hash(&bytes);
+        let context = match cached {
+            // Touched but unchanged; only the stored timestamp needs updating.
+            Some(cached) if cached.file_content_hash == file_content_hash => cached.context,
+            _ => parsing::parse_content(path, &bytes, compactness, options)?,
+        };
+        let entry = CachedFileContext {
+            file_content_hash,
+            size_bytes,
+            modified_nanos,
+            context,
+        };
+        if let Ok(bytes) = serde_json::to_vec(&entry) {
+            // A failed write only costs a re-parse next time.
+            let _ = self.db.insert(key, bytes);
+        }
+        Ok(entry.context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime};
+
+    /// A project root of its own under the temp directory, removed on drop.
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new() -> TempRoot {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let name = format!("rapid-parse-cache-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+            let path = std::env::temp_dir().join(name);
+            fs::create_dir_all(&path).unwrap();
+            TempRoot(path)
+        }
+
+        /// Writes `content` to `name` with the modification time `seconds` after the epoch.
+        fn write(&self, name: &str, content: &str, seconds: u64) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, content).unwrap();
+            touch(&path, seconds);
+            path
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn touch(path: &Path, seconds: u64) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+    }
+
+    fn function_names(cache: &ParseCache, path: &Path) -> Vec<String> {
+        let context = cache.parse_file(path, 0, &ScanOptions::default()).unwrap();
+        context.functions.into_iter().map(|function| function.name).collect()
+    }
+
+    /// Renames the functions of `path`'s cache entry, so a result served from the cache
+    /// can be told apart from a fresh parse.
+    fn mark_cached(cache: &ParseCache, path: &Path) {
+        let key = cache.key(path);
+        let mut entry: CachedFileContext = serde_json::from_slice(&cache.db.get(&key).unwrap().unwrap()).unwrap();
+        for function in &mut entry.context.functions {
+            function.name = "cached".to_string();
+        }
+        cache.db.insert(key, serde_json::to_vec(&entry).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unchanged_size_and_mtime_are_served_from_the_cache() {
+        let root = TempRoot::new();
+        let cache = ParseCache::open(&root.0, 0, &ScanOptions::default()).unwrap();
+        let path = root.write("a.py", "def first():\n    pass\n", 1_000);
+        assert_eq!(function_names(&cache, &path), vec!["first"]);
+
+        // Same size and modification time: the file is not read again.
+        root.write("a.py", "def other():\n    pass\n", 1_000);
+        assert_eq!(function_names(&cache, &path), vec!["first"]);
+
+        // A new modification time and different content: parsed again.
+        touch(&path, 2_000);
+        assert_eq!(function_names(&cache, &path), vec!["other"]);
+
+        // A new size alone is enough to notice a change.
+        root.write("a.py", "def longer_name():\n    pass\n", 2_000);
+        assert_eq!(function_names(&cache, &path), vec!["longer_name"]);
+    }
+
+    #[test]
+    fn touched_but_unchanged_files_are_not_parsed_again() {
+        let root = TempRoot::new();
+        let cache = ParseCache::open(&root.0, 0, &ScanOptions::default()).unwrap();
+        let path = root.write("a.py", "def first():\n    pass\n", 1_000);
+        function_names(&cache, &path);
+        mark_cached(&cache, &path);
+
+        touch(&path, 3_000);
+        assert_eq!(function_names(&cache, &path), vec!["cached"]);
+        let entry: CachedFileContext = serde_json::from_slice(&cache.db.get(cache.key(&path)).unwrap().unwrap()).unwrap();
+        assert_eq!(entry.modified_nanos, Some(Duration::from_secs(3_000).as_nanos()));
+    }
+
+    #[test]
+    fn other_settings_do_not_share_entries() {
+        let root = TempRoot::new();
+        let cache = ParseCache::open(&root.0, 0, &ScanOptions::default()).unwrap();
+        let path = root.write("a.py", "def first():\n    pass\n", 1_000);
+        function_names(&cache, &path);
+        mark_cached(&cache, &path);
+        assert_eq!(function_names(&cache, &path), vec!["cached"]);
+
+        let with_calls = ScanOptions { calls: true, ..Default::default() };
+        let other = ParseCache::open(&root.0, 0, &with_calls).unwrap();
+        assert_ne!(other.settings_key, cache.settings_key);
+        assert_eq!(function_names(&other, &path), vec!["first"]);
+        let other_level = ParseCache::open(&root.0, 1, &ScanOptions::default()).unwrap();
+        assert_eq!(function_names(&other_level, &path), vec!["first"]);
+    }
+}
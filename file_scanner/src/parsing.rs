@@ -1,29 +1,1016 @@
 use crate::config;
-use crate::structs::{FileContext, FunctionInfo};
+use crate::extract;
+use crate::options::ScanOptions;
+use crate::structs::{
+    FileContext, FunctionInfo, FunctionMetrics, OutlineSymbol, ParameterInfo, ParseError, TodoItem,
+};
 use crate::utils;
+use std::collections::HashSet;
 use std::fs;
+use std::io::Read;
 use std::path::Path;
-use tree_sitter::{Query, QueryCursor};
+use tree_sitter::{Node, Point, Query, QueryCursor, Tree};
+
+/// Node kinds that open a function scope, including anonymous functions.
+const FUNCTION_KINDS: &[&str] = &[
+    "function_definition",
+    "function_item",
+    "function_declaration",
+    "function_expression",
+    "function",
+    "method_definition",
+    "method_declaration",
+    "constructor_declaration",
+    "accessor_declaration",
+    "local_function_statement",
+    "closure_expression",
+    "arrow_function",
+    "lambda",
+];
+
+/// Node kinds inside a parameter list that are not parameters themselves.
+const NON_PARAMETER_KINDS: &[&str] = &[
+    "comment",
+    "attribute_item",
+    "attribute_list",
+    "decorator",
+    "keyword_separator",
+    "positional_separator",
+];
+
+/// Node kinds that add a decision point to a function's control flow.
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement",
+    "if_expression",
+    "elif_clause",
+    "while_statement",
+    "while_expression",
+    "for_statement",
+    "for_expression",
+    "for_in_statement",
+    "foreach_statement",
+    "do_statement",
+    "match_arm",
+    "case_clause",
+    "switch_section",
+    "switch_case",
+    "catch_clause",
+    "except_clause",
+    "conditional_expression",
+    "ternary_expression",
+];
+
+/// Node kinds that open a nested control-flow block.
+const NESTING_KINDS: &[&str] = &[
+    "if_statement",
+    "if_expression",
+    "while_statement",
+    "while_expression",
+    "for_statement",
+    "for_expression",
+    "for_in_statement",
+    "foreach_statement",
+    "do_statement",
+    "loop_expression",
+    "match_expression",
+    "match_statement",
+    "switch_statement",
+    "switch_expression",
+    "try_statement",
+    "with_statement",
+];
+
+/// Returns the text of a type node without the leading `:` used by TypeScript annotations.
+fn type_text(node: Node, code: &str) -> String {
+    node.utf8_text(code.as_bytes())
+        .unwrap_or("")
+        .trim_start_matches(':')
+        .trim()
+        .to_string()
+}
+
+/// Returns the name of `node` if it is a construct that encloses functions
+/// (class, impl block, trait, module, namespace, ...), otherwise `None`.
+fn container_name(node: Node, code: &str) -> Option<String> {
+    let name_field = match node.kind() {
+        "class_definition"
+        | "class_declaration"
+        | "abstract_class_declaration"
+        | "struct_declaration"
+        | "interface_declaration"
+        | "record_declaration"
+        | "trait_item"
+        | "mod_item"
+        | "namespace_declaration"
+        | "internal_module"
+        | "module" => "name",
+        "impl_item" => return impl_name(node, code),
+        _ => return None,
+    };
+    node.child_by_field_name(name_field)
+        .and_then(|n| n.utf8_text(code.as_bytes()).ok())
+        .map(|text| text.trim().to_string())
+}
+
+/// Names a Rust impl block after its type, written `<Type as Trait>` for trait impls
+/// so inherent and trait methods of the same name stay distinct.
+fn impl_name(node: Node, code: &str) -> Option<String> {
+    let text = |field| {
+        node.child_by_field_name(field)
+            .and_then(|n| n.utf8_text(code.as_bytes()).ok())
+            .map(|text| text.trim().to_string())
+    };
+    let self_type = text("type")?;
+    Some(match text("trait") {
+        Some(trait_name) => format!("<{} as {}>", self_type, trait_name),
+        None => self_type,
+    })
+}
+
+/// Returns the name of a function-scope node. Anonymous functions (closures, arrow
+/// functions, lambdas) take the name of the binding they are assigned to.
+fn function_scope_name(node: Node, code: &str) -> Option<String> {
+    if node.kind() == "accessor_declaration" {
+        return csharp_accessor_name(node, code);
+    }
+    node.child_by_field_name("name")
+        .or_else(|| {
+            let parent = node.parent()?;
+            match parent.kind() {
+                "let_declaration" => parent.child_by_field_name("pattern"),
+                "variable_declarator" => parent.child_by_field_name("name"),
+                "assignment" | "assignment_expression" => parent.child_by_field_name("left"),
+                _ => None,
+            }
+        })
+        .and_then(|n| n.utf8_text(code.as_bytes()).ok())
+        .map(|text| text.trim().to_string())
+}
+
+/// Names a C# property/indexer/event accessor the way the compiler does
+/// (`get_Name`, `set_Name`, `add_Changed`, `get_Item` for indexers). An
+/// expression-bodied property (`int Count => n;`) is named as its getter.
+fn csharp_accessor_name(node: Node, code: &str) -> Option<String> {
+    let (keyword, member) = match node.kind() {
+        "accessor_declaration" => {
+            let mut cursor = node.walk();
+            let keyword = node
+                .children(&mut cursor)
+                .find(|child| !matches!(child.kind(), "attribute_list" | "modifier"))?
+                .utf8_text(code.as_bytes())
+                .ok()?
+                .to_string();
+            // accessor_declaration -> accessor_list -> property/indexer/event declaration
+            (keyword, node.parent()?.parent()?)
+        }
+        "property_declaration" => ("get".to_string(), node),
+        _ => return None,
+    };
+    let member_name = match member.kind() {
+        "indexer_declaration" => "Item".to_string(),
+        _ => member
+            .child_by_field_name("name")?
+            .utf8_text(code.as_bytes())
+            .ok()?
+            .trim()
+            .to_string(),
+    };
+    Some(format!("{}_{}", keyword, member_name))
+}
+
+/// The scopes enclosing a function definition.
+struct EnclosingScopes {
+    /// Innermost enclosing class/impl/trait/module/namespace.
+    container: Option<String>,
+    /// Innermost enclosing function, for nested and local functions.
+    function: Option<String>,
+    /// Number of enclosing function scopes (0 for top-level functions and methods).
+    function_depth: usize,
+    /// Names of every enclosing container and function, outermost first.
+    path: Vec<String>,
+}
+
+/// Walks up from a function node and records its enclosing containers and functions.
+fn enclosing_scopes(function_node: Node, code: &str) -> EnclosingScopes {
+    let mut scopes = EnclosingScopes {
+        container: None,
+        function: None,
+        function_depth: 0,
+        path: Vec::new(),
+    };
+    let mut current = function_node.parent();
+    while let Some(node) = current {
+        if let Some(name) = container_name(node, code) {
+            scopes.container.get_or_insert_with(|| name.clone());
+            scopes.path.push(name);
+        } else if FUNCTION_KINDS.contains(&node.kind()) {
+            let name = function_scope_name(node, code).unwrap_or_else(|| "<anonymous>".to_string());
+            scopes.function.get_or_insert_with(|| name.clone());
+            scopes.function_depth += 1;
+            scopes.path.push(name);
+        }
+        current = node.parent();
+    }
+    scopes.path.reverse();
+    scopes
+}
+
+/// Returns the outline kind of a declaration node that should appear as a symbol
+/// in the document outline, or `None` for other nodes.
+fn outline_kind(node: Node) -> Option<&'static str> {
+    let kind = match node.kind() {
+        "class_definition" | "class_declaration" | "abstract_class_declaration" => "class",
+        "record_declaration" => "class",
+        "struct_declaration" | "struct_item" => "struct",
+        "enum_declaration" | "enum_item" => "enum",
+        "interface_declaration" => "interface",
+        "trait_item" => "trait",
+        "impl_item" => "impl",
+        "mod_item" | "internal_module" | "module" => "module",
+        "namespace_declaration" => "namespace",
+        "function_signature_item" | "method_signature" | "abstract_method_signature" => "function",
+        kind if FUNCTION_KINDS.contains(&kind) => "function",
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Builds the document outline of the subtree under `node`.
+///
+/// Declarations become symbols nested under their enclosing declaration; anything
+/// else (blocks, statements, anonymous closures) is transparent and its symbols are
+/// hoisted to the nearest enclosing symbol.
+fn build_outline(node: Node, code: &str, parent_kind: Option<&str>) -> Vec<OutlineSymbol> {
+    let mut symbols = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let symbol = outline_kind(child).and_then(|kind| {
+            let name = if kind == "function" {
+                function_scope_name(child, code)?
+            } else {
+                container_name(child, code).or_else(|| {
+                    child
+                        .child_by_field_name("name")
+                        .and_then(|n| n.utf8_text(code.as_bytes()).ok())
+                        .map(|text| text.trim().to_string())
+                })?
+            };
+            let kind = match (kind, parent_kind) {
+                (
+                    "function",
+                    Some("class" | "struct" | "enum" | "interface" | "trait" | "impl"),
+                ) => "method",
+                _ => kind,
+            };
+            Some(OutlineSymbol {
+                name,
+                kind: kind.to_string(),
+                start_line: child.start_position().row + 1,
+                end_line: child.end_position().row + 1,
+                children: build_outline(child, code, Some(kind)),
+            })
+        });
+        match symbol {
+            Some(symbol) => symbols.push(symbol),
+            None => symbols.extend(build_outline(child, code, parent_kind)),
+        }
+    }
+    symbols
+}
+
+/// Records the syntax errors under `node`: `ERROR` nodes (unexpected text) and the
+/// tokens tree-sitter inserted to recover (`MISSING` nodes). Errors nested in an
+/// `ERROR` node are not reported separately.
+fn collect_parse_errors(node: Node, code: &str, errors: &mut Vec<ParseError>) {
+    if !node.has_error() {
+        return;
+    }
+    let line = node.start_position().row + 1;
+    if node.is_missing() {
+        errors.push(ParseError {
+            line,
+            message: format!("Missing `{}`", node.kind()),
+        });
+        return;
+    }
+    if node.is_error() {
+        let text = node.utf8_text(code.as_bytes()).unwrap_or("");
+        let snippet: String = text
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(40)
+            .collect();
+        errors.push(ParseError {
+            line,
+            message: format!("Unexpected `{}`", snippet),
+        });
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_parse_errors(child, code, errors);
+    }
+}
+
+/// Returns the docstring of a Python function: a string literal that is the first
+/// statement of its body.
+fn python_docstring(function_node: Node, code: &str) -> Option<String> {
+    let body = function_node.child_by_field_name("body")?;
+    let first_statement = body.named_child(0)?;
+    if first_statement.kind() != "expression_statement" {
+        return None;
+    }
+    let literal = first_statement.named_child(0)?;
+    if literal.kind() != "string" {
+        return None;
+    }
+    literal
+        .utf8_text(code.as_bytes())
+        .ok()
+        .map(|text| text.trim().to_string())
+}
+
+/// Collects the documentation attached to a function.
+///
+/// This is the block of comments directly above the definition (Rust `///`, JSDoc,
+/// C# `///`, `#` comments), skipping over Rust attributes and stopping at the first
+/// blank line, followed by the Python docstring if there is one. Decorated Python
+/// definitions and exported TypeScript functions are anchored at their wrapper node,
+/// since that is where the preceding comments are siblings.
+fn extract_documentation(function_node: Node, code: &str) -> Option<String> {
+    let mut anchor = function_node;
+    while let Some(parent) = anchor.parent() {
+        if matches!(parent.kind(), "decorated_definition" | "export_statement") {
+            anchor = parent;
+        } else {
+            break;
+        }
+    }
+
+    let mut comments = Vec::new();
+    let mut next_row = anchor.start_position().row;
+    let mut sibling = anchor.prev_sibling();
+    while let Some(node) = sibling {
+        let kind = node.kind();
+        if kind == "attribute_item" {
+            next_row = node.start_position().row;
+            sibling = node.prev_sibling();
+            continue;
+        }
+        // Comments separated from the function by a blank line belong to something else.
+        if !kind.contains("comment") || node.end_position().row + 1 < next_row {
+            break;
+        }
+        if let Ok(text) = node.utf8_text(code.as_bytes()) {
+            comments.push(text.trim_end().to_string());
+        }
+        next_row = node.start_position().row;
+        sibling = node.prev_sibling();
+    }
+    comments.reverse();
+
+    if let Some(docstring) = python_docstring(function_node, code) {
+        comments.push(docstring);
+    }
+
+    if comments.is_empty() {
+        None
+    } else {
+        Some(comments.join("\n"))
+    }
+}
+
+/// Collects decorators and attributes applied to a function, in source order.
+///
+/// Python decorators (inside `decorated_definition`) and Rust `attribute_item`s are
+/// siblings preceding the definition; C# attribute lists are children of the
+/// declaration. TypeScript decorators may appear in either position.
+fn extract_annotations(function_node: Node, code: &str) -> Vec<String> {
+    let text = |n: Node| {
+        n.utf8_text(code.as_bytes())
+            .unwrap_or("")
+            .trim()
+            .to_string()
+    };
+    let mut annotations = Vec::new();
+
+    let mut sibling = function_node.prev_named_sibling();
+    let mut preceding = Vec::new();
+    while let Some(node) = sibling {
+        match node.kind() {
+            "attribute_item" | "decorator" => preceding.push(text(node)),
+            kind if kind.contains("comment") => {}
+            _ => break,
+        }
+        sibling = node.prev_named_sibling();
+    }
+    annotations.extend(preceding.into_iter().rev());
+
+    let mut cursor = function_node.walk();
+    annotations.extend(
+        function_node
+            .named_children(&mut cursor)
+            .filter(|n| matches!(n.kind(), "attribute_list" | "decorator"))
+            .map(text),
+    );
+    annotations
+}
+
+/// Whether `node` is a short-circuiting `&&`/`||`/`and`/`or` operator.
+fn is_short_circuit(node: Node) -> bool {
+    match node.kind() {
+        "boolean_operator" => true,
+        "binary_expression" => node
+            .child_by_field_name("operator")
+            .is_some_and(|op| matches!(op.kind(), "&&" | "||")),
+        _ => false,
+    }
+}
+
+/// Whether `node` continues an `else if` chain rather than opening a new block.
+fn is_else_if(node: Node) -> bool {
+    node.kind().starts_with("if_")
+        && node
+            .parent()
+            .is_some_and(|p| p.kind() == "else_clause" || p.kind() == node.kind())
+}
+
+/// Counts decision points and the deepest block nesting below `node`.
+///
+/// Nested functions and closures are skipped; they receive their own metrics.
+fn collect_complexity(node: Node, nesting: usize, branches: &mut usize, max_nesting: &mut usize) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if FUNCTION_KINDS.contains(&child.kind()) {
+            continue;
+        }
+        if BRANCH_KINDS.contains(&child.kind()) || is_short_circuit(child) {
+            *branches += 1;
+        }
+        let child_nesting = if NESTING_KINDS.contains(&child.kind()) && !is_else_if(child) {
+            nesting + 1
+        } else {
+            nesting
+        };
+        *max_nesting = (*max_nesting).max(child_nesting);
+        collect_complexity(child, child_nesting, branches, max_nesting);
+    }
+}
+
+/// Records every row spanned by a non-comment token below `node`.
+fn collect_code_rows(node: Node, rows: &mut HashSet<usize>) {
+    if node.kind().contains("comment") {
+        return;
+    }
+    if node.child_count() == 0 {
+        rows.extend(node.start_position().row..=node.end_position().row);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_code_rows(child, rows);
+    }
+}
+
+/// Returns the function-scope node for a captured definition.
+///
+/// For named closures the captured node is the enclosing declaration
+/// (`let c = |x| ...`, `c = lambda x: ...`), so the closure itself is located first.
+/// TypeScript's `const c = (x) => ...` nests it one level deeper, as the value of a
+/// `variable_declarator`.
+fn function_root(definition_node: Node) -> Node {
+    if FUNCTION_KINDS.contains(&definition_node.kind()) {
+        return definition_node;
+    }
+    let mut cursor = definition_node.walk();
+    let children: Vec<Node> = definition_node.named_children(&mut cursor).collect();
+    let closure = children
+        .iter()
+        .copied()
+        .find(|n| FUNCTION_KINDS.contains(&n.kind()))
+        .or_else(|| {
+            children
+                .iter()
+                .filter_map(|n| n.child_by_field_name("value"))
+                .find(|n| FUNCTION_KINDS.contains(&n.kind()))
+        });
+    closure.unwrap_or(definition_node)
+}
+
+/// Computes size and complexity metrics for a function definition node.
+fn compute_metrics(function_node: Node) -> FunctionMetrics {
+    let function_node = function_root(function_node);
+    let mut rows = HashSet::new();
+    collect_code_rows(function_node, &mut rows);
+    let mut branch_count = 0;
+    let mut max_nesting_depth = 0;
+    collect_complexity(function_node, 0, &mut branch_count, &mut max_nesting_depth);
+    FunctionMetrics {
+        lines_of_code: rows.len(),
+        max_nesting_depth,
+        branch_count,
+        cyclomatic_complexity: branch_count + 1,
+    }
+}
+
+/// Whether `kind` is an anonymous function (closure, lambda, arrow function), whose
+/// calls are attributed to the function it appears in.
+fn is_anonymous_function(kind: &str) -> bool {
+    matches!(
+        kind,
+        "closure_expression" | "arrow_function" | "lambda" | "function_expression" | "function"
+    )
+}
+
+/// Whether a function definition is declared `async`: an `async` keyword (Python,
+/// TypeScript), Rust's `function_modifiers`, or a C# `async` modifier.
+fn is_async(function_node: Node, code: &str) -> bool {
+    let function_node = function_root(function_node);
+    let mut cursor = function_node.walk();
+    let is_async = function_node
+        .children(&mut cursor)
+        .any(|child| match child.kind() {
+            "async" => true,
+            "function_modifiers" | "modifier" => child
+                .utf8_text(code.as_bytes())
+                .is_ok_and(|text| text.split_whitespace().any(|word| word == "async")),
+            _ => false,
+        });
+    is_async
+}
+
+/// Names of the functions called from a function definition, in first-call order.
+///
+/// Calls inside nested named functions belong to those functions and are skipped;
+/// calls inside closures and lambdas count for the enclosing function.
+fn extract_calls(function_node: Node, code: &str, query: &Query) -> Vec<String> {
+    let function_node = function_root(function_node);
+    let belongs_to_function = |callee: Node| {
+        let mut current = callee.parent();
+        while let Some(node) = current {
+            if node.id() == function_node.id() {
+                return true;
+            }
+            if FUNCTION_KINDS.contains(&node.kind()) && !is_anonymous_function(node.kind()) {
+                return false;
+            }
+            current = node.parent();
+        }
+        false
+    };
+
+    let mut seen = HashSet::new();
+    let mut cursor = QueryCursor::new();
+    cursor
+        .matches(query, function_node, code.as_bytes())
+        .flat_map(|mat| mat.captures.iter().map(|capture| capture.node))
+        .filter(|node| belongs_to_function(*node))
+        .filter_map(|node| node.utf8_text(code.as_bytes()).ok())
+        .filter(|name| seen.insert(name.to_string()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Collects the file's import/use/using statements, one line per statement.
+fn extract_imports(root: Node, code: &str, extension: &str) -> Vec<String> {
+    let query = match config::get_imports_query(extension)
+        .and_then(|query_str| Query::new(root.language(), query_str).ok())
+    {
+        Some(q) => q,
+        None => return Vec::new(),
+    };
+    let mut cursor = QueryCursor::new();
+    cursor
+        .matches(&query, root, code.as_bytes())
+        .flat_map(|mat| mat.captures.iter().map(|capture| capture.node))
+        .filter_map(|node| node.utf8_text(code.as_bytes()).ok())
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+/// Converts a parameter-list node into structured parameters.
+///
+/// Works across the supported grammars by relying on the common `name`/`pattern`
+/// and `type` field names; untyped parameters (e.g. plain Python identifiers or
+/// Rust `self`) keep their full text as the name.
+fn extract_parameters(params_node: Node, code: &str) -> Vec<ParameterInfo> {
+    let mut cursor = params_node.walk();
+    params_node
+        .named_children(&mut cursor)
+        .filter(|child| !NON_PARAMETER_KINDS.contains(&child.kind()))
+        .map(|child| {
+            let name_node = child
+                .child_by_field_name("name")
+                .or_else(|| child.child_by_field_name("pattern"))
+                .or_else(|| {
+                    // Python's typed_parameter keeps its identifier as an unnamed-field child.
+                    if child.kind() == "typed_parameter" {
+                        child.named_child(0)
+                    } else {
+                        None
+                    }
+                });
+            let name = name_node
+                .unwrap_or(child)
+                .utf8_text(code.as_bytes())
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            let type_name = child
+                .child_by_field_name("type")
+                .map(|type_node| type_text(type_node, code));
+            ParameterInfo { name, type_name }
+        })
+        .collect()
+}
+
+/// Bytes read from an extensionless file to look for a shebang or modeline.
+const SCRIPT_HEAD_BYTES: usize = 1024;
+
+/// Lines at the start of a file searched for an editor modeline.
+const MODELINE_LINES: usize = 5;
+
+/// Returns the interpreter named by a shebang line, skipping `env` and its flags
+/// (`#!/usr/bin/env -S deno run` → `deno`).
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program != "env" {
+        return Some(program);
+    }
+    words.find(|word| !word.starts_with('-') && !word.contains('='))
+}
+
+/// Returns the language named by a Vim (`vim: set ft=python:`) or Emacs
+/// (`-*- mode: python -*-`, `-*- python -*-`) modeline.
+fn modeline_language(line: &str) -> Option<&str> {
+    if let Some(start) = line.find("-*-") {
+        let rest = &line[start + 3..];
+        let settings = &rest[..rest.find("-*-")?];
+        if !settings.contains(':') {
+            return Some(settings.trim());
+        }
+        return settings.split(';').find_map(|setting| {
+            let (key, value) = setting.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("mode")
+                .then(|| value.trim())
+        });
+    }
+    let settings = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let start = line.find(marker)?;
+        let at_word_start = line[..start].chars().last().is_none_or(char::is_whitespace);
+        at_word_start.then(|| &line[start + marker.len()..])
+    })?;
+    settings
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .find_map(|setting| {
+            let (key, value) = setting.split_once('=')?;
+            matches!(key, "ft" | "filetype" | "syntax").then_some(value)
+        })
+}
+
+/// Detects the language of an extensionless script from its shebang or a modeline in
+/// its first lines, returning the extension of the matching registered language.
+pub fn script_extension(head: &str) -> Option<&'static str> {
+    let mut lines = head.lines().take(MODELINE_LINES);
+    let first = lines.next()?;
+    let language = shebang_interpreter(first)
+        .and_then(config::language_for_interpreter)
+        .or_else(|| {
+            std::iter::once(first)
+                .chain(lines)
+                .filter_map(modeline_language)
+                .find_map(config::language_for_interpreter)
+        })?;
+    language.extensions.first().copied()
+}
+
+/// Extension a file is parsed as: its own, or for files without one, the extension
+/// detected from a shebang or modeline (see `script_extension`).
+pub fn file_extension(path: &Path) -> Option<&str> {
+    if let Some(extension) = path.extension() {
+        return extension.to_str();
+    }
+    let mut head = Vec::with_capacity(SCRIPT_HEAD_BYTES);
+    fs::File::open(path)
+        .ok()?
+        .take(SCRIPT_HEAD_BYTES as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+    script_extension(&String::from_utf8_lossy(&head))
+}
+
+/// Markers reported by `extract_todos`.
+const TODO_TAGS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+/// Finds the first marker in a comment line, returning `(tag, author, text)`.
+///
+/// Markers must be upper-case whole words; an author may follow as `TODO(name)` or
+/// `TODO @name`, and a trailing `:` is dropped from the text.
+fn parse_todo_line(line: &str) -> Option<(&'static str, Option<String>, String)> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let (index, tag) = TODO_TAGS
+        .iter()
+        .flat_map(|tag| line.match_indices(tag).map(move |(i, _)| (i, *tag)))
+        .filter(|(i, tag)| {
+            !line[..*i].ends_with(is_word_char) && !line[i + tag.len()..].starts_with(is_word_char)
+        })
+        .min_by_key(|(i, _)| *i)?;
+
+    let mut rest = &line[index + tag.len()..];
+    let mut author = None;
+    if let Some(inner) = rest.strip_prefix('(') {
+        if let Some(end) = inner.find(')') {
+            author = Some(inner[..end].trim().to_string());
+            rest = &inner[end + 1..];
+        }
+    }
+    rest = rest.trim_start().trim_start_matches(':').trim_start();
+    if author.is_none() {
+        if let Some(handle) = rest.strip_prefix('@') {
+            let end = handle.find(|c: char| !(is_word_char(c) || c == '-' || c == '.'));
+            let end = end.unwrap_or(handle.len());
+            author = Some(handle[..end].to_string());
+            rest = handle[end..]
+                .trim_start()
+                .trim_start_matches(':')
+                .trim_start();
+        }
+    }
+    let text = rest
+        .trim_end()
+        .trim_end_matches("*/")
+        .trim_end()
+        .to_string();
+    Some((tag, author.filter(|a| !a.is_empty()), text))
+}
+
+/// Collects comment nodes below `node`.
+fn collect_comments<'a>(node: Node<'a>, comments: &mut Vec<Node<'a>>) {
+    if node.kind().contains("comment") {
+        comments.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comments(child, comments);
+    }
+}
+
+/// Qualified name of the innermost function enclosing `node`, if any.
+fn enclosing_function_name(node: Node, code: &str, extension: &str) -> Option<String> {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if FUNCTION_KINDS.contains(&candidate.kind()) {
+            let name =
+                function_scope_name(candidate, code).unwrap_or_else(|| "<anonymous>".to_string());
+            let separator = config::scope_separator(extension);
+            let mut path = enclosing_scopes(candidate, code).path;
+            path.push(name);
+            return Some(path.join(separator));
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
+/// Whether a node is a comment or a string or character literal, in any supported
+/// grammar. Interpolations inside a string count as part of it.
+fn is_comment_or_string(kind: &str) -> bool {
+    kind.contains("comment") || kind.contains("string") || kind.ends_with("char_literal") || kind == "character_literal"
+}
+
+/// Where a position in a file sits in its syntax tree.
+pub struct SyntaxLocation {
+    /// Qualified name of the innermost enclosing function or class/impl/module-like
+    /// container, named as in `FunctionInfo::qualified_name`.
+    pub symbol: Option<String>,
+    /// Inside the body of a function, past its signature.
+    pub in_function_body: bool,
+    /// Inside a comment or a string literal.
+    pub in_comment_or_string: bool,
+    /// Inside the name a declaration (function, class, field, ...) introduces, as
+    /// opposed to a use of that name.
+    pub in_declared_name: bool,
+}
+
+/// A parsed file that positions can be looked up in, for scoping text search matches.
+pub struct SyntaxIndex {
+    tree: Tree,
+    code: String,
+    extension: String,
+}
+
+impl SyntaxIndex {
+    /// Parses `code` in the language of `extension`. Returns `None` for unsupported
+    /// languages.
+    pub fn parse(extension: &str, code: String) -> Option<SyntaxIndex> {
+        let tree = config::get_parser(extension)?.parse(&code, None)?;
+        Some(SyntaxIndex {
+            tree,
+            code,
+            extension: extension.to_string(),
+        })
+    }
+
+    /// Locates the character at 0-based `row` and byte `column`.
+    pub fn locate(&self, row: usize, column: usize) -> SyntaxLocation {
+        let mut location = SyntaxLocation {
+            symbol: None,
+            in_function_body: false,
+            in_comment_or_string: false,
+            in_declared_name: false,
+        };
+        let point = Point { row, column };
+        let Some(node) = self
+            .tree
+            .root_node()
+            .descendant_for_point_range(point, Point { row, column: column + 1 })
+        else {
+            return location;
+        };
+        location.in_declared_name = node
+            .parent()
+            .and_then(|parent| parent.child_by_field_name("name"))
+            .is_some_and(|name| name.id() == node.id());
+        let mut current = Some(node);
+        while let Some(candidate) = current {
+            if is_comment_or_string(candidate.kind()) {
+                location.in_comment_or_string = true;
+            }
+            let is_function = FUNCTION_KINDS.contains(&candidate.kind());
+            if is_function
+                && candidate
+                    .child_by_field_name("body")
+                    .is_some_and(|body| body.start_position() <= point && point < body.end_position())
+            {
+                location.in_function_body = true;
+            }
+            if location.symbol.is_none() {
+                let name = if is_function {
+                    Some(function_scope_name(candidate, &self.code).unwrap_or_else(|| "<anonymous>".to_string()))
+                } else {
+                    container_name(candidate, &self.code)
+                };
+                if let Some(name) = name {
+                    let mut path = enclosing_scopes(candidate, &self.code).path;
+                    path.push(name);
+                    location.symbol = Some(path.join(config::scope_separator(&self.extension)));
+                }
+            }
+            current = candidate.parent();
+        }
+        location
+    }
+}
+
+/// Extracts `TODO`/`FIXME`/`HACK`/`XXX` markers from the comments of a single file.
+///
+/// Only comment nodes are inspected, so markers inside string literals are ignored.
+/// Returns `None` for binary, unreadable, or unsupported files.
+pub fn extract_todos(path: &Path) -> Option<Vec<TodoItem>> {
+    if utils::is_binary(path) {
+        return None;
+    }
+
+    let extension = file_extension(path)?;
+    let mut parser = config::get_parser(extension)?;
+    let bytes = fs::read(path).ok()?;
+    utils::detect_encoding(&bytes)?;
+    let code =
+        String::from_utf8_lossy(bytes.strip_prefix(utils::UTF8_BOM).unwrap_or(&bytes)).into_owned();
+    let tree = parser.parse(&code, None)?;
+
+    let mut comments = Vec::new();
+    collect_comments(tree.root_node(), &mut comments);
+    let file = path.to_str()?.to_string();
+    let todos = comments
+        .into_iter()
+        .flat_map(|comment| {
+            let text = comment.utf8_text(code.as_bytes()).unwrap_or("");
+            let start_row = comment.start_position().row;
+            let function = enclosing_function_name(comment, &code, extension);
+            text.lines()
+                .enumerate()
+                .filter_map(|(offset, line)| {
+                    parse_todo_line(line).map(|(tag, author, text)| TodoItem {
+                        file: file.clone(),
+                        line: start_row + offset + 1,
+                        tag: tag.to_string(),
+                        text,
+                        author,
+                        function: function.clone(),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    Some(todos)
+}
+
+/// The comment or docstring a file opens with, after any shebang and blank lines,
+/// without its comment markers.
+fn leading_comment(content: &str) -> Option<String> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty() || line.starts_with("#!"))
+        .peekable();
+    let first = *lines.peek()?;
+    let mut comment = Vec::new();
+    if let Some(quote) = ["\"\"\"", "'''"].into_iter().find(|quote| first.starts_with(quote)) {
+        let first = lines.next()?.trim_start_matches(quote);
+        if let Some(single_line) = first.strip_suffix(quote) {
+            comment.push(single_line);
+        } else {
+            comment.push(first);
+            for line in lines.by_ref() {
+                match line.strip_suffix(quote) {
+                    Some(last) => {
+                        comment.push(last);
+                        break;
+                    }
+                    None => comment.push(line),
+                }
+            }
+        }
+    } else if first.starts_with("/*") {
+        for line in lines.by_ref() {
+            comment.push(line.trim_end_matches("*/").trim_start_matches("/*").trim_start_matches('*'));
+            if line.ends_with("*/") {
+                break;
+            }
+        }
+    } else {
+        for line in lines.take_while(|line| line.starts_with("//") || *line == "#" || line.starts_with("# ")) {
+            comment.push(line.trim_start_matches(['/', '!', '#']));
+        }
+    }
+    let comment = comment.iter().map(|line| line.trim()).collect::<Vec<_>>().join("\n");
+    let comment = comment.trim();
+    (!comment.is_empty()).then(|| comment.to_string())
+}
+
+/// Why `parse_file` returned no `FileContext`, so scans can count the file without
+/// reading it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseSkip {
+    /// The file could not be read.
+    Unreadable,
+    /// Contains NUL bytes.
+    Binary,
+    /// In none of the encodings `utils::detect_encoding` recognizes.
+    Undecodable,
+    /// Unsupported language, or no functions (or outline) were extracted.
+    NoFunctions,
+}
 
 /// Parses a single file to extract function information using tree-sitter.
 ///
 /// # Arguments
 /// * `path` - Path to the file.
 /// * `compactness` - Controls the detail of extracted function information.
+/// * `options` - Outline and custom query settings (see `ScanOptions`).
 ///
 /// # Returns
-/// `Some(FileContext)` if parsing succeeds and functions are found, otherwise `None`.
-/// Returns `None` for binary files, unreadable files, or if no functions are extracted.
-pub fn parse_file(path: &Path, compactness: u8) -> Option<FileContext> {
-    if utils::is_binary(path) {
-        return None;
+/// The `FileContext` if parsing succeeds and functions are found, otherwise why not.
+pub fn parse_file(path: &Path, compactness: u8, options: &ScanOptions) -> Result<FileContext, ParseSkip> {
+    let bytes = fs::read(path).map_err(|_| ParseSkip::Unreadable)?;
+    parse_content(path, &bytes, compactness, options)
+}
+
+/// Same as `parse_file`, for `bytes` already read from `path`, so callers that hash the
+/// file first do not read it twice.
+pub fn parse_content(path: &Path, bytes: &[u8], compactness: u8, options: &ScanOptions) -> Result<FileContext, ParseSkip> {
+    if utils::is_binary_content(bytes) {
+        return Err(ParseSkip::Binary);
     }
+    let encoding = utils::detect_encoding(bytes).ok_or(ParseSkip::Undecodable)?;
+    parse_decoded(path, bytes, encoding, compactness, options).ok_or(ParseSkip::NoFunctions)
+}
 
-    let extension = path.extension().and_then(|ext| ext.to_str())?;
+fn parse_decoded(
+    path: &Path,
+    bytes: &[u8],
+    encoding: &str,
+    compactness: u8,
+    options: &ScanOptions,
+) -> Option<FileContext> {
+    let mut code =
+        String::from_utf8_lossy(bytes.strip_prefix(utils::UTF8_BOM).unwrap_or(bytes)).into_owned();
+    let mut extension = file_extension(path)?;
+    // Notebooks and other containers are parsed as the source extracted from them.
+    if extract::is_extracted(extension) {
+        let extracted = extract::extract(extension, &code)?;
+        extension = extracted.extension?;
+        code = extracted.text;
+    }
     let mut parser = config::get_parser(extension)?;
-    let query_str = config::get_query(extension, compactness)?;
-
-    let code = fs::read_to_string(path).ok()?;
+    let query_str = config::resolve_query(extension, compactness, options)?;
     let tree = parser.parse(&code, None)?;
 
     let mut functions = Vec::new();
@@ -41,14 +1028,26 @@ pub fn parse_file(path: &Path, compactness: u8) -> Option<FileContext> {
         }
     };
 
+    let calls_query = if options.calls {
+        config::get_calls_query(extension).and_then(|q| Query::new(tree.language(), q).ok())
+    } else {
+        None
+    };
+
     let mut cursor = QueryCursor::new();
     let matches = cursor.matches(&query, tree.root_node(), code.as_bytes());
+    // Patterns can overlap (e.g. a closure bound by `let` inside another function), so
+    // each definition is only reported once, keyed by the start of its name.
+    let mut seen_names: HashSet<usize> = HashSet::new();
 
     for mat in matches {
         let mut name = String::new();
         let mut comment: Option<String> = None;
+        let mut name_node: Option<tree_sitter::Node> = None;
         let mut function_definition_node: Option<tree_sitter::Node> = None;
         let mut body_node: Option<tree_sitter::Node> = None;
+        let mut parameters_node: Option<tree_sitter::Node> = None;
+        let mut return_type_node: Option<tree_sitter::Node> = None;
 
         for cap in mat.captures {
             let capture_name_result = query.capture_names().get(cap.index as usize);
@@ -64,14 +1063,31 @@ pub fn parse_file(path: &Path, compactness: u8) -> Option<FileContext> {
             let node_text = node.utf8_text(code.as_bytes()).unwrap_or("").to_string();
 
             match capture_name {
-                "method_name" | "name" => name = node_text, // "name" is used in Python queries.
+                "method_name" | "name" => {
+                    // "name" is used in Python queries.
+                    name = node_text;
+                    name_node = Some(node);
+                }
+                "accessor" => {
+                    // C# accessors have no name of their own.
+                    name = csharp_accessor_name(node, &code).unwrap_or_default();
+                    name_node = Some(node);
+                }
                 "comment" => comment = Some(node_text),
                 "function_definition" => function_definition_node = Some(node),
                 "body" => body_node = Some(node),
+                "parameters" => parameters_node = Some(node),
+                "return_type" => return_type_node = Some(node),
                 _ => {} // Ignore other captures not relevant for FunctionInfo.
             }
         }
 
+        if let Some(n) = name_node {
+            if !seen_names.insert(n.start_byte()) {
+                continue;
+            }
+        }
+
         if !name.is_empty() {
             let body_content = match compactness {
                 1 | 2 => {
@@ -114,21 +1130,243 @@ pub fn parse_file(path: &Path, compactness: u8) -> Option<FileContext> {
                 _ => None, // Compactness 0 (name only) or other invalid levels: no body content.
             };
 
+            // Compactness 0 queries do not capture the definition, so fall back to the
+            // name's parent node to locate the function.
+            let span_node = function_definition_node.or_else(|| name_node.and_then(|n| n.parent()));
+            let (start_line, end_line) = span_node
+                .map(|n| (n.start_position().row + 1, n.end_position().row + 1))
+                .unwrap_or((0, 0));
+
+            let comment = if compactness >= 2 {
+                comment.or_else(|| span_node.and_then(|n| extract_documentation(n, &code)))
+            } else {
+                None
+            };
+
+            let scopes = span_node.map(|n| enclosing_scopes(n, &code));
+            let separator = config::scope_separator(extension);
+            let qualified_name = scopes
+                .iter()
+                .flat_map(|s| s.path.iter().map(String::as_str))
+                .chain(std::iter::once(name.as_str()))
+                .collect::<Vec<_>>()
+                .join(separator);
+            let (parent, parent_function, depth) = match scopes {
+                Some(s) => (s.container, s.function, s.function_depth),
+                None => (None, None, 0),
+            };
+
             functions.push(FunctionInfo {
                 name,
+                parent,
+                qualified_name,
+                parent_function,
+                depth,
+                annotations: span_node
+                    .map(|n| extract_annotations(n, &code))
+                    .unwrap_or_default(),
+                is_async: span_node.is_some_and(|n| is_async(n, &code)),
+                start_line,
+                end_line,
                 body: body_content,
-                comment: if compactness >= 2 { comment } else { None }, // Include comment only if compactness is 2 or 3.
+                comment, // Only populated if compactness is 2 or 3.
+                parameters: parameters_node
+                    .map(|n| extract_parameters(n, &code))
+                    .unwrap_or_default(),
+                return_type: return_type_node.map(|n| type_text(n, &code)),
+                metrics: span_node.map(compute_metrics).unwrap_or_default(),
+                calls: match (span_node, &calls_query) {
+                    (Some(node), Some(calls_query)) => extract_calls(node, &code, calls_query),
+                    _ => Vec::new(),
+                },
+                uri: None,
             });
         }
     }
 
-    if functions.is_empty() {
+    let outline = options
+        .outline
+        .then(|| build_outline(tree.root_node(), &code, None));
+    // With an outline, files that only declare types (e.g. a Rust file of structs)
+    // still have symbols worth returning.
+    if functions.is_empty() && outline.as_ref().is_none_or(|outline| outline.is_empty()) {
         return None;
     }
 
+    let mut parse_errors = Vec::new();
+    collect_parse_errors(tree.root_node(), &code, &mut parse_errors);
+
     Some(FileContext {
         path: path.to_str()?.to_string(),
         description: String::new(), // TODO: Determine how to populate FileContext::description meaningfully.
+        language: config::language_name(extension)?.to_string(),
+        encoding: encoding.to_string(),
+        line_count: code.lines().count(),
+        size_bytes: bytes.len() as u64,
+        content_hash: utils::content_hash(bytes),
+        leading_comment: options.outline.then(|| leading_comment(&code)).flatten(),
+        imports: extract_imports(tree.root_node(), &code, extension),
         functions,
+        outline,
+        parse_errors,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses one of the sample files in the repository's `test_data` directory, with
+    /// signatures and comments.
+    fn sample(name: &str) -> FileContext {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("test_data").join(name);
+        parse_file(&path, 2, &ScanOptions::default()).unwrap()
+    }
+
+    fn function<'a>(context: &'a FileContext, qualified_name: &str) -> &'a FunctionInfo {
+        context
+            .functions
+            .iter()
+            .find(|function| function.qualified_name == qualified_name)
+            .unwrap_or_else(|| {
+                let names: Vec<&str> = context.functions.iter().map(|f| f.qualified_name.as_str()).collect();
+                panic!("no function {} in {:?}", qualified_name, names)
+            })
+    }
+
+    fn parameters(function: &FunctionInfo) -> Vec<(&str, Option<&str>)> {
+        function
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.name.as_str(), parameter.type_name.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn python_methods_have_their_class_and_docstring() {
+        let context = sample("python_sample.py");
+        let method = function(&context, "MyClass.my_method");
+        assert_eq!(method.parent.as_deref(), Some("MyClass"));
+        assert_eq!(parameters(method), vec![("self", None), ("arg1", None), ("arg2", None)]);
+        assert_eq!((method.start_line, method.end_line), (12, 14));
+        let comment = method.comment.as_deref().unwrap();
+        assert!(comment.contains("This is a docstring for my_method."), "{}", comment);
+
+        let top_level = function(&context, "my_function");
+        assert_eq!(top_level.parent, None);
+        assert!(top_level.comment.as_deref().unwrap().contains("This is a docstring for my_function."));
+    }
+
+    #[test]
+    fn python_decorators_types_and_nested_functions() {
+        let context = sample("python_sample.py");
+        let lookup = function(&context, "Service.lookup");
+        assert_eq!(lookup.annotations, vec!["@staticmethod", "@cached"]);
+        assert_eq!(parameters(lookup), vec![("key", Some("str"))]);
+        assert_eq!(lookup.return_type.as_deref(), Some("int"));
+
+        let wrapper = function(&context, "cached.wrapper");
+        assert_eq!(wrapper.parent_function.as_deref(), Some("cached"));
+        assert_eq!(wrapper.depth, 1);
+        assert_eq!(wrapper.annotations, vec!["@functools.wraps(func)"]);
+        assert_eq!(function(&context, "cached").depth, 0);
+    }
+
+    #[test]
+    fn python_async_functions_and_lambdas() {
+        let context = sample("python_sample.py");
+        let fetch = function(&context, "Service.fetch");
+        assert!(fetch.is_async);
+        assert_eq!(
+            parameters(fetch),
+            vec![("self", None), ("url", Some("str")), ("retries", Some("int"))]
+        );
+        assert_eq!(fetch.return_type.as_deref(), Some("bytes"));
+        assert!(!function(&context, "Service.lookup").is_async);
+
+        let square = function(&context, "square");
+        assert_eq!(parameters(square), vec![("x", None)]);
+        assert_eq!(square.parent, None);
+    }
+
+    #[test]
+    fn rust_functions_impl_and_trait_methods() {
+        let context = sample("rust_sample.rs");
+        let another = function(&context, "another_function");
+        assert_eq!(parameters(another), vec![("x", Some("i32"))]);
+        assert_eq!(another.return_type.as_deref(), Some("i32"));
+        assert!(another.comment.as_deref().unwrap().contains("block doc comment"));
+
+        let required = function(&context, "Shape::area");
+        assert_eq!(required.parent.as_deref(), Some("Shape"));
+        assert_eq!(required.comment.as_deref(), Some("/// Area of the shape."));
+        assert_eq!(function(&context, "Shape::describe").return_type.as_deref(), Some("String"));
+
+        let constructor = function(&context, "MyStruct::new");
+        assert_eq!(constructor.parent.as_deref(), Some("MyStruct"));
+        assert_eq!(constructor.return_type.as_deref(), Some("Self"));
+        let implementation = function(&context, "<MyStruct as Shape>::area");
+        assert_eq!(implementation.parent.as_deref(), Some("<MyStruct as Shape>"));
+        assert_eq!((implementation.start_line, implementation.end_line), (38, 40));
+    }
+
+    #[test]
+    fn rust_attributes_nested_and_async_functions() {
+        let context = sample("rust_sample.rs");
+        assert_eq!(function(&context, "doubles").annotations, vec!["#[test]"]);
+        let nested = function(&context, "doubles::double");
+        assert_eq!(nested.parent_function.as_deref(), Some("doubles"));
+        assert_eq!(nested.depth, 1);
+
+        let load = function(&context, "load");
+        assert!(load.is_async);
+        assert_eq!(parameters(load), vec![("path", Some("&str"))]);
+        assert_eq!(load.return_type.as_deref(), Some("Vec<u8>"));
+    }
+
+    #[test]
+    fn csharp_methods_constructors_and_properties() {
+        let context = sample("csharp_sample.cs");
+        let method = function(&context, "TestApp.MyTestClass.MyMethod2");
+        assert_eq!(method.parent.as_deref(), Some("MyTestClass"));
+        assert_eq!(parameters(method), vec![("x", Some("int")), ("y", Some("int"))]);
+        assert_eq!(method.return_type.as_deref(), Some("int"));
+        assert!(method.comment.as_deref().unwrap().contains("It spans multiple lines."));
+
+        let constructor = function(&context, "TestApp.MyTestClass.MyTestClass");
+        assert_eq!(parameters(constructor), vec![("name", Some("string"))]);
+        assert_eq!(constructor.return_type, None);
+        assert!(constructor.comment.as_deref().unwrap().contains("Creates a class"));
+
+        // Accessors are reported like the methods they compile to.
+        let property = function(&context, "TestApp.MyTestClass.get_Name");
+        assert_eq!(property.return_type.as_deref(), Some("string"));
+        function(&context, "TestApp.MyTestClass.set_Name");
+        let getter = function(&context, "TestApp.MyTestClass.get_Length");
+        assert_eq!(getter.return_type.as_deref(), Some("int"));
+        assert!(!context.functions.iter().any(|f| f.qualified_name == "TestApp.MyTestClass.set_Length"));
+
+        let obsolete = function(&context, "TestApp.MyTestClass.MyMethod3");
+        assert_eq!(obsolete.annotations, vec!["[Obsolete(\"Use MyMethod2 instead.\")]"]);
+        assert!(obsolete.is_async);
+        assert_eq!(obsolete.return_type.as_deref(), Some("Task<int>"));
+    }
+
+    #[test]
+    fn typescript_methods_arrow_functions_and_async_functions() {
+        let context = sample("typescript_sample.ts");
+        let method = function(&context, "MyClass.myMethod");
+        assert_eq!(method.parent.as_deref(), Some("MyClass"));
+        assert_eq!(parameters(method), vec![("arg1", Some("string")), ("arg2", Some("number"))]);
+        assert_eq!(method.return_type.as_deref(), Some("void"));
+
+        let add = function(&context, "add");
+        assert_eq!(parameters(add), vec![("a", Some("number")), ("b", Some("number"))]);
+        assert_eq!(add.return_type.as_deref(), Some("number"));
+        assert_eq!(add.comment.as_deref(), Some("/** Adds two numbers. */"));
+
+        let load = function(&context, "load");
+        assert!(load.is_async);
+        assert_eq!(load.return_type.as_deref(), Some("Promise<string>"));
+    }
+}
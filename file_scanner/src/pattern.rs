@@ -0,0 +1,566 @@
+use crate::options::{BooleanScope, SearchOptions};
+use aho_corasick::AhoCorasick;
+use memchr::memmem::Finder;
+use regex::{Captures, Regex, RegexBuilder};
+use std::collections::BTreeMap;
+
+/// Upper bound on the compiled size of a search regex, so a pathological pattern fails
+/// to compile instead of exhausting memory.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// Upper bound on the lazy DFA cache of a search regex.
+const REGEX_DFA_SIZE_LIMIT: usize = 8 << 20;
+
+/// What `project_wide_search` looks for, on each line or across a whole file.
+pub enum LinePattern {
+    /// A substring, found with memchr's SIMD-accelerated substring search.
+    Literal(Box<Finder<'static>>),
+    Regex(Regex),
+}
+
+impl LinePattern {
+    /// A literal substring, or a regex when `use_regex` is set. Invalid or oversized
+    /// patterns are reported with the regex crate's message.
+    ///
+    /// Case-insensitive and whole-word literals are compiled as escaped regexes, which
+    /// have no capture groups, so they still match like literals.
+    pub fn new(search: &str, options: &SearchOptions) -> Result<LinePattern, String> {
+        if !options.use_regex && !options.case_insensitive && !options.whole_word {
+            return Ok(LinePattern::Literal(Box::new(Finder::new(search).into_owned())));
+        }
+        let pattern = if options.use_regex { search.to_string() } else { regex::escape(search) };
+        // A non-capturing group keeps alternations inside the word boundaries and the
+        // caller's group indices unchanged.
+        let pattern = if options.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(options.case_insensitive)
+            // Across a whole file, `^` and `$` still mean the start and end of a line.
+            .multi_line(options.multiline)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .dfa_size_limit(REGEX_DFA_SIZE_LIMIT)
+            .build()
+            .map(LinePattern::Regex)
+            .map_err(|e| format!("Invalid search regex: {}", e))
+    }
+
+    /// The first match in `text`, if any.
+    pub fn find(&self, text: &str) -> Option<PatternMatch> {
+        match self {
+            LinePattern::Literal(finder) => finder.find(text.as_bytes()).map(|start| PatternMatch {
+                start,
+                end: start + finder.needle().len(),
+                captures: BTreeMap::new(),
+                pattern: 0,
+            }),
+            LinePattern::Regex(regex) => regex.captures(text).map(|captures| pattern_match(regex, &captures)),
+        }
+    }
+
+    /// Every non-overlapping match in `text`, in order.
+    pub fn find_all(&self, text: &str) -> Vec<PatternMatch> {
+        match self {
+            LinePattern::Literal(finder) => finder
+                .find_iter(text.as_bytes())
+                .map(|start| PatternMatch {
+                    start,
+                    end: start + finder.needle().len(),
+                    captures: BTreeMap::new(),
+                    pattern: 0,
+                })
+                .collect(),
+            LinePattern::Regex(regex) => regex
+                .captures_iter(text)
+                .map(|captures| pattern_match(regex, &captures))
+                .collect(),
+        }
+    }
+}
+
+/// What `project_wide_search` looks for: one pattern, or a boolean query of patterns
+/// (see `SearchOptions::boolean_query`).
+pub enum SearchQuery {
+    Pattern(LinePattern),
+    Boolean(QueryExpr, BooleanScope),
+    /// The search string and `SearchOptions::patterns`, found in one pass.
+    Multi(MultiPattern),
+}
+
+impl SearchQuery {
+    pub fn new(search: &str, options: &SearchOptions) -> Result<SearchQuery, String> {
+        if options.boolean_query {
+            QueryExpr::parse(search, options).map(|expr| SearchQuery::Boolean(expr, options.boolean_scope))
+        } else if !options.patterns.is_empty() {
+            let mut patterns = vec![search.to_string()];
+            for pattern in &options.patterns {
+                if !pattern.is_empty() && !patterns.contains(pattern) {
+                    patterns.push(pattern.clone());
+                }
+            }
+            MultiPattern::new(patterns, options).map(SearchQuery::Multi)
+        } else {
+            LinePattern::new(search, options).map(SearchQuery::Pattern)
+        }
+    }
+
+    /// Whether a file with content `text` can have matches. A literal that does not
+    /// occur in the whole file occurs on none of its lines, so such files are ruled out
+    /// with one substring search instead of being split into lines.
+    pub fn may_match(&self, text: &str) -> bool {
+        match self {
+            SearchQuery::Pattern(LinePattern::Literal(finder)) => finder.find(text.as_bytes()).is_some(),
+            SearchQuery::Multi(MultiPattern { matcher: MultiMatcher::Literals(automaton), .. }) => {
+                automaton.is_match(text)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Several patterns searched for together, e.g. a list of deprecated APIs. Matches carry
+/// the index of their pattern in `patterns`.
+pub struct MultiPattern {
+    pub patterns: Vec<String>,
+    pub matcher: MultiMatcher,
+}
+
+pub enum MultiMatcher {
+    /// Plain literals, all found in a single Aho-Corasick pass over each line.
+    Literals(AhoCorasick),
+    /// Regexes, or literals with case or word options: each pattern is tried in turn.
+    Each(Vec<LinePattern>),
+}
+
+impl MultiPattern {
+    fn new(patterns: Vec<String>, options: &SearchOptions) -> Result<MultiPattern, String> {
+        let matcher = if options.use_regex || options.case_insensitive || options.whole_word || options.multiline {
+            MultiMatcher::Each(
+                patterns
+                    .iter()
+                    .map(|pattern| LinePattern::new(pattern, options))
+                    .collect::<Result<_, _>>()?,
+            )
+        } else {
+            MultiMatcher::Literals(
+                AhoCorasick::new(&patterns).map_err(|e| format!("Failed to build pattern matcher: {}", e))?,
+            )
+        };
+        Ok(MultiPattern { patterns, matcher })
+    }
+
+    /// The first occurrence of each pattern on each line, as `(line, match)` ordered by
+    /// line and then pattern. Only for line-by-line searches; in `multiline` mode every
+    /// pattern is an `Each` pattern searched on its own.
+    pub fn matched_lines(&self, lines: &[&str]) -> Vec<(usize, PatternMatch)> {
+        let mut matched = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let start = matched.len();
+            match &self.matcher {
+                MultiMatcher::Literals(automaton) => {
+                    for found in automaton.find_overlapping_iter(line) {
+                        let pattern = found.pattern().as_usize();
+                        if !matched[start..].iter().any(|(_, m): &(usize, PatternMatch)| m.pattern == pattern) {
+                            matched.push((
+                                i,
+                                PatternMatch {
+                                    start: found.start(),
+                                    end: found.end(),
+                                    captures: BTreeMap::new(),
+                                    pattern,
+                                },
+                            ));
+                        }
+                    }
+                }
+                MultiMatcher::Each(patterns) => {
+                    for (pattern, line_pattern) in patterns.iter().enumerate() {
+                        if let Some(mut found) = line_pattern.find(line) {
+                            found.pattern = pattern;
+                            matched.push((i, found));
+                        }
+                    }
+                }
+            }
+            matched[start..].sort_by_key(|(_, found)| found.pattern);
+        }
+        matched
+    }
+}
+
+/// A parsed boolean query. Every term is a `LinePattern`.
+pub enum QueryExpr {
+    Term(LinePattern),
+    Not(Box<QueryExpr>),
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+impl Token {
+    /// The token as written, for error messages.
+    fn describe(&self) -> String {
+        match self {
+            Token::Open => "`(`".to_string(),
+            Token::Close => "`)`".to_string(),
+            Token::And => "`AND`".to_string(),
+            Token::Or => "`OR`".to_string(),
+            Token::Not => "`NOT`".to_string(),
+            Token::Term(term) => format!("`{}`", term),
+        }
+    }
+}
+
+/// Splits a boolean query into tokens. Operators are upper-case words, so `and` and
+/// `or` are ordinary terms; parentheses group when they open or close a word, so regex
+/// terms with parentheses at their edges need quoting.
+fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut term = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => term.push(c),
+                    None => return Err("Unterminated quote in boolean query.".to_string()),
+                }
+            }
+            if term.is_empty() {
+                return Err("Empty quoted term in boolean query.".to_string());
+            }
+            tokens.push(Token::Term(term));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            let inner = word.trim_start_matches('(');
+            tokens.extend((0..word.len() - inner.len()).map(|_| Token::Open));
+            let term = inner.trim_end_matches(')');
+            match term {
+                "" => {}
+                "AND" => tokens.push(Token::And),
+                "OR" => tokens.push(Token::Or),
+                "NOT" => tokens.push(Token::Not),
+                term => tokens.push(Token::Term(term.to_string())),
+            }
+            tokens.extend((0..inner.len() - term.len()).map(|_| Token::Close));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over the tokens of a boolean query. `OR` binds loosest,
+/// then `AND` (explicit or implied by adjacency), then `NOT`.
+struct QueryParser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    options: &'a SearchOptions,
+}
+
+impl QueryParser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        self.position += 1;
+        self.tokens.get(self.position - 1)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn or(&mut self) -> Result<QueryExpr, String> {
+        let mut operands = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            operands.push(self.and()?);
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { QueryExpr::Or(operands) })
+    }
+
+    fn and(&mut self) -> Result<QueryExpr, String> {
+        let mut operands = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.position += 1;
+                    operands.push(self.unary()?);
+                }
+                // `foo NOT bar` reads as `foo AND NOT bar`.
+                Some(Token::Not | Token::Open | Token::Term(_)) => operands.push(self.unary()?),
+                _ => break,
+            }
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { QueryExpr::And(operands) })
+    }
+
+    fn unary(&mut self) -> Result<QueryExpr, String> {
+        let options = self.options;
+        match self.next() {
+            Some(Token::Not) => Ok(QueryExpr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err("Missing `)` in boolean query.".to_string()),
+                }
+            }
+            Some(Token::Term(term)) => LinePattern::new(term, options).map(QueryExpr::Term),
+            Some(token) => Err(format!("Unexpected {} in boolean query.", token.describe())),
+            None => Err("Boolean query ends where a term was expected.".to_string()),
+        }
+    }
+}
+
+impl QueryExpr {
+    /// Parses `query`, compiling each term with `options`. Fails on a syntax error or
+    /// if every term is negated, since such a query would have nothing to point at.
+    pub fn parse(query: &str, options: &SearchOptions) -> Result<QueryExpr, String> {
+        let mut parser = QueryParser {
+            tokens: tokenize(query)?,
+            position: 0,
+            options,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {} in boolean query.", token.describe()));
+        }
+        if !expr.has_positive_term(false) {
+            return Err("A boolean query needs at least one term that is not negated.".to_string());
+        }
+        Ok(expr)
+    }
+
+    fn has_positive_term(&self, negated: bool) -> bool {
+        match self {
+            QueryExpr::Term(_) => !negated,
+            QueryExpr::Not(inner) => inner.has_positive_term(!negated),
+            QueryExpr::And(operands) | QueryExpr::Or(operands) => {
+                operands.iter().any(|operand| operand.has_positive_term(negated))
+            }
+        }
+    }
+
+    /// Whether the query holds for `text`: each term counts as true if it occurs in it.
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            QueryExpr::Term(pattern) => pattern.find(text).is_some(),
+            QueryExpr::Not(inner) => !inner.is_match(text),
+            QueryExpr::And(operands) => operands.iter().all(|operand| operand.is_match(text)),
+            QueryExpr::Or(operands) => operands.iter().any(|operand| operand.is_match(text)),
+        }
+    }
+
+    /// The earliest match in `text` of a term that is not negated.
+    fn first_positive_match(&self, text: &str, negated: bool) -> Option<PatternMatch> {
+        match self {
+            QueryExpr::Term(pattern) if !negated => pattern.find(text),
+            QueryExpr::Term(_) => None,
+            QueryExpr::Not(inner) => inner.first_positive_match(text, !negated),
+            QueryExpr::And(operands) | QueryExpr::Or(operands) => operands
+                .iter()
+                .filter_map(|operand| operand.first_positive_match(text, negated))
+                .min_by_key(|found| found.start),
+        }
+    }
+
+    /// The matching lines of a file, each with the earliest non-negated term on it. Per
+    /// line, a line that only matches through a negation (`foo OR NOT bar` on a line
+    /// without `foo`) is reported with an empty match at its start.
+    pub fn matched_lines(&self, lines: &[&str], scope: BooleanScope) -> Vec<(usize, PatternMatch)> {
+        if scope == BooleanScope::File && !self.is_match(&lines.join("\n")) {
+            return Vec::new();
+        }
+        lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match scope {
+                BooleanScope::Line if !self.is_match(line) => None,
+                BooleanScope::Line => Some((
+                    i,
+                    self.first_positive_match(line, false).unwrap_or(PatternMatch {
+                        start: 0,
+                        end: 0,
+                        captures: BTreeMap::new(),
+                        pattern: 0,
+                    }),
+                )),
+                BooleanScope::File => self.first_positive_match(line, false).map(|found| (i, found)),
+            })
+            .collect()
+    }
+}
+
+/// Where a pattern matched, as byte offsets into the searched text.
+pub struct PatternMatch {
+    pub start: usize,
+    pub end: usize,
+    /// Capture groups keyed by group name, or by index for unnamed groups (`"1"`, `"2"`,
+    /// ...). Groups that did not participate in the match are left out.
+    pub captures: BTreeMap<String, String>,
+    /// Index of the pattern that matched, in a multi-pattern search (see `MultiPattern`);
+    /// `0` otherwise.
+    pub pattern: usize,
+}
+
+fn pattern_match(regex: &Regex, captures: &Captures) -> PatternMatch {
+    let whole = captures.get(0).expect("group 0 is always the whole match");
+    PatternMatch {
+        start: whole.start(),
+        end: whole.end(),
+        captures: regex
+            .capture_names()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(index, name)| {
+                let group = captures.get(index)?;
+                let key = name.map_or_else(|| index.to_string(), str::to_string);
+                Some((key, group.as_str().to_string()))
+            })
+            .collect(),
+        pattern: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boolean(query: &str) -> Result<QueryExpr, String> {
+        QueryExpr::parse(query, &SearchOptions::default())
+    }
+
+    fn syntax_error(query: &str) -> String {
+        boolean(query).err().expect("query should be rejected")
+    }
+
+    fn matched(expr: &QueryExpr, lines: &[&str], scope: BooleanScope) -> Vec<(usize, usize, usize)> {
+        expr.matched_lines(lines, scope)
+            .into_iter()
+            .map(|(line, found)| (line, found.start, found.end))
+            .collect()
+    }
+
+    #[test]
+    fn boolean_operators_bind_or_loosest_then_and_then_not() {
+        let expr = boolean("alpha OR beta gamma NOT delta").unwrap();
+        assert!(expr.is_match("alpha"));
+        assert!(expr.is_match("beta gamma"));
+        assert!(!expr.is_match("beta"));
+        assert!(!expr.is_match("beta gamma delta"));
+        assert!(expr.is_match("alpha delta"));
+    }
+
+    #[test]
+    fn boolean_parentheses_and_quotes_group_terms() {
+        let expr = boolean("(alpha OR beta) AND \"two words\"").unwrap();
+        assert!(expr.is_match("beta: two words"));
+        assert!(!expr.is_match("beta: two"));
+        assert!(!expr.is_match("gamma: two words"));
+        // Lower-case `and` and `or` are ordinary terms.
+        let expr = boolean("read and write").unwrap();
+        assert!(expr.is_match("read and write"));
+        assert!(!expr.is_match("read write"));
+    }
+
+    #[test]
+    fn boolean_query_syntax_errors_are_reported() {
+        assert!(syntax_error("\"unterminated").contains("Unterminated quote"));
+        assert!(syntax_error("(alpha OR beta").contains("Missing `)`"));
+        assert!(syntax_error("alpha OR").contains("ends where a term was expected"));
+        assert!(syntax_error("alpha )").contains("Unexpected `)`"));
+        assert!(syntax_error("NOT alpha").contains("not negated"));
+        assert!(boolean("NOT (alpha OR NOT beta)").is_ok());
+    }
+
+    #[test]
+    fn boolean_line_scope_reports_the_earliest_positive_term() {
+        let expr = boolean("beta OR alpha").unwrap();
+        let lines = ["x alpha beta", "nothing", "beta"];
+        assert_eq!(matched(&expr, &lines, BooleanScope::Line), vec![(0, 2, 7), (2, 0, 4)]);
+        // A line that only matches through a negation gets an empty match at its start.
+        let expr = boolean("alpha OR NOT beta").unwrap();
+        assert_eq!(matched(&expr, &["beta", "gamma"], BooleanScope::Line), vec![(1, 0, 0)]);
+    }
+
+    #[test]
+    fn boolean_file_scope_evaluates_the_whole_file() {
+        let expr = boolean("alpha AND beta").unwrap();
+        let lines = ["alpha here", "unrelated", "beta there"];
+        assert_eq!(matched(&expr, &lines, BooleanScope::File), vec![(0, 0, 5), (2, 0, 4)]);
+        assert!(matched(&expr, &lines[..2], BooleanScope::File).is_empty());
+        assert!(matched(&expr, &[], BooleanScope::File).is_empty());
+    }
+
+    #[test]
+    fn boolean_terms_use_the_search_options() {
+        let options = SearchOptions { case_insensitive: true, whole_word: true, ..Default::default() };
+        let expr = QueryExpr::parse("Alpha NOT beta", &options).unwrap();
+        assert!(expr.is_match("ALPHA"));
+        assert!(!expr.is_match("alphabet"));
+        assert!(!expr.is_match("alpha BETA"));
+    }
+
+    fn multi(search: &str, patterns: &[&str], options: SearchOptions) -> MultiPattern {
+        let options = SearchOptions { patterns: patterns.iter().map(|p| p.to_string()).collect(), ..options };
+        match SearchQuery::new(search, &options) {
+            Ok(SearchQuery::Multi(multi)) => multi,
+            _ => panic!("expected a multi-pattern query"),
+        }
+    }
+
+    fn multi_matched(multi: &MultiPattern, lines: &[&str]) -> Vec<(usize, usize, usize, usize)> {
+        multi
+            .matched_lines(lines)
+            .into_iter()
+            .map(|(line, found)| (line, found.pattern, found.start, found.end))
+            .collect()
+    }
+
+    #[test]
+    fn multi_pattern_drops_empty_and_repeated_patterns() {
+        let multi = multi("alpha", &["beta", "", "alpha", "beta"], SearchOptions::default());
+        assert_eq!(multi.patterns, vec!["alpha", "beta"]);
+        assert!(matches!(multi.matcher, MultiMatcher::Literals(_)));
+    }
+
+    #[test]
+    fn multi_pattern_literals_report_each_pattern_once_per_line() {
+        let multi = multi("foo", &["foobar", "bar"], SearchOptions::default());
+        let lines = ["bar foobar foo", "", "nothing", "foo"];
+        // Overlapping matches are all found; each pattern's first occurrence counts.
+        assert_eq!(
+            multi_matched(&multi, &lines),
+            vec![(0, 0, 4, 7), (0, 1, 4, 10), (0, 2, 0, 3), (3, 0, 0, 3)]
+        );
+        assert!(multi_matched(&multi, &[]).is_empty());
+    }
+
+    #[test]
+    fn multi_pattern_with_options_tries_each_pattern() {
+        let options = SearchOptions { case_insensitive: true, ..Default::default() };
+        let multi = multi("Foo", &["BAR"], options);
+        assert!(matches!(multi.matcher, MultiMatcher::Each(_)));
+        assert_eq!(multi_matched(&multi, &["bar FOO"]), vec![(0, 0, 4, 7), (0, 1, 0, 3)]);
+    }
+
+    #[test]
+    fn multi_pattern_rejects_an_invalid_regex() {
+        let options = SearchOptions { use_regex: true, patterns: vec!["(".to_string()], ..Default::default() };
+        assert!(SearchQuery::new("ok", &options).is_err());
+    }
+}
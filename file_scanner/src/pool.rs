@@ -0,0 +1,81 @@
+use crate::runtime;
+use once_cell::sync::{Lazy, OnceCell};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Environment variable that overrides the number of worker threads.
+pub const NUM_THREADS_ENV_VAR: &str = "RAPID_NUM_THREADS";
+
+static SHARED_POOL: OnceCell<ThreadPool> = OnceCell::new();
+
+/// Pools built for a `num_threads` option below the shared budget, by size.
+static SIZED_POOLS: Lazy<Mutex<HashMap<usize, &'static ThreadPool>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Number of worker threads used for both directory walking and rayon work.
+///
+/// Uses `num_threads` from `rapid_init`, or else `RAPID_NUM_THREADS`, if set to a
+/// positive integer, otherwise the available parallelism of the machine.
+pub fn thread_count() -> usize {
+    runtime::config()
+        .and_then(|config| config.num_threads)
+        .or_else(|| std::env::var(NUM_THREADS_ENV_VAR).ok().and_then(|v| v.trim().parse::<usize>().ok()))
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+}
+
+/// Returns the process-wide rayon pool.
+///
+/// The `ignore` walker cannot run on rayon threads, so walkers are sized to the same
+/// thread count instead (see `scanner::perform_scan`). Running the rayon phases inside
+/// `shared().install(..)` keeps embedding preparation and similarity scoring from
+/// spinning up rayon's global pool next to the walker threads, so a concept search
+/// never has more than `thread_count()` busy workers.
+pub fn shared() -> &'static ThreadPool {
+    SHARED_POOL.get_or_init(|| {
+        ThreadPoolBuilder::new()
+            .num_threads(thread_count())
+            .thread_name(|i| format!("rapid-worker-{}", i))
+            .build()
+            .expect("Failed to build the shared rayon thread pool")
+    })
+}
+
+/// Workers for a call that sets the `num_threads` option: the option, capped at
+/// `thread_count()`, or `thread_count()` when it is `None` or `0`.
+pub fn threads_for(num_threads: Option<usize>) -> usize {
+    let budget = thread_count();
+    num_threads.filter(|&n| n > 0).map_or(budget, |n| n.min(budget))
+}
+
+/// Returns a pool with `threads_for(num_threads)` workers, or the shared pool when that
+/// is the whole budget.
+///
+/// Walkers of that call are sized to the same count. Like the shared pool, a pool is
+/// built once per size and kept for the life of the process, so hosts that throttle
+/// every call to the same count do not pay for a new pool each time. Capping the size
+/// at `thread_count()` bounds how many such pools can exist. Returns an error if the
+/// pool cannot be built (e.g. the OS refuses to spawn its threads).
+pub fn with_threads(num_threads: Option<usize>) -> Result<&'static ThreadPool, String> {
+    let num_threads = threads_for(num_threads);
+    if num_threads == thread_count() {
+        return Ok(shared());
+    }
+    let mut pools = SIZED_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&num_threads) {
+        return Ok(pool);
+    }
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(move |i| format!("rapid-worker-{}-{}", num_threads, i))
+        .build()
+        .map_err(|e| format!("Failed to build a pool of {} worker threads: {}", num_threads, e))?;
+    let pool: &'static ThreadPool = Box::leak(Box::new(pool));
+    pools.insert(num_threads, pool);
+    Ok(pool)
+}
@@ -0,0 +1,73 @@
+use crate::structs::{ProgressPhase, ScanProgress};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two reports, so hosts are not flooded with one call per file.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Receives scan progress. It is called from walker threads, one call at a time.
+pub type ScanProgressFn<'a> = &'a mut (dyn FnMut(&ScanProgress) + Send);
+
+struct ReporterState<'a> {
+    callback: ScanProgressFn<'a>,
+    last_report: Option<Instant>,
+}
+
+/// Counts the files a scan discovers and processes, and passes the counts to a
+/// callback at most every `REPORT_INTERVAL`. Without a callback only the counting
+/// remains.
+#[derive(Default)]
+pub struct ProgressReporter<'a> {
+    state: Option<Mutex<ReporterState<'a>>>,
+    files_discovered: AtomicUsize,
+    files_processed: AtomicUsize,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(callback: Option<ScanProgressFn<'a>>) -> ProgressReporter<'a> {
+        ProgressReporter {
+            state: callback.map(|callback| {
+                Mutex::new(ReporterState {
+                    callback,
+                    last_report: None,
+                })
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A file was found by the walk, whether or not it will be parsed.
+    pub fn file_discovered(&self) {
+        self.files_discovered.fetch_add(1, Ordering::Relaxed);
+        self.report(false);
+    }
+
+    /// A file with a requested extension was parsed (or failed to parse).
+    pub fn file_processed(&self) {
+        self.files_processed.fetch_add(1, Ordering::Relaxed);
+        self.report(false);
+    }
+
+    /// Current counts, in the scanning phase.
+    pub fn snapshot(&self) -> ScanProgress {
+        ScanProgress {
+            phase: ProgressPhase::Scanning,
+            files_discovered: self.files_discovered.load(Ordering::Relaxed),
+            files_processed: self.files_processed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reports the current counts, throttled unless `force` is set (e.g. for the final
+    /// counts of a scan).
+    pub fn report(&self, force: bool) {
+        let Some(state) = &self.state else { return };
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if !force && state.last_report.is_some_and(|last| last.elapsed() < REPORT_INTERVAL) {
+            return;
+        }
+        state.last_report = Some(Instant::now());
+        let snapshot = self.snapshot();
+        (state.callback)(&snapshot);
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+/// Weight of the share of a file's lines that match.
+const WEIGHT_DENSITY: f64 = 2.0;
+/// Weight of how close the file is to the root.
+const WEIGHT_PROXIMITY: f64 = 1.0;
+/// Bonus for a file whose name contains a search term.
+const BONUS_FILE_NAME: f64 = 1.0;
+
+/// Relevance of a file with `match_count` matches in `line_count` lines, for ordering
+/// `project_wide_search` results with `SearchOptions::ranked`. Higher is better. The
+/// score adds up:
+///
+/// - match density, the matches per line (at most 1), so a file that is about the
+///   searched term beats one that mentions it in passing;
+/// - proximity to the root, `1 / (1 + depth)` for `relative_path`, so top-level
+///   sources beat deeply nested fixtures;
+/// - a bonus when the file name contains one of `terms`, compared case-insensitively.
+pub fn relevance(relative_path: &Path, terms: &[&str], match_count: usize, line_count: usize) -> f64 {
+    let density = (match_count as f64 / line_count.max(1) as f64).min(1.0);
+    let depth = relative_path.components().count().saturating_sub(1);
+    let proximity = 1.0 / (1.0 + depth as f64);
+    let file_name = relative_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let named = terms
+        .iter()
+        .any(|term| !term.is_empty() && file_name.contains(&term.to_lowercase()));
+    WEIGHT_DENSITY * density + WEIGHT_PROXIMITY * proximity + if named { BONUS_FILE_NAME } else { 0.0 }
+}
+
+/// The literal words of a search, for `relevance`'s file-name bonus: the search string
+/// and extra patterns, or for a boolean query its terms that are not negated.
+pub fn search_terms<'a>(search: &'a str, patterns: &'a [String], boolean_query: bool) -> Vec<&'a str> {
+    let mut terms = Vec::new();
+    if boolean_query {
+        // Terms right after `NOT` are ones the file should not be about.
+        let mut negated = false;
+        for word in search.split_whitespace() {
+            let word = word.trim_matches(|c| c == '(' || c == ')' || c == '"');
+            match word {
+                "" | "AND" | "OR" => {}
+                "NOT" => negated = true,
+                _ if negated => negated = false,
+                _ => terms.push(word),
+            }
+        }
+    } else {
+        terms.push(search);
+    }
+    terms.extend(patterns.iter().map(String::as_str));
+    terms
+}
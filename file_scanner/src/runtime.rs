@@ -0,0 +1,16 @@
+use crate::options::RuntimeConfig;
+use once_cell::sync::OnceCell;
+
+/// Settings fixed by `rapid_init`; unset until then, and never changed afterwards.
+static CONFIG: OnceCell<RuntimeConfig> = OnceCell::new();
+
+/// Fixes the process-wide settings. Returns `false`, changing nothing, if they were
+/// already fixed by an earlier call, including one racing on another thread.
+pub fn init(config: RuntimeConfig) -> bool {
+    CONFIG.set(config).is_ok()
+}
+
+/// The settings fixed by `rapid_init`, if it was called.
+pub fn config() -> Option<&'static RuntimeConfig> {
+    CONFIG.get()
+}
@@ -1,8 +1,19 @@
-use crate::parsing;
-use crate::structs::{FileContext, ScanResult};
+use crate::cancellation::CancelFlag;
+use crate::config;
+use crate::continuation::{self, ScanContinuation};
+use crate::extract;
+use crate::options::ScanOptions;
+use crate::parse_cache::ParseCache;
+use crate::parsing::{self, ParseSkip};
+use crate::pool;
+use crate::progress::ProgressReporter;
+use crate::structs::{FileContext, ScanResult, ScanStats, SkipReason, SkippedEntry, TodoItem, TodoScanResult};
+use crate::utils;
 
+use ignore::overrides::Override;
 use ignore::WalkBuilder;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
@@ -18,7 +29,8 @@ use std::time::Instant;
 /// * `extensions` - A list of file extensions (e.g., "py", "rs") to include.
 /// * `compactness_level` - Controls the detail of parsed content.
 /// * `timeout_milliseconds` - Maximum duration for the scan. If 0, no internal timeout is applied,
-///                            though external callers (like FFI) might still impose one.
+///   though external callers (like FFI) might still impose one.
+/// * `options` - Additional scan settings (see `ScanOptions`).
 ///
 /// # Returns
 /// A `ScanResult` containing parsed file contexts, debug logs, and timeout status.
@@ -27,6 +39,22 @@ pub fn perform_scan(
     extensions: Vec<String>, // TODO: Consider using &[String] or similar to avoid clone if called internally often.
     compactness_level: u8,
     timeout_milliseconds: u32,
+    options: &ScanOptions,
+    debug: bool,
+) -> ScanResult {
+    let progress = ProgressReporter::default();
+    perform_scan_with_progress(root_path_str, extensions, compactness_level, timeout_milliseconds, options, &progress, debug)
+}
+
+/// Same as `perform_scan`, but counts discovered and processed files in `progress`,
+/// which reports them to its callback while the walk runs and once more at the end.
+pub fn perform_scan_with_progress(
+    root_path_str: &str,
+    extensions: Vec<String>,
+    compactness_level: u8,
+    timeout_milliseconds: u32,
+    options: &ScanOptions,
+    progress: &ProgressReporter,
     debug: bool,
 ) -> ScanResult {
     let start_time = Instant::now();
@@ -52,6 +80,11 @@ pub fn perform_scan(
             debug_log,
             timed_out_internally: false,
             files_processed_before_timeout: 0,
+            cancelled: false,
+            stats: ScanStats::default(),
+            continuation_token: None,
+            skipped: Vec::new(),
+            errors: Vec::new(),
         };
     }
     if !root_path.is_dir() {
@@ -66,38 +99,203 @@ pub fn perform_scan(
             debug_log,
             timed_out_internally: false,
             files_processed_before_timeout: 0,
+            cancelled: false,
+            stats: ScanStats::default(),
+            continuation_token: None,
+            skipped: Vec::new(),
+            errors: Vec::new(),
         };
     }
 
+    let mut errors = Vec::new();
+    let unsupported: Vec<&str> = extensions
+        .iter()
+        .map(|e| e.trim_start_matches('.'))
+        .filter(|e| {
+            !config::is_extension_glob(e) && config::language_name(e).is_none() && !extract::is_extracted(e)
+        })
+        .collect();
+    if !unsupported.is_empty() {
+        let message = format!("Unsupported extensions requested: {}", unsupported.join(", "));
+        if let Some(log) = &mut debug_log {
+            log.push(format!("[Scanner] {}", message));
+        }
+        if options.strict {
+            errors.push(message);
+        }
+    }
+
+    let path_filter = match config::path_filter(root_path, &options.include, &options.exclude) {
+        Ok(filter) => filter,
+        Err(e) => {
+            if let Some(log) = &mut debug_log {
+                log.push(format!("[Scanner] {}", e));
+            }
+            errors.push(e);
+            return ScanResult {
+                file_contexts: Vec::new(),
+                debug_log,
+                timed_out_internally: false,
+                files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors,
+            };
+        }
+    };
+
+    let extension_filter = match config::ExtensionFilter::new(&extensions) {
+        Ok(filter) => Arc::new(filter),
+        Err(e) => {
+            if let Some(log) = &mut debug_log {
+                log.push(format!("[Scanner] {}", e));
+            }
+            errors.push(e);
+            return ScanResult {
+                file_contexts: Vec::new(),
+                debug_log,
+                timed_out_internally: false,
+                files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors,
+            };
+        }
+    };
+
+    // Files that earlier, timed-out scans already visited (`ScanOptions::continuation_token`).
+    let resumed = match options.continuation_token.map(continuation::load).transpose() {
+        Ok(resumed) => resumed.unwrap_or_default(),
+        Err(e) => {
+            if let Some(log) = &mut debug_log {
+                log.push(format!("[Scanner] {}", e));
+            }
+            errors.push(e);
+            return ScanResult {
+                file_contexts: Vec::new(),
+                debug_log,
+                timed_out_internally: false,
+                files_processed_before_timeout: 0,
+                cancelled: false,
+                stats: ScanStats::default(),
+                continuation_token: None,
+                skipped: Vec::new(),
+                errors,
+            };
+        }
+    };
+    if let Some(log) = &mut debug_log {
+        if options.continuation_token.is_some() {
+            log.push(format!("[Scanner] Resuming; skipping {} visited file(s).", resumed.files.len()));
+        }
+    }
+
     // Using parallel walk for potential performance benefits.
     // This aligns with the FFI's `scan_and_parse` original behavior.
-    let mut walker_builder = WalkBuilder::new(root_path);
-    walker_builder.git_ignore(true).git_global(true);
+    let mut walker_builder = walk_builder(root_path, options, path_filter.clone());
     // TODO: Consider adding fallback_ignore if this becomes the primary scanning entry point.
 
+    // An explicit file list (`ScanOptions::files`) replaces the directory walk: each file
+    // is a walk root of depth 0, checked against the walk's filters up front, since the
+    // walker does not apply them to its roots.
+    if !options.files.is_empty() {
+        let (files, rejected) = config::listed_files(root_path, &options.files, &path_filter, options.skip_vendor_dirs);
+        if let Some(log) = &mut debug_log {
+            for rejected in &rejected {
+                log.push(format!("[Scanner] Skipping listed file {}", rejected));
+            }
+            log.push(format!("[Scanner] Scanning {} listed file(s) without walking.", files.len()));
+        }
+        let mut files = files.into_iter();
+        if let Some(first) = files.next() {
+            walker_builder = WalkBuilder::new(first);
+            for file in files {
+                walker_builder.add(file);
+            }
+        }
+        walker_builder
+            .standard_filters(false)
+            .max_depth(Some(0))
+            .threads(pool::threads_for(options.num_threads));
+    }
+
     let walker = walker_builder.build_parallel();
 
     let file_contexts_arc = Arc::new(Mutex::new(Vec::<FileContext>::new()));
+    let already_visited = Arc::new(resumed.files);
+    // Only a scan that can time out needs to remember where it has been.
+    let track_visited = timeout_milliseconds > 0;
+    let visited = Arc::new(Mutex::new(Vec::<PathBuf>::new()));
     let debug_log_arc = Arc::new(Mutex::new(debug_log)); // `debug_log` is moved into the Arc.
     let timed_out_flag = Arc::new(AtomicBool::new(false));
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token);
     let files_processed_count = Arc::new(AtomicUsize::new(0));
+    let scan_stats = Arc::new(Mutex::new(ScanStats::default()));
+    let skipped_entries = Arc::new(Mutex::new(Vec::<SkippedEntry>::new()));
 
     // Clone Arcs for the walker's closure.
     let start_time_clone = start_time; // `Instant` is Copy.
     let timeout_ms_clone = timeout_milliseconds; // `u32` is Copy.
     let timed_out_flag_clone = Arc::clone(&timed_out_flag);
+    let cancel_flag_clone = cancel_flag.clone();
     let files_processed_count_clone = Arc::clone(&files_processed_count);
+    let already_visited_walker = Arc::clone(&already_visited);
+    let visited_walker = Arc::clone(&visited);
     let debug_log_arc_walker = Arc::clone(&debug_log_arc);
     let file_contexts_arc_walker = Arc::clone(&file_contexts_arc);
-    let extensions_clone = extensions; // `Vec<String>` is cloned for the closure.
+    let scan_stats_walker = Arc::clone(&scan_stats);
+    let skipped_entries_walker = Arc::clone(&skipped_entries);
+    let mut options_clone = options.clone();
+    // Queries in `<root>/.rapid/queries/` replace the built-ins; explicit `query_overrides`
+    // from the caller still win.
+    for (extension, query) in config::load_project_queries(root_path, &extensions, compactness_level) {
+        if options_clone.query_overrides.contains_key(&extension) {
+            continue;
+        }
+        match config::validate_query(&extension, &query) {
+            Ok(()) => {
+                options_clone.query_overrides.insert(extension, query);
+            }
+            Err(e) => {
+                if let Some(log) = &mut *debug_log_arc.lock().unwrap() {
+                    log.push(format!("[Scanner] Ignoring project query override: {}", e));
+                }
+            }
+        }
+    }
+    // Opened after the project queries are merged, since they are part of the cache key.
+    let parse_cache = if options_clone.use_cache {
+        match ParseCache::open(root_path, compactness_level, &options_clone) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                if let Some(log) = &mut *debug_log_arc.lock().unwrap() {
+                    log.push(format!("[Scanner] Parsing without cache: {}", e));
+                }
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     walker.run(move || {
         // Per-thread clones of Arcs and other necessary data.
         let file_contexts_thread_arc = Arc::clone(&file_contexts_arc_walker);
         let debug_log_thread_arc = Arc::clone(&debug_log_arc_walker);
         let timed_out_thread_flag = Arc::clone(&timed_out_flag_clone);
+        let cancel_thread_flag = cancel_flag_clone.clone();
         let files_processed_thread_count = Arc::clone(&files_processed_count_clone);
-        let extensions_thread_clone = extensions_clone.clone();
+        let scan_stats_thread = Arc::clone(&scan_stats_walker);
+        let skipped_entries_thread = Arc::clone(&skipped_entries_walker);
+        let already_visited_thread = Arc::clone(&already_visited_walker);
+        let visited_thread = Arc::clone(&visited_walker);
+        let extension_filter_thread = Arc::clone(&extension_filter);
+        let options_thread_clone = options_clone.clone();
+        let parse_cache_thread = parse_cache.clone();
 
         Box::new(move |entry_result| {
             if timeout_ms_clone > 0
@@ -115,8 +313,8 @@ pub fn perform_scan(
                 }
                 return ignore::WalkState::Quit;
             }
-            // If already timed out by another thread, quit.
-            if timed_out_thread_flag.load(Ordering::Relaxed) {
+            // If already timed out by another thread, or cancelled, quit.
+            if timed_out_thread_flag.load(Ordering::Relaxed) || cancel_thread_flag.is_cancelled() {
                 return ignore::WalkState::Quit;
             }
 
@@ -126,15 +324,24 @@ pub fn perform_scan(
                     if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
                         log.push(format!("[Scanner] Error walking directory entry: {}", err));
                     }
+                    collect_skipped_entries(&err, None, &mut skipped_entries_thread.lock().unwrap());
                     return ignore::WalkState::Continue; // Skip problematic entries.
                 }
             };
 
             let path = entry.path();
             if path.is_file() {
+                if already_visited_thread.contains(path) {
+                    return ignore::WalkState::Continue;
+                }
+                if track_visited {
+                    visited_thread.lock().unwrap().push(path.to_path_buf());
+                }
                 let current_processed_count =
                     files_processed_thread_count.fetch_add(1, Ordering::Relaxed) + 1; // +1 because fetch_add returns previous value.
-                let ext_str = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+                progress.file_discovered();
+                scan_stats_thread.lock().unwrap().files_walked += 1;
+                let ext_str = parsing::file_extension(path).unwrap_or("");
 
                 if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
                     log.push(format!(
@@ -143,40 +350,98 @@ pub fn perform_scan(
                     ));
                 }
 
-                if !extensions_thread_clone
-                    .iter()
-                    .any(|e| e.trim_start_matches('.') == ext_str)
-                {
+                if !extension_filter_thread.matches(path, ext_str) {
                     if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
                         log.push(format!("[Scanner] Skipping (extension mismatch): {:?}", path));
                     }
+                    scan_stats_thread.lock().unwrap().skipped.extension += 1;
                     return ignore::WalkState::Continue;
                 }
 
-                // File size check (1MB limit).
-                if entry.metadata().map_or(true, |m| m.len() > 1_000_000) {
+                // File size check (`ScanOptions::max_file_size_bytes`).
+                let size_bytes = entry.metadata().map_or(u64::MAX, |m| m.len());
+                if size_bytes > options_thread_clone.max_file_size_bytes {
+                    scan_stats_thread.lock().unwrap().skipped.too_large += 1;
                     if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
-                        log.push(format!("[Scanner] Skipping (large file >1MB): {:?}", path));
+                        log.push(format!(
+                            "[Scanner] Skipping (large file >{} bytes): {:?}",
+                            options_thread_clone.max_file_size_bytes, path
+                        ));
                     }
                     return ignore::WalkState::Continue;
                 }
                 // Note: `is_binary` check is handled within `parsing::parse_file`.
 
-                if let Some(context) = parsing::parse_file(path, compactness_level) {
-                    if !context.functions.is_empty() {
-                        file_contexts_thread_arc.lock().unwrap().push(context);
-                    } else {
-                        if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
-                            log.push(format!("[Scanner] No functions extracted from: {:?}", path));
+                let parsed = match &parse_cache_thread {
+                    Some(cache) => cache.parse_file(path, compactness_level, &options_thread_clone),
+                    None => parsing::parse_file(path, compactness_level, &options_thread_clone),
+                };
+                progress.file_processed();
+                {
+                    let mut guard = scan_stats_thread.lock().unwrap();
+                    let stats = &mut *guard;
+                    stats.bytes_read += size_bytes;
+                    for counts in [
+                        stats.extensions.entry(ext_str.to_string()).or_default(),
+                        stats.directories.entry(top_level_directory(root_path, path)).or_default(),
+                    ] {
+                        counts.files_matched += 1;
+                        counts.files_parsed += usize::from(parsed.is_ok());
+                    }
+                }
+
+                match parsed {
+                    Ok(mut context) => {
+                        if !options_thread_clone.include_nested {
+                            context.functions.retain(|function| function.depth == 0);
+                        }
+                        if let Some(wanted) = &options_thread_clone.annotation_filter {
+                            context.functions.retain(|function| {
+                                function.annotations.iter().any(|a| a.contains(wanted.as_str()))
+                            });
+                        }
+                        if let Some(uri_format) = options_thread_clone.uri_format {
+                            for function in &mut context.functions {
+                                function.uri = Some(utils::location_uri(
+                                    uri_format,
+                                    &context.path,
+                                    function.start_line,
+                                    function.end_line,
+                                ));
+                            }
+                        }
+                        if !context.functions.is_empty() || context.outline.as_ref().is_some_and(|o| !o.is_empty()) {
+                            let fits = fit_result_budget(
+                                &mut context,
+                                &mut scan_stats_thread.lock().unwrap(),
+                                options_thread_clone.max_result_bytes,
+                            );
+                            if fits {
+                                file_contexts_thread_arc.lock().unwrap().push(context);
+                            } else if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
+                                log.push(format!("[Scanner] Result limit reached, dropping: {:?}", path));
+                            }
+                        } else {
+                            scan_stats_thread.lock().unwrap().skipped.no_functions += 1;
+                            if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
+                                log.push(format!("[Scanner] No functions extracted from: {:?}", path));
+                            }
                         }
                     }
-                } else {
-                    // `parse_file` returns `None` if binary, unreadable, or no relevant content found.
-                    if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
-                        log.push(format!(
-                            "[Scanner] Skipping (failed to parse or no relevant content): {:?}",
-                            path
-                        ));
+                    Err(skip) => {
+                        let mut stats = scan_stats_thread.lock().unwrap();
+                        match skip {
+                            ParseSkip::Binary => stats.skipped.binary += 1,
+                            ParseSkip::Unreadable | ParseSkip::Undecodable => stats.skipped.parse_failure += 1,
+                            ParseSkip::NoFunctions => stats.skipped.no_functions += 1,
+                        }
+                        drop(stats);
+                        if let Some(log) = &mut *debug_log_thread_arc.lock().unwrap() {
+                            log.push(format!(
+                                "[Scanner] Skipping (failed to parse or no relevant content): {:?}",
+                                path
+                            ));
+                        }
                     }
                 }
             }
@@ -186,7 +451,7 @@ pub fn perform_scan(
 
     // Attempt to unwrap Arcs. This should succeed if the walker has finished.
     // Provide default empty Vecs on error to prevent panic, though this indicates an issue.
-    let final_file_contexts = Arc::try_unwrap(file_contexts_arc)
+    let mut final_file_contexts = Arc::try_unwrap(file_contexts_arc)
         .unwrap_or_else(|arc| {
             // This case should ideally not be reached if walker completes.
             // Log or handle error appropriately if Arc is still shared.
@@ -195,6 +460,55 @@ pub fn perform_scan(
         })
         .into_inner()
         .unwrap_or_default();
+    if options.sort_results {
+        sort_file_contexts(&mut final_file_contexts);
+    }
+
+    let final_files_processed_count = files_processed_count.load(Ordering::Relaxed);
+    progress.report(true);
+    let was_timed_out = timed_out_flag.load(Ordering::Relaxed);
+    let was_cancelled = cancel_flag.is_cancelled();
+    if was_cancelled {
+        if let Some(log) = &mut *debug_log_arc.lock().unwrap() {
+            log.push(format!(
+                "[Scanner] Cancelled. Processed approx. {} files before stopping.",
+                final_files_processed_count
+            ));
+        }
+    }
+
+    // A timed-out scan can be resumed without revisiting what this and earlier calls saw.
+    let continuation_token = was_timed_out.then(|| {
+        let mut files = Arc::try_unwrap(already_visited).unwrap_or_else(|arc| (*arc).clone());
+        files.extend(std::mem::take(&mut *visited.lock().unwrap()));
+        continuation::save(ScanContinuation {
+            files,
+            completed_roots: resumed.completed_roots,
+        })
+    });
+
+    let mut final_stats = std::mem::take(&mut *scan_stats.lock().unwrap());
+    let mut skipped = std::mem::take(&mut *skipped_entries.lock().unwrap());
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
+    final_stats.duration_seconds = start_time.elapsed().as_secs_f32();
+
+    // A timed-out or cancelled walk may simply not have reached the matching files yet.
+    if options.strict && !was_timed_out && !was_cancelled {
+        if final_stats.extensions.is_empty() {
+            errors.push(format!(
+                "No files matched extensions {:?} under {}",
+                extensions, root_path_str
+            ));
+        }
+        for (extension, counts) in &final_stats.extensions {
+            if counts.files_parsed == 0 {
+                errors.push(format!(
+                    "Query for '.{}' produced no captures in {} matched file(s)",
+                    extension, counts.files_matched
+                ));
+            }
+        }
+    }
 
     let final_debug_log = Arc::try_unwrap(debug_log_arc)
         .unwrap_or_else(|arc| {
@@ -204,13 +518,381 @@ pub fn perform_scan(
         .into_inner()
         .unwrap_or_default();
 
-    let final_files_processed_count = files_processed_count.load(Ordering::Relaxed);
-    let was_timed_out = timed_out_flag.load(Ordering::Relaxed);
-
     ScanResult {
         file_contexts: final_file_contexts,
         debug_log: final_debug_log,
         timed_out_internally: was_timed_out,
         files_processed_before_timeout: final_files_processed_count,
+        cancelled: was_cancelled,
+        stats: final_stats,
+        continuation_token,
+        skipped,
+        errors,
+    }
+}
+
+/// Rough size of the text a file context holds, for `ScanOptions::max_result_bytes`.
+fn approximate_size(context: &FileContext) -> u64 {
+    let functions: usize = context
+        .functions
+        .iter()
+        .map(|f| {
+            f.qualified_name.len()
+                + f.body.as_ref().map_or(0, String::len)
+                + f.comment.as_ref().map_or(0, String::len)
+                + f.calls.iter().map(String::len).sum::<usize>()
+        })
+        .sum();
+    let imports: usize = context.imports.iter().map(String::len).sum();
+    (context.path.len() + context.description.len() + imports + functions) as u64
+}
+
+/// Charges `context` to the result budget in `stats`, first reducing its functions to
+/// their first line (usually the signature) if the full text does not fit. Returns
+/// `false` if even that does not fit and the file should be left out.
+fn fit_result_budget(context: &mut FileContext, stats: &mut ScanStats, limit: Option<u64>) -> bool {
+    let size = approximate_size(context);
+    let Some(limit) = limit else {
+        stats.result_bytes += size;
+        return true;
+    };
+    if stats.result_bytes + size <= limit {
+        stats.result_bytes += size;
+        return true;
+    }
+    let mut truncated = 0;
+    for function in &mut context.functions {
+        let first_line = function
+            .body
+            .as_deref()
+            .and_then(|body| body.lines().next())
+            .map(|line| line.trim_end().to_string());
+        if function.comment.is_some() || first_line != function.body {
+            function.body = first_line;
+            function.comment = None;
+            truncated += 1;
+        }
+    }
+    let size = approximate_size(context);
+    if stats.result_bytes + size > limit {
+        stats.skipped.over_result_limit += 1;
+        return false;
+    }
+    stats.result_bytes += size;
+    stats.bodies_truncated += truncated;
+    true
+}
+
+/// Records the paths behind a walk error. `ignore` wraps the underlying error in path,
+/// depth, and line context, and reports several at once as `Partial`.
+fn collect_skipped_entries(err: &ignore::Error, path: Option<&Path>, skipped: &mut Vec<SkippedEntry>) {
+    match err {
+        ignore::Error::Partial(errs) => {
+            for err in errs {
+                collect_skipped_entries(err, path, skipped);
+            }
+        }
+        ignore::Error::WithPath { path, err } => collect_skipped_entries(err, Some(path), skipped),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            collect_skipped_entries(err, path, skipped)
+        }
+        ignore::Error::Loop { child, .. } => skipped.push(SkippedEntry {
+            path: child.to_string_lossy().into_owned(),
+            reason: SkipReason::SymlinkLoop,
+            message: err.to_string(),
+        }),
+        ignore::Error::Io(io_err) => skipped.push(SkippedEntry {
+            path: path.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+            reason: match io_err.kind() {
+                std::io::ErrorKind::PermissionDenied => SkipReason::PermissionDenied,
+                _ => SkipReason::Unreadable,
+            },
+            message: io_err.to_string(),
+        }),
+        // Ignore-file and glob problems, which do not keep the walk out of a path.
+        _ => {}
+    }
+}
+
+/// Orders files by path and their functions by start line (then qualified name), so
+/// results do not depend on which walker thread reached a file first.
+pub fn sort_file_contexts(file_contexts: &mut [FileContext]) {
+    file_contexts.sort_by(|a, b| a.path.cmp(&b.path));
+    for context in file_contexts {
+        context
+            .functions
+            .sort_by(|a, b| (a.start_line, &a.qualified_name).cmp(&(b.start_line, &b.qualified_name)));
+    }
+}
+
+/// Groups `path` under its first directory below `root` for `ScanStats::directories`;
+/// files directly in `root` use `.`, and files outside it their parent directory.
+fn top_level_directory(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(relative) => {
+            let mut components = relative.components();
+            match (components.next(), components.next()) {
+                (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+                _ => ".".to_string(),
+            }
+        }
+        Err(_) => path.parent().unwrap_or(path).to_string_lossy().into_owned(),
+    }
+}
+
+/// Scans several roots as one workspace (e.g. the top-level folders of a monorepo) and
+/// merges their results. The timeout covers all roots together, and a file reached
+/// through more than one root is reported once. Progress counts accumulate across roots.
+/// A continuation token of a timed-out multi-root scan also skips the roots that were
+/// walked completely.
+pub fn perform_scan_roots(
+    roots: &[String],
+    extensions: Vec<String>,
+    compactness_level: u8,
+    timeout_milliseconds: u32,
+    options: &ScanOptions,
+    progress: &ProgressReporter,
+    debug: bool,
+) -> ScanResult {
+    if let [root] = roots {
+        return perform_scan_with_progress(root, extensions, compactness_level, timeout_milliseconds, options, progress, debug);
+    }
+
+    let start_time = Instant::now();
+    let mut merged = ScanResult {
+        file_contexts: Vec::new(),
+        debug_log: if debug { Some(Vec::new()) } else { None },
+        timed_out_internally: false,
+        files_processed_before_timeout: 0,
+        cancelled: false,
+        stats: ScanStats::default(),
+        continuation_token: None,
+        skipped: Vec::new(),
+        errors: Vec::new(),
+    };
+    let resumed = match options.continuation_token.map(continuation::load).transpose() {
+        Ok(resumed) => resumed.unwrap_or_default(),
+        Err(e) => {
+            merged.errors.push(e);
+            return merged;
+        }
+    };
+    let mut completed_roots = resumed.completed_roots;
+    // Visited files of the root that timed out, if one did.
+    let mut timed_out_files = None;
+    let mut seen = HashSet::new();
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token);
+    for root in roots {
+        let root_key = utils::canonical_or_original(Path::new(root));
+        if completed_roots.contains(&root_key) {
+            continue;
+        }
+        if cancel_flag.is_cancelled() {
+            merged.cancelled = true;
+            break;
+        }
+        // `0` means no timeout, so an exhausted budget has to stop the loop here.
+        let remaining_ms = if timeout_milliseconds == 0 {
+            0
+        } else {
+            match timeout_milliseconds.checked_sub(start_time.elapsed().as_millis() as u32) {
+                Some(remaining) if remaining > 0 => remaining,
+                _ => {
+                    merged.timed_out_internally = true;
+                    break;
+                }
+            }
+        };
+        // Roots share one result budget.
+        let root_options = ScanOptions {
+            max_result_bytes: options
+                .max_result_bytes
+                .map(|limit| limit.saturating_sub(merged.stats.result_bytes)),
+            ..options.clone()
+        };
+        let result = perform_scan_with_progress(
+            root,
+            extensions.clone(),
+            compactness_level,
+            remaining_ms,
+            &root_options,
+            progress,
+            debug,
+        );
+        merged.timed_out_internally |= result.timed_out_internally;
+        merged.cancelled |= result.cancelled;
+        match result.continuation_token {
+            Some(token) => timed_out_files = continuation::load(token).ok().map(|c| c.files),
+            None if !result.cancelled && result.errors.is_empty() => {
+                completed_roots.insert(root_key);
+            }
+            None => {}
+        }
+        merged.stats.merge(result.stats);
+        merged.files_processed_before_timeout += result.files_processed_before_timeout;
+        merged.errors.extend(result.errors.into_iter().map(|e| format!("{}: {}", root, e)));
+        merged.skipped.extend(result.skipped);
+        if let (Some(log), Some(root_log)) = (&mut merged.debug_log, result.debug_log) {
+            log.extend(root_log);
+        }
+        merged.file_contexts.extend(
+            result
+                .file_contexts
+                .into_iter()
+                .filter(|fc| seen.insert(utils::canonical_or_original(Path::new(&fc.path)))),
+        );
+    }
+    if merged.timed_out_internally {
+        merged.continuation_token = Some(continuation::save(ScanContinuation {
+            files: timed_out_files.unwrap_or(resumed.files),
+            completed_roots,
+        }));
+    }
+    if options.sort_results {
+        sort_file_contexts(&mut merged.file_contexts);
+    }
+    merged.stats.duration_seconds = start_time.elapsed().as_secs_f32();
+    merged
+}
+
+/// The walk of `root_path` that scans share: ignore files and the walk options of
+/// `options`, and filters for the crate's own cache, `.git`, vendored directories (unless
+/// disabled), and the include/exclude globs of `path_filter`.
+fn walk_builder(root_path: &Path, options: &ScanOptions, path_filter: Override) -> WalkBuilder {
+    let skip_vendor_dirs = options.skip_vendor_dirs;
+    let mut walker_builder = WalkBuilder::new(root_path);
+    walker_builder
+        .hidden(!options.include_hidden)
+        .git_ignore(options.respect_gitignore)
+        .git_global(options.respect_gitignore)
+        .git_exclude(options.respect_gitignore)
+        .max_depth(options.max_depth)
+        .follow_links(options.follow_links)
+        .add_custom_ignore_filename(config::IGNORE_FILE)
+        .filter_entry(move |entry| {
+            !config::is_own_cache_path(entry.path())
+                && !config::is_git_dir(entry.path())
+                && (!skip_vendor_dirs || !config::is_vendor_dir(entry))
+                && !config::is_filtered_out(&path_filter, entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+        })
+        .threads(pool::threads_for(options.num_threads));
+    walker_builder
+}
+
+/// Collects `TODO`/`FIXME`/`HACK`/`XXX` comments from every file under `root_path_str`
+/// matching `extensions`, using the same walk rules and `options` as `perform_scan`
+/// (ignore files, globs, size limit, cancellation token) within the timeout.
+///
+/// Results are sorted by file and line.
+pub fn scan_todos(
+    root_path_str: &str,
+    extensions: &[String],
+    timeout_milliseconds: u32,
+    options: &ScanOptions,
+    debug: bool,
+) -> TodoScanResult {
+    let start_time = Instant::now();
+    let mut debug_log: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+
+    let root_path = Path::new(root_path_str);
+    if !root_path.is_dir() {
+        return TodoScanResult {
+            error: Some(format!("Root path is not a directory: {}", root_path_str)),
+            debug_log,
+            ..Default::default()
+        };
+    }
+    let (extension_filter, path_filter) = match config::ExtensionFilter::new(extensions)
+        .and_then(|filter| Ok((filter, config::path_filter(root_path, &options.include, &options.exclude)?)))
+    {
+        Ok(filters) => filters,
+        Err(e) => {
+            return TodoScanResult {
+                error: Some(e),
+                debug_log,
+                ..Default::default()
+            };
+        }
+    };
+    if let Some(log) = &mut debug_log {
+        log.push(format!(
+            "[TodoScan] Root: {}, Extensions: {:?}, Timeout (ms): {}",
+            root_path_str, extensions, timeout_milliseconds
+        ));
+    }
+
+    let walker = walk_builder(root_path, options, path_filter).build_parallel();
+
+    let todos = Mutex::new(Vec::<TodoItem>::new());
+    let debug_log = Mutex::new(debug_log);
+    let timed_out = AtomicBool::new(false);
+    let cancel_flag = CancelFlag::for_token(options.cancellation_token);
+    let files_processed = AtomicUsize::new(0);
+
+    walker.run(|| {
+        Box::new(|entry_result| {
+            if timeout_milliseconds > 0
+                && start_time.elapsed().as_millis() as u32 > timeout_milliseconds
+            {
+                if !timed_out.swap(true, Ordering::Relaxed) {
+                    if let Some(log) = &mut *debug_log.lock().unwrap() {
+                        log.push("[TodoScan] Timeout reached during walk.".to_string());
+                    }
+                }
+                return ignore::WalkState::Quit;
+            }
+            if timed_out.load(Ordering::Relaxed) || cancel_flag.is_cancelled() {
+                return ignore::WalkState::Quit;
+            }
+
+            let entry = match entry_result {
+                Ok(e) => e,
+                Err(err) => {
+                    if let Some(log) = &mut *debug_log.lock().unwrap() {
+                        log.push(format!("[TodoScan] Error walking directory entry: {}", err));
+                    }
+                    return ignore::WalkState::Continue;
+                }
+            };
+            let path = entry.path();
+            if !path.is_file() {
+                return ignore::WalkState::Continue;
+            }
+            let ext_str = parsing::file_extension(path).unwrap_or("");
+            if !extension_filter.matches(path, ext_str) {
+                return ignore::WalkState::Continue;
+            }
+            if entry.metadata().map_or(true, |m| m.len() > options.max_file_size_bytes) {
+                if let Some(log) = &mut *debug_log.lock().unwrap() {
+                    log.push(format!(
+                        "[TodoScan] Skipping (large file >{} bytes): {:?}",
+                        options.max_file_size_bytes, path
+                    ));
+                }
+                return ignore::WalkState::Continue;
+            }
+
+            files_processed.fetch_add(1, Ordering::Relaxed);
+            match parsing::extract_todos(path) {
+                Some(found) => todos.lock().unwrap().extend(found),
+                None => {
+                    if let Some(log) = &mut *debug_log.lock().unwrap() {
+                        log.push(format!("[TodoScan] Skipping (failed to parse): {:?}", path));
+                    }
+                }
+            }
+            ignore::WalkState::Continue
+        })
+    });
+
+    let mut todos = todos.into_inner().unwrap_or_default();
+    todos.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    TodoScanResult {
+        todos,
+        debug_log: debug_log.into_inner().unwrap_or_default(),
+        timed_out_internally: timed_out.load(Ordering::Relaxed),
+        files_processed_before_timeout: files_processed.load(Ordering::Relaxed),
+        cancelled: cancel_flag.is_cancelled(),
+        error: None,
     }
 }
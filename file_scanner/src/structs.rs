@@ -1,11 +1,62 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParameterInfo {
+    pub name: String,
+    /// Declared type of the parameter, when the language/source provides one.
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FunctionInfo {
     pub name: String,
+    /// Name of the innermost enclosing class/impl/trait/module/namespace, if any.
+    pub parent: Option<String>,
+    /// `name` prefixed with every enclosing container and function (e.g.
+    /// `UserRepository.save`, `cache::FileCache::save`, `outer.inner`).
+    pub qualified_name: String,
+    /// Innermost enclosing function, for nested/local functions and named closures.
+    pub parent_function: Option<String>,
+    /// Number of enclosing function scopes; `0` for top-level functions and methods.
+    pub depth: usize,
+    /// Decorators and attributes applied to the function (`@app.route("/")`,
+    /// `#[test]`, `[HttpGet]`), in source order.
+    pub annotations: Vec<String>,
+    /// Declared `async` (`async def`, `async fn`, `async` methods, lambdas, and arrow functions).
+    pub is_async: bool,
+    /// 1-based line on which the function definition starts.
+    pub start_line: usize,
+    /// 1-based line on which the function definition ends (inclusive).
+    pub end_line: usize,
     pub body: Option<String>,
     pub comment: Option<String>,
+    /// Structured parameter list (populated for compactness >= 1).
+    pub parameters: Vec<ParameterInfo>,
+    /// Declared return type, if any (populated for compactness >= 1).
+    pub return_type: Option<String>,
+    /// Size and complexity metrics computed from the function's syntax tree.
+    pub metrics: FunctionMetrics,
+    /// Names of the functions this one calls, when `ScanOptions::calls` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<String>,
+    /// Editor-friendly location URI, when a `uri_format` is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FunctionMetrics {
+    /// Lines containing code, excluding blank and comment-only lines.
+    pub lines_of_code: usize,
+    /// Deepest nesting of control-flow blocks (`if`, loops, `match`, `try`, ...).
+    pub max_nesting_depth: usize,
+    /// Number of decision points: conditionals, loop heads, match arms, catch
+    /// clauses and short-circuit boolean operators.
+    pub branch_count: usize,
+    /// McCabe cyclomatic complexity (`branch_count + 1`).
+    pub cyclomatic_complexity: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,7 +64,231 @@ pub struct FileContext {
     pub path: String,
     // TODO: Evaluate if FileContext::description is still necessary or can be derived from other sources.
     pub description: String,
+    /// Language the file was parsed as (`"python"`, `"rust"`, `"csharp"`, `"typescript"`).
+    pub language: String,
+    /// Text encoding: `"utf-8"` or `"utf-8-bom"`.
+    pub encoding: String,
+    pub line_count: usize,
+    pub size_bytes: u64,
+    /// Hex SHA-256 of the file's bytes as scanned, so later stages can tell whether it
+    /// changed without reading it again.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content_hash: String,
+    /// The comment or docstring the file opens with, without comment markers, when
+    /// `outline` is requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leading_comment: Option<String>,
+    /// Import/use/using statements in source order, whitespace-collapsed to one line each.
+    pub imports: Vec<String>,
     pub functions: Vec<FunctionInfo>,
+    /// Hierarchical symbol tree (modules → classes → methods), when `outline` is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outline: Option<Vec<OutlineSymbol>>,
+    /// Syntax errors in the file. Functions are still extracted from the rest of the
+    /// tree, so a non-empty list means the file was only partially parsed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parse_errors: Vec<ParseError>,
+}
+
+/// A syntax error tree-sitter recovered from while parsing a file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParseError {
+    /// 1-based line on which the error starts.
+    pub line: usize,
+    pub message: String,
+}
+
+/// A node in a file's document outline, similar to an LSP `DocumentSymbol`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutlineSymbol {
+    pub name: String,
+    /// `"class"`, `"struct"`, `"enum"`, `"interface"`, `"trait"`, `"impl"`, `"module"`,
+    /// `"namespace"`, `"function"`, or `"method"`.
+    pub kind: String,
+    /// 1-based line on which the symbol starts.
+    pub start_line: usize,
+    /// 1-based line on which the symbol ends (inclusive).
+    pub end_line: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub children: Vec<OutlineSymbol>,
+}
+
+/// Result of `build_call_graph`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CallGraphResult {
+    /// Caller → functions it calls, keyed by `<relative path>:<qualified name>`.
+    /// Every scanned function has an entry, even if it calls nothing in the project.
+    pub adjacency: BTreeMap<String, Vec<String>>,
+    pub functions_analyzed: usize,
+    pub duration_seconds: f32,
+    pub timed_out_internally: bool,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<Vec<String>>,
+}
+
+/// A `TODO`/`FIXME`/`HACK`/`XXX` marker found in a source comment.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TodoItem {
+    pub file: String,
+    /// 1-based line of the marker.
+    pub line: usize,
+    /// The marker itself, e.g. `"FIXME"`.
+    pub tag: String,
+    /// Text following the marker on the same line.
+    pub text: String,
+    /// Name given as `TODO(name)` or `TODO @name`, if any.
+    pub author: Option<String>,
+    /// Qualified name of the innermost function containing the comment.
+    pub function: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TodoScanResult {
+    pub todos: Vec<TodoItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<Vec<String>>,
+    pub timed_out_internally: bool,
+    pub files_processed_before_timeout: usize,
+    /// True when the scan was stopped through its cancellation token.
+    #[serde(default)]
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+/// A function symbol tracked by `sync_workspace_symbols`, similar to an LSP
+/// `SymbolInformation`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkspaceSymbol {
+    pub name: String,
+    pub qualified_name: String,
+    /// Innermost enclosing class/impl/trait/module/namespace, if any.
+    pub container_name: Option<String>,
+    /// Path relative to the synced root, with `/` separators.
+    pub file: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A declaration found by `find_definition`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    /// `name` prefixed with its enclosing declarations, joined the way the language
+    /// does (`UserRepository.save`, `cache::FileCache::save`).
+    pub qualified_name: String,
+    /// Outline kind: `"function"`, `"method"`, `"class"`, `"struct"`, `"enum"`,
+    /// `"interface"`, `"trait"`, `"module"`, or `"namespace"`.
+    pub kind: String,
+    /// Path relative to the root, with `/` separators.
+    pub file: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// Response of `find_definition`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DefinitionResult {
+    pub definitions: Vec<Definition>,
+    pub files_scanned: usize,
+    pub duration_seconds: f32,
+    pub timed_out_internally: bool,
+    pub error: Option<String>,
+}
+
+/// How a `find_references` occurrence uses the symbol.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceKind {
+    /// The name in one of the symbol's declarations.
+    Definition,
+    /// A call: the name directly followed by `(`, or by `!(` for a Rust macro.
+    Call,
+    /// Any other use in code, such as an import, a type annotation, or passing a
+    /// function as a value.
+    Reference,
+    /// Text in a comment or string literal, which may not mean the symbol at all.
+    CommentOrString,
+}
+
+/// An occurrence of a symbol's name found by `find_references`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    /// Path relative to the root, with `/` separators.
+    pub file: String,
+    /// 1-based line.
+    pub line: usize,
+    /// 1-based column, in characters.
+    pub column: usize,
+    /// The line, trimmed.
+    pub text: String,
+    /// Innermost function or class enclosing the occurrence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+/// Response of `find_references`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ReferenceResult {
+    /// Occurrences sorted by file and position.
+    pub references: Vec<Reference>,
+    /// The symbol's declarations, as `find_definition` returns them.
+    pub definitions: Vec<Definition>,
+    pub files_searched: usize,
+    pub duration_seconds: f32,
+    pub timed_out_internally: bool,
+    pub error: Option<String>,
+}
+
+/// What a `fuzzy_find` match refers to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FuzzyMatchKind {
+    File,
+    Symbol,
+}
+
+/// A file or function whose name contains the `fuzzy_find` query as a subsequence.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FuzzyMatch {
+    pub kind: FuzzyMatchKind,
+    /// Path relative to the root, with `/` separators.
+    pub path: String,
+    /// Qualified function name, for symbols.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 1-based line the function starts on, for symbols.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Higher is better; only comparable within one query.
+    pub score: i64,
+    /// Character indices of the matched query characters in `name` for symbols, or in
+    /// `path` for files, for highlighting.
+    pub positions: Vec<usize>,
+}
+
+/// Response of `fuzzy_find`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FuzzyFindResult {
+    pub matches: Vec<FuzzyMatch>,
+    /// Files and functions the query was scored against.
+    pub candidates: usize,
+    pub duration_seconds: f32,
+    pub error: Option<String>,
+}
+
+/// Symbols added and removed since the previous `sync_workspace_symbols` call for a root,
+/// or by one batch of changes of a `subscribe_workspace_symbols` subscription.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SymbolSyncResult {
+    pub added: Vec<WorkspaceSymbol>,
+    pub removed: Vec<WorkspaceSymbol>,
+    pub files_synced: usize,
+    pub timed_out_internally: bool,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,24 +298,215 @@ pub struct ScanResult {
     pub debug_log: Option<Vec<String>>,
     pub timed_out_internally: bool,
     pub files_processed_before_timeout: usize,
+    /// True when the scan was stopped through its cancellation token.
+    #[serde(default)]
+    pub cancelled: bool,
+    /// What the walk found and why files were left out.
+    #[serde(default)]
+    pub stats: ScanStats,
+    /// Set when the scan timed out: pass it as `ScanOptions::continuation_token` to scan
+    /// only the files this and earlier calls did not reach.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<u64>,
+    /// Misconfigurations reported in strict mode (see `ScanOptions::strict`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Files and directories the walk could not enter, reported whether or not debug
+    /// logging is on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// Why the walk could not enter a path.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// A followed symlink points back to one of its own ancestors.
+    SymlinkLoop,
+    PermissionDenied,
+    /// Any other I/O error, such as a file deleted during the walk.
+    Unreadable,
+}
+
+/// A path the walk skipped because of an error.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkippedEntry {
+    pub path: String,
+    pub reason: SkipReason,
+    /// The underlying error, for display.
+    pub message: String,
+}
+
+/// Files with a requested extension, and how many of them produced functions.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct FileCounts {
+    pub files_matched: usize,
+    /// Files the query extracted at least one function from.
+    pub files_parsed: usize,
+}
+
+/// Files a scan walked past without returning them, by reason.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SkippedFiles {
+    /// Extension not among the requested ones.
+    pub extension: usize,
+    /// Larger than `max_file_size_bytes`.
+    pub too_large: usize,
+    /// Contains NUL bytes.
+    pub binary: usize,
+    /// Unreadable, or not valid UTF-8.
+    pub parse_failure: usize,
+    /// Parsed, but no functions were extracted or all were filtered out (e.g. by
+    /// `annotation_filter`).
+    pub no_functions: usize,
+    /// Parsed, but left out because not even its signatures fit `max_result_bytes`.
+    #[serde(default)]
+    pub over_result_limit: usize,
+}
+
+/// Summary of a scan, for diagnosing why it returned fewer files than expected.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ScanStats {
+    /// Files the walk visited, after ignore rules and globs.
+    pub files_walked: usize,
+    pub skipped: SkippedFiles,
+    /// Total size of the files handed to the parser, including ones served from the
+    /// parse cache.
+    pub bytes_read: u64,
+    /// Approximate size of the returned `file_contexts` (see `ScanOptions::max_result_bytes`).
+    #[serde(default)]
+    pub result_bytes: u64,
+    /// Functions reduced to their first line to stay within `max_result_bytes`.
+    #[serde(default)]
+    pub bodies_truncated: usize,
+    pub duration_seconds: f32,
+    /// Counts per extension (without the dot).
+    pub extensions: BTreeMap<String, FileCounts>,
+    /// Counts per top-level directory below the root (`.` for files in the root itself).
+    pub directories: BTreeMap<String, FileCounts>,
+}
+
+impl ScanStats {
+    /// Adds the counts of `other`, e.g. the scan of another root.
+    pub fn merge(&mut self, other: ScanStats) {
+        self.files_walked += other.files_walked;
+        self.skipped.extension += other.skipped.extension;
+        self.skipped.too_large += other.skipped.too_large;
+        self.skipped.binary += other.skipped.binary;
+        self.skipped.parse_failure += other.skipped.parse_failure;
+        self.skipped.no_functions += other.skipped.no_functions;
+        self.skipped.over_result_limit += other.skipped.over_result_limit;
+        self.bytes_read += other.bytes_read;
+        self.result_bytes += other.result_bytes;
+        self.bodies_truncated += other.bodies_truncated;
+        self.duration_seconds += other.duration_seconds;
+        for (counts, other_counts) in [
+            (&mut self.extensions, other.extensions),
+            (&mut self.directories, other.directories),
+        ] {
+            for (key, other) in other_counts {
+                let entry = counts.entry(key).or_default();
+                entry.files_matched += other.files_matched;
+                entry.files_parsed += other.files_parsed;
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchMatch {
     pub line_number: usize,
-    pub context: String,
+    /// Last line the match touches; differs from `line_number` only in multiline mode.
+    pub end_line_number: usize,
+    /// 1-based character column on `line_number` where the match starts.
+    pub column: usize,
+    /// Where the match starts within `line_number`.
+    pub match_start: LineOffset,
+    /// Where the match ends (exclusive) within `end_line_number`. A line break the match
+    /// ends with is not counted, so this never points past the end of the line.
+    pub match_end: LineOffset,
+    #[serde(flatten)]
+    pub context: MatchContext,
+    /// Capture groups of the first match on the line, in regex mode (see
+    /// `SearchOptions::use_regex`), keyed by name or by index.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub captures: BTreeMap<String, String>,
+    /// Innermost function or class enclosing the match, when `SearchOptions::symbol_context`
+    /// is set and the match is inside one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// The pattern this is a match of, in a multi-pattern search (see
+    /// `SearchOptions::patterns`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Editor-friendly location URI, when a `uri_format` is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+}
+
+/// The lines around a search match, in the shape `SearchOptions::output_version` selects.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum MatchContext {
+    /// Version 2: the matched line (several, joined by `\n`, for a multiline match) and
+    /// the unmodified lines around it.
+    Lines {
+        context_before: Vec<String>,
+        line: String,
+        context_after: Vec<String>,
+    },
+    /// Version 1: all lines joined by `\n`, matched lines prefixed with `>> ` and the
+    /// others with three spaces.
+    Joined { context: String },
+}
+
+/// A position within a line, counted from its start.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct LineOffset {
+    /// In UTF-8 bytes.
+    pub byte: usize,
+    /// In Unicode scalar values (Rust `char`s).
+    pub char: usize,
+}
+
+impl LineOffset {
+    /// The offset of byte `byte` in `line`, which must be on a character boundary.
+    pub fn in_line(line: &str, byte: usize) -> LineOffset {
+        LineOffset {
+            byte,
+            char: line[..byte].chars().count(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileSearchResult {
     pub path: String,
+    /// Encoding the file was decoded from, when it is not UTF-8 (see
+    /// `utils::decode_text`). Match offsets refer to the decoded text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
     pub matches: Vec<SearchMatch>,
+    /// Relevance of the file, set when the search was `ranked` (see
+    /// `ranking::relevance`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+fn default_output_version() -> u32 {
+    1
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SearchServiceResult {
+    /// Shape of the matches (see `SearchOptions::output_version`).
+    #[serde(default = "default_output_version")]
+    pub output_version: u32,
     pub results: Vec<FileSearchResult>,
     pub stats: SearchStats,
+    /// Invalid input, such as a search regex that does not compile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub debug_log: Option<Vec<String>>,
 }
@@ -50,14 +516,63 @@ pub struct SearchStats {
     pub files_scanned: usize,
     pub total_matches: usize,
     pub timed_out: bool,
+    /// True when the search was stopped through its cancellation token.
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Wall-clock duration of the search.
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    /// Files with a requested extension that were skipped as binary.
+    #[serde(default)]
+    pub files_skipped_binary: usize,
+    /// Files with a requested extension that were skipped for exceeding
+    /// `SearchOptions::max_file_size_bytes`.
+    #[serde(default)]
+    pub files_skipped_large: usize,
+    /// Bytes read from (or memory-mapped for) the files that were searched or found to be
+    /// binary. Compared with `elapsed_ms`, it shows whether a slow search is bound by IO.
+    #[serde(default)]
+    pub bytes_scanned: u64,
+    /// Matches of each pattern, in a multi-pattern search.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub matches_per_pattern: BTreeMap<String, usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ConceptSearchResultItem {
     pub file: String,
     pub function: String,
+    /// Function name qualified with its enclosing containers.
+    pub qualified_name: String,
+    /// Relevance to the query, from 0 (unrelated) to 1. With embeddings this is the
+    /// cosine similarity of function and query mapped from -1..1 onto 0..1, so it is
+    /// comparable across queries; with BM25 it is the score relative to the best
+    /// match, which always scores 1; with the hybrid backend it is the fused score
+    /// relative to a function ranked first by both.
     pub similarity: f32,
+    /// 1-based line on which the function starts.
+    #[serde(default)]
+    pub start_line: usize,
+    /// 1-based line on which the function ends (inclusive).
+    #[serde(default)]
+    pub end_line: usize,
+    /// First line of the function, which holds its signature, for a compact preview.
+    /// Absent for file summaries (`Granularity::File`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
     pub body: Option<String>, // Added to include the function body
+    /// True when `body` was cut to `max_body_chars` and ends with `…`.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Lines preceding the function, when `context_lines` is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_before: Option<String>,
+    /// Lines following the function, when `context_lines` is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_after: Option<String>,
+    /// Editor-friendly location URI, when a `uri_format` is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -69,14 +584,416 @@ pub struct ConceptSearchServiceResult {
     pub debug_log: Option<Vec<String>>,
 }
 
+/// What a scan or concept search is doing when it reports progress.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressPhase {
+    /// Walking the tree and parsing matching files.
+    #[default]
+    Scanning,
+    /// Embedding functions that are not cached yet, and scoring them.
+    Embedding,
+    /// Re-scoring the best candidates with the `rerank_model`.
+    Reranking,
+}
+
+/// Progress of a scan, reported through `scan_and_parse_streaming` (and as part of
+/// `ConceptSearchProgress`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct ScanProgress {
+    pub phase: ProgressPhase,
+    /// Files found by the walk so far, including ones with other extensions.
+    pub files_discovered: usize,
+    /// Files with a requested extension that were parsed so far.
+    pub files_processed: usize,
+}
+
+/// A partial concept search ranking reported while the corpus is still being scanned
+/// or embedded.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConceptSearchProgress {
+    /// Phase and scan counts; the scan counts stay final once embedding starts.
+    #[serde(flatten)]
+    pub scan: ScanProgress,
+    /// Current top-N over the functions scored so far.
+    pub results: Vec<ConceptSearchResultItem>,
+    pub functions_scored: usize,
+    /// Functions found by the scan; scoring is complete once `functions_scored` reaches it.
+    pub functions_total: usize,
+}
+
+/// One entry in a root's audit log (see `audit::record`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch when the operation finished.
+    pub timestamp_ms: u64,
+    /// FFI entry point, e.g. `"concept_search"`.
+    pub operation: String,
+    /// SHA-256 over the operation's inputs (query, extensions, options JSON).
+    pub inputs_hash: String,
+    pub duration_seconds: f32,
+    /// Files with functions for scans, matches for text search, ranked results for concept search.
+    pub result_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response of `read_audit_log`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AuditLogResult {
+    pub records: Vec<AuditRecord>,
+    pub error: Option<String>,
+}
+
+/// Outcome of `write_text_file`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct WriteFileResult {
+    pub bytes_written: usize,
+    pub error: Option<String>,
+}
+
+/// Outcome of `warm_cache`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct WarmCacheResult {
+    /// Files that were parsed and now have up-to-date cached embeddings.
+    pub files_warmed: usize,
+    /// Functions whose embeddings were already cached and still valid.
+    pub functions_cached: usize,
+    /// Functions embedded by this call.
+    pub functions_embedded: usize,
+    /// Requested paths that could not be parsed (missing, binary, unsupported, or no functions).
+    pub skipped: Vec<String>,
+    pub duration_seconds: f32,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<Vec<String>>,
+}
+
+/// Outcome of `compact_embedding_cache`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CacheCompactionResult {
+    /// Cache entries whose file still exists.
+    pub entries_kept: usize,
+    /// Entries of deleted or renamed files that were removed.
+    pub entries_removed: usize,
+    pub duration_seconds: f32,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<Vec<String>>,
+}
+
+/// Outcome of `clear_concept_cache`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CacheClearResult {
+    /// Cache entries removed, including approximate-search indexes.
+    pub entries_removed: usize,
+    pub error: Option<String>,
+}
+
+/// Contents of a root's embedding cache, reported by `concept_cache_stats`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ConceptCacheStats {
+    /// Entries of every kind in the cache.
+    pub entries: usize,
+    /// Size of the cache database on disk.
+    pub size_on_disk_bytes: u64,
+    /// Cached embeddings per model and granularity.
+    pub models: Vec<ConceptCacheModelStats>,
+    /// Models with an approximate nearest-neighbour index.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ann_indexes: Vec<String>,
+    /// Entries from an older cache schema or otherwise unreadable; they are re-embedded
+    /// when their file is next searched.
+    pub stale_entries: usize,
+    pub error: Option<String>,
+}
+
+/// Cached embeddings of one model at one granularity.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConceptCacheModelStats {
+    pub model: EmbeddingModelTag,
+    pub granularity: Granularity,
+    pub files: usize,
+    pub functions: usize,
+    pub dimension: usize,
+    /// When the most recently written entry was written, in milliseconds since the Unix epoch.
+    pub last_updated_ms: u64,
+}
+
+/// Outcome of `build_concept_index`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ConceptIndexResult {
+    /// Files whose functions are in the index.
+    pub files_indexed: usize,
+    /// Functions `query_concept_index` will rank.
+    pub functions_indexed: usize,
+    /// Functions embedded by this call; the rest came from the embedding cache.
+    pub functions_embedded: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub models_used: Vec<EmbeddingModelTag>,
+    pub duration_seconds: f32,
+    /// True when the timeout passed during the scan or the embedding; the index then
+    /// holds only the functions embedded in time.
+    pub timed_out_internally: bool,
+    /// True when the build was stopped through its cancellation token; no index is kept.
+    pub cancelled: bool,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<Vec<String>>,
+}
+
+/// Outcome of `rapid_init`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RuntimeInitResult {
+    /// True when an earlier call had already fixed the settings; this call changed nothing.
+    pub already_initialized: bool,
+    /// Workers of the shared thread pool.
+    pub num_threads: usize,
+    /// Directory models are downloaded to and loaded from.
+    pub model_cache_dir: String,
+    /// Models from `preload_models` that are loaded and ready.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub models_loaded: Vec<EmbeddingModelTag>,
+    pub error: Option<String>,
+}
+
+/// Outcome of `rapid_shutdown`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RuntimeShutdownResult {
+    pub watches_stopped: usize,
+    pub concept_indexes_released: usize,
+    /// Embedding and parse cache databases flushed and closed.
+    pub databases_closed: usize,
+    pub error: Option<String>,
+}
+
+/// Outcome of `watch_start`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct WatchStartResult {
+    /// The watched root, canonicalized.
+    pub root: String,
+    pub extensions: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// How concept search results were scored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoringBackend {
+    /// Cosine similarity between query and function embeddings.
+    #[default]
+    Embedding,
+    /// BM25 keyword scoring over function names, documentation, and bodies.
+    Bm25,
+    /// Embedding and BM25 rankings combined with reciprocal rank fusion.
+    Hybrid,
+}
+
+/// What concept search ranks.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// Individual functions and methods.
+    #[default]
+    Function,
+    /// Classes, structs, enums, interfaces, and traits, each summarized by its kind,
+    /// qualified name, and method signatures.
+    Class,
+    /// Whole files, each summarized by its path, imports, leading comment, and function
+    /// signatures.
+    File,
+}
+
+impl Granularity {
+    /// Stable name used in cache keys, matching the serialized form.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Function => "function",
+            Granularity::Class => "class",
+            Granularity::File => "file",
+        }
+    }
+}
+
+/// Precision cached embeddings are stored with. Vectors are always compared as `f32`;
+/// lower precisions only shrink the embedding cache, at a small cost in accuracy.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    /// Full precision, 4 bytes per dimension.
+    #[default]
+    F32,
+    /// Half precision, 2 bytes per dimension.
+    F16,
+    /// 1 byte per dimension, scaled by the largest component of each vector.
+    Int8,
+}
+
+/// A cached embedding at the precision it was stored with (see `Quantization`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum StoredEmbedding {
+    F32(Vec<f32>),
+    /// Bit patterns of `half::f16` values.
+    F16(Vec<u16>),
+    /// Components divided by `scale` and rounded.
+    Int8 { scale: f32, values: Vec<i8> },
+}
+
+/// Embedding models that functions and queries can be embedded with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbeddingModelTag {
+    /// BGE small (384 dimensions): fastest, suited to background indexing.
+    BgeSmall,
+    /// BGE base (768 dimensions): balances quality and speed.
+    #[default]
+    BgeBase,
+    /// BGE large (1024 dimensions): highest general-purpose quality, slowest.
+    BgeLarge,
+    /// Jina v2 base code: trained on source code and programming-language text.
+    JinaCode,
+}
+
+impl EmbeddingModelTag {
+    /// Stable name used in cache keys and logs, matching the serialized form.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EmbeddingModelTag::BgeSmall => "bge-small",
+            EmbeddingModelTag::BgeBase => "bge-base",
+            EmbeddingModelTag::BgeLarge => "bge-large",
+            EmbeddingModelTag::JinaCode => "jina-code",
+        }
+    }
+}
+
+/// Cross-encoders that can re-score the top concept search candidates. Unlike the
+/// embedding models they read the query and a function together, which ranks more
+/// accurately but needs one model run per candidate and query.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrossEncoderTag {
+    /// BGE reranker base: English and Chinese.
+    BgeRerankerBase,
+    /// Jina reranker v1 turbo: English, smaller and faster.
+    JinaRerankerTurbo,
+}
+
+impl CrossEncoderTag {
+    /// Stable name used in logs, matching the serialized form.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CrossEncoderTag::BgeRerankerBase => "bge-reranker-base",
+            CrossEncoderTag::JinaRerankerTurbo => "jina-reranker-turbo",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ConceptSearchStats {
     pub functions_analyzed: usize,
     pub search_duration_seconds: f32,
+    pub scoring_backend: ScoringBackend,
+    /// True when the embedding model could not be loaded and keyword scoring was used instead.
+    pub model_fallback: bool,
+    /// Why the embedding model could not be loaded, when `model_fallback` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_error: Option<String>,
+    /// Misspelled query terms that keyword scoring replaced with an indexed term.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub corrected_terms: Vec<TermCorrection>,
+    /// Functions whose embedding text exceeded the model's input length and was
+    /// embedded in overlapping chunks, averaged into one vector.
+    pub functions_chunked: usize,
+    /// Chunks those functions were split into.
+    pub function_chunks: usize,
+    /// The model's input length in tokens, when embeddings were used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<usize>,
+    /// Models that embedded the ranked functions (more than one when `language_models`
+    /// routes some extensions elsewhere).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub models_used: Vec<EmbeddingModelTag>,
+    /// Model that re-scored the top candidates, when `rerank_model` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reranked_with: Option<EmbeddingModelTag>,
+    /// Cross-encoder that re-scored the top candidates, when `cross_encoder` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cross_encoded_with: Option<CrossEncoderTag>,
+    /// True when functions were ranked through the approximate nearest-neighbour index
+    /// rather than compared with the query one by one (see `exact_search`).
+    #[serde(default)]
+    pub approximate: bool,
+    /// True when the timeout passed before every function was scanned and embedded;
+    /// `results` then rank only the functions embedded in time.
+    #[serde(default)]
+    pub timed_out: bool,
+    /// True when the search was stopped through its cancellation token; `results` then
+    /// rank only the functions scored before that.
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Time spent walking and parsing the project.
+    #[serde(default)]
+    pub scan_ms: u64,
+    /// Time spent loading the embedding models, including any first-use download.
+    #[serde(default)]
+    pub model_init_ms: u64,
+    /// Functions whose embedding came from the embedding cache.
+    #[serde(default)]
+    pub cache_hit_count: usize,
+    /// Functions that were not in the cache and had to be embedded (fewer are embedded
+    /// when the search times out or is cancelled first).
+    #[serde(default)]
+    pub cache_miss_count: usize,
+    /// Time spent embedding the query and the functions missing from the cache, and
+    /// reading and writing the cache.
+    #[serde(default)]
+    pub embed_ms: u64,
+    /// Time spent scoring and ranking functions against the query, including keyword
+    /// fusion and re-ranking.
+    #[serde(default)]
+    pub similarity_ms: u64,
+    /// Roots of a multi-root search that failed, as `"<root>: <error>"`. The results
+    /// rank the functions of the other roots; `error` is only set when every root failed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub root_errors: Vec<String>,
+}
+
+/// A query term rewritten to the closest term that occurs in the scanned code.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TermCorrection {
+    pub original: String,
+    pub corrected: String,
 }
 
+/// Layout of `CachedFileEmbeddings`. Bump it whenever the entry format or the text
+/// functions are embedded from changes, so existing entries are re-embedded.
+pub const EMBEDDING_CACHE_SCHEMA_VERSION: u32 = 4;
+
+/// A file's entry in the embedding cache. Entries that fail to deserialize or whose
+/// schema version, model, or dimension do not match are re-embedded.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CachedFileEmbeddings {
+    /// Kept first, so it stays readable across future layouts.
+    pub schema_version: u32,
+    /// Model the vectors were produced with; entries from another model are ignored.
+    pub model: EmbeddingModelTag,
+    /// Length of every vector in the entry.
+    pub dimension: usize,
+    /// When the entry was written, in milliseconds since the Unix epoch.
+    pub updated_ms: u64,
+    /// Embeddings of the file's functions, keyed by the hash of the text each was
+    /// embedded from rather than by name, so edits elsewhere in the file keep them.
+    pub function_embeddings: HashMap<String, StoredEmbedding>,
+}
+
+/// A parsed file in the scan cache (see `parse_cache`). Stored as JSON, since
+/// `FileContext` skips empty fields when serializing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedFileContext {
     pub file_content_hash: String,
-    pub function_embeddings: HashMap<String, Vec<f32>>, // Function name -> embedding vector
+    /// Size and modification time (nanoseconds since the epoch) when the hash was taken;
+    /// while both match, the file is not re-read.
+    pub size_bytes: u64,
+    pub modified_nanos: Option<u128>,
+    pub context: FileContext,
 }
@@ -0,0 +1,107 @@
+use crate::config;
+use crate::parsing;
+use crate::structs::{FileContext, FunctionInfo, FunctionMetrics, Granularity, OutlineSymbol};
+use rayon::prelude::*;
+use std::path::Path;
+
+/// Outline kinds summarized at `Granularity::Class`. `impl` blocks are not types of
+/// their own; their methods count towards the type they implement.
+const CLASS_KINDS: [&str; 5] = ["class", "struct", "enum", "interface", "trait"];
+
+/// Replaces the functions of each file with the units concept search ranks at
+/// `granularity`, so embedding, caching, and scoring treat them like functions:
+/// one summary of the whole file, or one per class-like type. Function granularity
+/// returns the contexts unchanged. Class granularity needs the files' outlines.
+pub fn summary_contexts(file_contexts: Vec<FileContext>, granularity: Granularity) -> Vec<FileContext> {
+    if granularity == Granularity::Function {
+        return file_contexts;
+    }
+    file_contexts
+        .into_par_iter()
+        .map(|mut file_context| {
+            file_context.functions = match granularity {
+                Granularity::File => vec![file_summary(&file_context)],
+                _ => class_summaries(&file_context),
+            };
+            file_context
+        })
+        .filter(|file_context| !file_context.functions.is_empty())
+        .collect()
+}
+
+/// A unit named `name` spanning `start_line..=end_line`, with `body` as its text.
+fn summary_unit(name: &str, qualified_name: String, start_line: usize, end_line: usize, comment: Option<String>, body: String) -> FunctionInfo {
+    FunctionInfo {
+        name: name.to_string(),
+        parent: None,
+        qualified_name,
+        parent_function: None,
+        depth: 0,
+        annotations: Vec::new(),
+        is_async: false,
+        start_line,
+        end_line,
+        body: Some(body),
+        comment,
+        parameters: Vec::new(),
+        return_type: None,
+        metrics: FunctionMetrics::default(),
+        calls: Vec::new(),
+        uri: None,
+    }
+}
+
+/// First line of a function, which holds its signature in every supported language.
+pub fn signature(function: &FunctionInfo) -> &str {
+    function.body.as_deref().and_then(|body| body.lines().next()).unwrap_or(&function.name).trim()
+}
+
+/// The file's path, imports, leading comment, and function signatures.
+fn file_summary(file_context: &FileContext) -> FunctionInfo {
+    let path = Path::new(&file_context.path);
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut body = format!("Path: {}\n", file_context.path);
+    if !file_context.imports.is_empty() {
+        body.push_str(&format!("Imports:\n{}\n", file_context.imports.join("\n")));
+    }
+    body.push_str("Definitions:\n");
+    for function in &file_context.functions {
+        body.push_str(signature(function));
+        body.push('\n');
+    }
+    summary_unit(&file_name, file_name.clone(), 1, file_context.line_count.max(1), file_context.leading_comment.clone(), body)
+}
+
+/// One summary per class-like type in the file's outline: its kind and qualified name,
+/// then the signatures of its methods, including those of `impl` blocks for it.
+fn class_summaries(file_context: &FileContext) -> Vec<FunctionInfo> {
+    let separator = config::scope_separator(parsing::file_extension(Path::new(&file_context.path)).unwrap_or(""));
+    let mut types = Vec::new();
+    collect_types(file_context.outline.as_deref().unwrap_or_default(), &[], separator, &mut types);
+    types
+        .into_iter()
+        .map(|(symbol, qualified_name)| {
+            let mut body = format!("{} {}\nMembers:\n", symbol.kind, qualified_name);
+            for function in file_context.functions.iter().filter(|function| {
+                function.parent.as_deref() == Some(symbol.name.as_str())
+                    || (symbol.start_line..=symbol.end_line).contains(&function.start_line)
+            }) {
+                body.push_str(signature(function));
+                body.push('\n');
+            }
+            summary_unit(&symbol.name, qualified_name, symbol.start_line, symbol.end_line, None, body)
+        })
+        .collect()
+}
+
+/// Collects the class-like symbols in `symbols` with their names qualified by `scope`.
+fn collect_types<'a>(symbols: &'a [OutlineSymbol], scope: &[&'a str], separator: &str, types: &mut Vec<(&'a OutlineSymbol, String)>) {
+    for symbol in symbols {
+        let mut path = scope.to_vec();
+        path.push(&symbol.name);
+        if CLASS_KINDS.contains(&symbol.kind.as_str()) {
+            types.push((symbol, path.join(separator)));
+        }
+        collect_types(&symbol.children, &path, separator, types);
+    }
+}
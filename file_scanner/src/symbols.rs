@@ -0,0 +1,467 @@
+use crate::config::{self, ExtensionFilter};
+use crate::options::{ScanOptions, SearchOptions};
+use crate::parsing::{self, SyntaxLocation};
+use crate::pattern::LinePattern;
+use crate::pool;
+use crate::scanner;
+use crate::structs::{
+    Definition, DefinitionResult, FileContext, OutlineSymbol, Reference, ReferenceKind, ReferenceResult, SymbolSyncResult,
+    WorkspaceSymbol,
+};
+use crate::utils;
+use crate::watcher::{self, WatchKind};
+use ignore::WalkBuilder;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Symbols of one root, keyed by relative file path.
+type Snapshot = BTreeMap<String, Vec<WorkspaceSymbol>>;
+
+/// Symbols last reported to clients, per root. Kept for the lifetime of the loaded
+/// library so each sync only reports what changed.
+static SNAPSHOTS: Lazy<Mutex<HashMap<PathBuf, Snapshot>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// `path` (absolute or relative to `root`) relative to `root`, for a file that no longer
+/// exists and so cannot be resolved. `None` when it is outside the root or goes through `..`.
+fn lexical_relative_path(root: &Path, path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let relative = if path.is_absolute() { path.strip_prefix(root).ok()? } else { path };
+    relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| relative.to_string_lossy().replace('\\', "/"))
+}
+
+fn file_symbols(root: &Path, file_context: &FileContext) -> Vec<WorkspaceSymbol> {
+    let file = relative_path(root, Path::new(&file_context.path));
+    file_context
+        .functions
+        .iter()
+        .map(|function| WorkspaceSymbol {
+            name: function.name.clone(),
+            qualified_name: function.qualified_name.clone(),
+            container_name: function.parent.clone(),
+            file: file.clone(),
+            start_line: function.start_line,
+            end_line: function.end_line,
+        })
+        .collect()
+}
+
+/// Symbols of the files of `root` that `paths` name, freshly parsed, or of the whole
+/// root when `paths` is empty.
+struct ParsedSymbols {
+    current: Snapshot,
+    full_resync: bool,
+    timed_out_internally: bool,
+    debug_log: Option<Vec<String>>,
+}
+
+fn parse_symbols(root: &Path, extensions: &[String], paths: &[String], debug: bool) -> Result<ParsedSymbols, String> {
+    let extension_filter = config::ExtensionFilter::new(extensions)?;
+    let mut debug_log: Option<Vec<String>> = if debug { Some(Vec::new()) } else { None };
+    let mut current = Snapshot::new();
+    let mut timed_out_internally = false;
+
+    let full_resync = paths.is_empty();
+    if full_resync {
+        let scan_result = scanner::perform_scan(
+            &root.to_string_lossy(),
+            extensions.to_vec(),
+            0,
+            0,
+            &ScanOptions::default(),
+            debug,
+        );
+        timed_out_internally = scan_result.timed_out_internally;
+        if let (Some(log), Some(scan_log)) = (&mut debug_log, scan_result.debug_log) {
+            log.extend(scan_log);
+        }
+        for file_context in &scan_result.file_contexts {
+            current.insert(
+                relative_path(root, Path::new(&file_context.path)),
+                file_symbols(root, file_context),
+            );
+        }
+    } else {
+        // Files that still exist get the same checks as the `files` option of a scan:
+        // inside the root, not cached, under `.git` or vendored, and within the size limit.
+        let (existing, deleted): (Vec<String>, Vec<String>) =
+            paths.iter().cloned().partition(|path| root.join(path).exists());
+        for path in deleted {
+            let extension = parsing::file_extension(Path::new(&path)).unwrap_or("");
+            if !extension_filter.matches(Path::new(&path), extension) {
+                continue;
+            }
+            if let Some(relative) = lexical_relative_path(root, &path) {
+                current.insert(relative, Vec::new());
+            }
+        }
+        let options = ScanOptions::default();
+        let path_filter = config::path_filter(root, &options.include, &options.exclude)?;
+        let (listed, rejected) = config::listed_files(root, &existing, &path_filter, options.skip_vendor_dirs);
+        if let Some(log) = &mut debug_log {
+            for rejected in &rejected {
+                log.push(format!("[Symbols] Ignoring {}", rejected));
+            }
+        }
+        let mut files = Vec::with_capacity(listed.len());
+        for path in listed {
+            let extension = parsing::file_extension(&path).unwrap_or("");
+            if !extension_filter.matches(&path, extension) {
+                if let Some(log) = &mut debug_log {
+                    log.push(format!(
+                        "[Symbols] Ignoring {:?}: extension not requested",
+                        path
+                    ));
+                }
+                continue;
+            }
+            // Files that no longer parse, or have no functions, drop their symbols.
+            let relative = relative_path(root, &path);
+            current.insert(relative.clone(), Vec::new());
+            files.push(relative);
+        }
+        if !files.is_empty() {
+            let scan_result = scanner::perform_scan(
+                &root.to_string_lossy(),
+                extensions.to_vec(),
+                0,
+                0,
+                &ScanOptions { files, ..options },
+                debug,
+            );
+            if let (Some(log), Some(scan_log)) = (&mut debug_log, scan_result.debug_log) {
+                log.extend(scan_log);
+            }
+            for file_context in &scan_result.file_contexts {
+                current.insert(
+                    relative_path(root, Path::new(&file_context.path)),
+                    file_symbols(root, file_context),
+                );
+            }
+        }
+    }
+    Ok(ParsedSymbols { current, full_resync, timed_out_internally, debug_log })
+}
+
+/// Replaces the symbols of the parsed files in `snapshot` and returns what was added
+/// and removed.
+fn apply(snapshot: &mut Snapshot, parsed: ParsedSymbols) -> SymbolSyncResult {
+    let ParsedSymbols { mut current, full_resync, timed_out_internally, debug_log } = parsed;
+    // Files that were not re-parsed keep their previous symbols, except after a full
+    // rescan, where files missing from the scan have been deleted.
+    if full_resync && !timed_out_internally {
+        let gone: Vec<String> = snapshot
+            .keys()
+            .filter(|file| !current.contains_key(*file))
+            .cloned()
+            .collect();
+        for file in gone {
+            current.insert(file, Vec::new());
+        }
+    }
+
+    let mut result = SymbolSyncResult {
+        files_synced: current.len(),
+        timed_out_internally,
+        ..Default::default()
+    };
+    for (file, symbols) in current {
+        let previous = if symbols.is_empty() {
+            snapshot.remove(&file).unwrap_or_default()
+        } else {
+            snapshot.insert(file, symbols.clone()).unwrap_or_default()
+        };
+        result
+            .removed
+            .extend(previous.iter().filter(|s| !symbols.contains(s)).cloned());
+        result
+            .added
+            .extend(symbols.into_iter().filter(|s| !previous.contains(s)));
+    }
+    result.debug_log = debug_log;
+    result
+}
+
+/// Re-parses `paths` (absolute or relative to `root`) and returns the symbols added
+/// and removed since the previous sync of `root`. Deleted files, files with no functions
+/// and files over the default size limit drop all their symbols; files whose extension
+/// is not in `extensions`, and files `config::listed_files` leaves out (outside the
+/// root, cached, under `.git` or a vendored directory), are ignored.
+///
+/// With no `paths`, the whole root is rescanned and compared against the previous
+/// snapshot. On the first sync of a root every symbol is reported as added.
+///
+/// Symbols are identified by qualified name and line span, so a moved function is
+/// reported as removed at its old location and added at its new one.
+pub fn sync(root: &Path, extensions: &[String], paths: &[String], debug: bool) -> SymbolSyncResult {
+    let parsed = match parse_symbols(root, extensions, paths, debug) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return SymbolSyncResult {
+                error: Some(e),
+                ..Default::default()
+            };
+        }
+    };
+    let mut snapshots = SNAPSHOTS.lock().unwrap_or_else(|e| e.into_inner());
+    apply(snapshots.entry(root.to_path_buf()).or_default(), parsed)
+}
+
+/// Receives the symbols added and removed by one batch of changes of a subscription.
+pub type SymbolDeltaFn = Box<dyn FnMut(SymbolSyncResult) + Send>;
+
+/// Watches `root` and pushes the symbols added and removed to `deliver` as the watcher
+/// settles each burst of changes, so editor integrations need not sync after every
+/// save. The first delivery reports every symbol of the root as added; batches that
+/// change no symbol are not delivered. Stop with `watcher::stop(root, WatchKind::Symbols)`.
+///
+/// A subscription keeps its own snapshot, separate from the one `sync` compares against.
+pub fn subscribe(root: &Path, extensions: Vec<String>, debug: bool, mut deliver: SymbolDeltaFn) -> Result<(), String> {
+    // Batches are relative to the canonical root the watcher reports them under.
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut snapshot = Snapshot::new();
+    let sync_extensions = extensions.clone();
+    let sync_root = root.clone();
+    watcher::start(
+        &root,
+        WatchKind::Symbols,
+        extensions,
+        Box::new(move |paths| {
+            let result = match parse_symbols(&sync_root, &sync_extensions, &paths, debug) {
+                Ok(parsed) => apply(&mut snapshot, parsed),
+                Err(e) => SymbolSyncResult { error: Some(e), ..Default::default() },
+            };
+            if !result.added.is_empty() || !result.removed.is_empty() || result.error.is_some() {
+                deliver(result);
+            }
+        }),
+    )
+}
+
+/// Whether `qualified_name` names the symbol `query`: by its own name, in full, or by a
+/// qualified suffix (`FileCache::save` for `cache::FileCache::save`). `.` and `::` are
+/// interchangeable in the query, so hosts need not know the language's separator.
+fn names_symbol(query: &str, name: &str, qualified_name: &str) -> bool {
+    if name == query {
+        return true;
+    }
+    let query = query.replace("::", ".");
+    let qualified_name = qualified_name.replace("::", ".");
+    qualified_name == query || qualified_name.ends_with(&format!(".{}", query))
+}
+
+/// Collects the definitions in `symbols` (an outline) that `query` names, qualifying
+/// names with `scope` and `separator`. `impl` blocks only qualify their methods; they
+/// do not define the type they implement.
+fn collect_definitions(
+    symbols: &[OutlineSymbol],
+    query: &str,
+    file: &str,
+    scope: &[&str],
+    separator: &str,
+    definitions: &mut Vec<Definition>,
+) {
+    for symbol in symbols {
+        let mut path = scope.to_vec();
+        path.push(&symbol.name);
+        let qualified_name = path.join(separator);
+        if symbol.kind != "impl" && names_symbol(query, &symbol.name, &qualified_name) {
+            definitions.push(Definition {
+                name: symbol.name.clone(),
+                qualified_name,
+                kind: symbol.kind.clone(),
+                file: file.to_string(),
+                start_line: symbol.start_line,
+                end_line: symbol.end_line,
+            });
+        }
+        collect_definitions(&symbol.children, query, file, &path, separator, definitions);
+    }
+}
+
+/// Finds the functions, classes and other declarations named `query` (see
+/// `names_symbol`) in the files with `extensions` under `root`, for a go-to-definition
+/// without a language server. Symbols come from an outline scan, which the parse cache
+/// serves for unchanged files, so repeated lookups only re-parse what changed.
+pub fn find_definitions(root: &Path, query: &str, extensions: &[String]) -> DefinitionResult {
+    let start_time = Instant::now();
+    let options = ScanOptions {
+        outline: true,
+        ..Default::default()
+    };
+    let scan_result = scanner::perform_scan(&root.to_string_lossy(), extensions.to_vec(), 0, 0, &options, false);
+
+    let mut definitions = Vec::new();
+    for file_context in &scan_result.file_contexts {
+        let path = Path::new(&file_context.path);
+        let separator = config::scope_separator(parsing::file_extension(path).unwrap_or(""));
+        if let Some(outline) = &file_context.outline {
+            let file = relative_path(root, path);
+            collect_definitions(outline, query.trim(), &file, &[], separator, &mut definitions);
+        }
+    }
+    definitions.sort_by(|a, b| a.file.cmp(&b.file).then(a.start_line.cmp(&b.start_line)));
+
+    DefinitionResult {
+        definitions,
+        files_scanned: scan_result.file_contexts.len(),
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        timed_out_internally: scan_result.timed_out_internally,
+        error: (!scan_result.errors.is_empty()).then(|| scan_result.errors.join("; ")),
+    }
+}
+
+/// How the occurrence of `name` at byte `column` of `line` uses it, given its place in
+/// the syntax tree and the symbol's `definitions` in the same file.
+fn reference_kind(
+    line: &str,
+    column: usize,
+    name: &str,
+    line_number: usize,
+    location: &SyntaxLocation,
+    definitions: &[&Definition],
+) -> ReferenceKind {
+    if location.in_comment_or_string {
+        return ReferenceKind::CommentOrString;
+    }
+    if location.in_declared_name
+        && definitions
+            .iter()
+            .any(|definition| (definition.start_line..=definition.end_line).contains(&line_number))
+    {
+        return ReferenceKind::Definition;
+    }
+    let rest = line[column + name.len()..].trim_start();
+    if rest.starts_with('(') || rest.starts_with("!(") {
+        ReferenceKind::Call
+    } else {
+        ReferenceKind::Reference
+    }
+}
+
+/// The occurrences of `name` (a whole word) in the file at `path`, classified with its
+/// syntax tree. Files in languages the parser does not support report nothing.
+fn file_references(
+    root: &Path,
+    path: &Path,
+    name: &str,
+    pattern: &LinePattern,
+    definitions: &[Definition],
+) -> Vec<Reference> {
+    let Some(extension) = parsing::file_extension(path) else {
+        return Vec::new();
+    };
+    let Ok(bytes) = utils::read_file_bytes(path) else {
+        return Vec::new();
+    };
+    let Some((text, _)) = utils::decode_text(&bytes) else {
+        return Vec::new();
+    };
+    if !text.contains(name) {
+        return Vec::new();
+    }
+    let Some(syntax) = parsing::SyntaxIndex::parse(extension, text.to_string()) else {
+        return Vec::new();
+    };
+    let file = relative_path(root, path);
+    let definitions: Vec<&Definition> = definitions.iter().filter(|definition| definition.file == file).collect();
+
+    let mut references = Vec::new();
+    for (row, line) in utils::split_lines(&text).into_iter().enumerate() {
+        for found in pattern.find_all(line) {
+            let location = syntax.locate(row, found.start);
+            references.push(Reference {
+                kind: reference_kind(line, found.start, name, row + 1, &location, &definitions),
+                file: file.clone(),
+                line: row + 1,
+                column: line[..found.start].chars().count() + 1,
+                text: line.trim().to_string(),
+                symbol: location.symbol,
+            });
+        }
+    }
+    references
+}
+
+/// Finds every occurrence of the symbol `query` under `root`, in all supported
+/// languages, and classifies each as its definition, a call, another use in code, or
+/// text in a comment or string. Definitions come from the outline index (see
+/// `find_definitions`); occurrences are found by a whole-word search for the last part
+/// of the name (`save` for `UserRepository.save`), so same-named symbols elsewhere are
+/// included too.
+pub fn find_references(root: &Path, query: &str) -> ReferenceResult {
+    let start_time = Instant::now();
+    let query = query.trim();
+    let name = query.rsplit([':', '.']).next().unwrap_or(query);
+    let extensions = config::supported_extensions();
+    let definition_result = find_definitions(root, query, &extensions);
+    if definition_result.error.is_some() {
+        return ReferenceResult {
+            error: definition_result.error,
+            ..Default::default()
+        };
+    }
+    let search_options = SearchOptions {
+        whole_word: true,
+        ..Default::default()
+    };
+    let pattern = match LinePattern::new(name, &search_options) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            return ReferenceResult {
+                error: Some(e),
+                ..Default::default()
+            };
+        }
+    };
+
+    let extension_filter = ExtensionFilter::new(&extensions).expect("supported extensions are not globs");
+    let walker = WalkBuilder::new(root)
+        .add_custom_ignore_filename(config::IGNORE_FILE)
+        .filter_entry(|entry| {
+            !config::is_own_cache_path(entry.path())
+                && !config::is_git_dir(entry.path())
+                && !config::is_vendor_dir(entry)
+        })
+        .build();
+    // Same size limit as scans, so a huge generated or minified file is not read whole.
+    let max_file_size_bytes = ScanOptions::default().max_file_size_bytes;
+    let files: Vec<PathBuf> = walker
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter(|entry| entry.metadata().is_ok_and(|metadata| metadata.len() <= max_file_size_bytes))
+        .map(|entry| entry.into_path())
+        .filter(|path| extension_filter.matches(path, parsing::file_extension(path).unwrap_or("")))
+        .collect();
+
+    let mut references: Vec<Reference> = pool::shared().install(|| {
+        files
+            .par_iter()
+            .flat_map_iter(|path| file_references(root, path, name, &pattern, &definition_result.definitions))
+            .collect()
+    });
+    references.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+
+    ReferenceResult {
+        references,
+        definitions: definition_result.definitions,
+        files_searched: files.len(),
+        duration_seconds: start_time.elapsed().as_secs_f32(),
+        timed_out_internally: definition_result.timed_out_internally,
+        error: None,
+    }
+}
@@ -1,13 +1,131 @@
+use crate::options::UriFormat;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Checks if a file is likely binary by looking for null bytes.
 pub fn is_binary(path: &Path) -> bool {
     fs::read(path)
-        .map(|bytes| bytes.iter().any(|&b| b == 0))
+        .map(|bytes| is_binary_content(&bytes))
         .unwrap_or(true) // Treat read errors as if the file is binary or inaccessible
 }
 
+/// Same as `is_binary`, for content already read.
+pub fn is_binary_content(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+/// Hex SHA-256 of `bytes`, the content hash stored in caches and `FileContext`.
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// UTF-8 byte order mark.
+pub const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Names the text encoding of `bytes`: `"utf-8"`, `"utf-8-bom"`, or `None` if the
+/// content is not valid UTF-8.
+pub fn detect_encoding(bytes: &[u8]) -> Option<&'static str> {
+    match bytes.strip_prefix(UTF8_BOM) {
+        Some(rest) => std::str::from_utf8(rest).ok().map(|_| "utf-8-bom"),
+        None => std::str::from_utf8(bytes).ok().map(|_| "utf-8"),
+    }
+}
+
+/// Files at least this large are memory-mapped by `read_file_bytes` instead of copied
+/// into memory.
+const MMAP_THRESHOLD_BYTES: u64 = 1 << 20;
+
+/// The content of a file, either read into memory or memory-mapped.
+pub enum FileBytes {
+    Read(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Read(bytes) => bytes,
+            FileBytes::Mapped(map) => map,
+        }
+    }
+}
+
+/// Reads a file for searching, memory-mapping it if it is large so that its lines can
+/// be borrowed from the page cache rather than copied.
+pub fn read_file_bytes(path: &Path) -> io::Result<FileBytes> {
+    let mut file = fs::File::open(path)?;
+    if file.metadata()?.len() >= MMAP_THRESHOLD_BYTES {
+        // SAFETY: the map is only read, and dropped before this call's caller returns. A
+        // file truncated by another process meanwhile can still fault, which is the usual
+        // trade-off search tools make to avoid copying large files.
+        return unsafe { memmap2::Mmap::map(&file) }.map(FileBytes::Mapped);
+    }
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok(FileBytes::Read(bytes))
+}
+
+/// Decodes the text of a file for searching, with the name of the encoding it was read
+/// as: `"utf-8"` or `"utf-8-bom"`; `"utf-16le"` or `"utf-16be"` when the file starts
+/// with a UTF-16 byte order mark; `"utf-8-lossy"` for UTF-8 text with a few invalid
+/// bytes, which become U+FFFD; otherwise `"windows-1252"`, the superset of Latin-1 that
+/// Windows tools write, which decodes any byte. Returns `None` for binary content (a
+/// NUL byte outside UTF-16). Valid UTF-8 text is borrowed from `bytes`.
+pub fn decode_text(bytes: &[u8]) -> Option<(Cow<'_, str>, &'static str)> {
+    if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(bytes) {
+        if encoding != encoding_rs::UTF_8 {
+            let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+            let name = if encoding == encoding_rs::UTF_16LE { "utf-16le" } else { "utf-16be" };
+            return Some((text, name));
+        }
+    }
+    if memchr::memchr(0, bytes).is_some() {
+        return None;
+    }
+    if let Some(name) = detect_encoding(bytes) {
+        let text = std::str::from_utf8(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)).ok()?;
+        return Some((Cow::Borrowed(text), name));
+    }
+    // A stray byte in UTF-8 text (a pasted Latin-1 character, a cut-off write) should
+    // not turn every other non-ASCII character into mojibake, so the text stays UTF-8
+    // unless invalid sequences outnumber valid multi-byte characters, as they do in
+    // legacy single-byte files.
+    let text = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    let (mut valid_non_ascii, mut invalid) = (0usize, 0usize);
+    for chunk in text.utf8_chunks() {
+        valid_non_ascii += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+        invalid += usize::from(!chunk.invalid().is_empty());
+    }
+    if valid_non_ascii > invalid {
+        return Some((String::from_utf8_lossy(text), "utf-8-lossy"));
+    }
+    let (text, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes);
+    Some((text, "windows-1252"))
+}
+
+/// Splits `text` into lines like `str::lines` (dropping each `\n` and a `\r` before it,
+/// with no empty line after a final line break), finding the breaks with memchr.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for end in memchr::memchr_iter(b'\n', text.as_bytes()) {
+        let line = &text[start..end];
+        lines.push(line.strip_suffix('\r').unwrap_or(line));
+        start = end + 1;
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
 /// Calculates the cosine similarity between two f32 slices.
 ///
 /// Returns `0.0` if either slice is empty or if the norm of either vector is zero.
@@ -36,3 +154,124 @@ pub fn cosine_similarity(v1: &[f32], v2: &[f32]) -> f32 {
 
     dot_product / (norm_v1 * norm_v2)
 }
+
+/// Returns up to `count` lines immediately before and after the 1-based, inclusive
+/// `start_line..=end_line` range of `path`, or `None` for a side with no lines.
+pub fn read_surrounding_lines(
+    path: &Path,
+    start_line: usize,
+    end_line: usize,
+    count: usize,
+) -> (Option<String>, Option<String>) {
+    if count == 0 || start_line == 0 {
+        return (None, None);
+    }
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return (None, None),
+    };
+    let lines: Vec<&str> = content.lines().collect();
+
+    let start_idx = (start_line - 1).min(lines.len());
+    let before_start = start_idx.saturating_sub(count);
+    let after_end = (end_line + count).min(lines.len());
+
+    let before = (before_start < start_idx).then(|| lines[before_start..start_idx].join("\n"));
+    let after = (end_line < after_end).then(|| lines[end_line..after_end].join("\n"));
+    (before, after)
+}
+
+/// Percent-encodes `path` for the path of a URI: every byte of its UTF-8 form is encoded
+/// except unreserved characters, `/` separators, and `:` (as in Windows drive letters).
+fn encode_uri_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Builds an editor-friendly URI pointing at the 1-based `start_line..=end_line` range of `path`.
+pub fn location_uri(format: UriFormat, path: &str, start_line: usize, end_line: usize) -> String {
+    // URIs always use forward slashes and need a leading slash before Windows drive letters.
+    let mut normalized = encode_uri_path(&path.replace('\\', "/"));
+    if !normalized.starts_with('/') {
+        normalized.insert(0, '/');
+    }
+    match format {
+        UriFormat::File if end_line > start_line => {
+            format!("file://{}#L{}-L{}", normalized, start_line, end_line)
+        }
+        UriFormat::File => format!("file://{}#L{}", normalized, start_line),
+        UriFormat::Vscode => format!("vscode://file{}:{}", normalized, start_line),
+    }
+}
+
+/// Resolves symlinks and `..` in `path` for comparing file identities, falling back to
+/// `path` itself when it cannot be resolved (e.g. it no longer exists).
+pub fn canonical_or_original(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Milliseconds since the Unix epoch, or `0` if the clock is set before it.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_uri_percent_encodes_reserved_and_non_ascii_characters() {
+        assert_eq!(
+            location_uri(UriFormat::File, "/src/a b#1%?.rs", 3, 3),
+            "file:///src/a%20b%231%25%3F.rs#L3"
+        );
+        assert_eq!(location_uri(UriFormat::File, "/src/café.rs", 1, 2), "file:///src/caf%C3%A9.rs#L1-L2");
+    }
+
+    #[test]
+    fn decode_text_keeps_mostly_utf8_text_as_utf8() {
+        let (text, encoding) = decode_text("café naïve \u{2014} ".as_bytes()).unwrap();
+        assert_eq!((text.as_ref(), encoding), ("café naïve \u{2014} ", "utf-8"));
+
+        let mut bytes = "café naïve ".as_bytes().to_vec();
+        bytes.push(0xFF);
+        bytes.extend_from_slice(" résumé".as_bytes());
+        let (text, encoding) = decode_text(&bytes).unwrap();
+        assert_eq!((text.as_ref(), encoding), ("café naïve \u{FFFD} résumé", "utf-8-lossy"));
+    }
+
+    #[test]
+    fn decode_text_reads_single_byte_text_as_windows_1252() {
+        let (text, encoding) = decode_text(b"caf\xE9 na\xEFve \x93quoted\x94").unwrap();
+        assert_eq!((text.as_ref(), encoding), ("café naïve \u{201C}quoted\u{201D}", "windows-1252"));
+    }
+
+    #[test]
+    fn decode_text_handles_byte_order_marks_and_binary() {
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter().chain("hé".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let (text, encoding) = decode_text(&utf16).unwrap();
+        assert_eq!((text.as_ref(), encoding), ("hé", "utf-16le"));
+
+        let (text, encoding) = decode_text(b"\xEF\xBB\xBFplain").unwrap();
+        assert_eq!((text.as_ref(), encoding), ("plain", "utf-8-bom"));
+
+        assert!(decode_text(b"ELF\0\x01").is_none());
+    }
+
+    #[test]
+    fn location_uri_keeps_windows_drive_letters() {
+        assert_eq!(location_uri(UriFormat::File, "C:\\src\\main.rs", 4, 4), "file:///C:/src/main.rs#L4");
+        assert_eq!(location_uri(UriFormat::Vscode, "C:\\src\\main.rs", 4, 9), "vscode://file/C:/src/main.rs:4");
+    }
+}
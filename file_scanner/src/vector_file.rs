@@ -0,0 +1,318 @@
+use crate::atomic_write;
+use crate::config;
+use crate::structs::{EmbeddingModelTag, Granularity};
+use memmap2::Mmap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Identifies a vector file.
+const MAGIC: &[u8; 8] = b"RAPIDVEC";
+/// Bumped whenever the layout below changes; files of other versions are rebuilt.
+const FORMAT_VERSION: u32 = 1;
+/// Magic, then format version, vector length, row count, and id table length as
+/// little-endian `u32`s. A multiple of 4, so the matrix after it stays `f32`-aligned.
+const HEADER_LEN: usize = 24;
+
+/// Directory of the vector files of `root`, next to its sled embedding cache.
+fn directory(root: &Path) -> PathBuf {
+    root.join(config::CACHE_DIR).join("file_scanner_embedding_cache").join("vectors")
+}
+
+/// Vector file of `tag`'s embeddings at `granularity` under `root`.
+pub fn path(root: &Path, tag: EmbeddingModelTag, granularity: Granularity) -> PathBuf {
+    directory(root).join(format!("{}-{}.vec", tag.as_str(), granularity.as_str()))
+}
+
+/// Removes every vector file of `root`, returning how many there were.
+pub fn remove_all(root: &Path) -> io::Result<usize> {
+    let entries = match fs::read_dir(directory(root)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        fs::remove_file(entry?.path())?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Combined size of the vector files of `root`, in bytes.
+pub fn size_on_disk(root: &Path) -> u64 {
+    fs::read_dir(directory(root))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// Every cached embedding of one model and granularity in a single memory-mapped file:
+/// a header, the vectors as one row-major matrix of little-endian `f32`s, and an id
+/// table naming the rows of each file by the hash of the text they were embedded from
+/// (see `embedding_text_hash`). A warm search reads its rows straight from the map
+/// instead of deserializing one sled entry per file.
+///
+/// The file is derived from the sled cache, which stays authoritative: it is rewritten
+/// after any search that had to fall back to sled, and can be deleted at any time.
+pub struct VectorFile {
+    map: Mmap,
+    dimension: usize,
+    /// Row of every function, by the file's relative path and then its text hash.
+    rows: HashMap<String, HashMap<String, u32>>,
+}
+
+impl VectorFile {
+    /// Opens the vector file at `path`. Returns `None` when it is missing, of another
+    /// format version, truncated, or holds vectors of a length other than `dimension`
+    /// (when known).
+    pub fn open(path: &Path, dimension: Option<usize>) -> Option<VectorFile> {
+        let file = fs::File::open(path).ok()?;
+        // SAFETY: the map is only read. Writers replace the file by renaming a new one
+        // over it, which leaves this map on the old content.
+        let map = unsafe { Mmap::map(&file) }.ok()?;
+        if map.len() < HEADER_LEN || &map[..MAGIC.len()] != MAGIC || read_u32(&map, 8) != FORMAT_VERSION {
+            return None;
+        }
+        let file_dimension = read_u32(&map, 12) as usize;
+        let row_count = read_u32(&map, 16) as usize;
+        let table_len = read_u32(&map, 20) as usize;
+        if dimension.is_some_and(|dimension| dimension != file_dimension) {
+            return None;
+        }
+        let table_start = HEADER_LEN + row_count * file_dimension * 4;
+        if map.len() != table_start + table_len {
+            return None;
+        }
+        let table: Vec<(String, Vec<String>)> = bincode::deserialize(&map[table_start..]).ok()?;
+        let mut next_row = 0u32;
+        let rows = table
+            .into_iter()
+            .map(|(relative_path, text_hashes)| {
+                let file_rows = text_hashes
+                    .into_iter()
+                    .map(|text_hash| {
+                        next_row += 1;
+                        (text_hash, next_row - 1)
+                    })
+                    .collect();
+                (relative_path, file_rows)
+            })
+            .collect();
+        if next_row as usize != row_count {
+            return None;
+        }
+        Some(VectorFile { map, dimension: file_dimension, rows })
+    }
+
+    /// Rows of the functions of `relative_path`, in the order of `text_hashes`, when
+    /// the file holds exactly these functions for it. `None` when any of them is
+    /// missing or it holds others too, i.e. the file changed since it was written.
+    pub fn rows_for(&self, relative_path: &str, text_hashes: &[&str]) -> Option<Vec<u32>> {
+        let file_rows = self.rows.get(relative_path);
+        let rows: Vec<u32> = text_hashes
+            .iter()
+            .map(|text_hash| file_rows.and_then(|file_rows| file_rows.get(*text_hash)).copied())
+            .collect::<Option<_>>()?;
+        let distinct: HashSet<&str> = text_hashes.iter().copied().collect();
+        (distinct.len() == file_rows.map_or(0, HashMap::len)).then_some(rows)
+    }
+
+    /// Bytes of `row`'s vector.
+    fn row_bytes(&self, row: u32) -> &[u8] {
+        let start = HEADER_LEN + row as usize * self.dimension * 4;
+        &self.map[start..start + self.dimension * 4]
+    }
+
+    /// The vector of `row`.
+    pub fn row(&self, row: u32) -> Vec<f32> {
+        self.row_bytes(row)
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    }
+
+    /// Relative paths of the files with rows in this file.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.rows.keys().map(String::as_str)
+    }
+}
+
+/// Builds a vector file file by file; `finish` writes it.
+pub struct VectorFileWriter {
+    dimension: usize,
+    matrix: Vec<u8>,
+    table: Vec<(String, Vec<String>)>,
+}
+
+impl VectorFileWriter {
+    pub fn new(dimension: usize) -> VectorFileWriter {
+        VectorFileWriter { dimension, matrix: Vec::new(), table: Vec::new() }
+    }
+
+    /// Adds the functions of `relative_path`, as text hash and vector pairs. Vectors of
+    /// another length are skipped.
+    pub fn push_file<'a>(&mut self, relative_path: String, functions: impl IntoIterator<Item = (&'a str, &'a [f32])>) {
+        let mut text_hashes = Vec::new();
+        for (text_hash, vector) in functions {
+            if vector.len() != self.dimension {
+                continue;
+            }
+            self.matrix.extend(vector.iter().flat_map(|x| x.to_le_bytes()));
+            text_hashes.push(text_hash.to_string());
+        }
+        self.table.push((relative_path, text_hashes));
+    }
+
+    /// Copies the rows of `relative_path` from `source` unchanged.
+    pub fn copy_file(&mut self, source: &VectorFile, relative_path: &str) {
+        let Some(file_rows) = source.rows.get(relative_path).filter(|_| source.dimension == self.dimension) else {
+            return;
+        };
+        let mut text_hashes = Vec::with_capacity(file_rows.len());
+        for (text_hash, &row) in file_rows {
+            self.matrix.extend_from_slice(source.row_bytes(row));
+            text_hashes.push(text_hash.clone());
+        }
+        self.table.push((relative_path.to_string(), text_hashes));
+    }
+
+    /// Writes the file to `path` atomically, returning the number of rows written.
+    pub fn finish(self, path: &Path) -> io::Result<usize> {
+        let row_count = self.table.iter().map(|(_, text_hashes)| text_hashes.len()).sum::<usize>();
+        let table = bincode::serialize(&self.table).map_err(io::Error::other)?;
+        let mut contents = Vec::with_capacity(HEADER_LEN + self.matrix.len() + table.len());
+        contents.extend_from_slice(MAGIC);
+        for field in [FORMAT_VERSION, self.dimension as u32, row_count as u32, table.len() as u32] {
+            contents.extend_from_slice(&field.to_le_bytes());
+        }
+        contents.extend_from_slice(&self.matrix);
+        contents.extend_from_slice(&table);
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        atomic_write::write_atomic(path, &contents)?;
+        Ok(row_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A vector file path of its own under the temp directory, removed on drop.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> TempPath {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let name = format!("rapid-vector-file-{}-{}.vec", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+            TempPath(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// Writes `a.rs` with two functions and `b.rs` with one, all of length 3.
+    fn write_sample(path: &Path) {
+        let mut writer = VectorFileWriter::new(3);
+        writer.push_file("a.rs".to_string(), [("h1", &[1.0, 2.0, 3.0][..]), ("h2", &[4.0, 5.0, 6.0][..])]);
+        writer.push_file("b.rs".to_string(), [("h3", &[7.0, 8.0, 9.0][..]), ("short", &[1.0][..])]);
+        assert_eq!(writer.finish(path).unwrap(), 3);
+    }
+
+    #[test]
+    fn written_rows_read_back() {
+        let path = TempPath::new();
+        write_sample(&path.0);
+        let file = VectorFile::open(&path.0, Some(3)).expect("valid file");
+        let rows = file.rows_for("a.rs", &["h2", "h1"]).unwrap();
+        assert_eq!(file.row(rows[0]), vec![4.0, 5.0, 6.0]);
+        assert_eq!(file.row(rows[1]), vec![1.0, 2.0, 3.0]);
+        let rows = file.rows_for("b.rs", &["h3"]).unwrap();
+        assert_eq!(file.row(rows[0]), vec![7.0, 8.0, 9.0]);
+        let mut files: Vec<&str> = file.files().collect();
+        files.sort();
+        assert_eq!(files, vec!["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn rows_for_changed_functions_are_missing() {
+        let path = TempPath::new();
+        write_sample(&path.0);
+        let file = VectorFile::open(&path.0, None).unwrap();
+        // A function was removed, added, or the file was never written.
+        assert!(file.rows_for("a.rs", &["h1"]).is_none());
+        assert!(file.rows_for("a.rs", &["h1", "h2", "h4"]).is_none());
+        assert!(file.rows_for("c.rs", &["h1"]).is_none());
+        assert_eq!(file.rows_for("c.rs", &[]), Some(Vec::new()));
+    }
+
+    #[test]
+    fn mismatched_or_damaged_files_are_rejected() {
+        let path = TempPath::new();
+        assert!(VectorFile::open(&path.0, None).is_none());
+        write_sample(&path.0);
+        assert!(VectorFile::open(&path.0, Some(4)).is_none());
+
+        let contents = fs::read(&path.0).unwrap();
+        fs::write(&path.0, &contents[..contents.len() - 1]).unwrap();
+        assert!(VectorFile::open(&path.0, Some(3)).is_none());
+        fs::write(&path.0, &contents[..HEADER_LEN - 1]).unwrap();
+        assert!(VectorFile::open(&path.0, Some(3)).is_none());
+        fs::write(&path.0, []).unwrap();
+        assert!(VectorFile::open(&path.0, Some(3)).is_none());
+
+        let mut other_version = contents.clone();
+        other_version[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&path.0, &other_version).unwrap();
+        assert!(VectorFile::open(&path.0, Some(3)).is_none());
+
+        let mut other_magic = contents.clone();
+        other_magic[0] = b'X';
+        fs::write(&path.0, &other_magic).unwrap();
+        assert!(VectorFile::open(&path.0, Some(3)).is_none());
+
+        // A row count that disagrees with the id table.
+        let mut wrong_count = contents;
+        wrong_count[16..20].copy_from_slice(&2u32.to_le_bytes());
+        fs::write(&path.0, &wrong_count).unwrap();
+        assert!(VectorFile::open(&path.0, Some(3)).is_none());
+    }
+
+    #[test]
+    fn copied_files_keep_their_rows() {
+        let (source_path, copy_path) = (TempPath::new(), TempPath::new());
+        write_sample(&source_path.0);
+        let source = VectorFile::open(&source_path.0, Some(3)).unwrap();
+        let mut writer = VectorFileWriter::new(3);
+        writer.copy_file(&source, "a.rs");
+        writer.copy_file(&source, "missing.rs");
+        assert_eq!(writer.finish(&copy_path.0).unwrap(), 2);
+
+        let copy = VectorFile::open(&copy_path.0, Some(3)).unwrap();
+        let rows = copy.rows_for("a.rs", &["h1", "h2"]).unwrap();
+        assert_eq!(copy.row(rows[0]), vec![1.0, 2.0, 3.0]);
+        assert_eq!(copy.row(rows[1]), vec![4.0, 5.0, 6.0]);
+        assert_eq!(copy.files().collect::<Vec<_>>(), vec!["a.rs"]);
+
+        // Rows of another length are not copied.
+        let mut writer = VectorFileWriter::new(2);
+        writer.copy_file(&source, "a.rs");
+        assert_eq!(writer.finish(&copy_path.0).unwrap(), 0);
+    }
+}
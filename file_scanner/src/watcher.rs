@@ -0,0 +1,197 @@
+use crate::config::{self, ExtensionFilter};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long a burst of changes (a save, a checkout, a formatter run) may take before the
+/// changed files are refreshed together.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Refreshes the caches for a batch of changed files (paths relative to the root). An
+/// empty batch asks for the whole root, which is how a watch starts.
+pub type RefreshFn = Box<dyn FnMut(Vec<String>) + Send>;
+
+struct Watch {
+    /// Dropping the watcher closes the event channel, which ends the worker.
+    watcher: RecommendedWatcher,
+    worker: JoinHandle<()>,
+}
+
+/// What a watch keeps up to date. A root can have one watch of each kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    /// The parse and embedding caches (`watch_start`).
+    Caches,
+    /// A workspace symbol subscription (`subscribe_workspace_symbols`).
+    Symbols,
+}
+
+/// Active watches, per canonical root and kind.
+static WATCHES: Lazy<Mutex<HashMap<(PathBuf, WatchKind), Watch>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn canonical_root(root: &Path) -> PathBuf {
+    root.canonicalize().unwrap_or_else(|_| root.to_path_buf())
+}
+
+/// `path` relative to `root`, if it is a file the watch should refresh: one of
+/// `extensions`, and not inside `.git`, a vendor directory, or this crate's own caches
+/// (which every refresh writes to).
+fn relevant_path(root: &Path, path: &Path, extensions: &ExtensionFilter) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    // Deleted files cannot be sniffed for a shebang, so only the name counts here.
+    let extension = relative.extension().map_or(Some(""), |e| e.to_str())?;
+    if !extensions.matches(relative, extension) {
+        return None;
+    }
+    let mut ancestor = root.to_path_buf();
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        ancestor.push(name);
+        let name = name.to_str()?;
+        if config::is_own_cache_path(&ancestor)
+            || config::is_git_dir(&ancestor)
+            || config::VENDOR_DIRS.contains(&name)
+        {
+            return None;
+        }
+    }
+    Some(relative.to_string_lossy().into_owned())
+}
+
+/// Starts watching `root` recursively. `refresh` is first called once for the whole root,
+/// then with every debounced batch of changed files, on a background thread. Fails if
+/// the root already has a watch of this `kind` or cannot be watched.
+pub fn start(root: &Path, kind: WatchKind, extensions: Vec<String>, mut refresh: RefreshFn) -> Result<(), String> {
+    let root = canonical_root(root);
+    let extensions = ExtensionFilter::new(&extensions)?;
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    if watches.contains_key(&(root.clone(), kind)) {
+        return Err(format!("{:?} is already being watched", root));
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            // The worker is gone once the watch stops; late events have nowhere to go.
+            let _ = sender.send(event.paths);
+        }
+    })
+    .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch {:?}: {}", root, e))?;
+
+    let worker_root = root.clone();
+    let worker = thread::spawn(move || {
+        refresh(Vec::new());
+        loop {
+            let Ok(paths) = receiver.recv() else { return };
+            let mut changed = BTreeSet::new();
+            changed.extend(
+                paths
+                    .iter()
+                    .filter_map(|p| relevant_path(&worker_root, p, &extensions)),
+            );
+            let stopped = loop {
+                match receiver.recv_timeout(DEBOUNCE) {
+                    Ok(paths) => changed.extend(
+                        paths
+                            .iter()
+                            .filter_map(|p| relevant_path(&worker_root, p, &extensions)),
+                    ),
+                    Err(RecvTimeoutError::Timeout) => break false,
+                    Err(RecvTimeoutError::Disconnected) => break true,
+                }
+            };
+            if stopped {
+                return;
+            }
+            if !changed.is_empty() {
+                refresh(changed.into_iter().collect());
+            }
+        }
+    });
+    watches.insert((root, kind), Watch { watcher, worker });
+    Ok(())
+}
+
+/// Stops every watch, waiting for refreshes in progress. Returns how many were stopped.
+pub fn stop_all() -> usize {
+    let watches: Vec<Watch> = WATCHES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain()
+        .map(|(_, watch)| watch)
+        .collect();
+    let stopped = watches.len();
+    for Watch { watcher, worker } in watches {
+        drop(watcher);
+        let _ = worker.join();
+    }
+    stopped
+}
+
+/// Stops the watch of `kind` on `root` and waits for a refresh in progress to finish.
+/// Returns `false` if the root had no such watch.
+pub fn stop(root: &Path, kind: WatchKind) -> bool {
+    let watch = WATCHES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&(canonical_root(root), kind));
+    match watch {
+        Some(Watch { watcher, worker }) => {
+            drop(watcher);
+            let _ = worker.join();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relevant(path: &str) -> Option<String> {
+        let extensions = ExtensionFilter::new(&["py", "*.test.ts"]).unwrap();
+        relevant_path(Path::new("/project"), Path::new(path), &extensions)
+    }
+
+    #[test]
+    fn sources_of_the_watched_extensions_are_relevant() {
+        assert_eq!(
+            relevant("/project/src/main.py").map(PathBuf::from),
+            Some(Path::new("src").join("main.py"))
+        );
+        assert_eq!(relevant("/project/a.test.ts"), Some("a.test.ts".to_string()));
+    }
+
+    #[test]
+    fn other_extensions_are_not_relevant() {
+        assert_eq!(relevant("/project/src/main.rs"), None);
+        assert_eq!(relevant("/project/src/a.ts"), None);
+        assert_eq!(relevant("/project/Makefile"), None);
+    }
+
+    #[test]
+    fn own_caches_git_and_vendor_directories_are_not_relevant() {
+        assert_eq!(relevant("/project/.cache/file_scanner_parse_cache/contexts.sled/db.py"), None);
+        assert_eq!(relevant("/project/.git/hooks/pre-commit.py"), None);
+        assert_eq!(relevant("/project/node_modules/pkg/index.py"), None);
+        assert_eq!(relevant("/project/src/.venv/lib/site.py"), None);
+        assert_eq!(relevant("/project/target/build.py"), None);
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_not_relevant() {
+        assert_eq!(relevant("/elsewhere/main.py"), None);
+        assert_eq!(relevant("/project/../elsewhere/main.py"), None);
+    }
+}
@@ -12,14 +12,23 @@ def _get_relative_path_str(abs_path_str: str, project_root_path: Path) -> str:
         # Ensure project_root_path is an ancestor or the same as abs_path
         if project_root_path in abs_path.parents or project_root_path == abs_path:
             return str(abs_path.relative_to(project_root_path))
-        else:  # If not an ancestor, return the original absolute path or just the name
-            return abs_path.name  # Or abs_path_str if full path is preferred in this edge case
+        else:  # Not under the root, e.g. a file from an additional workspace root
+            return abs_path_str
     except ValueError:  # Handles cases like paths on different drives for Windows
         return Path(abs_path_str).name  # Fallback to filename
     except Exception:  # Catch any other path-related errors
         return abs_path_str  # Fallback to original string
 
 
+def _file_header(relative_file_path_str: str, file_data: Dict[str, Any]) -> str:
+    """'FILE: <path>' line, noting syntax errors when the file was only partially parsed."""
+    parse_errors = file_data.get("parse_errors") or []
+    if not parse_errors:
+        return f'FILE: {relative_file_path_str}'
+    lines = ", ".join(str(error.get("line")) for error in parse_errors)
+    return f'FILE: {relative_file_path_str} (partially parsed, syntax errors at lines {lines})'
+
+
 def format_project_context(
     project_root_path: Path,
     file_contexts: List[Dict[str, Any]],
@@ -49,7 +58,7 @@ def format_project_context(
 
             func_representation = ""
             if compactness_level == 0:  # Function names only
-                func_representation = name
+                func_representation = func.get('qualified_name') or name
             elif compactness_level == 1:  # Function signature
                 func_representation = body.strip() if body else name
             elif compactness_level == 2:  # Signature and comments
@@ -69,7 +78,40 @@ def format_project_context(
         file_content_str = "\n".join(bullet_point_parts)
 
         output_blocks.append(
-            f'FILE: {relative_file_path_str}\n{file_content_str}')
+            f'{_file_header(relative_file_path_str, file_data)}\n{file_content_str}')
+
+    return "\n\n".join(output_blocks)
+
+
+def _format_outline_symbols(symbols: List[Dict[str, Any]], indent: int) -> List[str]:
+    """Renders outline symbols as an indented bullet tree."""
+    lines = []
+    for symbol in symbols:
+        lines.append(
+            f"{'  ' * indent}- {symbol.get('kind', '')} {symbol.get('name', '')} "
+            f"(L{symbol.get('start_line')}-{symbol.get('end_line')})")
+        lines.extend(_format_outline_symbols(
+            symbol.get('children', []), indent + 1))
+    return lines
+
+
+def format_project_outline(
+    project_root_path: Path,
+    file_contexts: List[Dict[str, Any]]
+) -> str:
+    """
+    Formats each file's hierarchical symbol tree (modules -> classes -> methods),
+    grouped by file. Paths are relative to project_root_path.
+    """
+    output_blocks = []
+    for file_data in sorted(file_contexts, key=lambda x: x.get('path', '')):
+        outline = file_data.get("outline")
+        if not outline:
+            continue
+        relative_file_path_str = _get_relative_path_str(
+            file_data.get("path", "UNKNOWN_FILE"), project_root_path)
+        symbols_str = "\n".join(_format_outline_symbols(outline, 0))
+        output_blocks.append(f'{_file_header(relative_file_path_str, file_data)}\n{symbols_str}')
 
     return "\n\n".join(output_blocks)
 
@@ -83,8 +125,11 @@ def format_search_results(
     """
     output_blocks = []
     results = search_results.get("results", [])
+    # Ranked results carry a score and arrive best first; keep that order.
+    if not any("score" in file_result for file_result in results):
+        results = sorted(results, key=lambda x: x.get('path', ''))
 
-    for file_result in sorted(results, key=lambda x: x.get('path', '')):
+    for file_result in results:
         abs_file_path_str = file_result.get("path", "UNKNOWN_FILE")
         relative_file_path_str = _get_relative_path_str(
             abs_file_path_str, project_root_path)
@@ -96,13 +141,30 @@ def format_search_results(
         match_parts = []
         for match in matches:
             line_number = match.get('line_number', 'N/A')
-            context = match.get('context', '')
+            end_line_number = match.get('end_line_number', line_number)
+            if end_line_number != line_number:
+                line_number = f"{line_number}-{end_line_number}"
+            if 'line' in match:
+                # Output version 2: plain lines, marked here the way version 1 does.
+                context = "\n".join(
+                    [f"   {line}" for line in match.get('context_before', [])]
+                    + [f">> {line}" for line in match['line'].split("\n")]
+                    + [f"   {line}" for line in match.get('context_after', [])])
+            else:
+                context = match.get('context', '')
+            captures = "".join(
+                f' capture_{name}="{value}"' for name, value in match.get('captures', {}).items())
+            column = f' column="{match["column"]}"' if "column" in match else ""
+            symbol = f' symbol="{match["symbol"]}"' if match.get("symbol") else ""
+            pattern = f' pattern="{match["pattern"]}"' if match.get("pattern") else ""
             match_parts.append(
-                f'<MATCH line="{line_number}">\n{context}\n</MATCH>')
+                f'<MATCH line="{line_number}"{column}{symbol}{pattern}{captures}>\n{context}\n</MATCH>')
 
         file_content_str = "\n".join(match_parts)
+        encoding = f' encoding="{file_result["encoding"]}"' if file_result.get("encoding") else ""
+        score = f' score="{file_result["score"]:.2f}"' if "score" in file_result else ""
         output_blocks.append(
-            f'<FILE path="{relative_file_path_str}">\n{file_content_str}\n</FILE>')
+            f'<FILE path="{relative_file_path_str}"{encoding}{score}>\n{file_content_str}\n</FILE>')
 
     return "\n\n".join(output_blocks)
 
@@ -139,10 +201,15 @@ def format_concept_search_results(
             'similarity', 0.0), reverse=True)
 
         for match in sorted_functions:
-            function_name = match.get('function', 'N/A')
+            function_name = match.get(
+                'qualified_name') or match.get('function', 'N/A')
             similarity = match.get('similarity', 0.0)
             match_parts.append(
                 f"- {function_name} (Similarity: {similarity:.4f})")
+            if match.get('context_before'):
+                match_parts.append(f"  Context before:\n{match['context_before']}")
+            if match.get('context_after'):
+                match_parts.append(f"  Context after:\n{match['context_after']}")
 
         file_content_str = "\n".join(match_parts)
         output_blocks.append(
@@ -173,13 +173,40 @@ def _invoke_ffi_function(
 # --- Public FFI Invocation Functions ---
 
 
+# Signature of the Rust `ScanProgressCallback` and `ConceptSearchProgressCallback`:
+# (progress_json, user_data).
+PROGRESS_CALLBACK = ctypes.CFUNCTYPE(None, ctypes.c_char_p, ctypes.c_void_p)
+
+
+def _progress_callback(on_progress: Callable[[Dict[str, Any]], None]) -> Any:
+    """
+    Wraps on_progress as a PROGRESS_CALLBACK. The result must stay referenced until the
+    FFI call returns.
+    """
+    def _progress_trampoline(progress_json: bytes, _user_data: Any) -> None:
+        try:
+            on_progress(json.loads(progress_json.decode('utf-8')))
+        except Exception:
+            # Exceptions cannot propagate through the Rust frames.
+            pass
+
+    return PROGRESS_CALLBACK(_progress_trampoline)
+
+
 def invoke_scan_and_parse(
-    project_path: str, extensions: List[str], compactness_level: int, timeout_sec: int, debug: bool = False
+    project_path: str, extensions: List[str], compactness_level: int, timeout_sec: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None,
+    on_progress: Optional[Callable[[Dict[str, Any]], None]] = None
 ) -> Dict[str, Any]:
     """
     Invokes the 'scan_and_parse' FFI function.
+    Note: options are passed as a JSON string to Rust.
+    If on_progress is given, 'scan_and_parse_streaming' is used instead and on_progress
+    receives {"phase", "files_discovered", "files_processed"} while the scan runs.
+    It is called from Rust walker threads.
     """
     extensions_str = ",".join(extensions)
+    options_json_str = json.dumps(options or {})
     timeout_ms = timeout_sec * 1000
 
     # Prepare ctype arguments
@@ -187,23 +214,62 @@ def invoke_scan_and_parse(
     extensions_c = ctypes.c_char_p(extensions_str.encode('utf-8'))
     compactness_level_c = ctypes.c_uint8(compactness_level)
     timeout_ms_c = ctypes.c_uint32(timeout_ms)
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
     debug_c = ctypes.c_bool(debug)
 
-    arg_types = [ctypes.c_char_p, ctypes.c_char_p,
-                 ctypes.c_uint8, ctypes.c_uint32, ctypes.c_bool]
-    args_tuple = (root_path_c, extensions_c,
-                  compactness_level_c, timeout_ms_c, debug_c)
+    if on_progress is None:
+        rust_fn_name = "scan_and_parse"
+        arg_types = [ctypes.c_char_p, ctypes.c_char_p,
+                     ctypes.c_uint8, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
+        args_tuple = (root_path_c, extensions_c,
+                      compactness_level_c, timeout_ms_c, options_json_c, debug_c)
+    else:
+        progress_callback_c = _progress_callback(on_progress)
+        rust_fn_name = "scan_and_parse_streaming"
+        arg_types = [ctypes.c_char_p, ctypes.c_char_p,
+                     ctypes.c_uint8, ctypes.c_uint32, ctypes.c_char_p,
+                     PROGRESS_CALLBACK, ctypes.c_void_p, ctypes.c_bool]
+        args_tuple = (root_path_c, extensions_c,
+                      compactness_level_c, timeout_ms_c, options_json_c,
+                      progress_callback_c, None, debug_c)
+
+    return _invoke_ffi_function(rust_fn_name, arg_types, args_tuple, debug, "invoke_scan_and_parse")
+
+
+
+def invoke_scan_changed_since(
+    project_path: str, git_ref: str, extensions: List[str], compactness_level: int, timeout_sec: int,
+    debug: bool = False, options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Invokes the 'scan_changed_since' FFI function: 'scan_and_parse' limited to the files
+    changed since 'git_ref'.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    git_ref_c = ctypes.c_char_p(git_ref.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(",".join(extensions).encode('utf-8'))
+    compactness_level_c = ctypes.c_uint8(compactness_level)
+    timeout_ms_c = ctypes.c_uint32(timeout_sec * 1000)
+    options_json_c = ctypes.c_char_p(json.dumps(options or {}).encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
 
-    return _invoke_ffi_function("scan_and_parse", arg_types, args_tuple, debug, "invoke_scan_and_parse")
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p,
+                 ctypes.c_uint8, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, git_ref_c, extensions_c,
+                  compactness_level_c, timeout_ms_c, options_json_c, debug_c)
 
+    return _invoke_ffi_function("scan_changed_since", arg_types, args_tuple, debug, "invoke_scan_changed_since")
 
 def invoke_project_wide_search(
-    project_path: str, search_string: str, extensions: List[str], context_lines: int, timeout_sec: int, debug: bool = False
+    project_path: str, search_string: str, extensions: List[str], context_lines: int, timeout_sec: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
 ) -> Dict[str, Any]:
     """
     Invokes the 'project_wide_search' FFI function.
+    Note: options are passed as a JSON string to Rust.
     """
     extensions_str = ",".join(extensions)
+    options_json_str = json.dumps(options or {})
     timeout_ms = timeout_sec * 1000
 
     root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
@@ -211,24 +277,33 @@ def invoke_project_wide_search(
     extensions_c = ctypes.c_char_p(extensions_str.encode('utf-8'))
     context_lines_c = ctypes.c_uint8(context_lines)
     timeout_ms_c = ctypes.c_uint32(timeout_ms)
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
     debug_c = ctypes.c_bool(debug)
 
     arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p,
-                 ctypes.c_uint8, ctypes.c_uint32, ctypes.c_bool]
+                 ctypes.c_uint8, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
     args_tuple = (root_path_c, search_string_c, extensions_c,
-                  context_lines_c, timeout_ms_c, debug_c)
+                  context_lines_c, timeout_ms_c, options_json_c, debug_c)
 
     return _invoke_ffi_function("project_wide_search", arg_types, args_tuple, debug, "invoke_project_wide_search")
 
 
 def invoke_concept_search(
-    project_path: str, query: str, extensions: List[str], top_n: int, timeout_sec: int, debug: bool = False
+    project_path: str, query: str, extensions: List[str], top_n: int, timeout_sec: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None,
+    on_progress: Optional[Callable[[Dict[str, Any]], None]] = None
 ) -> Dict[str, Any]:
     """
     Invokes the 'concept_search' FFI function.
-    Note: extensions are passed as a JSON string to Rust for concept_search.
+    Note: extensions and options are passed as JSON strings to Rust for concept_search.
+    If on_progress is given, 'concept_search_streaming' is used instead and on_progress
+    receives the scan counts ({"phase": "scanning", "files_discovered", "files_processed"})
+    and then each partial ranking ({"phase": "embedding" or "reranking", "results",
+    "functions_scored", "functions_total"}) as it is produced. It may be called from a
+    Rust worker thread.
     """
     extensions_json_str = json.dumps(extensions)
+    options_json_str = json.dumps(options or {})
     timeout_ms = timeout_sec * 1000
 
     root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
@@ -236,16 +311,29 @@ def invoke_concept_search(
     extensions_json_c = ctypes.c_char_p(extensions_json_str.encode('utf-8'))
     top_n_c = ctypes.c_size_t(top_n)
     timeout_ms_c = ctypes.c_uint32(timeout_ms)
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
     debug_c = ctypes.c_bool(debug)
 
-    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p,
-                 ctypes.c_size_t, ctypes.c_uint32, ctypes.c_bool]
-    args_tuple = (root_path_c, query_c, extensions_json_c,
-                  top_n_c, timeout_ms_c, debug_c)
+    if on_progress is None:
+        rust_fn_name = "concept_search"
+        arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p,
+                     ctypes.c_size_t, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
+        args_tuple = (root_path_c, query_c, extensions_json_c,
+                      top_n_c, timeout_ms_c, options_json_c, debug_c)
+    else:
+        # Must stay referenced until the FFI call returns.
+        progress_callback_c = _progress_callback(on_progress)
+        rust_fn_name = "concept_search_streaming"
+        arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p,
+                     ctypes.c_size_t, ctypes.c_uint32, ctypes.c_char_p,
+                     PROGRESS_CALLBACK, ctypes.c_void_p, ctypes.c_bool]
+        args_tuple = (root_path_c, query_c, extensions_json_c,
+                      top_n_c, timeout_ms_c, options_json_c,
+                      progress_callback_c, None, debug_c)
 
     # Special handling for concept_search results
     raw_result = _invoke_ffi_function(
-        "concept_search", arg_types, args_tuple, debug, "invoke_concept_search")
+        rust_fn_name, arg_types, args_tuple, debug, "invoke_concept_search")
 
     # Ensure debug_log list exists if debug is true, done early.
     if debug and "debug_log" not in raw_result:
@@ -298,6 +386,407 @@ def invoke_concept_search(
 
     return raw_result
 
+
+def invoke_warm_cache(
+    project_path: str, paths: List[str], debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Invokes the 'warm_cache' FFI function.
+    Note: paths and options are passed as JSON strings to Rust; paths are absolute or
+    relative to project_path.
+    """
+    paths_json_str = json.dumps(paths)
+    options_json_str = json.dumps(options or {})
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    paths_json_c = ctypes.c_char_p(paths_json_str.encode('utf-8'))
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, paths_json_c, options_json_c, debug_c)
+
+    return _invoke_ffi_function("warm_cache", arg_types, args_tuple, debug, "invoke_warm_cache")
+
+
+def invoke_build_concept_index(
+    project_path: str, extensions: List[str], timeout_sec: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Invokes the 'build_concept_index' FFI function, which embeds the project's functions
+    into an in-memory index so invoke_query_concept_index only has to embed the query.
+    Note: extensions and options are passed as JSON strings to Rust; options are the
+    concept search options and also apply to every query against the index.
+    """
+    extensions_json_str = json.dumps(extensions)
+    options_json_str = json.dumps(options or {})
+    timeout_ms = timeout_sec * 1000
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    extensions_json_c = ctypes.c_char_p(extensions_json_str.encode('utf-8'))
+    timeout_ms_c = ctypes.c_uint32(timeout_ms)
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, extensions_json_c, timeout_ms_c, options_json_c, debug_c)
+
+    return _invoke_ffi_function("build_concept_index", arg_types, args_tuple, debug, "invoke_build_concept_index")
+
+
+def invoke_query_concept_index(project_path: str, query: str, top_n: int) -> Dict[str, Any]:
+    """
+    Invokes the 'query_concept_index' FFI function. Returns the same shape as
+    invoke_concept_search, or an error if invoke_build_concept_index has not been
+    called for project_path.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    query_c = ctypes.c_char_p(query.encode('utf-8'))
+    top_n_c = ctypes.c_size_t(top_n)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_size_t]
+    args_tuple = (root_path_c, query_c, top_n_c)
+
+    return _invoke_ffi_function("query_concept_index", arg_types, args_tuple, False, "invoke_query_concept_index")
+
+
+def invoke_find_similar_functions(
+    project_path: str, file_path: str, function_name: str, top_n: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None, line: int = 0, timeout_sec: int = 0
+) -> Dict[str, Any]:
+    """
+    Invokes the 'find_similar_functions' FFI function, which ranks the project's functions
+    by similarity to function_name in file_path. Returns the same shape as
+    invoke_concept_search. line (1-based, 0 for none) picks one of several functions of
+    that name; timeout_sec (0 for none) bounds embedding uncached functions.
+    Note: options are the concept search options, passed as a JSON string to Rust.
+    """
+    options_json_str = json.dumps(options or {})
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    file_c = ctypes.c_char_p(file_path.encode('utf-8'))
+    function_name_c = ctypes.c_char_p(function_name.encode('utf-8'))
+    line_c = ctypes.c_size_t(line)
+    top_n_c = ctypes.c_size_t(top_n)
+    timeout_ms_c = ctypes.c_uint32(timeout_sec * 1000)
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_size_t,
+                 ctypes.c_size_t, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, file_c, function_name_c, line_c,
+                  top_n_c, timeout_ms_c, options_json_c, debug_c)
+
+    return _invoke_ffi_function("find_similar_functions", arg_types, args_tuple, debug, "invoke_find_similar_functions")
+
+
+def invoke_watch_start(
+    project_path: str, extensions: List[str], options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Invokes the 'watch_start' FFI function, which keeps the project's parse and embedding
+    caches up to date in the background until invoke_watch_stop is called.
+    Note: options select the embedding models and are passed as a JSON string to Rust.
+    """
+    options_json_str = json.dumps(options or {})
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(",".join(extensions).encode('utf-8'))
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p]
+    args_tuple = (root_path_c, extensions_c, options_json_c)
+
+    return _invoke_ffi_function("watch_start", arg_types, args_tuple, False, "invoke_watch_start")
+
+
+def invoke_watch_stop(project_path: str) -> bool:
+    """
+    Stops the watch started by invoke_watch_start. Returns False if the project was not watched.
+    """
+    rust_lib = _get_rust_library()
+    rust_lib.watch_stop.argtypes = [ctypes.c_char_p]
+    rust_lib.watch_stop.restype = ctypes.c_bool
+    return rust_lib.watch_stop(project_path.encode('utf-8'))
+
+
+def invoke_build_call_graph(
+    project_path: str, extensions: List[str], timeout_sec: int, debug: bool = False
+) -> Dict[str, Any]:
+    """
+    Invokes the 'build_call_graph' FFI function.
+    """
+    extensions_str = ",".join(extensions)
+    timeout_ms = timeout_sec * 1000
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(extensions_str.encode('utf-8'))
+    timeout_ms_c = ctypes.c_uint32(timeout_ms)
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_uint32, ctypes.c_bool]
+    args_tuple = (root_path_c, extensions_c, timeout_ms_c, debug_c)
+
+    return _invoke_ffi_function("build_call_graph", arg_types, args_tuple, debug, "invoke_build_call_graph")
+
+
+def invoke_scan_todos(
+    project_path: str, extensions: List[str], timeout_sec: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Invokes the 'scan_todos' FFI function. 'options' takes the same keys as for
+    'scan_and_parse' (globs, size limit, cancellation token, ...).
+    """
+    extensions_str = ",".join(extensions)
+    timeout_ms = timeout_sec * 1000
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(extensions_str.encode('utf-8'))
+    timeout_ms_c = ctypes.c_uint32(timeout_ms)
+    options_json_c = ctypes.c_char_p(json.dumps(options or {}).encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_uint32, ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, extensions_c, timeout_ms_c, options_json_c, debug_c)
+
+    return _invoke_ffi_function("scan_todos", arg_types, args_tuple, debug, "invoke_scan_todos")
+
+
+def invoke_sync_workspace_symbols(
+    project_path: str, extensions: List[str], paths: Optional[List[str]] = None, debug: bool = False
+) -> Dict[str, Any]:
+    """
+    Invokes the 'sync_workspace_symbols' FFI function.
+    Note: paths are passed as a JSON string to Rust; they are absolute or relative to
+    project_path. None (or an empty list) rescans the whole project.
+    """
+    extensions_str = ",".join(extensions)
+    paths_json_str = json.dumps(paths or [])
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(extensions_str.encode('utf-8'))
+    paths_json_c = ctypes.c_char_p(paths_json_str.encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, extensions_c, paths_json_c, debug_c)
+
+    return _invoke_ffi_function("sync_workspace_symbols", arg_types, args_tuple, debug, "invoke_sync_workspace_symbols")
+
+
+# Callbacks of active symbol subscriptions, per project path. Rust calls them until the
+# subscription stops, so they must stay referenced until then.
+_symbol_subscriptions: Dict[str, Any] = {}
+
+
+def invoke_subscribe_workspace_symbols(
+    project_path: str, extensions: List[str], on_delta: Callable[[Dict[str, Any]], None], debug: bool = False
+) -> Dict[str, Any]:
+    """
+    Invokes the 'subscribe_workspace_symbols' FFI function, which watches the project and
+    calls on_delta with {"added", "removed", ...} for each settled burst of changes until
+    invoke_unsubscribe_workspace_symbols is called. The first call reports every symbol
+    as added. on_delta is called from a Rust background thread.
+    """
+    delta_callback_c = _progress_callback(on_delta)
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(",".join(extensions).encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, PROGRESS_CALLBACK, ctypes.c_void_p, ctypes.c_bool]
+    args_tuple = (root_path_c, extensions_c, delta_callback_c, None, debug_c)
+
+    result = _invoke_ffi_function(
+        "subscribe_workspace_symbols", arg_types, args_tuple, False, "invoke_subscribe_workspace_symbols")
+    if not result.get("error"):
+        _symbol_subscriptions[project_path] = delta_callback_c
+    return result
+
+
+def invoke_unsubscribe_workspace_symbols(project_path: str) -> bool:
+    """
+    Stops the subscription started by invoke_subscribe_workspace_symbols. Returns False if
+    the project had no subscription.
+    """
+    rust_lib = _get_rust_library()
+    rust_lib.unsubscribe_workspace_symbols.argtypes = [ctypes.c_char_p]
+    rust_lib.unsubscribe_workspace_symbols.restype = ctypes.c_bool
+    stopped = rust_lib.unsubscribe_workspace_symbols(project_path.encode('utf-8'))
+    # Rust no longer calls back once the stop returns.
+    _symbol_subscriptions.pop(project_path, None)
+    return stopped
+
+def invoke_fuzzy_find(
+    project_path: str, query: str, extensions: List[str], options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Invokes the 'fuzzy_find' FFI function, which ranks file paths and function names
+    against an fzf-style query for "Go to file/symbol" pickers.
+    Note: options (max_results, files, symbols) are passed as a JSON string to Rust.
+    """
+    options_json_str = json.dumps(options or {})
+
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    query_c = ctypes.c_char_p(query.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(",".join(extensions).encode('utf-8'))
+    options_json_c = ctypes.c_char_p(options_json_str.encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p]
+    args_tuple = (root_path_c, query_c, extensions_c, options_json_c)
+
+    return _invoke_ffi_function("fuzzy_find", arg_types, args_tuple, False, "invoke_fuzzy_find")
+
+
+def invoke_find_definition(project_path: str, symbol: str, extensions: List[str]) -> Dict[str, Any]:
+    """
+    Invokes the 'find_definition' FFI function, which returns the file and line span of
+    every declaration named by a plain or qualified symbol name, for go-to-definition.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    symbol_c = ctypes.c_char_p(symbol.encode('utf-8'))
+    extensions_c = ctypes.c_char_p(",".join(extensions).encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p]
+    args_tuple = (root_path_c, symbol_c, extensions_c)
+
+    return _invoke_ffi_function("find_definition", arg_types, args_tuple, False, "invoke_find_definition")
+
+
+def invoke_find_references(project_path: str, symbol: str) -> Dict[str, Any]:
+    """
+    Invokes the 'find_references' FFI function, which lists every occurrence of a symbol
+    classified as definition, call, reference, or comment_or_string text.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    symbol_c = ctypes.c_char_p(symbol.encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p]
+    args_tuple = (root_path_c, symbol_c)
+
+    return _invoke_ffi_function("find_references", arg_types, args_tuple, False, "invoke_find_references")
+
+
+def invoke_write_text_file(path: str, text: str, debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'write_text_file' FFI function: an atomic write that keeps the replaced
+    file's CRLF line endings and byte order mark.
+    """
+    path_c = ctypes.c_char_p(path.encode('utf-8'))
+    text_c = ctypes.c_char_p(text.encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p, ctypes.c_char_p]
+    args_tuple = (path_c, text_c)
+
+    return _invoke_ffi_function("write_text_file", arg_types, args_tuple, debug, "invoke_write_text_file")
+
+
+def invoke_read_audit_log(project_path: str, limit: int = 0, debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'read_audit_log' FFI function. A limit of 0 returns every record.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    limit_c = ctypes.c_size_t(limit)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_size_t]
+    args_tuple = (root_path_c, limit_c)
+
+    return _invoke_ffi_function("read_audit_log", arg_types, args_tuple, debug, "invoke_read_audit_log")
+
+
+def invoke_compact_embedding_cache(project_path: str, debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'compact_embedding_cache' FFI function, which drops the cached
+    embeddings of deleted and renamed files.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+    debug_c = ctypes.c_bool(debug)
+
+    arg_types = [ctypes.c_char_p, ctypes.c_bool]
+    args_tuple = (root_path_c, debug_c)
+
+    return _invoke_ffi_function("compact_embedding_cache", arg_types, args_tuple, debug, "invoke_compact_embedding_cache")
+
+
+def invoke_concept_cache_stats(project_path: str, debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'concept_cache_stats' FFI function.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p]
+    args_tuple = (root_path_c,)
+
+    return _invoke_ffi_function("concept_cache_stats", arg_types, args_tuple, debug, "invoke_concept_cache_stats")
+
+
+def invoke_clear_concept_cache(project_path: str, debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'clear_concept_cache' FFI function, which removes every cached embedding
+    of the project.
+    """
+    root_path_c = ctypes.c_char_p(project_path.encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p]
+    args_tuple = (root_path_c,)
+
+    return _invoke_ffi_function("clear_concept_cache", arg_types, args_tuple, debug, "invoke_clear_concept_cache")
+
+
+def invoke_rapid_init(config: Optional[Dict[str, Any]] = None, debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'rapid_init' FFI function, which fixes the thread pool size, model cache
+    location, and preloaded models for the process. Only the first call takes effect.
+    """
+    config_json_c = ctypes.c_char_p(json.dumps(config or {}).encode('utf-8'))
+
+    arg_types = [ctypes.c_char_p]
+    args_tuple = (config_json_c,)
+
+    return _invoke_ffi_function("rapid_init", arg_types, args_tuple, debug, "invoke_rapid_init")
+
+
+def invoke_rapid_shutdown(debug: bool = False) -> Dict[str, Any]:
+    """
+    Invokes the 'rapid_shutdown' FFI function, which stops watches and closes caches.
+    """
+    return _invoke_ffi_function("rapid_shutdown", [], (), debug, "invoke_rapid_shutdown")
+
+
+def create_cancellation_token() -> int:
+    """
+    Creates a cancellation token. Pass it as "cancellation_token" in the options of a
+    scan, concept search, or project-wide search, and call cancel_token from another
+    thread to stop that call early.
+    """
+    rust_lib = _get_rust_library()
+    rust_lib.create_cancellation_token.argtypes = []
+    rust_lib.create_cancellation_token.restype = ctypes.c_uint64
+    return rust_lib.create_cancellation_token()
+
+
+def cancel_token(token: int) -> bool:
+    """
+    Cancels every call running with the token. Returns False for unknown or released tokens.
+    """
+    rust_lib = _get_rust_library()
+    rust_lib.cancel.argtypes = [ctypes.c_uint64]
+    rust_lib.cancel.restype = ctypes.c_bool
+    return rust_lib.cancel(token)
+
+
+def release_cancellation_token(token: int) -> bool:
+    """
+    Releases a token from create_cancellation_token once no call uses it anymore.
+    """
+    rust_lib = _get_rust_library()
+    rust_lib.release_cancellation_token.argtypes = [ctypes.c_uint64]
+    rust_lib.release_cancellation_token.restype = ctypes.c_bool
+    return rust_lib.release_cancellation_token(token)
+
 # Example of how to potentially unload the library if needed, e.g., for testing or specific scenarios.
 # This is OS-dependent and can be tricky.
 # For Windows:
@@ -321,3 +810,4 @@ def invoke_concept_search(
 
 # For now, we rely on Python's GC to unload the DLL when s_rust_lib is no longer referenced
 # or when the program exits. Explicit unloading is commented out.
+
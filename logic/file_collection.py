@@ -1,17 +1,28 @@
+import json
 import sys
 from pathlib import Path
-from typing import List, Dict, Any
+from typing import Callable, List, Dict, Any, Optional, Sequence, Union
 
 # Import from the new consolidated FFI module
-from logic.ffi import invoke_scan_and_parse, invoke_project_wide_search, invoke_concept_search
+from logic.ffi import invoke_scan_and_parse, invoke_scan_changed_since, invoke_project_wide_search, invoke_concept_search, invoke_warm_cache, invoke_build_call_graph, invoke_scan_todos, invoke_sync_workspace_symbols, invoke_write_text_file, invoke_read_audit_log, invoke_compact_embedding_cache
+
+
+def _root_argument(project_path: Union[Path, Sequence[Path]]) -> str:
+    """One root as a plain path; several roots (a multi-root workspace) as a JSON array."""
+    if isinstance(project_path, (str, Path)):
+        return str(project_path)
+    return json.dumps([str(path) for path in project_path])
 
 
 def collect_and_parse_files_from_rust(
-    project_path: Path, extensions: List[str], compactness_level: int, timeout: int, debug: bool = False
+    project_path: Union[Path, Sequence[Path]], extensions: List[str], compactness_level: int, timeout: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None,
+    on_progress: Optional[Callable[[Dict[str, Any]], None]] = None
 ) -> Dict[str, Any]:
     """
     Calls the FFI layer to perform a high-speed scan.
     'timeout' is in seconds.
+    'on_progress', if given, receives the files discovered and processed while the scan runs.
     """
     if debug:
         # Example of a debug log specific to this layer, if needed.
@@ -21,11 +32,13 @@ def collect_and_parse_files_from_rust(
 
     try:
         raw_result = invoke_scan_and_parse(
-            project_path=str(project_path),
+            project_path=_root_argument(project_path),
             extensions=extensions,  # Pass list directly
             compactness_level=compactness_level,
             timeout_sec=timeout,    # Pass timeout in seconds
-            debug=debug
+            debug=debug,
+            options=options,
+            on_progress=on_progress
         )
 
         # The ffi.py layer now handles initial error checking (lib load, null ptr, json decode)
@@ -49,6 +62,14 @@ def collect_and_parse_files_from_rust(
         # It should include 'file_contexts', 'debug_log', and 'timed_out_internally'.
         timed_out_internally = raw_result.get("timed_out_internally", False)
         status = "success"
+        if raw_result.get("errors"):
+            # Strict mode turned a silent skip into an explicit failure.
+            return {
+                **raw_result,
+                "status": "error_strict",
+                "error": "\n".join(raw_result["errors"]),
+                "timed_out": timed_out_internally,
+            }
         if timed_out_internally:
             status = "success_partial_internal_timeout"
             if "files_processed_before_timeout" not in raw_result:
@@ -83,7 +104,8 @@ def collect_and_parse_files_from_rust(
 
 
 def search_in_files_from_rust(
-    project_path: Path, search_string: str, extensions: List[str], context_lines: int, timeout: int, debug: bool = False
+    project_path: Union[Path, Sequence[Path]], search_string: str, extensions: List[str], context_lines: int, timeout: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
 ) -> Dict[str, Any]:
     """
     Calls the FFI layer to perform a project-wide search.
@@ -91,12 +113,13 @@ def search_in_files_from_rust(
     """
     try:
         raw_result = invoke_project_wide_search(
-            project_path=str(project_path),
+            project_path=_root_argument(project_path),
             search_string=search_string,
             extensions=extensions,  # Pass list directly
             context_lines=context_lines,
             timeout_sec=timeout,   # Pass timeout in seconds
-            debug=debug
+            debug=debug,
+            options=options
         )
 
         if "error" in raw_result:
@@ -124,11 +147,14 @@ def search_in_files_from_rust(
 
 
 def concept_search_from_rust(
-    project_path: Path, query: str, extensions: List[str], top_n: int, timeout: int, debug: bool = False
+    project_path: Union[Path, Sequence[Path]], query: str, extensions: List[str], top_n: int, timeout: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None,
+    on_progress: Optional[Callable[[Dict[str, Any]], None]] = None
 ) -> Dict[str, Any]:
     """
     Calls the FFI layer to perform a concept search.
     'timeout' is in seconds.
+    'on_progress', if given, receives partial top-N rankings while the search runs.
     """
     fc_debug_logs: List[str] = []
     if debug:
@@ -138,12 +164,14 @@ def concept_search_from_rust(
     try:
         # Extensions are passed as a list; ffi.py handles JSON conversion for concept_search
         raw_result = invoke_concept_search(
-            project_path=str(project_path),
+            project_path=_root_argument(project_path),
             query=query,
             extensions=extensions,  # Pass list directly
             top_n=top_n,
             timeout_sec=timeout,   # Pass timeout in seconds
-            debug=debug
+            debug=debug,
+            options=options,
+            on_progress=on_progress
         )
 
         # ffi.py's invoke_concept_search already handles merging its debug logs
@@ -184,3 +212,222 @@ def concept_search_from_rust(
             "status": "error_file_collection_critical",
             "error": critical_error_msg,
         }
+
+
+def warm_cache_from_rust(
+    project_path: Path, paths: List[str], debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Calls the FFI layer to embed exactly the given files into the concept search cache.
+    'options' selects the embedding models ({"model": ..., "language_models": {...}})
+    and where they load from ({"local_models": {...}, "offline": True}), and the
+    precision the embeddings are cached with ({"quantization": "int8"}).
+    """
+    try:
+        raw_result = invoke_warm_cache(
+            project_path=str(project_path),
+            paths=paths,
+            debug=debug,
+            options=options
+        )
+        status = "error_ffi_call" if raw_result.get("error") else "success"
+        return {**raw_result, "status": status}
+
+    except Exception as ex:
+        return {
+            "debug_log": [f"Critical error in warm_cache_from_rust: {ex}"],
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
+
+
+def build_call_graph_from_rust(
+    project_path: Path, extensions: List[str], timeout: int, debug: bool = False
+) -> Dict[str, Any]:
+    """
+    Calls the FFI layer to build the project's call graph. The result's 'adjacency'
+    maps each function to the functions it calls; 'callers' is the inverse mapping.
+    'timeout' is in seconds.
+    """
+    try:
+        raw_result = invoke_build_call_graph(
+            project_path=str(project_path),
+            extensions=extensions,
+            timeout_sec=timeout,
+            debug=debug
+        )
+        if raw_result.get("error"):
+            return {**raw_result, "status": "error_ffi_call"}
+
+        callers: Dict[str, List[str]] = {}
+        for caller, callees in raw_result.get("adjacency", {}).items():
+            for callee in callees:
+                callers.setdefault(callee, []).append(caller)
+        status = "success_partial_internal_timeout" if raw_result.get(
+            "timed_out_internally") else "success"
+        return {**raw_result, "callers": callers, "status": status}
+
+    except Exception as ex:
+        return {
+            "adjacency": {},
+            "debug_log": [f"Critical error in build_call_graph_from_rust: {ex}"],
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
+
+
+
+def scan_changed_since_from_rust(
+    project_path: Path, git_ref: str, extensions: List[str], compactness_level: int, timeout: int,
+    debug: bool = False, options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Calls the FFI layer to parse only the files changed since 'git_ref' (a commit, branch,
+    or tag), including uncommitted and untracked files. Returns the same shape as
+    collect_and_parse_files_from_rust. 'timeout' is in seconds.
+    """
+    try:
+        raw_result = invoke_scan_changed_since(
+            project_path=str(project_path),
+            git_ref=git_ref,
+            extensions=extensions,
+            compactness_level=compactness_level,
+            timeout_sec=timeout,
+            debug=debug,
+            options=options
+        )
+        if raw_result.get("errors") or raw_result.get("error"):
+            status = "error_ffi_call"
+        elif raw_result.get("timed_out_internally"):
+            status = "success_partial_internal_timeout"
+        else:
+            status = "success"
+        return {**raw_result, "status": status}
+
+    except Exception as ex:
+        return {
+            "file_contexts": [],
+            "debug_log": [f"Critical error in scan_changed_since_from_rust: {ex}"],
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
+
+def scan_todos_from_rust(
+    project_path: Path, extensions: List[str], timeout: int, debug: bool = False,
+    options: Optional[Dict[str, Any]] = None
+) -> Dict[str, Any]:
+    """
+    Calls the FFI layer to list TODO/FIXME/HACK/XXX comments.
+    'timeout' is in seconds; 'options' are the same scan options as for
+    collect_and_parse_files_from_rust.
+    """
+    try:
+        raw_result = invoke_scan_todos(
+            project_path=str(project_path),
+            extensions=extensions,
+            timeout_sec=timeout,
+            debug=debug,
+            options=options
+        )
+        if raw_result.get("error"):
+            status = "error_ffi_call"
+        elif raw_result.get("timed_out_internally"):
+            status = "success_partial_internal_timeout"
+        else:
+            status = "success"
+        return {**raw_result, "status": status}
+
+    except Exception as ex:
+        return {
+            "todos": [],
+            "debug_log": [f"Critical error in scan_todos_from_rust: {ex}"],
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
+
+
+def sync_workspace_symbols_from_rust(
+    project_path: Path, extensions: List[str], paths: Optional[List[str]] = None, debug: bool = False
+) -> Dict[str, Any]:
+    """
+    Returns the function symbols added and removed since the previous sync of this project,
+    re-parsing only 'paths' (the files changed since then). With no paths the whole project
+    is rescanned; the first sync reports every symbol as added.
+    """
+    try:
+        raw_result = invoke_sync_workspace_symbols(
+            project_path=str(project_path),
+            extensions=extensions,
+            paths=paths,
+            debug=debug
+        )
+        if raw_result.get("error"):
+            status = "error_ffi_call"
+        elif raw_result.get("timed_out_internally"):
+            status = "success_partial_internal_timeout"
+        else:
+            status = "success"
+        return {**raw_result, "status": status}
+
+    except Exception as ex:
+        return {
+            "added": [],
+            "removed": [],
+            "debug_log": [f"Critical error in sync_workspace_symbols_from_rust: {ex}"],
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
+
+
+def write_text_file_from_rust(path: Path, text: str, debug: bool = False) -> None:
+    """
+    Writes a text file atomically (temp file + rename), preserving the line endings and
+    byte order mark of the file it replaces. Raises OSError if the write fails.
+    """
+    raw_result = invoke_write_text_file(path=str(path), text=text, debug=debug)
+    if raw_result.get("error"):
+        raise OSError(raw_result["error"])
+
+
+def compact_embedding_cache_from_rust(project_path: Path, debug: bool = False) -> Dict[str, Any]:
+    """
+    Removes the cached embeddings of files that no longer exist in the project.
+    """
+    try:
+        raw_result = invoke_compact_embedding_cache(
+            project_path=str(project_path),
+            debug=debug
+        )
+        status = "error_ffi_call" if raw_result.get("error") else "success"
+        return {**raw_result, "status": status}
+
+    except Exception as ex:
+        return {
+            "entries_kept": 0,
+            "entries_removed": 0,
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
+
+
+def read_audit_log_from_rust(project_path: Path, limit: int = 0, debug: bool = False) -> Dict[str, Any]:
+    """
+    Returns the most recent audit records for a project (all of them if limit is 0).
+    Records are only written while the RAPID_AUDIT_LOG environment variable is enabled.
+    """
+    try:
+        raw_result = invoke_read_audit_log(
+            project_path=str(project_path),
+            limit=limit,
+            debug=debug
+        )
+        status = "error_ffi_call" if raw_result.get("error") else "success"
+        return {**raw_result, "status": status}
+
+    except Exception as ex:
+        return {
+            "records": [],
+            "status": "error_file_collection_critical",
+            "error": str(ex),
+        }
@@ -1,12 +1,17 @@
 import time
 import os
 from pathlib import Path
-from typing import Any, Dict, List
+from typing import Any, Dict, List, Optional, Tuple
 
-from logic.file_collection import collect_and_parse_files_from_rust, search_in_files_from_rust, concept_search_from_rust
-from logic.context_processing import format_project_context, format_search_results, format_concept_search_results
+from logic.file_collection import collect_and_parse_files_from_rust, search_in_files_from_rust, concept_search_from_rust, write_text_file_from_rust
+from logic.context_processing import format_project_context, format_project_outline, format_search_results, format_concept_search_results
 
 PLAN_MD_FILENAME = "plan.md"
+# Options controlling which files a scan or search visits; forwarded only when set.
+WALK_OPTION_KEYS = (
+    "max_file_size_bytes", "include", "exclude", "respect_gitignore", "include_hidden",
+    "max_depth", "follow_links", "skip_vendor_dirs",
+)
 DEFAULT_PLAN_MD_CONTENT = """# Project Plan
 
 This plan.md file was automatically created.
@@ -17,6 +22,20 @@ This will help the AI assistant understand the project context.
 """
 
 
+def _additional_roots(args: Dict[str, Any]) -> Tuple[List[Path], Optional[str]]:
+    """Validates the optional 'additional_paths' roots scanned alongside 'path' (e.g. the
+    top-level folders of a monorepo). Returns the roots, or an error text output."""
+    roots = []
+    for path_str in args.get("additional_paths") or []:
+        root = Path(path_str)
+        if not root.is_absolute():
+            return [], f"--- Error ---\nAdditional path '{path_str}' must be an absolute path."
+        if not root.is_dir():
+            return [], f"--- Error ---\nAdditional path '{path_str}' not found or not a directory"
+        roots.append(root)
+    return roots, None
+
+
 def _format_stats_for_text_output(stats_dict: Dict[str, Any], title: str = "Stats") -> str:
     """Helper function to format a dictionary of stats into a readable string."""
     if not stats_dict:
@@ -82,8 +101,7 @@ async def initialize_project_context_impl(args: Dict[str, Any]) -> Dict[str, Any
             if debug_mode:
                 debug_log_internal.append(f"Read existing {PLAN_MD_FILENAME}")
         else:
-            with open(plan_md_path, "w", encoding="utf-8") as f:
-                f.write(DEFAULT_PLAN_MD_CONTENT)
+            write_text_file_from_rust(plan_md_path, DEFAULT_PLAN_MD_CONTENT, debug_mode)
             plan_md_content = DEFAULT_PLAN_MD_CONTENT
             if debug_mode:
                 debug_log_internal.append(f"Created new {PLAN_MD_FILENAME}")
@@ -241,6 +259,18 @@ async def get_full_context_impl(args: Dict[str, Any]) -> Dict[str, Any]:
     timeout_seconds = args.get("timeout", 10)
     compactness_level = args.get("compactness_level", 1)
     extensions = args.get("extensions", [".cs", ".py", ".rs", ".js", ".ts"])
+    outline_mode = args.get("outline", False)
+    scan_options = {
+        "annotation_filter": args.get("annotation"),
+        "outline": outline_mode,
+        "query_overrides": args.get("query_overrides", {}),
+        "extra_queries": args.get("extra_queries", {}),
+        "strict": args.get("strict", False),
+        "budget": args.get("budget", "balanced"),
+    }
+    for key in WALK_OPTION_KEYS + ("files", "continuation_token", "max_result_bytes"):
+        if args.get(key) is not None:
+            scan_options[key] = args[key]
 
     debug_log_internal: List[str] = []
 
@@ -252,6 +282,10 @@ async def get_full_context_impl(args: Dict[str, Any]) -> Dict[str, Any]:
             return {"status": "error_text_output", "text_output": f"--- Error ---\nProject path '{input_path_str}' not found or not a directory"}
     except Exception as e:
         return {"status": "error_text_output", "text_output": f"--- Error ---\nInvalid project path: {e}"}
+    additional_roots, additional_roots_error = _additional_roots(args)
+    if additional_roots_error:
+        return {"status": "error_text_output", "text_output": additional_roots_error}
+    roots = [project_path, *additional_roots] if additional_roots else project_path
 
     overall_start_time = time.time()
     text_output_parts = []
@@ -260,7 +294,8 @@ async def get_full_context_impl(args: Dict[str, Any]) -> Dict[str, Any]:
 
     try:
         rust_result = collect_and_parse_files_from_rust(
-            project_path, extensions, compactness_level, timeout_seconds, debug_mode
+            roots, extensions, compactness_level, timeout_seconds, debug_mode,
+            options=scan_options
         )
 
         if debug_mode:
@@ -309,15 +344,36 @@ async def get_full_context_impl(args: Dict[str, Any]) -> Dict[str, Any]:
             final_status_str = "success_text_output"
         elif rust_status == "success_partial_internal_timeout":
             final_status_str = "partial_text_output"
+            continuation_token = rust_result.get("continuation_token")
             text_output_parts.append(
-                "[Warning: Scan timed out internally, results may be incomplete.]\n")
+                "[Warning: Scan timed out internally, results may be incomplete."
+                + (f" Pass continuation_token={continuation_token} to scan the remaining files." if continuation_token is not None else "")
+                + "]\n")
         elif "error" in rust_status:
             final_status_str = "error_text_output"
             text_output_parts.append(
                 f"--- Error during scan ---\n{rust_result.get('error', 'Unknown error from Rust layer.')}\n")
 
-        formatted_context = format_project_context(
-            project_path, file_contexts, compactness_level)  # Added project_path
+        bodies_truncated = final_stats.get("bodies_truncated", 0)
+        files_over_limit = final_stats.get("skipped", {}).get("over_result_limit", 0)
+        if bodies_truncated or files_over_limit:
+            text_output_parts.append(
+                f"[Warning: max_result_bytes reached; {bodies_truncated} function(s) shortened to their first line"
+                f" and {files_over_limit} file(s) left out.]\n")
+
+        skipped_entries = rust_result.get("skipped") or []
+        if skipped_entries:
+            shown = ", ".join(f"{entry['path']} ({entry['reason']})" for entry in skipped_entries[:10])
+            more = f" and {len(skipped_entries) - 10} more" if len(skipped_entries) > 10 else ""
+            text_output_parts.append(
+                f"[Warning: Skipped {len(skipped_entries)} path(s) the scan could not read: {shown}{more}]\n")
+
+        if outline_mode:
+            formatted_context = format_project_outline(
+                project_path, file_contexts)
+        else:
+            formatted_context = format_project_context(
+                project_path, file_contexts, compactness_level)  # Added project_path
         text_output_parts.append(
             formatted_context if formatted_context else "No processable files found or an error occurred.")
 
@@ -374,6 +430,22 @@ async def project_wide_search_impl(args: Dict[str, Any]) -> Dict[str, Any]:
     timeout_seconds = args.get("timeout", 10)
     extensions = args.get("extensions", [".cs", ".py", ".rs", ".js", ".ts"])
     context_lines = args.get("context_lines", 2)
+    search_options = {
+        "budget": args.get("budget", "balanced"),
+        "use_regex": args.get("use_regex", False),
+        "case_insensitive": args.get("case_insensitive", False),
+        "whole_word": args.get("whole_word", False),
+        "multiline": args.get("multiline", False),
+        "symbol_context": args.get("symbol_context", False),
+        "function_bodies_only": args.get("function_bodies_only", False),
+        "skip_comments_and_strings": args.get("skip_comments_and_strings", False),
+        "boolean_query": args.get("boolean_query", False),
+        "boolean_scope": args.get("boolean_scope", "line"),
+        "ranked": args.get("ranked", False),
+    }
+    for key in WALK_OPTION_KEYS + ("files", "patterns"):
+        if args.get(key) is not None:
+            search_options[key] = args[key]
 
     debug_log_internal: List[str] = []
     text_output_parts = []
@@ -387,11 +459,16 @@ async def project_wide_search_impl(args: Dict[str, Any]) -> Dict[str, Any]:
             return {"status": "error_text_output", "text_output": f"--- Error ---\nProject path '{input_path_str}' not found or not a directory"}
     except Exception as e:
         return {"status": "error_text_output", "text_output": f"--- Error ---\nInvalid project path: {e}"}
+    additional_roots, additional_roots_error = _additional_roots(args)
+    if additional_roots_error:
+        return {"status": "error_text_output", "text_output": additional_roots_error}
+    roots = [project_path, *additional_roots] if additional_roots else project_path
 
     start_time = time.time()
     try:
         rust_result = search_in_files_from_rust(
-            project_path, search_string, extensions, context_lines, timeout_seconds, debug_mode
+            roots, search_string, extensions, context_lines, timeout_seconds, debug_mode,
+            options=search_options
         )
         if debug_mode:
             debug_log_internal.append(
@@ -452,6 +529,18 @@ async def concept_search_impl(args: Dict[str, Any]) -> Dict[str, Any]:
     timeout_seconds = args.get("timeout", 20)
     extensions = args.get("extensions", [".cs", ".py", ".rs", ".js", ".ts"])
     top_n = args.get("top_n", 10)
+    # Only forward settings the caller chose, so the budget bundle fills in the rest.
+    concept_options = {
+        key: args[key]
+        for key in (
+            "context_lines", "max_body_chars", "parameter_type", "keyword_fallback", "backend",
+            "spelling_tolerance", "model", "language_models", "rerank_model", "cross_encoder",
+            "local_models", "offline", "quantization", "exact_search", "min_similarity", "granularity",
+            "diversity", "exclude_tests", "negative_query", "negative_weight",
+        ) + WALK_OPTION_KEYS
+        if args.get(key) is not None
+    }
+    concept_options["budget"] = args.get("budget", "balanced")
 
     debug_log_internal: List[str] = []
     text_output_parts = []
@@ -465,6 +554,10 @@ async def concept_search_impl(args: Dict[str, Any]) -> Dict[str, Any]:
             return {"status": "error_text_output", "text_output": f"--- Error ---\nProject path '{input_path_str}' not found or not a directory"}
     except Exception as e:
         return {"status": "error_text_output", "text_output": f"--- Error ---\nInvalid project path: {e}"}
+    additional_roots, additional_roots_error = _additional_roots(args)
+    if additional_roots_error:
+        return {"status": "error_text_output", "text_output": additional_roots_error}
+    roots = [project_path, *additional_roots] if additional_roots else project_path
 
     start_time = time.time()
     try:
@@ -473,7 +566,8 @@ async def concept_search_impl(args: Dict[str, Any]) -> Dict[str, Any]:
                 f"Calling concept_search_from_rust with: project_path='{project_path}', query='{query[:50]}...', extensions={extensions}, top_n={top_n}, timeout_seconds={timeout_seconds}, debug_mode={debug_mode}")
 
         rust_result = concept_search_from_rust(
-            project_path, query, extensions, top_n, timeout_seconds, debug_mode
+            roots, query, extensions, top_n, timeout_seconds, debug_mode,
+            options=concept_options
         )
         if debug_mode:
             debug_log_internal.append(
@@ -497,6 +591,29 @@ async def concept_search_impl(args: Dict[str, Any]) -> Dict[str, Any]:
             text_output_parts.append(
                 f"--- Error during concept search ---\n{rust_result.get('error', 'Unknown error from Rust concept search.')}\n")
 
+        if rust_result.get("stats", {}).get("model_fallback"):
+            text_output_parts.append(
+                "[Warning: Embedding model unavailable, results ranked by keyword (BM25) scoring instead.]\n")
+
+        corrected_terms = rust_result.get("stats", {}).pop("corrected_terms", [])
+        if corrected_terms:
+            corrections_str = ", ".join(
+                f"'{c['original']}' -> '{c['corrected']}'" for c in corrected_terms)
+            text_output_parts.append(
+                f"[Info: Corrected query terms: {corrections_str}]\n")
+
+        functions_chunked = rust_result.get("stats", {}).get("functions_chunked", 0)
+        if functions_chunked:
+            max_tokens = rust_result.get("stats", {}).get("max_input_tokens")
+            function_chunks = rust_result.get("stats", {}).get("function_chunks", 0)
+            text_output_parts.append(
+                f"[Info: {functions_chunked} function(s) exceed the embedding model's input length"
+                f"{f' of {max_tokens} tokens' if max_tokens else ''} and were embedded in {function_chunks} overlapping chunks.]\n")
+
+        if rust_result.get("stats", {}).get("timed_out"):
+            text_output_parts.append(
+                "[Warning: Timeout reached before every function was embedded; results rank only the functions embedded in time. Repeat the search to continue from the cache.]\n")
+
         formatted_results = format_concept_search_results(
             project_path, rust_result)  # Added project_path
         text_output_parts.append(
@@ -107,6 +107,57 @@ The tool also provides a lightweight complexity assessment to guide your next st
                         "type": "string",
                         "description": "The string to search for."
                     },
+                    "use_regex": {
+                        "type": "boolean",
+                        "description": "Treat search_string as a regular expression (Rust regex syntax). Capture groups of each match are listed with it. Default is false."
+                    },
+                    "case_insensitive": {
+                        "type": "boolean",
+                        "description": "Match regardless of letter case, for literal and regex searches. Default is false."
+                    },
+                    "whole_word": {
+                        "type": "boolean",
+                        "description": "Only match at word boundaries, so 'get' does not match 'get_value'. Default is false."
+                    },
+                    "multiline": {
+                        "type": "boolean",
+                        "description": "Search each file as a whole so matches can span lines, e.g. a signature split across lines (with use_regex, '\\s' matches line breaks). Default is false."
+                    },
+                    "symbol_context": {
+                        "type": "boolean",
+                        "description": "Name the enclosing function or class of every match, e.g. 'UserRepository.save'. Default is false."
+                    },
+                    "function_bodies_only": {
+                        "type": "boolean",
+                        "description": "Only keep matches inside function bodies, skipping signatures and module- or class-level code. Default is false."
+                    },
+                    "skip_comments_and_strings": {
+                        "type": "boolean",
+                        "description": "Skip matches inside comments and string literals. Default is false."
+                    },
+                    "patterns": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Further strings to search for in the same pass as search_string, e.g. a list of deprecated APIs. Each match names the pattern it matched."
+                    },
+                    "boolean_query": {
+                        "type": "boolean",
+                        "description": "Read search_string as a boolean query of terms with AND, OR, NOT and parentheses, e.g. 'foo AND bar NOT baz'. Adjacent terms are ANDed; quote terms containing spaces or operators. Default is false."
+                    },
+                    "boolean_scope": {
+                        "type": "string",
+                        "enum": ["line", "file"],
+                        "description": "Evaluate a boolean query per line, or per file (then every line with a non-negated term of a matching file is reported). Default is 'line'."
+                    },
+                    "ranked": {
+                        "type": "boolean",
+                        "description": "List the most relevant files first instead of in walk order, scoring match density, closeness to the project root, and file names containing the search terms. Default is false."
+                    },
+                    "files": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Search exactly these files (absolute or relative to 'path') instead of walking the project, e.g. the files of a previous search to narrow its results. Extension and size limits, include/exclude globs, and vendored-directory skipping still apply, and files outside 'path', under .git, or in the cache are left out; only .gitignore/.rapidignore rules are not applied."
+                    },
                     "extensions": {
                         "type": "array",
                         "items": {"type": "string"},
@@ -114,7 +165,7 @@ The tool also provides a lightweight complexity assessment to guide your next st
                     },
                     "max_depth": {
                         "type": "integer",
-                        "description": "Maximum depth to scan directories. Default is 6."
+                        "description": "Maximum directory depth to scan below 'path' (1 = only files directly in it). Unlimited by default."
                     },
                     "max_files": {
                         "type": "integer",
@@ -128,6 +179,51 @@ The tool also provides a lightweight complexity assessment to guide your next st
                         "type": "integer",
                         "description": "Number of context lines to show around each match. Default is 2."
                     },
+                    "include": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Only include files matching one of these globs, relative to the project root (e.g. 'src/**')."
+                    },
+                    "exclude": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Skip files and directories matching any of these globs (e.g. '**/vendor/**', '**/*_test.go')."
+                    },
+                    "respect_gitignore": {
+                        "type": "boolean",
+                        "description": "Skip files ignored by .gitignore. Set to false to include ignored files such as build output. Defaults to true.",
+                        "default": True
+                    },
+                    "follow_links": {
+                        "type": "boolean",
+                        "description": "Follow symbolic links, e.g. to index symlinked source trees. Defaults to false.",
+                        "default": False
+                    },
+                    "skip_vendor_dirs": {
+                        "type": "boolean",
+                        "description": "Skip node_modules, target, .venv, dist, and build directories even when they are not gitignored. Defaults to true.",
+                        "default": True
+                    },
+                    "include_hidden": {
+                        "type": "boolean",
+                        "description": "Also include hidden files and dot directories (except .git). Defaults to false.",
+                        "default": False
+                    },
+                    "additional_paths": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Further absolute project directories to include alongside 'path', e.g. the top-level folders of a monorepo. Results are merged; files under more than one root are reported once."
+                    },
+                    "max_file_size_bytes": {
+                        "type": "integer",
+                        "description": "Skip files larger than this many bytes, e.g. to leave out generated or minified files. Overrides the budget's limit. Default is 5000000."
+                    },
+                    "budget": {
+                        "type": "string",
+                        "enum": ["fast", "balanced", "thorough"],
+                        "description": "Latency budget: 'fast' skips files over 1 MB, 'thorough' searches files up to 20 MB. Default is 'balanced'.",
+                        "default": "balanced"
+                    },
                     "debug": {
                         "type": "boolean",
                         "description": "Whether to include the debug log in the output. Defaults to false.",
@@ -157,7 +253,7 @@ The tool also provides a lightweight complexity assessment to guide your next st
                     },
                     "max_depth": {
                         "type": "integer",
-                        "description": "Maximum depth to scan directories. Default is 6."
+                        "description": "Maximum directory depth to scan below 'path' (1 = only files directly in it). Unlimited by default."
                     },
                     "max_files": {
                         "type": "integer",
@@ -171,6 +267,87 @@ The tool also provides a lightweight complexity assessment to guide your next st
                         "type": "integer",
                         "description": "Controls output verbosity: 0 (ultra-compact summary), 1 (compact, default), 2 (medium detail), 3 (highly detailed with full code snippets). Choose based on the level of detail required."
                     },
+                    "outline": {
+                        "type": "boolean",
+                        "description": "Return each file's hierarchical symbol tree (modules -> classes -> methods, with line ranges) instead of a flat function list. Defaults to false.",
+                        "default": False
+                    },
+                    "query_overrides": {
+                        "type": "object",
+                        "additionalProperties": {"type": "string"},
+                        "description": "Tree-sitter queries that replace the built-in query per extension, keyed without the dot (e.g. {\"rs\": \"...\"}). Capture names: @method_name, @function_definition, @body, @parameters, @return_type."
+                    },
+                    "extra_queries": {
+                        "type": "object",
+                        "additionalProperties": {"type": "string"},
+                        "description": "Tree-sitter patterns appended to the built-in query per extension, e.g. to extract project-specific macros as functions. Same captures as query_overrides."
+                    },
+                    "annotation": {
+                        "type": "string",
+                        "description": "Only include functions with a decorator or attribute containing this text (e.g. 'test', 'route', 'HttpGet')."
+                    },
+                    "include": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Only include files matching one of these globs, relative to the project root (e.g. 'src/**')."
+                    },
+                    "exclude": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Skip files and directories matching any of these globs (e.g. '**/vendor/**', '**/*_test.go')."
+                    },
+                    "respect_gitignore": {
+                        "type": "boolean",
+                        "description": "Skip files ignored by .gitignore. Set to false to include ignored files such as build output. Defaults to true.",
+                        "default": True
+                    },
+                    "follow_links": {
+                        "type": "boolean",
+                        "description": "Follow symbolic links, e.g. to index symlinked source trees. Defaults to false.",
+                        "default": False
+                    },
+                    "skip_vendor_dirs": {
+                        "type": "boolean",
+                        "description": "Skip node_modules, target, .venv, dist, and build directories even when they are not gitignored. Defaults to true.",
+                        "default": True
+                    },
+                    "include_hidden": {
+                        "type": "boolean",
+                        "description": "Also include hidden files and dot directories (except .git). Defaults to false.",
+                        "default": False
+                    },
+                    "files": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Parse exactly these files (absolute or relative to 'path') instead of walking the project, e.g. the files changed in a diff. Extension and size limits, include/exclude globs, and vendored-directory skipping still apply, and files outside 'path', under .git, or in the cache are left out; only .gitignore/.rapidignore rules are not applied."
+                    },
+                    "continuation_token": {
+                        "type": "integer",
+                        "description": "Token reported by a scan of the same path that timed out. Continues that scan, returning only files it did not reach."
+                    },
+                    "max_result_bytes": {
+                        "type": "integer",
+                        "description": "Approximate cap on the returned code text. Past it, functions are shortened to their first line, then further files are left out."
+                    },
+                    "additional_paths": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Further absolute project directories to include alongside 'path', e.g. the top-level folders of a monorepo. Results are merged; files under more than one root are reported once."
+                    },
+                    "max_file_size_bytes": {
+                        "type": "integer",
+                        "description": "Skip files larger than this many bytes, e.g. to leave out generated or minified files. Overrides the budget's limit. Default is 1000000."
+                    },
+                    "budget": {
+                        "type": "string",
+                        "enum": ["fast", "balanced", "thorough"],
+                        "description": "Latency budget: 'fast' skips files over 250 KB, 'thorough' scans files up to 4 MB. Default is 'balanced'.",
+                        "default": "balanced"
+                    },
+                    "strict": {
+                        "type": "boolean",
+                        "description": "Fail with an explicit error instead of returning silently empty results when an extension is unsupported, no files match, or a query extracts nothing. Useful in CI. Defaults to false."
+                    },
                     "debug": {
                         "type": "boolean",
                         "description": "Whether to include the debug log in the output. Defaults to false.",
@@ -206,6 +383,154 @@ The tool also provides a lightweight complexity assessment to guide your next st
                         "type": "integer",
                         "description": "Number of top results to return. Default is 10."
                     },
+                    "context_lines": {
+                        "type": "integer",
+                        "description": "Number of lines of surrounding file context (imports, class header) to include before and after each matched function. Default is 0."
+                    },
+                    "max_body_chars": {
+                        "type": "integer",
+                        "description": "Cut each result's body to this many characters, ending it with '…' and marking the result 'truncated', to keep the response small. Default is 0 (whole bodies)."
+                    },
+                    "parameter_type": {
+                        "type": "string",
+                        "description": "Only return functions that take a parameter whose declared type contains this string (e.g. 'UserRepository')."
+                    },
+                    "keyword_fallback": {
+                        "type": "boolean",
+                        "description": "If the embedding model cannot be loaded, rank results by keyword (BM25) scoring instead of failing. Defaults to false.",
+                        "default": False
+                    },
+                    "backend": {
+                        "type": "string",
+                        "enum": ["embedding", "bm25", "hybrid"],
+                        "description": "Retrieval backend. 'embedding' (default) ranks by semantic similarity; 'bm25' ranks by keyword relevance and is better for exact identifier queries; 'hybrid' combines both rankings, so a query naming an identifier still finds it.",
+                        "default": "embedding"
+                    },
+                    "spelling_tolerance": {
+                        "type": "integer",
+                        "description": "Maximum edit distance for correcting misspelled query terms during keyword (BM25) scoring, e.g. 'authetication' -> 'authentication'. 0 disables correction. Default is 1.",
+                        "default": 1
+                    },
+                    "model": {
+                        "type": "string",
+                        "enum": ["bge-small", "bge-base", "bge-large", "jina-code"],
+                        "description": "Embedding model for functions and the query. 'bge-small' is fastest, 'bge-large' most accurate. Default is 'bge-base'.",
+                        "default": "bge-base"
+                    },
+                    "language_models": {
+                        "type": "object",
+                        "additionalProperties": {"type": "string", "enum": ["bge-small", "bge-base", "bge-large", "jina-code"]},
+                        "description": "Per-extension model overrides keyed without the dot, e.g. {\"rs\": \"jina-code\"}."
+                    },
+                    "rerank_model": {
+                        "type": "string",
+                        "enum": ["bge-small", "bge-base", "bge-large", "jina-code"],
+                        "description": "Optional higher-quality model that re-scores the top candidates found with 'model'."
+                    },
+                    "cross_encoder": {
+                        "type": "string",
+                        "enum": ["bge-reranker-base", "jina-reranker-turbo"],
+                        "description": "Optional cross-encoder that re-scores the top candidates by reading the query and each function together. Slower, but more accurate for ambiguous queries."
+                    },
+                    "local_models": {
+                        "type": "object",
+                        "additionalProperties": {"type": "string"},
+                        "description": "Pre-downloaded models to load from disk instead of the Hugging Face hub, mapping a model name to a directory holding its ONNX file and tokenizer files, e.g. {\"bge-small\": \"/models/bge-small-en-v1.5\"}."
+                    },
+                    "offline": {
+                        "type": "boolean",
+                        "description": "Never download models: use local_models or models already in the shared model cache, and fail otherwise. For air-gapped CI. Default is false."
+                    },
+                    "quantization": {
+                        "type": "string",
+                        "enum": ["f32", "f16", "int8"],
+                        "description": "Precision of cached embeddings: 'f16' halves and 'int8' quarters the embedding cache on disk, at a small cost in ranking accuracy. Default is 'f32'."
+                    },
+                    "granularity": {
+                        "type": "string",
+                        "enum": ["function", "class", "file"],
+                        "description": "What to rank: 'function' (default), 'class' (classes, structs, enums, interfaces, traits), or 'file' (whole files). Use 'file' or 'class' for questions like 'where is the billing module'.",
+                        "default": "function"
+                    },
+                    "exclude_tests": {
+                        "type": "boolean",
+                        "description": "Leave test directories, test files, and functions marked as tests out of the ranking, so test fixtures do not take the top results when asking about production logic. Default is false."
+                    },
+                    "negative_query": {
+                        "type": "string",
+                        "description": "A concept to steer results away from, e.g. query 'authentication' with negative_query 'unit tests' to favour the production code. Ignored by the 'bm25' backend."
+                    },
+                    "negative_weight": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "description": "How strongly negative_query pushes results away, from 0 (ignored) to 1. Default is 0.5."
+                    },
+                    "diversity": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "description": "Spread results over distinct files and directories instead of returning many near-identical functions from one file. 0 (default) ranks by similarity alone; 0.3 is a good start."
+                    },
+                    "min_similarity": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "description": "Drop results whose similarity (0 to 1) is below this, e.g. 0.75 to hide weak matches. Default is 0, which keeps every result."
+                    },
+                    "exact_search": {
+                        "type": "boolean",
+                        "description": "Compare the query with every function instead of using the approximate nearest-neighbour index that large projects (20,000+ functions) use by default. Slower, but never misses a match. Default is false; true with budget 'thorough'."
+                    },
+                    "include": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Only include files matching one of these globs, relative to the project root (e.g. 'src/**')."
+                    },
+                    "exclude": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Skip files and directories matching any of these globs (e.g. '**/vendor/**', '**/*_test.go')."
+                    },
+                    "respect_gitignore": {
+                        "type": "boolean",
+                        "description": "Skip files ignored by .gitignore. Set to false to include ignored files such as build output. Defaults to true.",
+                        "default": True
+                    },
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Maximum directory depth to scan below 'path' (1 = only files directly in it). Unlimited by default."
+                    },
+                    "follow_links": {
+                        "type": "boolean",
+                        "description": "Follow symbolic links, e.g. to index symlinked source trees. Defaults to false.",
+                        "default": False
+                    },
+                    "skip_vendor_dirs": {
+                        "type": "boolean",
+                        "description": "Skip node_modules, target, .venv, dist, and build directories even when they are not gitignored. Defaults to true.",
+                        "default": True
+                    },
+                    "include_hidden": {
+                        "type": "boolean",
+                        "description": "Also include hidden files and dot directories (except .git). Defaults to false.",
+                        "default": False
+                    },
+                    "additional_paths": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Further absolute project directories to include alongside 'path', e.g. the top-level folders of a monorepo. Results are merged; files under more than one root are reported once."
+                    },
+                    "max_file_size_bytes": {
+                        "type": "integer",
+                        "description": "Skip files larger than this many bytes, e.g. to leave out generated or minified files. Overrides the budget's limit. Default is 1000000."
+                    },
+                    "budget": {
+                        "type": "string",
+                        "enum": ["fast", "balanced", "thorough"],
+                        "description": "Latency budget: 'fast' uses the small model without re-ranking or spelling correction, 'thorough' re-ranks the top 100 candidates with 'bge-large'. Explicitly set options take precedence. Default is 'balanced'.",
+                        "default": "balanced"
+                    },
                     "timeout": {
                         "type": "integer",
                         "description": "Timeout in seconds for the operation. Default is 20."
@@ -1,5 +1,6 @@
 // This is a file-level comment.
 using System;
+using System.Threading.Tasks;
 
 namespace TestApp
 {
@@ -22,5 +23,26 @@ namespace TestApp
         {
             return x + y;
         }
+
+        /// <summary>Creates a class with the given name.</summary>
+        public MyTestClass(string name)
+        {
+            Name = name;
+        }
+
+        /// <summary>The name given to the constructor.</summary>
+        public string Name { get; set; }
+
+        public int Length
+        {
+            get { return Name.Length; }
+        }
+
+        [Obsolete("Use MyMethod2 instead.")]
+        public async Task<int> MyMethod3(int x)
+        {
+            await Task.Delay(x);
+            return x;
+        }
     }
 }
@@ -1,5 +1,8 @@
 # This is a file-level comment.
 
+import functools
+
+
 class MyClass:
     """
     This is a docstring for MyClass.
@@ -14,3 +17,26 @@ class MyClass:
 def my_function():
     """This is a docstring for my_function."""
     pass
+
+
+def cached(func):
+    """Caches the results of func."""
+    @functools.wraps(func)
+    def wrapper(*args):
+        return func(*args)
+    return wrapper
+
+
+class Service:
+    @staticmethod
+    @cached
+    def lookup(key: str) -> int:
+        """Looks up a key."""
+        return len(key)
+
+    async def fetch(self, url: str, retries: int = 3) -> bytes:
+        """Fetches a URL."""
+        return b""
+
+
+square = lambda x: x * x
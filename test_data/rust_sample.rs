@@ -16,3 +16,38 @@ fn my_function() {
 fn another_function(x: i32) -> i32 {
     x + 1
 }
+
+trait Shape {
+    /// Area of the shape.
+    fn area(&self) -> f64;
+
+    /// Describes the shape by its area.
+    fn describe(&self) -> String {
+        format!("area {}", self.area())
+    }
+}
+
+impl MyStruct {
+    /// Creates a struct holding `field`.
+    pub fn new(field: i32) -> Self {
+        MyStruct { field }
+    }
+}
+
+impl Shape for MyStruct {
+    fn area(&self) -> f64 {
+        self.field as f64
+    }
+}
+
+#[test]
+fn doubles() {
+    fn double(x: i32) -> i32 {
+        x * 2
+    }
+    assert_eq!(double(2), 4);
+}
+
+async fn load(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_default()
+}
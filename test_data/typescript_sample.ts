@@ -16,3 +16,10 @@ class MyClass {
 function myFunction(): void {
     // This is a comment inside a function.
 }
+
+/** Adds two numbers. */
+export const add = (a: number, b: number): number => a + b;
+
+async function load(url: string): Promise<string> {
+    return url;
+}
@@ -0,0 +1,96 @@
+"""
+Measures what sharing one worker-thread budget between the directory walker and the
+rayon phases (embedding preparation, similarity scoring) does to scan and concept
+search times.
+
+Each configuration runs in its own process, since the shared pool is sized once per
+process from RAPID_NUM_THREADS:
+
+  - "shared budget":  RAPID_NUM_THREADS = cores, as the library runs by default.
+  - "oversubscribed": RAPID_NUM_THREADS = 2 x cores, the thread count the walker and
+                      rayon's global pool reached together before they shared a budget.
+
+Usage (after building the library, from the project root):
+
+    python tests/bench_thread_budget.py [project_path] [--repeats N] [--query TEXT]
+
+The first concept search of each process warms the embedding cache and is not timed,
+so the numbers compare walking, parsing, and scoring rather than model inference.
+"""
+import argparse
+import json
+import os
+import statistics
+import subprocess
+import sys
+import time
+from pathlib import Path
+from typing import Any, Dict, List
+
+PROJECT_ROOT = Path(__file__).resolve().parent.parent
+if str(PROJECT_ROOT) not in sys.path:
+    sys.path.insert(0, str(PROJECT_ROOT))
+
+EXTENSIONS = ["py", "rs", "ts", "cs"]
+
+
+def _median_ms(samples: List[float]) -> float:
+    return round(statistics.median(samples), 1)
+
+
+def run_worker(project_path: str, repeats: int, query: str) -> Dict[str, Any]:
+    """Times scans and warm concept searches in this process and returns the medians."""
+    from logic.ffi import invoke_scan_and_parse, invoke_concept_search
+
+    options = {"use_cache": False}
+    invoke_concept_search(project_path, query, EXTENSIONS, 10, 0)
+
+    scan_ms, search_ms, stage_ms = [], [], {"scan_ms": [], "embed_ms": [], "similarity_ms": []}
+    for _ in range(repeats):
+        start = time.perf_counter()
+        invoke_scan_and_parse(project_path, EXTENSIONS, 3, 0, options=options)
+        scan_ms.append((time.perf_counter() - start) * 1000)
+
+        start = time.perf_counter()
+        result = invoke_concept_search(project_path, query, EXTENSIONS, 10, 0)
+        search_ms.append((time.perf_counter() - start) * 1000)
+        if result.get("error"):
+            raise RuntimeError(result["error"])
+        for stage, samples in stage_ms.items():
+            samples.append(result.get("stats", {}).get(stage, 0))
+
+    return {
+        "scan_and_parse_ms": _median_ms(scan_ms),
+        "concept_search_ms": _median_ms(search_ms),
+        **{f"concept_search_{stage}": _median_ms(samples) for stage, samples in stage_ms.items()},
+    }
+
+
+def main() -> None:
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("project_path", nargs="?", default=str(PROJECT_ROOT))
+    parser.add_argument("--repeats", type=int, default=5)
+    parser.add_argument("--query", default="parse the options of a search")
+    parser.add_argument("--worker", action="store_true", help=argparse.SUPPRESS)
+    args = parser.parse_args()
+
+    if args.worker:
+        print(json.dumps(run_worker(args.project_path, args.repeats, args.query)))
+        return
+
+    cores = os.cpu_count() or 1
+    configurations = [("shared budget", cores), ("oversubscribed", 2 * cores)]
+    print(f"Project: {args.project_path}  cores: {cores}  repeats: {args.repeats} (medians, ms)")
+    for name, threads in configurations:
+        env = {**os.environ, "RAPID_NUM_THREADS": str(threads)}
+        output = subprocess.run(
+            [sys.executable, __file__, args.project_path, "--worker",
+             "--repeats", str(args.repeats), "--query", args.query],
+            env=env, check=True, capture_output=True, text=True,
+        ).stdout
+        timings = json.loads(output.strip().splitlines()[-1])
+        print(f"  {name:<15} ({threads:>3} threads): " + ", ".join(f"{k}={v}" for k, v in timings.items()))
+
+
+if __name__ == "__main__":
+    main()